@@ -0,0 +1,270 @@
+/// Abstract generation from a note's first body paragraph.
+///
+/// `first_paragraph` extracts the prose that follows the title heading.
+/// `generate_abstract_edit` turns it into a single `TextEdit` that sets
+/// `abstract` in the TOML metadata block, or adds a metadata block when the
+/// note has none. `set_abstract_edit` does the same for any text, e.g. a
+/// selection promoted by the "Set as abstract" code action.
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+
+use crate::config::ZkLspConfig;
+use crate::note_ops::build_metadata_block;
+use crate::parser::{self, Parser};
+
+/// Abstracts longer than this many characters are cut at a word boundary.
+pub const MAX_ABSTRACT_CHARS: usize = 200;
+
+/// First paragraph of prose at or after line `body_start`, joined onto one
+/// line and truncated to `MAX_ABSTRACT_CHARS`.
+///
+/// Blank lines, headings, `//` comments, Typst code lines (`#…`) and fenced
+/// code blocks are skipped before the paragraph and end it once it started.
+pub fn first_paragraph(content: &str, body_start: usize) -> Option<String> {
+    let mut words: Vec<&str> = Vec::new();
+    let mut in_fence = false;
+    for line in parser::split_lines(content).skip(body_start) {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+            if !words.is_empty() {
+                break;
+            }
+            continue;
+        }
+        let is_prose = !in_fence
+            && !trimmed.is_empty()
+            && !trimmed.starts_with('=')
+            && !trimmed.starts_with('#')
+            && !trimmed.starts_with("//");
+        if is_prose {
+            words.extend(trimmed.split_whitespace());
+        } else if !words.is_empty() {
+            break;
+        }
+    }
+    if words.is_empty() {
+        return None;
+    }
+    Some(truncate(&words.join(" ")))
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_ABSTRACT_CHARS {
+        return text.to_string();
+    }
+    let cut = text
+        .char_indices()
+        .nth(MAX_ABSTRACT_CHARS)
+        .map_or(text.len(), |(i, _)| i);
+    let head = &text[..cut];
+    let head = match head.rfind(' ') {
+        Some(space) if space > 0 => &head[..space],
+        _ => head,
+    };
+    format!("{}…", head.trim_end())
+}
+
+/// Compute the edit that sets `abstract` from the note's first paragraph
+/// (see `set_abstract_edit`). `note_parser` is the wiki's configured parser.
+///
+/// Returns `Err` when the note has no body paragraph to summarise.
+pub fn generate_abstract_edit(
+    content: &str,
+    zk_config: &ZkLspConfig,
+    note_parser: &Parser,
+) -> Result<TextEdit, String> {
+    let lines: Vec<&str> = parser::split_lines(content).collect();
+    let body_start = match note_parser.parse_header(content) {
+        Some(header) => header.title_line_idx + 1,
+        None => lines
+            .iter()
+            .position(|l| note_parser.re_title.is_match(l))
+            .or_else(|| lines.iter().position(|l| note_parser.is_import_line(l)))
+            .map_or(0, |i| i + 1),
+    };
+    let text = first_paragraph(content, body_start)
+        .ok_or_else(|| "note has no body paragraph to summarise".to_string())?;
+    Ok(set_abstract_edit(content, &text, zk_config, note_parser))
+}
+
+/// Compute the edit that sets `abstract` to `text`.
+///
+/// - With a metadata block: replaces the `abstract = …` line, or inserts one
+///   before the first sub-table (e.g. `[user]`) or the closing fence.
+/// - Without one: inserts a fresh block (see `build_metadata_block`) after the
+///   `#import` line, or at the top of the file.
+pub fn set_abstract_edit(
+    content: &str,
+    text: &str,
+    zk_config: &ZkLspConfig,
+    note_parser: &Parser,
+) -> TextEdit {
+    let lines: Vec<&str> = parser::split_lines(content).collect();
+    let escaped = text.replace('\\', "\\\\").replace('"', "\\\"");
+    let abstract_line = format!("abstract = \"{escaped}\"");
+
+    let Some(block) = parser::find_toml_metadata_block(content) else {
+        let block = build_metadata_block(zk_config).replace("abstract = \"\"", &abstract_line);
+        let at = lines
+            .iter()
+            .position(|l| note_parser.is_import_line(l))
+            .map_or(0, |i| i + 1) as u32;
+        return insert_at(at, format!("{block}\n"));
+    };
+
+    for (i, line) in lines
+        .iter()
+        .enumerate()
+        .take(block.end_line + 1)
+        .skip(block.start_line)
+    {
+        let trimmed = line.trim_start();
+        let is_abstract_key = trimmed
+            .strip_prefix("abstract")
+            .is_some_and(|rest| rest.trim_start().starts_with('='));
+        if is_abstract_key {
+            let indent = &line[..line.len() - trimmed.len()];
+            return TextEdit {
+                range: Range {
+                    start: Position::new(i as u32, 0),
+                    end: Position::new(i as u32, parser::byte_to_utf16(line, line.len())),
+                },
+                new_text: format!("{indent}{abstract_line}"),
+            };
+        }
+    }
+    let mut at = (block.start_line..block.end_line)
+        .find(|&i| lines[i].trim_start().starts_with('['))
+        .unwrap_or(block.end_line);
+    // Keep the blank separator line above a sub-table.
+    while at > block.start_line && lines[at - 1].trim().is_empty() {
+        at -= 1;
+    }
+    insert_at(at as u32, format!("  {abstract_line}\n"))
+}
+
+fn insert_at(line: u32, new_text: String) -> TextEdit {
+    TextEdit {
+        range: Range {
+            start: Position::new(line, 0),
+            end: Position::new(line, 0),
+        },
+        new_text,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(toml_body: &str, body: &str) -> String {
+        format!(
+            "#import \"../include.typ\": *\n\
+             #let zk-metadata = toml(bytes(\n  ```toml\n{toml_body}  ```.text,\n))\n\
+             #show: zettel.with(metadata: zk-metadata)\n\n= Title <2603150000>\n{body}"
+        )
+    }
+
+    fn apply(content: &str, edit: &TextEdit) -> String {
+        let mut lines: Vec<String> = parser::split_lines(content).map(String::from).collect();
+        let (start, end) = (edit.range.start, edit.range.end);
+        assert_eq!(start.line, end.line);
+        let line = &mut lines[start.line as usize];
+        if start == end {
+            lines.insert(
+                start.line as usize,
+                edit.new_text.trim_end_matches('\n').into(),
+            );
+        } else {
+            *line = edit.new_text.clone();
+        }
+        lines.join("\n") + "\n"
+    }
+
+    #[test]
+    fn test_first_paragraph_skips_code_and_stops_at_blank_line() {
+        let content = "#tag.idea\n\n```\ncode\n```\nFirst line\n  second   line.\n\nNext.\n";
+        assert_eq!(
+            first_paragraph(content, 0).as_deref(),
+            Some("First line second line.")
+        );
+        assert_eq!(first_paragraph("#tag.idea\n\n", 0), None);
+    }
+
+    #[test]
+    fn test_truncates_at_word_boundary() {
+        let text = "word ".repeat(60);
+        let out = first_paragraph(&text, 0).unwrap();
+        assert!(out.ends_with("word…"));
+        assert!(out.chars().count() <= MAX_ABSTRACT_CHARS + 1);
+    }
+
+    #[test]
+    fn test_updates_existing_abstract_line() {
+        let content = note(
+            "  schema-version = 1\n  abstract = \"\"\n  keywords = []\n",
+            "\nSay \"hi\" here.\n",
+        );
+        let edit =
+            generate_abstract_edit(&content, &ZkLspConfig::default(), parser::default_parser())
+                .unwrap();
+        assert_eq!(edit.new_text, r#"  abstract = "Say \"hi\" here.""#);
+        let header = parser::parse_header(&apply(&content, &edit)).unwrap();
+        assert_eq!(header.abstract_text.as_deref(), Some("Say \"hi\" here."));
+    }
+
+    #[test]
+    fn test_inserts_missing_abstract_before_user_table() {
+        let content = note(
+            "  schema-version = 1\n\n  [user]\n  course = \"x\"\n",
+            "Body text.\n",
+        );
+        let edit =
+            generate_abstract_edit(&content, &ZkLspConfig::default(), parser::default_parser())
+                .unwrap();
+        let out = apply(&content, &edit);
+        assert!(out.contains("  abstract = \"Body text.\"\n\n  [user]"));
+        let header = parser::parse_header(&out).unwrap();
+        assert_eq!(header.abstract_text.as_deref(), Some("Body text."));
+    }
+
+    #[test]
+    fn test_inserts_missing_abstract_with_bare_cr_line_breaks() {
+        let content = note("  schema-version = 1\n", "Body text.\n").replace('\n', "\r");
+        let edit =
+            generate_abstract_edit(&content, &ZkLspConfig::default(), parser::default_parser())
+                .unwrap();
+        // Line 4 is the closing fence.
+        assert_eq!(edit.range.start, Position::new(4, 0));
+        assert_eq!(edit.new_text, "  abstract = \"Body text.\"\n");
+    }
+
+    #[test]
+    fn test_adds_metadata_block_when_missing() {
+        let content =
+            "#import \"../include.typ\": *\n#show: zettel\n\n= Old <2603150000>\nLegacy body.\n";
+        let edit =
+            generate_abstract_edit(content, &ZkLspConfig::default(), parser::default_parser())
+                .unwrap();
+        assert_eq!(edit.range.start, Position::new(1, 0));
+        let out = apply(content, &edit);
+        let block = parser::find_toml_metadata_block(&out).unwrap();
+        let parsed = parser::parse_toml_metadata(&block.toml_content).unwrap();
+        assert_eq!(parsed.abstract_text.as_deref(), Some("Legacy body."));
+    }
+
+    #[test]
+    fn test_errors_without_body_paragraph() {
+        let content = note("  abstract = \"\"\n", "\n");
+        assert!(generate_abstract_edit(
+            &content,
+            &ZkLspConfig::default(),
+            parser::default_parser()
+        )
+        .is_err());
+    }
+}
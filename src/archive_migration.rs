@@ -0,0 +1,438 @@
+/// Archived-note reference migration.
+///
+/// `pending_migrations` combines the set of archived notes that name a
+/// replacement (`alt_id`) with the backlink index and lists every `@ID`
+/// reference that still points at the archived note.
+/// `migrate_archived_edit` turns those references into a single `WorkspaceEdit`;
+/// `retire_edit` does the same for every reference to a note about to be deleted.
+/// `render_pending_migrations` formats the result as a CLI checklist.
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tower_lsp::lsp_types::{Position, Range, TextEdit, Url, WorkspaceEdit};
+
+use crate::cancel::CancelToken;
+use crate::index::NoteIndex;
+use crate::parser;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingReference {
+    pub file: PathBuf,
+    pub line: u32,       // 0-based
+    pub start_char: u32, // UTF-16
+    pub end_char: u32,   // UTF-16
+    pub line_text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingMigration {
+    pub old_id: String,
+    pub new_id: String,
+    pub title: String,
+    pub references: Vec<PendingReference>,
+}
+
+/// List every archived note with an `alt_id` that is still referenced.
+///
+/// A reference counts as migrated once its own span no longer names the
+/// archived note, whatever else its line mentions.
+/// References from the archived note itself and from the replacement note are
+/// skipped. Notes without pending references are omitted.
+pub async fn pending_migrations(index: &NoteIndex, cancel: &CancelToken) -> Vec<PendingMigration> {
+    let mut archived: Vec<(String, String, String)> = index
+        .notes
+        .iter()
+        .filter(|e| e.value().archived)
+        .filter_map(|e| {
+            let n = e.value();
+            let alt = n.alt_id.clone()?;
+            Some((n.id.clone(), alt, n.title.clone()))
+        })
+        .collect();
+    archived.sort();
+
+    let mut line_cache: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    let mut migrations = Vec::new();
+    for (old_id, new_id, title) in archived {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let references = pending_references(index, &old_id, &new_id, &mut line_cache).await;
+        if !references.is_empty() {
+            migrations.push(PendingMigration {
+                old_id,
+                new_id,
+                title,
+                references,
+            });
+        }
+    }
+    migrations
+}
+
+/// Build one `WorkspaceEdit` replacing every pending `@old_id` with `@{alt_id}`.
+///
+/// Uses the same reference selection as `pending_migrations`. Fails when the
+/// note is unknown, not archived, or has no replacement.
+pub async fn migrate_archived_edit(
+    index: &NoteIndex,
+    old_id: &str,
+) -> Result<WorkspaceEdit, String> {
+    let note = index
+        .get(old_id)
+        .ok_or_else(|| format!("note {old_id} not found"))?;
+    if !note.archived {
+        return Err(format!("note {old_id} is not archived"));
+    }
+    let new_id = note
+        .alt_id
+        .ok_or_else(|| format!("archived note {old_id} has no relation-target"))?;
+
+    let refs = pending_references(index, old_id, &new_id, &mut HashMap::new()).await;
+    Ok(rewrite_refs_edit(refs.into_iter().map(|r| {
+        let new_ref = span_text(&r.line_text, r.start_char, r.end_char).replace(old_id, &new_id);
+        (r.file, r.line, r.start_char, r.end_char, new_ref)
+    })))
+}
+
+/// Build one `WorkspaceEdit` pointing every reference to `old_id` at
+/// `new_id`, so `old_id` can be deleted without leaving dead links.
+///
+/// Unlike `migrate_archived_edit` this covers all references outside the
+/// retired note itself, including lines that already mention `new_id` and
+/// the replacement note. Fails when either note is unknown or both are the
+/// same.
+pub async fn retire_edit(
+    index: &NoteIndex,
+    old_id: &str,
+    new_id: &str,
+) -> Result<WorkspaceEdit, String> {
+    if old_id == new_id {
+        return Err(format!("note {old_id} cannot replace itself"));
+    }
+    let old_path = index
+        .get(old_id)
+        .ok_or_else(|| format!("note {old_id} not found"))?
+        .path;
+    if !index.contains(new_id) {
+        return Err(format!("replacement note {new_id} not found"));
+    }
+    let mut line_cache: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    let mut refs = Vec::new();
+    for loc in index.get_backlinks(old_id) {
+        if loc.file == old_path {
+            continue;
+        }
+        let line_text = cached_line(&mut line_cache, &loc.file, loc.line).await;
+        let span = span_text(&line_text, loc.start_char, loc.end_char);
+        if !span.contains(old_id) {
+            continue;
+        }
+        let new_ref = span.replace(old_id, new_id);
+        refs.push((loc.file, loc.line, loc.start_char, loc.end_char, new_ref));
+    }
+    Ok(rewrite_refs_edit(refs))
+}
+
+/// Replace each `(file, line, start_char, end_char, new_ref)` span with
+/// `new_ref`, the original span text pointing at the new ID. Keeping the
+/// span's own form turns a `<ID>` label reference into `<new>` rather than
+/// into an `@new` that would no longer be a label.
+fn rewrite_refs_edit(
+    refs: impl IntoIterator<Item = (PathBuf, u32, u32, u32, String)>,
+) -> WorkspaceEdit {
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for (file, line, start_char, end_char, new_ref) in refs {
+        let Ok(uri) = Url::from_file_path(&file) else {
+            continue;
+        };
+        changes.entry(uri).or_default().push(TextEdit {
+            range: Range {
+                start: Position {
+                    line,
+                    character: start_char,
+                },
+                end: Position {
+                    line,
+                    character: end_char,
+                },
+            },
+            new_text: new_ref,
+        });
+    }
+    WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }
+}
+
+/// Line `line` of `file`, read from disk once per file through `line_cache`.
+async fn cached_line(
+    line_cache: &mut HashMap<PathBuf, Vec<String>>,
+    file: &PathBuf,
+    line: u32,
+) -> String {
+    if !line_cache.contains_key(file) {
+        let lines = tokio::fs::read_to_string(file)
+            .await
+            .map(|c| parser::split_lines(&c).map(String::from).collect())
+            .unwrap_or_default();
+        line_cache.insert(file.clone(), lines);
+    }
+    line_cache[file]
+        .get(line as usize)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// The text of a backlink span given in UTF-16 columns.
+fn span_text(line: &str, start_char: u32, end_char: u32) -> &str {
+    let start = parser::utf16_to_byte(line, start_char);
+    let end = parser::utf16_to_byte(line, end_char).max(start);
+    &line[start..end]
+}
+
+/// References to `old_id` whose span still names it, sorted by location.
+async fn pending_references(
+    index: &NoteIndex,
+    old_id: &str,
+    new_id: &str,
+    line_cache: &mut HashMap<PathBuf, Vec<String>>,
+) -> Vec<PendingReference> {
+    let mut references = Vec::new();
+    for loc in index.get_backlinks(old_id) {
+        let stem = loc.file.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        if stem == old_id || stem == new_id {
+            continue;
+        }
+        let line_text = cached_line(line_cache, &loc.file, loc.line).await;
+        if !span_text(&line_text, loc.start_char, loc.end_char).contains(old_id) {
+            continue;
+        }
+        references.push(PendingReference {
+            file: loc.file,
+            line: loc.line,
+            start_char: loc.start_char,
+            end_char: loc.end_char,
+            line_text,
+        });
+    }
+    references.sort_by(|a, b| {
+        a.file
+            .cmp(&b.file)
+            .then(a.line.cmp(&b.line))
+            .then(a.start_char.cmp(&b.start_char))
+    });
+    references
+}
+
+/// Render pending migrations as a plain-text checklist for CLI output.
+pub fn render_pending_migrations(migrations: &[PendingMigration]) -> String {
+    let mut out = String::new();
+    for m in migrations {
+        out.push_str(&format!("@{} → @{}  {}\n", m.old_id, m.new_id, m.title));
+        for r in &m.references {
+            out.push_str(&format!(
+                "  - [ ] {}:{}:{}  {}\n",
+                r.file.display(),
+                r.line + 1,
+                r.start_char + 1,
+                r.line_text.trim()
+            ));
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WikiConfig;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn note(id: &str, relation: &str, target: &str, body: &str) -> String {
+        format!(
+            "#import \"../include.typ\": *\n\
+             #let zk-metadata = toml(bytes(\n\
+             \x20 ```toml\n\
+             \x20 schema-version = 1\n\
+             \x20 relation = \"{relation}\"\n\
+             \x20 relation-target = [{target}]\n\
+             \x20 ```.text,\n\
+             ))\n\
+             #show: zettel.with(metadata: zk-metadata)\n\
+             \n\
+             = Note {id} <{id}>\n\
+             {body}\n"
+        )
+    }
+
+    fn write(dir: &Path, id: &str, content: &str) {
+        std::fs::write(dir.join(format!("{id}.typ")), content).unwrap();
+    }
+
+    async fn build_index(suffix: &str) -> (PathBuf, NoteIndex) {
+        let tmp = std::env::temp_dir().join(format!("zk_archive_migration_{suffix}"));
+        let _ = std::fs::remove_dir_all(&tmp);
+        let dir = tmp.join("note");
+        std::fs::create_dir_all(&dir).unwrap();
+        write(
+            &dir,
+            "1111111111",
+            &note(
+                "1111111111",
+                "archived",
+                "\"2222222222\"",
+                "see @1111111111",
+            ),
+        );
+        write(
+            &dir,
+            "2222222222",
+            &note("2222222222", "active", "", "replaces @1111111111"),
+        );
+        write(
+            &dir,
+            "3333333333",
+            &note(
+                "3333333333",
+                "active",
+                "",
+                "old @1111111111\nboth @1111111111 @2222222222",
+            ),
+        );
+        let config = WikiConfig::from_root(tmp.clone());
+        let index = NoteIndex::new(Arc::new(tokio::sync::RwLock::new(config)));
+        index.rebuild_full().await.unwrap();
+        (tmp, index)
+    }
+
+    #[tokio::test]
+    async fn test_pending_migrations_lists_unmigrated_refs_only() {
+        let (tmp, index) = build_index("pending").await;
+        let pending = pending_migrations(&index, &CancelToken::new()).await;
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(pending.len(), 1);
+        let m = &pending[0];
+        assert_eq!(m.old_id, "1111111111");
+        assert_eq!(m.new_id, "2222222222");
+        // Self-reference and replacement note are skipped; a line that also
+        // mentions the replacement still holds a pending reference.
+        assert_eq!(m.references.len(), 2);
+        assert!(m.references[0].file.ends_with("3333333333.typ"));
+        assert_eq!(m.references[0].line_text, "old @1111111111");
+        assert_eq!(m.references[1].line_text, "both @1111111111 @2222222222");
+    }
+
+    #[tokio::test]
+    async fn test_pending_migrations_render() {
+        let (tmp, index) = build_index("render").await;
+        let rendered =
+            render_pending_migrations(&pending_migrations(&index, &CancelToken::new()).await);
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert!(rendered.starts_with("@1111111111 → @2222222222  Note 1111111111\n"));
+        assert!(rendered.contains("- [ ] "));
+        assert!(rendered.contains("old @1111111111"));
+    }
+
+    #[tokio::test]
+    async fn test_migrate_archived_edit_replaces_pending_refs() {
+        let (tmp, index) = build_index("edit").await;
+        let edit = migrate_archived_edit(&index, "1111111111").await.unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let changes = edit.changes.unwrap();
+        assert_eq!(changes.len(), 1);
+        let (uri, edits) = changes.into_iter().next().unwrap();
+        assert!(uri.path().ends_with("3333333333.typ"));
+        let mut edits = edits;
+        edits.sort_by_key(|e| e.range.start.line);
+        assert_eq!(edits.len(), 2);
+        assert!(edits.iter().all(|e| e.new_text == "@2222222222"));
+        assert_eq!(edits[0].range.start.character, 4);
+        assert_eq!(edits[0].range.end.character, 15);
+        assert_eq!(edits[1].range.start.character, 5);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_archived_edit_refuses_active_note() {
+        let (tmp, index) = build_index("refuse").await;
+        let active = migrate_archived_edit(&index, "3333333333").await;
+        let missing = migrate_archived_edit(&index, "9999999999").await;
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert!(active.unwrap_err().contains("not archived"));
+        assert!(missing.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retire_edit_rewrites_every_outside_ref() {
+        let (tmp, index) = build_index("retire").await;
+        let edit = retire_edit(&index, "1111111111", "2222222222")
+            .await
+            .unwrap();
+        let same = retire_edit(&index, "1111111111", "1111111111").await;
+        let unknown = retire_edit(&index, "1111111111", "9999999999").await;
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let mut edits: Vec<(String, u32, u32)> = Vec::new();
+        for (uri, file_edits) in edit.changes.unwrap() {
+            let name = uri.path().rsplit('/').next().unwrap().to_string();
+            for e in file_edits {
+                assert_eq!(e.new_text, "@2222222222");
+                edits.push((name.clone(), e.range.start.line, e.range.start.character));
+            }
+        }
+        edits.sort();
+        // The retired note's self-reference is left alone; the file goes away.
+        assert_eq!(
+            edits,
+            vec![
+                ("2222222222.typ".to_string(), 11, 9),
+                ("3333333333.typ".to_string(), 11, 4),
+                ("3333333333.typ".to_string(), 12, 5),
+            ]
+        );
+        assert!(same.unwrap_err().contains("itself"));
+        assert!(unknown.unwrap_err().contains("9999999999"));
+    }
+
+    #[tokio::test]
+    async fn test_retire_edit_keeps_label_form() {
+        let (tmp, index) = build_index("retire_labels").await;
+        std::fs::write(tmp.join("zk-lsp.toml"), "label_refs = true\n").unwrap();
+        write(
+            &tmp.join("note"),
+            "4444444444",
+            &note(
+                "4444444444",
+                "active",
+                "",
+                "see <1111111111> and @<1111111111>",
+            ),
+        );
+        *index.config.write().await = WikiConfig::from_root(tmp.clone());
+        index.rebuild_full().await.unwrap();
+        let edit = retire_edit(&index, "1111111111", "2222222222")
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let changes = edit.changes.unwrap();
+        let (_, edits) = changes
+            .iter()
+            .find(|(uri, _)| uri.path().ends_with("4444444444.typ"))
+            .unwrap();
+        let mut texts: Vec<&str> = edits.iter().map(|e| e.new_text.as_str()).collect();
+        texts.sort();
+        assert_eq!(texts, vec!["<2222222222>", "@<2222222222>"]);
+    }
+}
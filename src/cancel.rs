@@ -0,0 +1,139 @@
+/// Cooperative cancellation for whole-wiki scans.
+///
+/// `tower-lsp` handles `$/cancelRequest` by dropping the request future, which
+/// cannot interrupt synchronous scan loops. Handlers therefore hold a
+/// `CancelOnDrop` guard while a scan runs on a blocking thread; dropping the
+/// future drops the guard, and the scan sees `is_cancelled()` and stops early.
+#[cfg(test)]
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    /// `is_cancelled` calls left before the token cancels itself.
+    #[cfg(test)]
+    fuse: Option<Arc<AtomicUsize>>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that cancels itself on its `checks`-th `is_cancelled` call,
+    /// so a test can stop a scan partway through.
+    #[cfg(test)]
+    pub fn cancel_after(checks: usize) -> Self {
+        CancelToken {
+            fuse: Some(Arc::new(AtomicUsize::new(checks))),
+            ..Self::default()
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        #[cfg(test)]
+        if let Some(fuse) = &self.fuse {
+            if fuse.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                == Ok(1)
+            {
+                self.cancel();
+            }
+        }
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Guard that cancels this token when dropped.
+    pub fn drop_guard(&self) -> CancelOnDrop {
+        CancelOnDrop(self.clone())
+    }
+}
+
+pub struct CancelOnDrop(CancelToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_guard_cancels_token() {
+        let token = CancelToken::new();
+        {
+            let _guard = token.drop_guard();
+            assert!(!token.is_cancelled());
+        }
+        assert!(token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request_future_cancels_index_search() {
+        use crate::config::WikiConfig;
+        use crate::index::NoteIndex;
+
+        let tmp = std::env::temp_dir().join("zk_cancel_test_search");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        for i in 0..200 {
+            let id = format!("260310{:02}{:02}", i / 60, i % 60);
+            std::fs::write(
+                tmp.join(format!("note/{id}.typ")),
+                format!(
+                    "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n= Note {i} <{id}>\n"
+                ),
+            )
+            .unwrap();
+        }
+        let config = WikiConfig::from_root(tmp.clone());
+        let index = Arc::new(NoteIndex::new(Arc::new(tokio::sync::RwLock::new(config))));
+        index.rebuild_full().await.unwrap();
+        assert_eq!(index.search("note", false, &CancelToken::new()).len(), 200);
+
+        let token = CancelToken::new();
+        let request = {
+            let guard = token.drop_guard();
+            async move {
+                let _guard = guard;
+                std::future::pending::<()>().await
+            }
+        };
+        // Simulate tower-lsp dropping the future on `$/cancelRequest`.
+        let _ = tokio::time::timeout(std::time::Duration::from_millis(5), request).await;
+        let scan_index = Arc::clone(&index);
+        let matches = tokio::task::spawn_blocking(move || scan_index.search("note", false, &token))
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert!(
+            matches.is_empty(),
+            "search ran to completion despite cancellation"
+        );
+    }
+
+    #[test]
+    fn test_search_cancelled_mid_scan_returns_partial_matches() {
+        use crate::index::{NoteIndex, NoteInfo};
+
+        let index =
+            NoteIndex::with_notes((0..200).map(|i| NoteInfo::stub(&format!("260310{i:04}"))));
+        let all = index.search("note", false, &CancelToken::new());
+        // The 50th check fires, after 49 notes have been ranked.
+        let partial = index.search("note", false, &CancelToken::cancel_after(50));
+        assert_eq!(all.len(), 200);
+        assert_eq!(partial.len(), 49);
+    }
+}
@@ -19,15 +19,36 @@ pub enum Command {
     Lsp,
     /// Regenerate link.typ from the note directory
     Generate,
+    /// Regenerate link.typ and report added, removed, reordered and re-pathed entries
+    NormalizeLinks,
     /// Create a new note and print its path to stdout
     New,
+    /// Print the path of today's daily note, creating it if needed
+    Daily,
     /// Delete a note and remove it from link.typ
     Remove {
         /// The 10-digit note ID (YYMMDDHHMM)
         id: String,
+        /// Move the note to .trash/ instead of deleting it (default: trash_on_delete)
+        #[arg(long)]
+        trash: bool,
+        /// Remove the note even if other notes still link to it
+        #[arg(long)]
+        force: bool,
+    },
+    /// Move a note back from .trash/ and add it to link.typ
+    Restore {
+        /// The 10-digit note ID (YYMMDDHHMM)
+        id: String,
     },
     /// Format a note: read from stdin, write formatted content to stdout
     Format,
+    /// Format every note in place and list the files that changed
+    FormatAll {
+        /// Only report notes that would change; exit 1 if any would
+        #[arg(long, default_value_t = false)]
+        check: bool,
+    },
     /// Migrate legacy comment-format notes to TOML schema v1
     Migrate,
     /// Reconcile cross-file checkbox states across the whole wiki
@@ -46,15 +67,40 @@ pub enum Command {
         /// Traverse inbound links instead of outgoing; output ancestors first, entry last
         #[arg(long, default_value_t = false)]
         inverse: bool,
+        /// Traverse outgoing links and backlinks together
+        #[arg(long, default_value_t = false, conflicts_with = "inverse")]
+        both: bool,
+        /// Stop adding notes once the document would exceed this many characters
+        #[arg(long)]
+        max_chars: Option<usize>,
+    },
+    /// Export the subgraph within N link hops of a note (both directions)
+    Neighborhood {
+        /// Center note ID (10-digit YYMMDDHHMM)
+        id: String,
+        /// Maximum number of hops from the center note
+        #[arg(long, short, default_value_t = 2)]
+        depth: usize,
+        /// Output format
+        #[arg(long, default_value = "dot", value_parser = ["dot", "json"])]
+        format: String,
     },
     /// Check graph integrity: dead links and orphan notes
     Check {
+        /// `-`: check one note read from stdin instead of the whole wiki
+        input: Option<String>,
+        /// With `-`: check only the note itself, without building the index
+        #[arg(long, requires = "input")]
+        no_index: bool,
         /// Only report dead links (skip orphan check)
         #[arg(long)]
         no_orphans: bool,
         /// Only report orphans (skip dead link check)
         #[arg(long)]
         no_dead_links: bool,
+        /// Do not report notes marked `generated = true` as orphans
+        #[arg(long)]
+        exclude_generated: bool,
     },
     /// Initialise a new wiki in the current directory (or --wiki-root)
     Init,
@@ -63,4 +109,84 @@ pub enum Command {
         /// The 10-digit note ID (YYMMDDHHMM)
         id: String,
     },
+    /// Print the absolute path of a note
+    Path {
+        /// The 10-digit note ID (YYMMDDHHMM)
+        id: String,
+    },
+    /// List references to a note as file:line:column (byte column)
+    Backlinks {
+        /// The 10-digit note ID (YYMMDDHHMM)
+        id: String,
+        /// Output JSON with both UTF-16 and byte offsets
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// List the most-referenced notes by number of distinct linking notes
+    Hubs {
+        /// How many notes to list
+        #[arg(long, short, default_value_t = 10)]
+        limit: usize,
+        /// Output JSON instead of a table
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// List notes that no other note links to
+    Leaves {
+        /// Also list archived notes
+        #[arg(long, default_value_t = false)]
+        include_archived: bool,
+        /// Output JSON instead of a list
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// List groups of notes with the same (or nearly the same) title
+    DupTitles {
+        /// Also group titles within this many character edits
+        #[arg(long, short, default_value_t = 0)]
+        distance: usize,
+        /// Also consider archived notes
+        #[arg(long, default_value_t = false)]
+        include_archived: bool,
+        /// Output JSON instead of a list
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// List notes created since a date (IDs are creation timestamps), oldest first
+    Since {
+        /// Start date: YYYY-MM-DD or "YYYY-MM-DD HH:MM"
+        date: String,
+    },
+    /// Count notes created per day, week or month (IDs are creation timestamps)
+    Timeline {
+        /// Bucket size; weeks are ISO weeks (`2026-W11`)
+        #[arg(long, default_value = "day", value_parser = ["day", "week", "month"])]
+        by: String,
+        /// Output JSON instead of `<period>  <count>` lines
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Print every keyword, alias, topic tag and status with note counts, as JSON
+    Vocab,
+    /// List notes whose status tag would change on format (dry run unless --fix)
+    StaleTags {
+        /// Rewrite the stale tags in place
+        #[arg(long, default_value_t = false)]
+        fix: bool,
+        /// Output JSON instead of a list
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// List note files the index skips because their header does not parse
+    Malformed {
+        /// Output JSON instead of a list
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// List references that still point at archived notes with a replacement
+    Migrations {
+        /// Print the result as JSON instead of a checklist
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
 }
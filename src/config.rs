@@ -1,6 +1,11 @@
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use tower_lsp::lsp_types::{InitializeParams, Url};
+use regex::Regex;
+use tower_lsp::lsp_types::{DiagnosticSeverity, InitializeParams, Url};
+
+use crate::parser::{self, Parser, ParserConfig};
 
 /// Core TOML metadata fields that cannot be overridden by user-defined fields.
 const CORE_METADATA_FIELDS: &[&str] = &[
@@ -9,11 +14,19 @@ const CORE_METADATA_FIELDS: &[&str] = &[
     "abstract",
     "keywords",
     "generated",
+    "auto-status",
     "checklist-status",
     "relation",
     "relation-target",
 ];
 
+/// `max_note_bytes` when unset: 8 MiB, far above any hand-written note.
+pub const DEFAULT_MAX_NOTE_BYTES: u64 = 8 * 1024 * 1024;
+
+/// `checkbox_ref_depth` when unset: formatting leaves `@ID` checkboxes to
+/// `reconcile`.
+pub const DEFAULT_CHECKBOX_REF_DEPTH: usize = 0;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum MetadataFieldKind {
     String,
@@ -208,15 +221,161 @@ fn parse_reconcile_rules_config(table: &toml::Table) -> Vec<PathBuf> {
     rules
 }
 
+fn parse_string_list(table: &toml::Table, key: &str) -> Vec<String> {
+    match table.get(key) {
+        None => Vec::new(),
+        Some(toml::Value::Array(arr)) => arr
+            .iter()
+            .filter_map(|v| match v.as_str() {
+                Some(s) => Some(s.to_string()),
+                None => {
+                    eprintln!("zk-lsp config: '{key}' entries must be strings");
+                    None
+                }
+            })
+            .collect(),
+        Some(_) => {
+            eprintln!("zk-lsp config: '{key}' must be an array of strings");
+            Vec::new()
+        }
+    }
+}
+
+fn parse_subdir_layout(table: &toml::Table) -> Option<SubdirLayout> {
+    let value = table.get("subdir_layout")?;
+    let layout = value.as_str().and_then(SubdirLayout::from_setting);
+    if layout.is_none() {
+        eprintln!("zk-lsp config: 'subdir_layout' must be \"flat\" or \"yearMonth\"");
+    }
+    layout
+}
+
+fn parse_reference_resolution(table: &toml::Table) -> Option<ReferenceResolution> {
+    let value = table.get("reference_resolution")?;
+    let resolution = value.as_str().and_then(ReferenceResolution::from_setting);
+    if resolution.is_none() {
+        eprintln!("zk-lsp config: 'reference_resolution' must be \"global\" or \"relative\"");
+    }
+    resolution
+}
+
+/// `[wikis]` table: wiki name → root path.
+fn parse_wikis_config(table: &toml::Table) -> BTreeMap<String, PathBuf> {
+    let Some(wikis) = table.get("wikis") else {
+        return BTreeMap::new();
+    };
+    let Some(wikis) = wikis.as_table() else {
+        eprintln!("zk-lsp config: 'wikis' must be a table of name = \"path\"");
+        return BTreeMap::new();
+    };
+    let mut out = BTreeMap::new();
+    for (name, path) in wikis {
+        match path.as_str() {
+            Some(path) => {
+                out.insert(name.clone(), expand_tilde(path));
+            }
+            None => eprintln!("zk-lsp config: wiki '{name}' must be a path string"),
+        }
+    }
+    out
+}
+
+fn parse_ref_style(table: &toml::Table) -> Option<RefStyle> {
+    let value = table.get("ref_style")?;
+    let style = value.as_str().and_then(RefStyle::from_setting);
+    if style.is_none() {
+        eprintln!("zk-lsp config: 'ref_style' must be \"at\" or \"wikilink\"");
+    }
+    style
+}
+
+fn parse_max_note_bytes(table: &toml::Table) -> Option<u64> {
+    let value = table.get("max_note_bytes")?;
+    let bytes = value.as_integer().and_then(|n| u64::try_from(n).ok());
+    if bytes.is_none() {
+        eprintln!("zk-lsp config: 'max_note_bytes' must be a non-negative integer");
+    }
+    bytes
+}
+
+fn parse_checkbox_ref_depth(table: &toml::Table) -> Option<usize> {
+    let value = table.get("checkbox_ref_depth")?;
+    let depth = value.as_integer().and_then(|n| usize::try_from(n).ok());
+    if depth.is_none() {
+        eprintln!("zk-lsp config: 'checkbox_ref_depth' must be a non-negative integer");
+    }
+    depth
+}
+
+fn parse_import_marker(table: &toml::Table) -> Option<Regex> {
+    let pattern = table.get("import_marker")?;
+    let Some(pattern) = pattern.as_str() else {
+        eprintln!("zk-lsp config: 'import_marker' must be a string");
+        return None;
+    };
+    match Regex::new(pattern) {
+        Ok(re) => Some(re),
+        Err(e) => {
+            eprintln!("zk-lsp config: invalid import_marker '{pattern}': {e}");
+            None
+        }
+    }
+}
+
+/// Resolve `template_file` relative to the directory holding the config file.
+fn read_template_file(config_path: &Path, template: &str) -> Option<String> {
+    let path = expand_tilde(template);
+    let path = match config_path.parent() {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path,
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(s) => Some(s),
+        Err(e) => {
+            eprintln!(
+                "zk-lsp config: cannot read template_file {}: {e}",
+                path.display()
+            );
+            None
+        }
+    }
+}
+
+/// Match a file name against an `ignore` pattern; `*` matches any run of characters.
+fn matches_ignore_pattern(pattern: &str, name: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if parts.len() == 1 {
+        return pattern == name;
+    }
+    if name.len() < first.len() + last.len() || !name.starts_with(first) || !name.ends_with(last) {
+        return false;
+    }
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
 /// Merged zk-lsp configuration.
 ///
 /// Load order (later overrides earlier):
 /// 1. `$XDG_CONFIG_HOME/zk-lsp/config.toml`  (user-level)
-/// 2. `<wiki-root>/zk-lsp.toml`               (project-level)
+/// 2. `<wiki-root>/zk-lsp.toml`, or `<wiki-root>/.zk-lsp.toml` when the
+///    former does not exist                   (project-level)
 #[derive(Debug, Clone, Default)]
 pub struct ZkLspConfig {
     /// Custom template for `zk-lsp new`. Supports `{{id}}` and `{{metadata}}`.
+    /// Set inline via `[new_note] template` or loaded from `template_file`.
     pub new_note_template: Option<String>,
+    /// Note directory, relative to the wiki root (default `note`).
+    pub note_dir: Option<PathBuf>,
+    /// File-name patterns (`*` wildcard) of notes to leave out of the index and link.typ.
+    pub ignore: Vec<String>,
     /// User-defined metadata fields added to new notes.
     pub metadata: MetadataConfig,
     /// Lua hook scripts to run during formatting, in order (after default hooks).
@@ -227,6 +386,45 @@ pub struct ZkLspConfig {
     pub disable_default_hooks: bool,
     /// If true, do not preload the built-in reconcile DSL module.
     pub disable_default_reconcile_rules: bool,
+    /// Regex matched against each trimmed line to find a note's `#import`
+    /// anchor (e.g. when migrating legacy notes). `None` uses
+    /// `parser::DEFAULT_IMPORT_MARKER`.
+    pub import_marker: Option<Regex>,
+    /// If true, body `<ID>` / `@<ID>` Typst labels also count as links.
+    pub label_refs: bool,
+    /// Where new notes are created under `note_dir`. `None` means flat.
+    pub subdir_layout: Option<SubdirLayout>,
+    /// If true, removed notes are moved to `.trash/` instead of deleted.
+    pub trash_on_delete: bool,
+    /// How navigation resolves an `@ID` from the note it is written in.
+    /// `None` means global.
+    pub reference_resolution: Option<ReferenceResolution>,
+    /// If true, symlinked date directories in `note_dir` are scanned and
+    /// notes opened through their symlink target map back to the link.
+    pub follow_symlinks: bool,
+    /// Other wikis `@<name>:<ID>` references may point into, by name. Paths
+    /// are relative to this wiki's root.
+    pub wikis: BTreeMap<String, PathBuf>,
+    /// Body reference syntax. `None` means `@ID`.
+    pub ref_style: Option<RefStyle>,
+    /// If true, `[[Note Title]]` links are resolved against note titles and
+    /// aliases.
+    pub title_links: bool,
+    /// If true, link.typ lists each note's aliases as `#zk_alias` lines.
+    pub include_aliases_in_link_typ: bool,
+    /// If true, notes that are not valid UTF-8 are indexed with the bad
+    /// bytes replaced by U+FFFD instead of being skipped.
+    pub lossy_reads: bool,
+    /// Note files larger than this are not indexed. `None` means
+    /// `DEFAULT_MAX_NOTE_BYTES`.
+    pub max_note_bytes: Option<u64>,
+    /// How many levels of `@ID` references formatting follows to decide
+    /// whether a referenced note is done. `None` means
+    /// `DEFAULT_CHECKBOX_REF_DEPTH`; 0 turns the check off.
+    pub checkbox_ref_depth: Option<usize>,
+    /// If true, formatting also trims trailing whitespace and collapses runs
+    /// of three or more blank lines into one.
+    pub format_whitespace: bool,
 }
 
 impl ZkLspConfig {
@@ -241,19 +439,50 @@ impl ZkLspConfig {
         base.join("zk-lsp").join("config.toml")
     }
 
+    /// Project-level config path: `zk-lsp.toml`, falling back to `.zk-lsp.toml`.
+    pub fn project_config_path(wiki_root: &Path) -> PathBuf {
+        let visible = wiki_root.join("zk-lsp.toml");
+        let hidden = wiki_root.join(".zk-lsp.toml");
+        if !visible.exists() && hidden.exists() {
+            hidden
+        } else {
+            visible
+        }
+    }
+
     fn from_path(path: &Path) -> Self {
-        let Ok(raw) = std::fs::read_to_string(path) else {
-            return Self::default();
+        let raw = match std::fs::read_to_string(path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                eprintln!("zk-lsp config: cannot read {}: {e}", path.display());
+                return Self::default();
+            }
         };
-        let Ok(table) = raw.parse::<toml::Table>() else {
-            return Self::default();
+        let table = match raw.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(e) => {
+                eprintln!("zk-lsp config: failed to parse {}: {e}", path.display());
+                return Self::default();
+            }
         };
         Self {
             new_note_template: table
                 .get("new_note")
                 .and_then(|v| v.get("template"))
                 .and_then(|v| v.as_str())
-                .map(String::from),
+                .map(String::from)
+                .or_else(|| {
+                    table
+                        .get("template_file")
+                        .and_then(|v| v.as_str())
+                        .and_then(|t| read_template_file(path, t))
+                }),
+            note_dir: table
+                .get("note_dir")
+                .and_then(|v| v.as_str())
+                .map(expand_tilde),
+            ignore: parse_string_list(&table, "ignore"),
             metadata: parse_metadata_config(&table),
             hooks: parse_hooks_config(&table),
             reconcile_rules: parse_reconcile_rules_config(&table),
@@ -265,6 +494,41 @@ impl ZkLspConfig {
                 .get("disable_default_reconcile_rules")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false),
+            import_marker: parse_import_marker(&table),
+            label_refs: table
+                .get("label_refs")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            subdir_layout: parse_subdir_layout(&table),
+            trash_on_delete: table
+                .get("trash_on_delete")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            reference_resolution: parse_reference_resolution(&table),
+            follow_symlinks: table
+                .get("follow_symlinks")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            wikis: parse_wikis_config(&table),
+            ref_style: parse_ref_style(&table),
+            title_links: table
+                .get("title_links")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            include_aliases_in_link_typ: table
+                .get("include_aliases_in_link_typ")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            lossy_reads: table
+                .get("lossy_reads")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            max_note_bytes: parse_max_note_bytes(&table),
+            checkbox_ref_depth: parse_checkbox_ref_depth(&table),
+            format_whitespace: table
+                .get("format_whitespace")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
         }
     }
 
@@ -274,30 +538,58 @@ impl ZkLspConfig {
     /// then project), so both levels apply in order.
     pub fn load(wiki_root: &Path) -> Self {
         let user = Self::from_path(&Self::user_config_path());
-        let project = Self::from_path(&wiki_root.join("zk-lsp.toml"));
+        let project = Self::from_path(&Self::project_config_path(wiki_root));
         let mut hooks = user.hooks;
         hooks.extend(project.hooks);
         let mut reconcile_rules = user.reconcile_rules;
         reconcile_rules.extend(project.reconcile_rules);
+        let mut ignore = user.ignore;
+        ignore.extend(project.ignore);
+        let mut wikis = user.wikis;
+        wikis.extend(project.wikis);
         Self {
             new_note_template: project.new_note_template.or(user.new_note_template),
+            note_dir: project.note_dir.or(user.note_dir),
+            ignore,
             metadata: merge_metadata(user.metadata, project.metadata),
             hooks,
             reconcile_rules,
             disable_default_hooks: user.disable_default_hooks || project.disable_default_hooks,
             disable_default_reconcile_rules: user.disable_default_reconcile_rules
                 || project.disable_default_reconcile_rules,
+            import_marker: project.import_marker.or(user.import_marker),
+            label_refs: user.label_refs || project.label_refs,
+            subdir_layout: project.subdir_layout.or(user.subdir_layout),
+            trash_on_delete: user.trash_on_delete || project.trash_on_delete,
+            reference_resolution: project.reference_resolution.or(user.reference_resolution),
+            follow_symlinks: user.follow_symlinks || project.follow_symlinks,
+            wikis,
+            ref_style: project.ref_style.or(user.ref_style),
+            title_links: user.title_links || project.title_links,
+            include_aliases_in_link_typ: user.include_aliases_in_link_typ
+                || project.include_aliases_in_link_typ,
+            lossy_reads: user.lossy_reads || project.lossy_reads,
+            max_note_bytes: project.max_note_bytes.or(user.max_note_bytes),
+            checkbox_ref_depth: project.checkbox_ref_depth.or(user.checkbox_ref_depth),
+            format_whitespace: user.format_whitespace || project.format_whitespace,
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct WikiConfig {
-    #[allow(dead_code)]
     pub root: PathBuf,
     pub note_dir: PathBuf,
     pub link_file: PathBuf,
     pub zk_config: ZkLspConfig,
+    /// Editor settings layered on top of the config files.
+    pub settings: ClientSettings,
+    /// Built from `zk_config` once, so its regexes are compiled once per
+    /// config load rather than per use.
+    parser: Arc<Parser>,
+    /// Configs of `[wikis]` entries by root, loaded on first use and kept for
+    /// the life of this config load.
+    linked_wikis: Arc<std::sync::Mutex<HashMap<PathBuf, Arc<WikiConfig>>>>,
 }
 
 impl WikiConfig {
@@ -316,17 +608,41 @@ impl WikiConfig {
     }
 
     pub fn from_root(root: PathBuf) -> Self {
-        let note_dir = root.join("note");
-        let link_file = root.join("link.typ");
         let zk_config = ZkLspConfig::load(&root);
+        let note_dir = match &zk_config.note_dir {
+            Some(dir) => root.join(dir),
+            None => root.join("note"),
+        };
+        let link_file = root.join("link.typ");
+        let parser = Arc::new(Parser::new(ParserConfig::from_zk_config(&zk_config)));
         WikiConfig {
             root,
             note_dir,
             link_file,
             zk_config,
+            settings: ClientSettings::default(),
+            parser,
+            linked_wikis: Default::default(),
         }
     }
 
+    /// Parser configured from the wiki's config files.
+    pub fn parser(&self) -> Arc<Parser> {
+        Arc::clone(&self.parser)
+    }
+
+    /// Apply editor settings on top of the file-based config.
+    pub fn with_settings(mut self, settings: ClientSettings) -> Self {
+        if let Some(dir) = &settings.note_dir {
+            self.note_dir = self.root.join(dir);
+        }
+        if let Some(ignore) = &settings.ignore {
+            self.zk_config.ignore = ignore.clone();
+        }
+        self.settings = settings;
+        self
+    }
+
     pub fn lsp_root(params: &InitializeParams) -> Option<PathBuf> {
         params
             .initialization_options
@@ -341,6 +657,435 @@ impl WikiConfig {
                     .and_then(|folder| url_to_path(&folder.uri))
             })
     }
+
+    /// True if `path` matches one of the configured `ignore` patterns.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return false;
+        };
+        self.zk_config
+            .ignore
+            .iter()
+            .any(|pattern| matches_ignore_pattern(pattern, name))
+    }
+
+    /// Where note `id` belongs under the configured `subdir_layout`:
+    /// `note_dir/<id>.typ`, or `note_dir/YYYY/MM/<id>.typ` for `yearMonth`
+    /// (flat when the ID is not a valid timestamp).
+    pub fn note_path(&self, id: &str) -> PathBuf {
+        match self.zk_config.subdir_layout.unwrap_or_default() {
+            SubdirLayout::Flat => self.note_dir.join(format!("{id}.typ")),
+            SubdirLayout::YearMonth => self.dated_note_path(id),
+        }
+    }
+
+    fn dated_note_path(&self, id: &str) -> PathBuf {
+        match parser::id_to_datetime(id) {
+            Some(created) => self
+                .note_dir
+                .join(created.format("%Y/%m").to_string())
+                .join(format!("{id}.typ")),
+            None => self.note_dir.join(format!("{id}.typ")),
+        }
+    }
+
+    /// The existing file of note `id`, flat or dated whatever the current
+    /// layout, so notes made before a layout change are still found. Prefer
+    /// `NoteIndex::path_of` where the index is at hand.
+    pub fn locate_note(&self, id: &str) -> Option<PathBuf> {
+        [
+            self.note_path(id),
+            self.note_dir.join(format!("{id}.typ")),
+            self.dated_note_path(id),
+        ]
+        .into_iter()
+        .find(|path| path.is_file())
+    }
+
+    /// Size above which note files are skipped by the index and watcher.
+    pub fn max_note_bytes(&self) -> u64 {
+        self.zk_config
+            .max_note_bytes
+            .unwrap_or(DEFAULT_MAX_NOTE_BYTES)
+    }
+
+    /// Reference levels followed when formatting syncs `@ID` checkboxes.
+    pub fn checkbox_ref_depth(&self) -> usize {
+        self.zk_config
+            .checkbox_ref_depth
+            .unwrap_or(DEFAULT_CHECKBOX_REF_DEPTH)
+    }
+
+    /// The configured body reference syntax (`@ID` unless `ref_style` is set).
+    pub fn ref_style(&self) -> RefStyle {
+        self.zk_config.ref_style.unwrap_or_default()
+    }
+
+    /// Config of the wiki `name` from `[wikis]`, or `None` if it is not
+    /// listed. Each linked wiki's config files are read once per load of
+    /// this config.
+    pub fn linked_wiki(&self, name: &str) -> Option<Arc<WikiConfig>> {
+        let root = self.root.join(self.zk_config.wikis.get(name)?);
+        let mut linked = self.linked_wikis.lock().unwrap_or_else(|e| e.into_inner());
+        let config = linked
+            .entry(root.clone())
+            .or_insert_with(|| Arc::new(WikiConfig::from_root(root)));
+        Some(Arc::clone(config))
+    }
+
+    /// The `links.json` sidecar written when `writeLinksJson` is on.
+    pub fn links_json_path(&self) -> PathBuf {
+        self.root.join("links.json")
+    }
+
+    /// Where a soft-deleted note `id` is kept: `<root>/.trash/<id>.typ`.
+    pub fn trash_path(&self, id: &str) -> PathBuf {
+        self.root.join(".trash").join(format!("{id}.typ"))
+    }
+
+    /// True if `path` is one of the project-level config files at the wiki root.
+    pub fn is_project_config_file(&self, path: &Path) -> bool {
+        path == self.root.join("zk-lsp.toml") || path == self.root.join(".zk-lsp.toml")
+    }
+
+    /// True if switching from `self` to `other` changes which files are indexed.
+    pub fn needs_reindex(&self, other: &WikiConfig) -> bool {
+        self.note_dir != other.note_dir || self.zk_config.ignore != other.zk_config.ignore
+    }
+}
+
+/// Directory layout for new notes (`subdir_layout`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SubdirLayout {
+    /// Every note directly in `note_dir`.
+    #[default]
+    Flat,
+    /// `note_dir/YYYY/MM/`, from the date encoded in the note ID.
+    YearMonth,
+}
+
+impl SubdirLayout {
+    fn from_setting(s: &str) -> Option<Self> {
+        match s {
+            "flat" => Some(Self::Flat),
+            "yearMonth" | "year_month" => Some(Self::YearMonth),
+            _ => None,
+        }
+    }
+}
+
+/// Which file an `@ID` resolves to (`reference_resolution`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReferenceResolution {
+    /// The indexed note with that ID.
+    #[default]
+    Global,
+    /// A `<ID>.typ` in the referencing note's directory or a parent of it
+    /// inside `note_dir`, before the indexed note.
+    Relative,
+}
+
+impl ReferenceResolution {
+    fn from_setting(s: &str) -> Option<Self> {
+        match s {
+            "global" => Some(Self::Global),
+            "relative" => Some(Self::Relative),
+            _ => None,
+        }
+    }
+}
+
+/// How notes reference each other in the body (`ref_style`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RefStyle {
+    /// `@2602082037`
+    #[default]
+    At,
+    /// `[[2602082037]]`
+    Wikilink,
+}
+
+impl RefStyle {
+    fn from_setting(s: &str) -> Option<Self> {
+        match s {
+            "at" => Some(Self::At),
+            "wikilink" => Some(Self::Wikilink),
+            _ => None,
+        }
+    }
+
+    /// The reference to `id` as written in this style.
+    pub fn format(self, id: &str) -> String {
+        match self {
+            RefStyle::At => format!("@{id}"),
+            RefStyle::Wikilink => format!("[[{id}]]"),
+        }
+    }
+}
+
+/// What an `@ID` inlay hint shows (`inlayHintLabel`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HintLabel {
+    #[default]
+    Title,
+    /// The first alias, or the title when the note has none.
+    Alias,
+    /// The title followed by the first alias in parentheses.
+    Both,
+}
+
+impl HintLabel {
+    fn from_setting(s: &str) -> Option<Self> {
+        match s {
+            "title" => Some(Self::Title),
+            "alias" => Some(Self::Alias),
+            "both" => Some(Self::Both),
+            _ => None,
+        }
+    }
+}
+
+/// Which `@ID` references get an inlay hint (`inlayHintFilter`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HintFilter {
+    #[default]
+    All,
+    /// Only references to archived or legacy notes, marked as such.
+    ArchivedLegacy,
+    /// No inlay hints at all.
+    Off,
+}
+
+impl HintFilter {
+    fn from_setting(s: &str) -> Option<Self> {
+        match s {
+            "all" => Some(Self::All),
+            "archivedLegacy" | "archived_legacy" => Some(Self::ArchivedLegacy),
+            "none" => Some(Self::Off),
+            _ => None,
+        }
+    }
+}
+
+/// Editor-side settings sent through `initializationOptions` or
+/// `workspace/didChangeConfiguration`.
+///
+/// Keys are accepted in camelCase (LSP convention) or snake_case, either at the
+/// top level or under a `"zk-lsp"` section. Unknown keys are ignored.
+#[derive(Debug, Clone)]
+pub struct ClientSettings {
+    /// Run the internal `notify` watcher even when the client can deliver
+    /// `workspace/didChangeWatchedFiles` events itself.
+    pub force_internal_watcher: bool,
+    /// Overrides `note_dir` from the config files.
+    pub note_dir: Option<PathBuf>,
+    /// Overrides `ignore` from the config files.
+    pub ignore: Option<Vec<String>>,
+    /// Notes per batch when publishing workspace-wide diagnostics.
+    pub workspace_diagnostics_batch_size: usize,
+    /// Report notes without a TOML metadata block (off by default).
+    pub missing_metadata_diagnostic: bool,
+    /// Format the note after `didSave` and push the result with
+    /// `workspace/applyEdit` (off by default).
+    pub format_on_save: bool,
+    /// Leave `generated = true` notes out of workspace symbol search and
+    /// orphan hints (off by default).
+    pub exclude_generated: bool,
+    /// Most workspace symbols returned per query, best matches first.
+    pub max_symbol_results: usize,
+    /// Label of `@ID` inlay hints.
+    pub inlay_hint_label: HintLabel,
+    /// Which references get an inlay hint.
+    pub inlay_hint_filter: HintFilter,
+    /// Characters of a note abstract shown in hover and completion popups.
+    pub max_preview_chars: usize,
+    /// List notes sharing keywords with the hovered note (off by default).
+    pub hover_see_also: bool,
+    /// Keep `links.json` at the wiki root in sync with the index (off by default).
+    pub write_links_json: bool,
+    /// `@ID` diagnostic kinds (`DiagnosticData::kind`) that are never reported.
+    pub disabled_diagnostics: Vec<String>,
+    /// Severity overrides per `@ID` diagnostic kind.
+    pub diagnostic_severity: HashMap<String, DiagnosticSeverity>,
+    /// Index a note's `@<own ID>` as a backlink to itself (off by default).
+    pub count_self_references: bool,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        Self {
+            force_internal_watcher: false,
+            note_dir: None,
+            ignore: None,
+            workspace_diagnostics_batch_size: 50,
+            missing_metadata_diagnostic: false,
+            format_on_save: false,
+            exclude_generated: false,
+            max_symbol_results: 200,
+            inlay_hint_label: HintLabel::Title,
+            inlay_hint_filter: HintFilter::All,
+            max_preview_chars: 280,
+            hover_see_also: false,
+            write_links_json: false,
+            disabled_diagnostics: Vec::new(),
+            diagnostic_severity: HashMap::new(),
+            count_self_references: false,
+        }
+    }
+}
+
+impl ClientSettings {
+    pub fn from_json(value: &serde_json::Value) -> Self {
+        Self::default().merge_json(value)
+    }
+
+    /// These settings with the keys present in `value` replaced, so a
+    /// partial `didChangeConfiguration` payload leaves the rest as they are.
+    /// A present key with an invalid value keeps the current setting.
+    pub fn merge_json(&self, value: &serde_json::Value) -> Self {
+        let value = value.get("zk-lsp").unwrap_or(value);
+        let current = self.clone();
+        Self {
+            force_internal_watcher: json_setting(
+                value,
+                "forceInternalWatcher",
+                "force_internal_watcher",
+            )
+            .and_then(|v| v.as_bool())
+            .unwrap_or(current.force_internal_watcher),
+            note_dir: json_setting(value, "noteDir", "note_dir")
+                .and_then(|v| v.as_str())
+                .map(expand_tilde)
+                .or(current.note_dir),
+            ignore: json_setting(value, "ignore", "ignore")
+                .and_then(|v| v.as_array())
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_str().map(String::from))
+                        .collect()
+                })
+                .or(current.ignore),
+            workspace_diagnostics_batch_size: json_setting(
+                value,
+                "workspaceDiagnosticsBatchSize",
+                "workspace_diagnostics_batch_size",
+            )
+            .and_then(|v| v.as_u64())
+            .filter(|n| *n > 0)
+            .map(|n| n as usize)
+            .unwrap_or(current.workspace_diagnostics_batch_size),
+            missing_metadata_diagnostic: json_setting(
+                value,
+                "missingMetadataDiagnostic",
+                "missing_metadata_diagnostic",
+            )
+            .and_then(|v| v.as_bool())
+            .unwrap_or(current.missing_metadata_diagnostic),
+            format_on_save: json_setting(value, "formatOnSave", "format_on_save")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(current.format_on_save),
+            exclude_generated: json_setting(value, "excludeGenerated", "exclude_generated")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(current.exclude_generated),
+            max_symbol_results: json_setting(value, "maxSymbolResults", "max_symbol_results")
+                .and_then(|v| v.as_u64())
+                .filter(|n| *n > 0)
+                .map(|n| n as usize)
+                .unwrap_or(current.max_symbol_results),
+            inlay_hint_label: json_setting(value, "inlayHintLabel", "inlay_hint_label")
+                .and_then(|v| v.as_str())
+                .and_then(HintLabel::from_setting)
+                .unwrap_or(current.inlay_hint_label),
+            inlay_hint_filter: json_setting(value, "inlayHintFilter", "inlay_hint_filter")
+                .and_then(|v| v.as_str())
+                .and_then(HintFilter::from_setting)
+                .unwrap_or(current.inlay_hint_filter),
+            max_preview_chars: json_setting(value, "maxPreviewChars", "max_preview_chars")
+                .and_then(|v| v.as_u64())
+                .filter(|n| *n > 0)
+                .map(|n| n as usize)
+                .unwrap_or(current.max_preview_chars),
+            hover_see_also: json_setting(value, "hoverSeeAlso", "hover_see_also")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(current.hover_see_also),
+            write_links_json: json_setting(value, "writeLinksJson", "write_links_json")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(current.write_links_json),
+            disabled_diagnostics: json_setting(
+                value,
+                "disabledDiagnostics",
+                "disabled_diagnostics",
+            )
+            .and_then(|v| v.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|v| v.as_str().map(diagnostic_kind))
+                    .collect()
+            })
+            .unwrap_or(current.disabled_diagnostics),
+            diagnostic_severity: json_setting(value, "diagnosticSeverity", "diagnostic_severity")
+                .and_then(|v| v.as_object())
+                .map(|map| {
+                    map.iter()
+                        .filter_map(|(kind, level)| {
+                            Some((
+                                diagnostic_kind(kind),
+                                severity_from_setting(level.as_str()?)?,
+                            ))
+                        })
+                        .collect()
+                })
+                .unwrap_or(current.diagnostic_severity),
+            count_self_references: json_setting(
+                value,
+                "countSelfReferences",
+                "count_self_references",
+            )
+            .and_then(|v| v.as_bool())
+            .unwrap_or(current.count_self_references),
+        }
+    }
+
+    /// Severity for an `@ID` diagnostic of `kind` whose built-in severity is
+    /// `default`, or `None` when the kind is disabled.
+    pub fn severity_for(
+        &self,
+        kind: &str,
+        default: DiagnosticSeverity,
+    ) -> Option<DiagnosticSeverity> {
+        if self.disabled_diagnostics.iter().any(|k| k == kind) {
+            return None;
+        }
+        Some(
+            self.diagnostic_severity
+                .get(kind)
+                .copied()
+                .unwrap_or(default),
+        )
+    }
+}
+
+/// Diagnostic kinds are kebab-case (`dead-link`); accept snake_case too.
+fn diagnostic_kind(s: &str) -> String {
+    s.replace('_', "-")
+}
+
+fn severity_from_setting(s: &str) -> Option<DiagnosticSeverity> {
+    match s {
+        "error" => Some(DiagnosticSeverity::ERROR),
+        "warning" => Some(DiagnosticSeverity::WARNING),
+        "information" | "info" => Some(DiagnosticSeverity::INFORMATION),
+        "hint" => Some(DiagnosticSeverity::HINT),
+        _ => None,
+    }
+}
+
+fn json_setting<'a>(
+    value: &'a serde_json::Value,
+    camel: &str,
+    snake: &str,
+) -> Option<&'a serde_json::Value> {
+    value.get(camel).or_else(|| value.get(snake))
 }
 
 fn parse_root_dir_value(value: &serde_json::Value) -> Option<PathBuf> {
@@ -364,6 +1109,8 @@ mod tests {
         let table = toml_str.parse::<toml::Table>().unwrap();
         ZkLspConfig {
             new_note_template: None,
+            note_dir: None,
+            ignore: parse_string_list(&table, "ignore"),
             metadata: parse_metadata_config(&table),
             hooks: parse_hooks_config(&table),
             reconcile_rules: parse_reconcile_rules_config(&table),
@@ -375,6 +1122,41 @@ mod tests {
                 .get("disable_default_reconcile_rules")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false),
+            import_marker: parse_import_marker(&table),
+            label_refs: table
+                .get("label_refs")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            subdir_layout: parse_subdir_layout(&table),
+            trash_on_delete: table
+                .get("trash_on_delete")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            reference_resolution: parse_reference_resolution(&table),
+            follow_symlinks: table
+                .get("follow_symlinks")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            wikis: parse_wikis_config(&table),
+            ref_style: parse_ref_style(&table),
+            title_links: table
+                .get("title_links")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            include_aliases_in_link_typ: table
+                .get("include_aliases_in_link_typ")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            lossy_reads: table
+                .get("lossy_reads")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            max_note_bytes: parse_max_note_bytes(&table),
+            checkbox_ref_depth: parse_checkbox_ref_depth(&table),
+            format_whitespace: table
+                .get("format_whitespace")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
         }
     }
 
@@ -703,4 +1485,302 @@ name = "missing"
         assert_eq!(merged.fields.len(), 1);
         assert_eq!(merged.fields[0].default.as_str(), Some("project"));
     }
+
+    #[test]
+    fn test_client_settings_force_internal_watcher() {
+        let default = ClientSettings::from_json(&serde_json::json!({}));
+        assert!(!default.force_internal_watcher);
+        let camel = ClientSettings::from_json(&serde_json::json!({"forceInternalWatcher": true}));
+        assert!(camel.force_internal_watcher);
+        let snake = ClientSettings::from_json(&serde_json::json!({"force_internal_watcher": true}));
+        assert!(snake.force_internal_watcher);
+    }
+
+    #[test]
+    fn test_client_settings_merge_keeps_absent_keys() {
+        let current = ClientSettings::from_json(&serde_json::json!({
+            "forceInternalWatcher": true,
+            "workspaceDiagnosticsBatchSize": 10,
+            "noteDir": "notes",
+        }));
+        let merged = current.merge_json(&serde_json::json!({"zk-lsp": {"formatOnSave": true}}));
+        assert!(merged.format_on_save);
+        assert!(merged.force_internal_watcher);
+        assert_eq!(merged.workspace_diagnostics_batch_size, 10);
+        assert_eq!(merged.note_dir, Some(PathBuf::from("notes")));
+
+        let off = merged.merge_json(&serde_json::json!({"forceInternalWatcher": false}));
+        assert!(!off.force_internal_watcher);
+        assert!(off.format_on_save);
+    }
+
+    #[test]
+    fn test_client_settings_format_on_save() {
+        assert!(!ClientSettings::from_json(&serde_json::json!({})).format_on_save);
+        let camel = ClientSettings::from_json(&serde_json::json!({"formatOnSave": true}));
+        assert!(camel.format_on_save);
+        let snake = ClientSettings::from_json(&serde_json::json!({"format_on_save": true}));
+        assert!(snake.format_on_save);
+    }
+
+    #[test]
+    fn test_note_dir_and_ignore_parsing() {
+        let table = r#"
+note_dir = "notes"
+ignore = ["26*.typ", "2601010000.typ"]
+"#
+        .parse::<toml::Table>()
+        .unwrap();
+        let tmp = std::env::temp_dir().join("zk_config_test_note_dir");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(&tmp).unwrap();
+        std::fs::write(tmp.join(".zk-lsp.toml"), table.to_string()).unwrap();
+        let config = WikiConfig::from_root(tmp.clone());
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert_eq!(config.note_dir, tmp.join("notes"));
+        assert!(config.is_ignored(Path::new("notes/2603110000.typ")));
+        assert!(config.is_ignored(Path::new("2601010000.typ")));
+        assert!(!config.is_ignored(Path::new("2501010000.typ")));
+    }
+
+    #[test]
+    fn test_visible_project_config_wins_over_hidden() {
+        let tmp = std::env::temp_dir().join("zk_config_test_project_path");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(&tmp).unwrap();
+        std::fs::write(tmp.join(".zk-lsp.toml"), "").unwrap();
+        let hidden = ZkLspConfig::project_config_path(&tmp);
+        std::fs::write(tmp.join("zk-lsp.toml"), "").unwrap();
+        let visible = ZkLspConfig::project_config_path(&tmp);
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert_eq!(hidden, tmp.join(".zk-lsp.toml"));
+        assert_eq!(visible, tmp.join("zk-lsp.toml"));
+    }
+
+    #[test]
+    fn test_ignore_pattern_matching() {
+        assert!(matches_ignore_pattern("*.typ", "2603110000.typ"));
+        assert!(matches_ignore_pattern("26*00.typ", "2603110000.typ"));
+        assert!(matches_ignore_pattern("*", "anything"));
+        assert!(!matches_ignore_pattern("25*", "2603110000.typ"));
+        assert!(!matches_ignore_pattern("2603110000", "2603110000.typ"));
+    }
+
+    #[test]
+    fn test_template_file_relative_to_config() {
+        let tmp = std::env::temp_dir().join("zk_config_test_template_file");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(&tmp).unwrap();
+        std::fs::write(tmp.join("tmpl.typ"), "= {{id}}\n").unwrap();
+        std::fs::write(tmp.join("zk-lsp.toml"), "template_file = \"tmpl.typ\"\n").unwrap();
+        let cfg = ZkLspConfig::from_path(&tmp.join("zk-lsp.toml"));
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert_eq!(cfg.new_note_template.as_deref(), Some("= {{id}}\n"));
+    }
+
+    #[test]
+    fn test_note_path_follows_subdir_layout() {
+        let mut config = WikiConfig::from_root(PathBuf::from("/wiki"));
+        assert_eq!(
+            config.note_path("2603150000"),
+            PathBuf::from("/wiki/note/2603150000.typ")
+        );
+        config.zk_config.subdir_layout = Some(SubdirLayout::YearMonth);
+        assert_eq!(
+            config.note_path("2603150000"),
+            PathBuf::from("/wiki/note/2026/03/2603150000.typ")
+        );
+        // Not a real date: falls back to flat.
+        assert_eq!(
+            config.note_path("2613150000"),
+            PathBuf::from("/wiki/note/2613150000.typ")
+        );
+
+        let parse = |s: &str| parse_subdir_layout(&s.parse::<toml::Table>().unwrap());
+        assert_eq!(
+            parse("subdir_layout = 'yearMonth'"),
+            Some(SubdirLayout::YearMonth)
+        );
+        assert_eq!(parse("subdir_layout = 'flat'"), Some(SubdirLayout::Flat));
+        assert_eq!(parse("subdir_layout = 'weekly'"), None);
+    }
+
+    #[test]
+    fn test_reference_resolution_parsing() {
+        let parse = |s: &str| parse_reference_resolution(&s.parse::<toml::Table>().unwrap());
+        assert_eq!(
+            parse("reference_resolution = 'relative'"),
+            Some(ReferenceResolution::Relative)
+        );
+        assert_eq!(
+            parse("reference_resolution = 'global'"),
+            Some(ReferenceResolution::Global)
+        );
+        assert_eq!(parse("reference_resolution = 'nearest'"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_max_note_bytes_parsing_and_default() {
+        let parse = |s: &str| parse_max_note_bytes(&s.parse::<toml::Table>().unwrap());
+        assert_eq!(parse("max_note_bytes = 1024"), Some(1024));
+        assert_eq!(parse("max_note_bytes = -1"), None);
+        assert_eq!(parse("max_note_bytes = '1MB'"), None);
+        assert_eq!(
+            WikiConfig::from_root(PathBuf::from("/nonexistent")).max_note_bytes(),
+            DEFAULT_MAX_NOTE_BYTES
+        );
+    }
+
+    #[test]
+    fn test_checkbox_ref_depth_parsing_and_default() {
+        let parse = |s: &str| parse_checkbox_ref_depth(&s.parse::<toml::Table>().unwrap());
+        assert_eq!(parse("checkbox_ref_depth = 3"), Some(3));
+        assert_eq!(parse("checkbox_ref_depth = 0"), Some(0));
+        assert_eq!(parse("checkbox_ref_depth = -1"), None);
+        assert_eq!(
+            WikiConfig::from_root(PathBuf::from("/nonexistent")).checkbox_ref_depth(),
+            DEFAULT_CHECKBOX_REF_DEPTH
+        );
+    }
+
+    #[test]
+    fn test_ref_style_parsing() {
+        let parse = |s: &str| parse_ref_style(&s.parse::<toml::Table>().unwrap());
+        assert_eq!(parse("ref_style = 'wikilink'"), Some(RefStyle::Wikilink));
+        assert_eq!(parse("ref_style = 'at'"), Some(RefStyle::At));
+        assert_eq!(parse("ref_style = '[['"), None);
+        assert_eq!(RefStyle::Wikilink.format("2602082037"), "[[2602082037]]");
+    }
+
+    #[test]
+    fn test_import_marker_parsing() {
+        use crate::parser::{Parser, ParserConfig};
+        let parser_for = |c: &ZkLspConfig| Parser::new(ParserConfig::from_zk_config(c));
+        let default = parser_for(&ZkLspConfig::default());
+        assert!(default.is_import_line("#import \"../include.typ\": *"));
+        assert!(!default.is_import_line("#import \"../../lib/include.typ\": *"));
+
+        let tmp = std::env::temp_dir().join("zk_config_test_import_marker");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(&tmp).unwrap();
+        let path = tmp.join("zk-lsp.toml");
+        std::fs::write(&path, "import_marker = '^#import \"@preview/zettel:'\n").unwrap();
+        let custom = ZkLspConfig::from_path(&path);
+        std::fs::write(&path, "import_marker = '('\n").unwrap();
+        let invalid = ZkLspConfig::from_path(&path);
+        let _ = std::fs::remove_dir_all(&tmp);
+        let custom = parser_for(&custom);
+        assert!(custom.is_import_line("  #import \"@preview/zettel:0.1.0\": *"));
+        assert!(!custom.is_import_line("#import \"../include.typ\": *"));
+        assert!(invalid.import_marker.is_none());
+    }
+
+    #[test]
+    fn test_client_settings_section_and_unknown_keys() {
+        let settings = ClientSettings::from_json(&serde_json::json!({
+            "zk-lsp": {"noteDir": "notes", "ignore": ["x*"], "unknownKey": 1}
+        }));
+        assert_eq!(settings.note_dir, Some(PathBuf::from("notes")));
+        assert_eq!(settings.ignore, Some(vec!["x*".to_string()]));
+        let config =
+            WikiConfig::from_root(PathBuf::from("/tmp/zk_no_such_wiki")).with_settings(settings);
+        assert_eq!(config.note_dir, PathBuf::from("/tmp/zk_no_such_wiki/notes"));
+        assert!(config.is_ignored(Path::new("x.typ")));
+    }
+
+    #[test]
+    fn test_workspace_diagnostics_batch_size() {
+        let default = ClientSettings::from_json(&serde_json::json!({}));
+        assert_eq!(default.workspace_diagnostics_batch_size, 50);
+        let set = ClientSettings::from_json(&serde_json::json!({
+            "workspaceDiagnosticsBatchSize": 8
+        }));
+        assert_eq!(set.workspace_diagnostics_batch_size, 8);
+        let zero = ClientSettings::from_json(&serde_json::json!({
+            "workspaceDiagnosticsBatchSize": 0
+        }));
+        assert_eq!(zero.workspace_diagnostics_batch_size, 50);
+    }
+
+    #[test]
+    fn test_max_symbol_results() {
+        let default = ClientSettings::from_json(&serde_json::json!({}));
+        assert_eq!(default.max_symbol_results, 200);
+        let set = ClientSettings::from_json(&serde_json::json!({"maxSymbolResults": 25}));
+        assert_eq!(set.max_symbol_results, 25);
+        let zero = ClientSettings::from_json(&serde_json::json!({"max_symbol_results": 0}));
+        assert_eq!(zero.max_symbol_results, 200);
+    }
+
+    #[test]
+    fn test_max_preview_chars() {
+        let default = ClientSettings::from_json(&serde_json::json!({}));
+        assert_eq!(default.max_preview_chars, 280);
+        let set = ClientSettings::from_json(&serde_json::json!({"maxPreviewChars": 40}));
+        assert_eq!(set.max_preview_chars, 40);
+    }
+
+    #[test]
+    fn test_hover_see_also() {
+        assert!(!ClientSettings::from_json(&serde_json::json!({})).hover_see_also);
+        assert!(
+            ClientSettings::from_json(&serde_json::json!({"hoverSeeAlso": true})).hover_see_also
+        );
+    }
+
+    #[test]
+    fn test_inlay_hint_label() {
+        let default = ClientSettings::from_json(&serde_json::json!({}));
+        assert_eq!(default.inlay_hint_label, HintLabel::Title);
+        let alias = ClientSettings::from_json(&serde_json::json!({"inlayHintLabel": "alias"}));
+        assert_eq!(alias.inlay_hint_label, HintLabel::Alias);
+        let both = ClientSettings::from_json(&serde_json::json!({"inlay_hint_label": "both"}));
+        assert_eq!(both.inlay_hint_label, HintLabel::Both);
+        let unknown = ClientSettings::from_json(&serde_json::json!({"inlayHintLabel": "id"}));
+        assert_eq!(unknown.inlay_hint_label, HintLabel::Title);
+    }
+
+    #[test]
+    fn test_inlay_hint_filter() {
+        let default = ClientSettings::from_json(&serde_json::json!({}));
+        assert_eq!(default.inlay_hint_filter, HintFilter::All);
+        let stale =
+            ClientSettings::from_json(&serde_json::json!({"inlayHintFilter": "archivedLegacy"}));
+        assert_eq!(stale.inlay_hint_filter, HintFilter::ArchivedLegacy);
+        let off = ClientSettings::from_json(&serde_json::json!({"inlay_hint_filter": "none"}));
+        assert_eq!(off.inlay_hint_filter, HintFilter::Off);
+        let unknown = ClientSettings::from_json(&serde_json::json!({"inlayHintFilter": "dead"}));
+        assert_eq!(unknown.inlay_hint_filter, HintFilter::All);
+    }
+
+    #[test]
+    fn test_diagnostic_kind_settings() {
+        let default = ClientSettings::from_json(&serde_json::json!({}));
+        assert_eq!(
+            default.severity_for("legacy", DiagnosticSeverity::INFORMATION),
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+        let set = ClientSettings::from_json(&serde_json::json!({
+            "disabledDiagnostics": ["legacy", "missing_checkbox_target"],
+            "diagnosticSeverity": {"archived": "error", "dead_link": "hint", "orphan": "loud"}
+        }));
+        assert_eq!(
+            set.severity_for("legacy", DiagnosticSeverity::INFORMATION),
+            None
+        );
+        assert_eq!(
+            set.severity_for("missing-checkbox-target", DiagnosticSeverity::WARNING),
+            None
+        );
+        assert_eq!(
+            set.severity_for("archived", DiagnosticSeverity::WARNING),
+            Some(DiagnosticSeverity::ERROR)
+        );
+        assert_eq!(
+            set.severity_for("dead-link", DiagnosticSeverity::ERROR),
+            Some(DiagnosticSeverity::HINT)
+        );
+        assert!(!set.diagnostic_severity.contains_key("orphan"));
+    }
 }
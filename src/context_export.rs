@@ -1,28 +1,57 @@
 /// BFS context export for AI consumption.
 ///
-/// `export_context` starts at a given note ID, traverses outgoing links (or
-/// inbound links when `inverse = true`) up to `depth` hops, and returns a
-/// structured Markdown document.
+/// `export_context` starts at a given note ID, traverses outgoing links,
+/// inbound links, or both up to `depth` hops, and returns a structured
+/// Markdown document.
 use std::collections::{HashMap, HashSet, VecDeque};
 
 use crate::config::WikiConfig;
+use crate::note_ops;
 use crate::parser::{self, ChecklistStatus, Relation};
 
+/// Which links the BFS follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Notes the entry references.
+    Outgoing,
+    /// Notes that reference the entry (backlinks).
+    Incoming,
+    /// Both: the entry's full link neighbourhood.
+    Both,
+}
+
+impl Direction {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "out" | "outgoing" => Some(Direction::Outgoing),
+            "in" | "incoming" | "inverse" => Some(Direction::Incoming),
+            "both" => Some(Direction::Both),
+            _ => None,
+        }
+    }
+}
+
 /// Export a BFS context document starting from `entry_id` to the given `depth`.
 ///
-/// When `inverse = true`, traverses inbound links (backlinks) instead of
+/// With `Direction::Incoming`, traverses inbound links (backlinks) instead of
 /// outgoing links, and reverses the output so ancestors appear first with the
 /// entry note last — "先给前提，再给结论".
 ///
+/// `max_chars` caps the document size: notes are kept nearest-first until the
+/// cap would be exceeded (the entry note is always kept), and the rest are
+/// listed as omitted.
+///
 /// Returns the Markdown string or an error if the entry note cannot be read.
 pub async fn export_context(
     entry_id: &str,
     depth: usize,
-    inverse: bool,
+    direction: Direction,
+    max_chars: Option<usize>,
     config: &WikiConfig,
 ) -> anyhow::Result<String> {
-    // For inverse mode, build a reverse map: target_id → Vec<source_id>
-    let reverse_map: HashMap<String, Vec<String>> = if inverse {
+    let inverse = direction == Direction::Incoming;
+    // Reverse map for backlinks: target_id → Vec<source_id>
+    let reverse_map: HashMap<String, Vec<String>> = if direction != Direction::Outgoing {
         build_reverse_map(config).await
     } else {
         HashMap::new()
@@ -35,15 +64,18 @@ pub async fn export_context(
     visited.insert(entry_id.to_string());
 
     let mut sections: Vec<NoteSection> = Vec::new();
+    let note_parser = config.parser();
 
     while let Some((id, d)) = queue.pop_front() {
-        let path = config.note_dir.join(format!("{id}.typ"));
+        let Some(path) = config.locate_note(&id) else {
+            continue;
+        };
         let content = match tokio::fs::read_to_string(&path).await {
             Ok(c) => c,
             Err(_) => continue,
         };
 
-        let header = parser::parse_header(&content);
+        let header = note_parser.parse_header(&content);
         let title = header.as_ref().map(|h| h.title.clone()).unwrap_or_default();
         let abstract_text = header
             .as_ref()
@@ -71,7 +103,8 @@ pub async fn export_context(
             .unwrap_or(Relation::Active);
 
         // Extract outgoing refs (filtered: skips TOML block, comments, fences)
-        let out_refs: Vec<String> = parser::find_all_refs_filtered(&content)
+        let out_refs: Vec<String> = note_parser
+            .find_all_refs_filtered(&content)
             .into_iter()
             .map(|r| r.id)
             .collect::<HashSet<_>>()
@@ -80,10 +113,11 @@ pub async fn export_context(
 
         // Enqueue unvisited neighbours if within depth
         if d < depth {
-            let neighbours: &[String] = if inverse {
-                reverse_map.get(&id).map(|v| v.as_slice()).unwrap_or(&[])
-            } else {
-                &out_refs
+            let backlinks: &[String] = reverse_map.get(&id).map(|v| v.as_slice()).unwrap_or(&[]);
+            let neighbours: Vec<&String> = match direction {
+                Direction::Outgoing => out_refs.iter().collect(),
+                Direction::Incoming => backlinks.iter().collect(),
+                Direction::Both => out_refs.iter().chain(backlinks).collect(),
             };
             for ref_id in neighbours {
                 if visited.insert(ref_id.clone()) {
@@ -110,16 +144,31 @@ pub async fn export_context(
         });
     }
 
+    // Apply the size cap in BFS order, so the nearest notes survive.
+    let mut rendered: Vec<(String, &NoteSection)> =
+        sections.iter().map(|s| (render_section(s), s)).collect();
+    let mut omitted: Vec<String> = Vec::new();
+    if let Some(cap) = max_chars {
+        let mut total = 0;
+        let mut keep = 0;
+        for (i, (text, _)) in rendered.iter().enumerate() {
+            let chars = text.chars().count();
+            if i > 0 && total + chars > cap {
+                break;
+            }
+            total += chars;
+            keep = i + 1;
+        }
+        omitted = rendered[keep..].iter().map(|(_, s)| s.id.clone()).collect();
+        rendered.truncate(keep);
+    }
+
     // In inverse mode, reverse so ancestors appear first and entry note is last
     if inverse {
-        sections.reverse();
+        rendered.reverse();
     }
 
-    let entry_title = if inverse {
-        sections.last().map(|s| s.title.as_str()).unwrap_or("")
-    } else {
-        sections.first().map(|s| s.title.as_str()).unwrap_or("")
-    };
+    let entry_title = sections.first().map(|s| s.title.as_str()).unwrap_or("");
     let today = chrono_today();
 
     let mut out = String::new();
@@ -130,54 +179,75 @@ pub async fn export_context(
     }
     out.push_str(&format!("**Entry:** {entry_id} — {entry_title}\n"));
     out.push_str(&format!("**Depth:** {depth}\n"));
+    if direction == Direction::Both {
+        out.push_str("**Direction:** both (outgoing links and backlinks)\n");
+    }
     out.push_str(&format!("**Generated:** {today}\n\n"));
     out.push_str("---\n\n");
 
-    for section in &sections {
-        out.push_str(&format!("## {} · {}\n\n", section.id, section.title));
+    for (text, _) in &rendered {
+        out.push_str(text);
+    }
 
-        if !section.abstract_text.is_empty() {
-            out.push_str(&format!("> {}\n\n", section.abstract_text));
-        }
+    if !omitted.is_empty() {
+        let ids = omitted
+            .iter()
+            .map(|id| format!("@{id}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        out.push_str(&format!(
+            "_Size cap reached; {} note(s) omitted: {ids}_\n",
+            omitted.len()
+        ));
+    }
 
-        if !section.keywords.is_empty() {
-            out.push_str(&format!("**Keywords:** {}\n", section.keywords.join(", ")));
-        }
+    Ok(out)
+}
 
-        let cs = match section.checklist_status {
-            ChecklistStatus::None => "none",
-            ChecklistStatus::Todo => "todo",
-            ChecklistStatus::Wip => "wip",
-            ChecklistStatus::Done => "done",
-        };
-        let rel = match section.relation {
-            Relation::Active => "active",
-            Relation::Archived => "archived",
-            Relation::Legacy => "legacy",
-        };
-        out.push_str(&format!("**Status:** checklist={cs} · relation={rel}\n"));
-
-        if !section.out_refs.is_empty() {
-            let refs_str = section
-                .out_refs
-                .iter()
-                .map(|r| format!("@{r}"))
-                .collect::<Vec<_>>()
-                .join(", ");
-            out.push_str(&format!("**Outgoing links:** {refs_str}\n"));
-        }
+fn render_section(section: &NoteSection) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("## {} · {}\n\n", section.id, section.title));
 
-        out.push('\n');
+    if !section.abstract_text.is_empty() {
+        out.push_str(&format!("> {}\n\n", section.abstract_text));
+    }
 
-        if !section.body.is_empty() {
-            out.push_str(&section.body);
-            out.push('\n');
-        }
+    if !section.keywords.is_empty() {
+        out.push_str(&format!("**Keywords:** {}\n", section.keywords.join(", ")));
+    }
 
-        out.push_str("---\n\n");
+    let cs = match section.checklist_status {
+        ChecklistStatus::None => "none",
+        ChecklistStatus::Todo => "todo",
+        ChecklistStatus::Wip => "wip",
+        ChecklistStatus::Done => "done",
+    };
+    let rel = match section.relation {
+        Relation::Active => "active",
+        Relation::Archived => "archived",
+        Relation::Legacy => "legacy",
+    };
+    out.push_str(&format!("**Status:** checklist={cs} · relation={rel}\n"));
+
+    if !section.out_refs.is_empty() {
+        let refs_str = section
+            .out_refs
+            .iter()
+            .map(|r| format!("@{r}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        out.push_str(&format!("**Outgoing links:** {refs_str}\n"));
     }
 
-    Ok(out)
+    out.push('\n');
+
+    if !section.body.is_empty() {
+        out.push_str(&section.body);
+        out.push('\n');
+    }
+
+    out.push_str("---\n\n");
+    out
 }
 
 struct NoteSection {
@@ -194,30 +264,17 @@ struct NoteSection {
 /// Build a reverse map: target_id → Vec<source_id> by scanning all .typ files.
 async fn build_reverse_map(config: &WikiConfig) -> HashMap<String, Vec<String>> {
     let mut map: HashMap<String, Vec<String>> = HashMap::new();
-    let mut dir = match tokio::fs::read_dir(&config.note_dir).await {
-        Ok(d) => d,
-        Err(_) => return map,
-    };
-    while let Ok(Some(entry)) = dir.next_entry().await {
-        let path = entry.path();
-        let Some(ext) = path.extension() else {
+    let files = note_ops::wiki_note_files(config).await.unwrap_or_default();
+    let note_parser = config.parser();
+    for path in files {
+        let Some(source_id) = parser::note_id_from_path(&path).map(String::from) else {
             continue;
         };
-        if ext != "typ" {
-            continue;
-        }
-        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
-            continue;
-        };
-        if stem.len() != 10 || !stem.chars().all(|c| c.is_ascii_digit()) {
-            continue;
-        }
-        let source_id = stem.to_string();
         let content = match tokio::fs::read_to_string(&path).await {
             Ok(c) => c,
             Err(_) => continue,
         };
-        for r in parser::find_all_refs_filtered(&content) {
+        for r in note_parser.find_all_refs_filtered(&content) {
             map.entry(r.id).or_default().push(source_id.clone());
         }
     }
@@ -227,7 +284,7 @@ async fn build_reverse_map(config: &WikiConfig) -> HashMap<String, Vec<String>>
 /// Extract the body of a note: everything from the title line (`= ... <id>`) onwards.
 fn extract_body(content: &str, id: &str) -> String {
     let needle = format!("<{id}>");
-    let lines: Vec<&str> = content.lines().collect();
+    let lines: Vec<&str> = parser::split_lines(content).collect();
     let start = lines
         .iter()
         .position(|l| l.contains(&needle))
@@ -315,7 +372,7 @@ mod tests {
         let tmp = make_test_dir("single");
         write_note(&tmp.join("note"), "1111111111", "Entry Note", &[]);
         let config = WikiConfig::from_root(tmp.clone());
-        let out = export_context("1111111111", 0, false, &config)
+        let out = export_context("1111111111", 0, Direction::Outgoing, None, &config)
             .await
             .unwrap();
         let _ = std::fs::remove_dir_all(&tmp);
@@ -335,7 +392,7 @@ mod tests {
         );
         write_note(&tmp.join("note"), "2222222222", "Linked Note", &[]);
         let config = WikiConfig::from_root(tmp.clone());
-        let out = export_context("1111111111", 1, false, &config)
+        let out = export_context("1111111111", 1, Direction::Outgoing, None, &config)
             .await
             .unwrap();
         let _ = std::fs::remove_dir_all(&tmp);
@@ -344,4 +401,83 @@ mod tests {
         assert!(out.contains("2222222222"));
         assert!(out.contains("Linked Note"));
     }
+
+    #[tokio::test]
+    async fn test_export_both_directions() {
+        let tmp = make_test_dir("both");
+        let dir = tmp.join("note");
+        write_note(&dir, "1111111111", "Entry Note", &["2222222222"]);
+        write_note(&dir, "2222222222", "Linked Note", &[]);
+        write_note(&dir, "3333333333", "Citing Note", &["1111111111"]);
+        let config = WikiConfig::from_root(tmp.clone());
+        let out_only = export_context("1111111111", 1, Direction::Outgoing, None, &config)
+            .await
+            .unwrap();
+        let both = export_context("1111111111", 1, Direction::Both, None, &config)
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert!(!out_only.contains("Citing Note"));
+        assert!(both.contains("Linked Note"));
+        assert!(both.contains("Citing Note"));
+    }
+
+    #[tokio::test]
+    async fn test_export_follows_wikilinks() {
+        let tmp = make_test_dir("wikilink");
+        let dir = tmp.join("note");
+        std::fs::write(tmp.join("zk-lsp.toml"), "ref_style = \"wikilink\"\n").unwrap();
+        let entry = make_note_content("1111111111", "Entry Note", &[]) + "See [[2222222222]].\n";
+        std::fs::write(dir.join("1111111111.typ"), entry).unwrap();
+        write_note(&dir, "2222222222", "Linked Note", &[]);
+        let config = WikiConfig::from_root(tmp.clone());
+        let outgoing = export_context("1111111111", 1, Direction::Outgoing, None, &config)
+            .await
+            .unwrap();
+        let incoming = export_context("2222222222", 1, Direction::Incoming, None, &config)
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert!(outgoing.contains("Linked Note"));
+        assert!(incoming.contains("Entry Note"));
+    }
+
+    #[tokio::test]
+    async fn test_export_size_cap_keeps_entry_and_lists_omitted() {
+        let tmp = make_test_dir("cap");
+        let dir = tmp.join("note");
+        write_note(&dir, "1111111111", "Entry Note", &["2222222222"]);
+        write_note(&dir, "2222222222", "Linked Note", &[]);
+        let config = WikiConfig::from_root(tmp.clone());
+        let out = export_context("1111111111", 1, Direction::Outgoing, Some(1), &config)
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert!(out.contains("## 1111111111 · Entry Note"));
+        assert!(!out.contains("## 2222222222"));
+        assert!(out.contains("1 note(s) omitted: @2222222222"));
+    }
+
+    #[tokio::test]
+    async fn test_export_size_cap_counts_chars_not_bytes() {
+        let tmp = make_test_dir("cap_chars");
+        let dir = tmp.join("note");
+        let entry = "入口笔记".repeat(50);
+        let linked = "关联笔记".repeat(50);
+        write_note(&dir, "1111111111", &entry, &["2222222222"]);
+        write_note(&dir, "2222222222", &linked, &[]);
+        let config = WikiConfig::from_root(tmp.clone());
+        let full = export_context("1111111111", 1, Direction::Outgoing, None, &config)
+            .await
+            .unwrap();
+        // Both sections fit in this many characters, but not in this many bytes.
+        let cap = full.chars().count();
+        assert!(full.len() > 2 * cap);
+        let out = export_context("1111111111", 1, Direction::Outgoing, Some(cap), &config)
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert!(out.contains(&format!("## 2222222222 · {linked}")));
+        assert!(!out.contains("omitted"));
+    }
 }
@@ -0,0 +1,114 @@
+/// References into other wikis: `@<wiki>:<ID>`, where `<wiki>` is a name
+/// from the `[wikis]` config table.
+///
+/// The other wiki is not indexed. Its own config is loaded to find its note
+/// directory, and the note file is looked up there the way
+/// `WikiConfig::locate_note` finds notes in this wiki.
+use std::path::PathBuf;
+
+use tower_lsp::lsp_types::Position;
+
+use crate::config::WikiConfig;
+use crate::parser::{self, CrossRef};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CrossRefTarget {
+    /// The prefix is not listed under `[wikis]`.
+    UnknownWiki,
+    /// The wiki is known but has no `<ID>.typ`.
+    Missing,
+    Found(PathBuf),
+}
+
+/// Where `@<wiki>:<id>` points, as seen from the wiki of `config`.
+pub fn resolve(config: &WikiConfig, wiki: &str, id: &str) -> CrossRefTarget {
+    let Some(other) = config.linked_wiki(wiki) else {
+        return CrossRefTarget::UnknownWiki;
+    };
+    match other.locate_note(id) {
+        Some(path) => CrossRefTarget::Found(std::path::absolute(&path).unwrap_or(path)),
+        None => CrossRefTarget::Missing,
+    }
+}
+
+/// The cross-wiki reference under `position` (UTF-16 column) in `content`.
+pub fn cross_ref_at(content: &str, position: Position) -> Option<CrossRef> {
+    let line = parser::split_lines(content).nth(position.line as usize)?;
+    let col = parser::utf16_to_byte(line, position.character);
+    parser::find_cross_refs(line)
+        .into_iter()
+        .find(|r| (r.start_char as usize..=r.end_char as usize).contains(&col))
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::path::Path;
+
+    pub(crate) const WORK_NOTE: &str = "2602082037";
+
+    /// Two wikis under a fresh temp dir: `home`, whose config names the
+    /// other one `work`, and `work`, with notes in `notes/` holding
+    /// `WORK_NOTE`. Returns the temp dir; the wikis are `home/` and `work/`.
+    pub(crate) fn two_wikis(name: &str) -> PathBuf {
+        let tmp = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&tmp);
+        let home = tmp.join("home");
+        let work = tmp.join("work");
+        std::fs::create_dir_all(home.join("note")).unwrap();
+        std::fs::create_dir_all(work.join("notes")).unwrap();
+        std::fs::write(home.join("zk-lsp.toml"), "[wikis]\nwork = \"../work\"\n").unwrap();
+        std::fs::write(work.join("zk-lsp.toml"), "note_dir = \"notes\"\n").unwrap();
+        std::fs::write(
+            work.join(format!("notes/{WORK_NOTE}.typ")),
+            format!(
+                "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n= Work Plan <{WORK_NOTE}>\nQuarterly goals.\n"
+            ),
+        )
+        .unwrap();
+        tmp
+    }
+
+    pub(crate) fn home_config(tmp: &Path) -> WikiConfig {
+        WikiConfig::from_root(tmp.join("home"))
+    }
+
+    #[test]
+    fn test_resolve_through_other_wikis_config() {
+        let tmp = two_wikis("zk_cross_wiki_resolve");
+        let config = home_config(&tmp);
+        let found = resolve(&config, "work", WORK_NOTE);
+        let missing = resolve(&config, "work", "2602082038");
+        let unknown = resolve(&config, "play", WORK_NOTE);
+        // The other wiki's config is loaded once, then reused.
+        let loaded = config.linked_wiki("work").unwrap();
+        std::fs::remove_file(tmp.join("work/zk-lsp.toml")).unwrap();
+        let reused = config.clone().linked_wiki("work").unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert!(std::sync::Arc::ptr_eq(&loaded, &reused));
+        assert!(reused.note_dir.ends_with("work/notes"));
+
+        let CrossRefTarget::Found(path) = found else {
+            panic!("expected a path, got {found:?}");
+        };
+        assert!(path.ends_with(format!("work/notes/{WORK_NOTE}.typ")));
+        assert_eq!(missing, CrossRefTarget::Missing);
+        assert_eq!(unknown, CrossRefTarget::UnknownWiki);
+    }
+
+    #[test]
+    fn test_cross_ref_at_position() {
+        let content = "Intro\n见 @work:2602082037 和 @2602082037\n";
+        let at = |character| cross_ref_at(content, Position { line: 1, character });
+        // `见 ` is two UTF-16 units; the ref spans columns 2..=18.
+        assert_eq!(at(2).unwrap().wiki, "work");
+        assert_eq!(at(18).unwrap().id, WORK_NOTE);
+        assert!(at(1).is_none());
+        assert!(at(23).is_none());
+    }
+}
@@ -109,7 +109,7 @@ pub fn detect_cycles(graph: &DependencyGraph) -> Vec<DependencyCycle> {
             && graph
                 .adj
                 .get(&scc[0])
-                .map_or(false, |ns| ns.contains(&scc[0]));
+                .is_some_and(|ns| ns.contains(&scc[0]));
         if scc.len() > 1 || is_self_loop {
             let scc_set: HashSet<&str> = scc.iter().map(String::as_str).collect();
             let edges: Vec<CycleEdgeOccurrence> = graph
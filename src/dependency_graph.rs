@@ -37,7 +37,7 @@ pub fn build_dependency_graph(notes: &HashMap<String, (PathBuf, String)>) -> Dep
     for (from_id, (path, content)) in notes {
         adj.entry(from_id.clone()).or_default();
         let items = parser::parse_checklist_items(content);
-        let lines: Vec<&str> = content.lines().collect();
+        let lines: Vec<&str> = parser::split_lines(content).collect();
 
         for item in items {
             if let parser::ChecklistItemKind::Ref { targets } = item.kind {
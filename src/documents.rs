@@ -0,0 +1,240 @@
+/// In-memory contents of the documents open in the editor.
+///
+/// Cross-file evaluation (reconcile diagnostics) prefers these buffers over
+/// the files on disk, so results match what the user currently sees even with
+/// unsaved edits in other notes. The CLI has no open documents and reads disk.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use dashmap::DashMap;
+use tower_lsp::lsp_types::{Position, TextDocumentContentChangeEvent};
+
+use crate::parser;
+
+#[derive(Debug, Default)]
+pub struct DocumentStore {
+    docs: DashMap<PathBuf, String>,
+    /// Client version of each open document, from didOpen / didChange.
+    versions: DashMap<PathBuf, i32>,
+}
+
+impl DocumentStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the full text of a document (didOpen / didSave).
+    pub fn open(&self, path: PathBuf, text: String) {
+        self.docs.insert(path, text);
+    }
+
+    /// Apply `didChange` content changes in order. Changes for documents that
+    /// were never opened are ignored.
+    pub fn change(&self, path: &Path, changes: &[TextDocumentContentChangeEvent]) {
+        if let Some(mut text) = self.docs.get_mut(path) {
+            for change in changes {
+                apply_content_change(&mut text, change);
+            }
+        }
+    }
+
+    /// Record the client's version of a document (didOpen / didChange).
+    pub fn set_version(&self, path: &Path, version: i32) {
+        self.versions.insert(path.to_path_buf(), version);
+    }
+
+    /// The last version the client reported, if the document is open.
+    pub fn version(&self, path: &Path) -> Option<i32> {
+        self.versions.get(path).map(|v| *v)
+    }
+
+    pub fn close(&self, path: &Path) {
+        self.docs.remove(path);
+        self.versions.remove(path);
+    }
+
+    pub fn get(&self, path: &Path) -> Option<String> {
+        self.docs.get(path).map(|r| r.clone())
+    }
+
+    /// Copy of all open documents, for use as an overlay over disk contents.
+    pub fn snapshot(&self) -> HashMap<PathBuf, String> {
+        self.docs
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect()
+    }
+}
+
+/// Apply one `didChange` event to `doc`: a full replacement when `range` is
+/// absent, otherwise a splice of the UTF-16 `range`. Inverted ranges are
+/// treated as empty at their start.
+pub fn apply_content_change(doc: &mut String, change: &TextDocumentContentChangeEvent) {
+    match change.range {
+        Some(range) => {
+            let start = position_to_byte(doc, range.start);
+            let end = position_to_byte(doc, range.end).max(start);
+            doc.replace_range(start..end, &change.text);
+        }
+        None => *doc = change.text.clone(),
+    }
+}
+
+/// Convert an LSP position (UTF-16 code units) to a byte offset, clamping
+/// past-the-end lines and columns. Lines end at `\n`, `\r\n` or a bare `\r`,
+/// as in `parser::split_lines`; a column never lands inside the terminator.
+fn position_to_byte(text: &str, pos: Position) -> usize {
+    let bytes = text.as_bytes();
+    let mut line_start = 0;
+    for _ in 0..pos.line {
+        match bytes[line_start..]
+            .iter()
+            .position(|&b| b == b'\n' || b == b'\r')
+        {
+            Some(i) => {
+                let at = line_start + i;
+                line_start = if bytes[at] == b'\r' && bytes.get(at + 1) == Some(&b'\n') {
+                    at + 2
+                } else {
+                    at + 1
+                };
+            }
+            None => return text.len(),
+        }
+    }
+    let line_end = bytes[line_start..]
+        .iter()
+        .position(|&b| b == b'\n' || b == b'\r')
+        .map_or(text.len(), |i| line_start + i);
+    line_start + parser::utf16_to_byte(&text[line_start..line_end], pos.character)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::Range;
+
+    fn edit(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range::new(
+                Position::new(start.0, start.1),
+                Position::new(end.0, end.1),
+            )),
+            range_length: None,
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn test_incremental_changes_use_utf16_positions() {
+        let store = DocumentStore::new();
+        let path = PathBuf::from("/tmp/note/2603150000.typ");
+        store.open(path.clone(), "= 😀 Title\n- [ ] task\n".into());
+        store.change(
+            &path,
+            &[edit((1, 3), (1, 4), "x"), edit((0, 5), (0, 10), "Renamed")],
+        );
+        assert_eq!(
+            store.get(&path).as_deref(),
+            Some("= 😀 Renamed\n- [x] task\n")
+        );
+    }
+
+    fn applied(text: &str, changes: &[TextDocumentContentChangeEvent]) -> String {
+        let mut doc = text.to_string();
+        for change in changes {
+            apply_content_change(&mut doc, change);
+        }
+        doc
+    }
+
+    #[test]
+    fn test_apply_insert_delete_replace_around_cjk() {
+        // "中文" is two UTF-16 units and six bytes; 😀 is two units and four bytes.
+        let text = "= 中文标题 😀\n正文\n";
+        assert_eq!(
+            applied(text, &[edit((0, 4), (0, 4), "的")]),
+            "= 中文的标题 😀\n正文\n"
+        );
+        assert_eq!(
+            applied(text, &[edit((0, 2), (0, 4), "")]),
+            "= 标题 😀\n正文\n"
+        );
+        assert_eq!(
+            applied(text, &[edit((0, 7), (0, 9), "🎉")]),
+            "= 中文标题 🎉\n正文\n"
+        );
+        assert_eq!(
+            applied(text, &[edit((1, 0), (1, 2), "body")]),
+            "= 中文标题 😀\nbody\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_edits_spanning_line_boundaries() {
+        let text = "第一行\n第二行\n第三行\n";
+        // Join lines 0 and 1 by deleting the newline.
+        assert_eq!(
+            applied(text, &[edit((0, 3), (1, 0), "")]),
+            "第一行第二行\n第三行\n"
+        );
+        // Replace from mid line 0 to mid line 2.
+        assert_eq!(applied(text, &[edit((0, 2), (2, 1), "X")]), "第一X三行\n");
+        // Insert a multi-line block.
+        assert_eq!(
+            applied(text, &[edit((1, 0), (1, 0), "新\n行\n")]),
+            "第一行\n新\n行\n第二行\n第三行\n"
+        );
+        // Sequential edits see the result of earlier ones.
+        assert_eq!(
+            applied(
+                text,
+                &[edit((0, 0), (1, 0), ""), edit((0, 0), (0, 3), "二")]
+            ),
+            "二\n第三行\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_handles_crlf_cr_and_clamping() {
+        assert_eq!(
+            applied("ab\r\ncd\r\n", &[edit((1, 1), (1, 2), "X")]),
+            "ab\r\ncX\r\n"
+        );
+        assert_eq!(applied("ab\rcd", &[edit((1, 0), (1, 1), "X")]), "ab\rXd");
+        // A column past the line end stops before the terminator.
+        assert_eq!(
+            applied("ab\r\ncd", &[edit((0, 9), (0, 9), "!")]),
+            "ab!\r\ncd"
+        );
+        // Lines past the end clamp to the end of the document.
+        assert_eq!(applied("ab", &[edit((5, 0), (5, 0), "!")]), "ab!");
+        // Inverted ranges insert at the start.
+        assert_eq!(applied("abc", &[edit((0, 2), (0, 1), "X")]), "abXc");
+    }
+
+    #[test]
+    fn test_full_change_replaces_text_and_close_forgets() {
+        let store = DocumentStore::new();
+        let path = PathBuf::from("/tmp/note/2603150000.typ");
+        store.open(path.clone(), "old".into());
+        store.change(
+            &path,
+            &[TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "new".into(),
+            }],
+        );
+        assert_eq!(store.get(&path).as_deref(), Some("new"));
+        store.set_version(&path, 3);
+        assert_eq!(store.version(&path), Some(3));
+        store.close(&path);
+        assert!(store.snapshot().is_empty());
+        assert_eq!(store.version(&path), None);
+    }
+}
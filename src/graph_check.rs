@@ -9,7 +9,7 @@ use std::path::PathBuf;
 use unicode_width::UnicodeWidthStr;
 
 use crate::config::WikiConfig;
-use crate::parser;
+use crate::{note_ops, parser};
 
 #[derive(Debug)]
 pub struct DeadLinkEntry {
@@ -27,29 +27,54 @@ pub struct OrphanEntry {
     pub id: String,
     pub path: PathBuf,
     pub title: String,
+    /// The note is marked `generated = true`.
+    pub generated: bool,
+}
+
+/// An archived or legacy note whose `relation-target` names itself.
+#[derive(Debug)]
+pub struct SelfLinkEntry {
+    pub id: String,
+    pub path: PathBuf,
+    /// `"alternative"` or `"evolution"` (see `NoteHeader::self_link`).
+    pub kind: &'static str,
+}
+
+/// A `.typ` file whose name looks like a note ID but is not a valid one
+/// (e.g. full-width digits), so it is silently left out of the wiki.
+#[derive(Debug)]
+pub struct InvalidIdEntry {
+    pub path: PathBuf,
 }
 
 #[derive(Debug)]
 pub struct CheckReport {
     pub dead_links: Vec<DeadLinkEntry>,
     pub orphans: Vec<OrphanEntry>,
+    pub self_links: Vec<SelfLinkEntry>,
+    pub invalid_ids: Vec<InvalidIdEntry>,
 }
 
 /// Scan the wiki and produce a `CheckReport` of dead links and orphan notes.
 pub async fn check_graph(config: &WikiConfig) -> anyhow::Result<CheckReport> {
-    let mut rd = tokio::fs::read_dir(&config.note_dir).await?;
     // notes: id → (path, content)
     let mut notes: HashMap<String, (PathBuf, String)> = HashMap::new();
     // titles: id → title string
     let mut titles: HashMap<String, String> = HashMap::new();
+    let mut generated: HashSet<String> = HashSet::new();
+    let mut self_links: Vec<SelfLinkEntry> = Vec::new();
+    let mut invalid_ids: Vec<InvalidIdEntry> = Vec::new();
 
-    while let Some(entry) = rd.next_entry().await? {
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) != Some("typ") {
+    for path in note_ops::wiki_note_files(config).await? {
+        if path.extension().and_then(|e| e.to_str()) != Some("typ") || config.is_ignored(&path) {
             continue;
         }
         let stem = match path.file_stem().and_then(|s| s.to_str()) {
-            Some(s) if s.len() == 10 && s.chars().all(|c| c.is_ascii_digit()) => s.to_string(),
+            Some(s) if parser::is_valid_id(s) => s.to_string(),
+            Some(s) if parser::is_lookalike_id(s) => {
+                invalid_ids.push(InvalidIdEntry { path });
+                continue;
+            }
             _ => continue,
         };
         let content = match tokio::fs::read_to_string(&path).await {
@@ -57,6 +82,16 @@ pub async fn check_graph(config: &WikiConfig) -> anyhow::Result<CheckReport> {
             Err(_) => continue,
         };
         if let Some(header) = parser::parse_header(&content) {
+            if header.generated {
+                generated.insert(stem.clone());
+            }
+            if let Some(kind) = header.self_link() {
+                self_links.push(SelfLinkEntry {
+                    id: stem.clone(),
+                    path: path.clone(),
+                    kind,
+                });
+            }
             titles.insert(stem.clone(), header.title);
         }
         notes.insert(stem, (path, content));
@@ -67,9 +102,10 @@ pub async fn check_graph(config: &WikiConfig) -> anyhow::Result<CheckReport> {
     // notes that have at least one outgoing ref
     let mut has_outgoing: HashSet<String> = HashSet::new();
 
+    let note_parser = config.parser();
     for (from_id, (from_path, content)) in &notes {
-        let lines: Vec<&str> = content.lines().collect();
-        let refs = parser::find_all_refs_filtered(content);
+        let lines: Vec<&str> = parser::split_lines(content).collect();
+        let refs = note_parser.find_all_refs_filtered(content);
         if !refs.is_empty() {
             has_outgoing.insert(from_id.clone());
         }
@@ -110,13 +146,19 @@ pub async fn check_graph(config: &WikiConfig) -> anyhow::Result<CheckReport> {
             id: id.clone(),
             path: path.clone(),
             title: titles.get(id).cloned().unwrap_or_default(),
+            generated: generated.contains(id),
         })
         .collect();
     orphans.sort_by(|a, b| a.id.cmp(&b.id));
 
+    self_links.sort_by(|a, b| a.id.cmp(&b.id));
+    invalid_ids.sort_by(|a, b| a.path.cmp(&b.path));
+
     Ok(CheckReport {
         dead_links,
         orphans,
+        self_links,
+        invalid_ids,
     })
 }
 
@@ -193,6 +235,44 @@ pub fn render_check_report(report: &CheckReport) -> String {
         out.push('\n');
     }
 
+    for entry in &report.self_links {
+        let msg = format!("note links to itself as {}", entry.kind);
+        if color {
+            out.push_str(&format!("\x1b[1;33mwarning\x1b[0m\x1b[1m: {msg}\x1b[0m\n"));
+            out.push_str(&format!(
+                " \x1b[1;34m┌─\x1b[0m \x1b[36m{}\x1b[0m\n",
+                entry.path.display()
+            ));
+            out.push_str(&format!(
+                " \x1b[1;34m│\x1b[0m  relation-target contains {}\n",
+                entry.id
+            ));
+        } else {
+            out.push_str(&format!("warning: {msg}\n"));
+            out.push_str(&format!(" ┌─ {}\n", entry.path.display()));
+            out.push_str(&format!(" │  relation-target contains {}\n", entry.id));
+        }
+        out.push('\n');
+    }
+
+    for entry in &report.invalid_ids {
+        let msg = format!(
+            "file name is not a valid note ID (expected {} ASCII digits)",
+            parser::ID_DIGITS
+        );
+        if color {
+            out.push_str(&format!("\x1b[1;33mwarning\x1b[0m\x1b[1m: {msg}\x1b[0m\n"));
+            out.push_str(&format!(
+                " \x1b[1;34m┌─\x1b[0m \x1b[36m{}\x1b[0m\n",
+                entry.path.display()
+            ));
+        } else {
+            out.push_str(&format!("warning: {msg}\n"));
+            out.push_str(&format!(" ┌─ {}\n", entry.path.display()));
+        }
+        out.push('\n');
+    }
+
     let dl = report.dead_links.len();
     let or = report.orphans.len();
     if dl > 0 || or > 0 {
@@ -242,7 +322,7 @@ mod tests {
         let mut has_outgoing: HashSet<String> = HashSet::new();
 
         for (from_id, (from_path, content)) in notes {
-            let lines: Vec<&str> = content.lines().collect();
+            let lines: Vec<&str> = parser::split_lines(content).collect();
             let refs = parser::find_all_refs_filtered(content);
             if !refs.is_empty() {
                 has_outgoing.insert(from_id.clone());
@@ -275,6 +355,7 @@ mod tests {
                 id: id.clone(),
                 path: path.clone(),
                 title: String::new(),
+                generated: false,
             })
             .collect();
         orphans.sort_by(|a, b| a.id.cmp(&b.id));
@@ -282,6 +363,8 @@ mod tests {
         CheckReport {
             dead_links,
             orphans,
+            self_links: Vec::new(),
+            invalid_ids: Vec::new(),
         }
     }
 
@@ -333,4 +416,41 @@ mod tests {
         let report = build_report(&notes);
         assert!(report.orphans.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_check_reports_lookalike_id_files() {
+        let tmp = std::env::temp_dir().join("zk_check_test_invalid_ids");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::write(tmp.join("note/２６０３１１０９００.typ"), "").unwrap();
+        std::fs::write(tmp.join("note/scratch.typ"), "").unwrap();
+        let config = crate::config::WikiConfig::from_root(tmp.clone());
+        let report = check_graph(&config).await.unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert_eq!(report.invalid_ids.len(), 1);
+        assert!(render_check_report(&report).contains("not a valid note ID"));
+    }
+
+    #[tokio::test]
+    async fn test_check_reports_self_relation_target() {
+        let tmp = std::env::temp_dir().join("zk_check_test_self_links");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::write(
+            tmp.join("note/2603110000.typ"),
+            "#import \"../include.typ\": *\n\
+             #let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+             relation = \"archived\"\n  relation-target = [\"2603110000\"]\n  ```.text,\n))\n\
+             #show: zettel.with(metadata: zk-metadata)\n\n= Old <2603110000>\n",
+        )
+        .unwrap();
+        let config = crate::config::WikiConfig::from_root(tmp.clone());
+        let report = check_graph(&config).await.unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert_eq!(report.self_links.len(), 1);
+        assert_eq!(report.self_links[0].kind, "alternative");
+        assert!(
+            render_check_report(&report).contains("warning: note links to itself as alternative\n")
+        );
+    }
 }
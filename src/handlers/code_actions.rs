@@ -1,10 +1,19 @@
+use std::collections::HashMap;
+
 use tower_lsp::lsp_types::*;
 
 use super::diagnostics::DiagnosticData;
-use crate::parser;
+use crate::abstract_gen;
+use crate::config::{RefStyle, ZkLspConfig};
+use crate::parser::{self, Parser};
+use crate::reconcile::writeback::ref_checkbox_update;
 
 /// Build code actions from diagnostics with source "zk-lsp".
-pub fn get_code_actions(uri: &Url, diagnostics: &[Diagnostic]) -> Vec<CodeActionOrCommand> {
+pub fn get_code_actions(
+    uri: &Url,
+    diagnostics: &[Diagnostic],
+    ref_style: RefStyle,
+) -> Vec<CodeActionOrCommand> {
     let mut actions = Vec::new();
 
     for diag in diagnostics {
@@ -31,6 +40,32 @@ pub fn get_code_actions(uri: &Url, diagnostics: &[Diagnostic]) -> Vec<CodeAction
             ));
             continue;
         }
+        if data.kind == "missing-metadata" {
+            let Some(block) = data.replacement.clone() else {
+                continue;
+            };
+            actions.push(make_replace_action(
+                uri,
+                diag,
+                "Add metadata block".into(),
+                block,
+            ));
+            continue;
+        }
+        if data.kind == "missing-checkbox-target" {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Create note @{}", data.old_id),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diag.clone()]),
+                command: Some(Command {
+                    title: format!("Create note @{}", data.old_id),
+                    command: "zk.newNote".into(),
+                    arguments: Some(vec![serde_json::Value::String(data.old_id.clone())]),
+                }),
+                ..Default::default()
+            }));
+            continue;
+        }
 
         let Some(new_ids) = data.new_ids.clone() else {
             continue;
@@ -39,9 +74,20 @@ pub fn get_code_actions(uri: &Url, diagnostics: &[Diagnostic]) -> Vec<CodeAction
             continue;
         }
 
-        let old_text = format!("@{}", data.old_id);
+        let old_text = ref_style.format(&data.old_id);
+        if data.kind == "dead-link" {
+            for new_id in &new_ids {
+                actions.push(make_replace_action(
+                    uri,
+                    diag,
+                    format!("Fix: Did you mean {}?", ref_style.format(new_id)),
+                    ref_style.format(new_id),
+                ));
+            }
+            continue;
+        }
         for new_id in &new_ids {
-            let new_text = format!("@{new_id}");
+            let new_text = ref_style.format(new_id);
             actions.push(make_replace_action(
                 uri,
                 diag,
@@ -60,7 +106,7 @@ pub fn get_code_actions(uri: &Url, diagnostics: &[Diagnostic]) -> Vec<CodeAction
         if new_ids.len() > 1 {
             let all_text = new_ids
                 .iter()
-                .map(|id| format!("@{id}"))
+                .map(|id| ref_style.format(id))
                 .collect::<Vec<_>>()
                 .join(" ");
             actions.push(make_replace_action(
@@ -95,8 +141,8 @@ pub fn get_metadata_actions(uri: &Url, content: &str, range: Range) -> Vec<CodeA
         return Vec::new();
     };
 
-    let lines: Vec<&str> = content.lines().collect();
-    let toml_line_count = block.toml_content.lines().count();
+    let lines: Vec<&str> = parser::split_lines(content).collect();
+    let toml_line_count = parser::split_lines(&block.toml_content).count();
     let toml_start = block.end_line.saturating_sub(toml_line_count);
 
     let mut actions = Vec::new();
@@ -131,7 +177,7 @@ pub fn get_metadata_actions(uri: &Url, content: &str, range: Range) -> Vec<CodeA
     // --- Action B: Toggle relation ---
     let mut relation_line_idx: Option<usize> = None;
     let mut relation_target_line_idx: Option<usize> = None;
-    for (i, toml_line) in block.toml_content.lines().enumerate() {
+    for (i, toml_line) in parser::split_lines(&block.toml_content).enumerate() {
         let t = toml_line.trim_start();
         if t.starts_with("relation") && !t.starts_with("relation-target") {
             relation_line_idx = Some(toml_start + i);
@@ -199,8 +245,7 @@ pub fn get_metadata_actions(uri: &Url, content: &str, range: Range) -> Vec<CodeA
             }
         } else {
             // Mark as active: preserve existing relation-target values
-            let mut edits = Vec::new();
-            edits.push(TextEdit {
+            let edits = vec![TextEdit {
                 range: Range {
                     start: Position {
                         line: rel_file_line as u32,
@@ -212,7 +257,7 @@ pub fn get_metadata_actions(uri: &Url, content: &str, range: Range) -> Vec<CodeA
                     },
                 },
                 new_text: "  relation = \"active\"".to_string(),
-            });
+            }];
             let workspace_edit = WorkspaceEdit {
                 changes: Some([(uri.clone(), edits)].into_iter().collect()),
                 ..Default::default()
@@ -275,9 +320,164 @@ pub fn get_metadata_actions(uri: &Url, content: &str, range: Range) -> Vec<CodeA
     actions
 }
 
+/// Refactor actions on a selection in the note body.
+///
+/// "ZK: Set as abstract" writes the selected text, joined onto one line, to
+/// `abstract`, adding a metadata block when the note has none. Not offered
+/// for an empty selection or one touching the metadata block.
+pub fn get_selection_actions(
+    uri: &Url,
+    content: &str,
+    range: Range,
+    zk_config: &ZkLspConfig,
+    note_parser: &Parser,
+) -> Vec<CodeActionOrCommand> {
+    if range.start == range.end {
+        return Vec::new();
+    }
+    if let Some(block) = parser::find_toml_metadata_block(content) {
+        if range.start.line <= block.end_line as u32 && range.end.line >= block.start_line as u32 {
+            return Vec::new();
+        }
+    }
+    let text = selected_text(content, range)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let edit = abstract_gen::set_abstract_edit(content, &text, zk_config, note_parser);
+    vec![CodeActionOrCommand::CodeAction(CodeAction {
+        title: "ZK: Set as abstract".to_string(),
+        kind: Some(CodeActionKind::REFACTOR),
+        edit: Some(WorkspaceEdit {
+            changes: Some([(uri.clone(), vec![edit])].into_iter().collect()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })]
+}
+
+/// The text `range` covers in `content`, one `\n` after each line.
+fn selected_text(content: &str, range: Range) -> String {
+    let lines: Vec<&str> = parser::split_lines(content).collect();
+    let mut text = String::new();
+    for line_idx in range.start.line..=range.end.line {
+        let Some(line) = lines.get(line_idx as usize) else {
+            break;
+        };
+        let start = if line_idx == range.start.line {
+            parser::utf16_to_byte(line, range.start.character)
+        } else {
+            0
+        };
+        let end = if line_idx == range.end.line {
+            parser::utf16_to_byte(line, range.end.character)
+        } else {
+            line.len()
+        };
+        text.push_str(&line[start..end.max(start)]);
+        text.push('\n');
+    }
+    text
+}
+
+/// `zk.insertReference`: insert a reference to `id` in `note_parser`'s style
+/// at `position` (after a space when the cursor follows a word character,
+/// and after the `- [ ]` prefix when the cursor is inside it) and, if that makes the line an `@ID` checklist item, set its checkbox from
+/// `dep_states` the way formatting would.
+pub fn insert_reference_edit(
+    uri: &Url,
+    content: &str,
+    position: Position,
+    id: &str,
+    dep_states: &HashMap<String, bool>,
+    note_parser: &Parser,
+) -> WorkspaceEdit {
+    let line = parser::split_lines(content)
+        .nth(position.line as usize)
+        .unwrap_or("");
+    let indent = line.len() - line.trim_start().len();
+    let mut at = parser::utf16_to_byte(line, position.character);
+    // A cursor inside or before a `- [ ]` prefix inserts after it, so the
+    // reference never splits the checkbox or overlaps the mark edit below.
+    if let Some(prefix) = checkbox_prefix_len(&line[indent..]) {
+        at = at.max(indent + prefix);
+    }
+    let needs_space = line[..at]
+        .chars()
+        .next_back()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let inserted = format!(
+        "{}{}",
+        if needs_space { " " } else { "" },
+        note_parser.config.ref_style.format(id)
+    );
+    let insert_pos = Position::new(position.line, parser::byte_to_utf16(line, at));
+    let mut edits = vec![TextEdit {
+        range: Range::new(insert_pos, insert_pos),
+        new_text: inserted.clone(),
+    }];
+
+    let new_line = format!("{}{inserted}{}", &line[..at], &line[at..]);
+    if let Some(state) = ref_checkbox_update(&new_line, dep_states, note_parser) {
+        let mark = parser::byte_to_utf16(line, indent + 3);
+        edits.push(TextEdit {
+            range: Range::new(
+                Position::new(position.line, mark),
+                Position::new(position.line, mark + 1),
+            ),
+            new_text: state.to_string(),
+        });
+    }
+    WorkspaceEdit {
+        changes: Some([(uri.clone(), edits)].into_iter().collect()),
+        ..Default::default()
+    }
+}
+
+/// Byte length of a leading `- [?]` checkbox, plus the space after it.
+fn checkbox_prefix_len(line: &str) -> Option<usize> {
+    let rest = line.strip_prefix("- [")?;
+    let mark = rest.chars().next()?;
+    let rest = rest[mark.len_utf8()..].strip_prefix(']')?;
+    Some(line.len() - rest.strip_prefix(' ').unwrap_or(rest).len())
+}
+
+fn make_replace_action(
+    uri: &Url,
+    diag: &Diagnostic,
+    title: String,
+    new_text: String,
+) -> CodeActionOrCommand {
+    let edit = WorkspaceEdit {
+        changes: Some(
+            [(
+                uri.clone(),
+                vec![TextEdit {
+                    range: diag.range,
+                    new_text,
+                }],
+            )]
+            .into_iter()
+            .collect(),
+        ),
+        ..Default::default()
+    };
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diag.clone()]),
+        edit: Some(edit),
+        ..Default::default()
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::ParserConfig;
 
     const NOTE_TOML_ACTIVE: &str = concat!(
         "#import \"../include.typ\": *\n",
@@ -511,6 +711,78 @@ mod tests {
         assert_eq!(edits[0].new_text, "  relation = \"archived\"");
     }
 
+    #[test]
+    fn test_code_actions_dead_link_offers_replace_only() {
+        let uri = make_uri();
+        let diagnostic = Diagnostic {
+            source: Some("zk-lsp".into()),
+            message: "Note @2603150390 does not exist".into(),
+            data: Some(
+                serde_json::to_value(DiagnosticData {
+                    kind: "dead-link".into(),
+                    old_id: "2603150390".into(),
+                    new_ids: Some(vec!["2603150930".into(), "2603150939".into()]),
+                    replacement: None,
+                })
+                .unwrap(),
+            ),
+            ..Default::default()
+        };
+        let titles = |style: RefStyle| -> Vec<String> {
+            get_code_actions(&uri, std::slice::from_ref(&diagnostic), style)
+                .into_iter()
+                .map(|a| match a {
+                    CodeActionOrCommand::CodeAction(ca) => ca.title,
+                    CodeActionOrCommand::Command(c) => c.title,
+                })
+                .collect()
+        };
+        assert_eq!(
+            titles(RefStyle::At),
+            vec![
+                "Fix: Did you mean @2603150930?",
+                "Fix: Did you mean @2603150939?"
+            ]
+        );
+        assert_eq!(
+            titles(RefStyle::Wikilink),
+            vec![
+                "Fix: Did you mean [[2603150930]]?",
+                "Fix: Did you mean [[2603150939]]?"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_code_actions_create_missing_checkbox_target() {
+        let uri = make_uri();
+        let diagnostic = Diagnostic {
+            source: Some("zk-lsp".into()),
+            message: "Checkbox depends on nonexistent note @2699999999".into(),
+            data: Some(
+                serde_json::to_value(DiagnosticData {
+                    kind: "missing-checkbox-target".into(),
+                    old_id: "2699999999".into(),
+                    new_ids: None,
+                    replacement: None,
+                })
+                .unwrap(),
+            ),
+            ..Default::default()
+        };
+        let actions = get_code_actions(&uri, &[diagnostic], RefStyle::At);
+        let [CodeActionOrCommand::CodeAction(action)] = actions.as_slice() else {
+            panic!("expected a single code action, got {actions:?}");
+        };
+        let command = action.command.as_ref().unwrap();
+        assert_eq!(action.title, "Create note @2699999999");
+        assert_eq!(command.command, "zk.newNote");
+        assert_eq!(
+            command.arguments,
+            Some(vec![serde_json::Value::String("2699999999".into())])
+        );
+    }
+
     #[test]
     fn test_code_actions_add_missing_toml_field() {
         let uri = make_uri();
@@ -538,7 +810,7 @@ mod tests {
             ),
             ..Default::default()
         };
-        let actions = get_code_actions(&uri, &[diagnostic]);
+        let actions = get_code_actions(&uri, &[diagnostic], RefStyle::At);
         let action = actions.iter().find_map(|a| match a {
             CodeActionOrCommand::CodeAction(ca)
                 if ca.title == "Fix: Add missing TOML field aliases" =>
@@ -584,7 +856,7 @@ mod tests {
             ),
             ..Default::default()
         };
-        let actions = get_code_actions(&uri, &[diagnostic]);
+        let actions = get_code_actions(&uri, &[diagnostic], RefStyle::At);
         let titles = actions
             .iter()
             .filter_map(|a| match a {
@@ -598,33 +870,133 @@ mod tests {
         assert!(titles.contains(&"Fix: Keep @1111111111 and append @2222222222"));
         assert!(titles.contains(&"Fix: Keep @1111111111 and append @3333333333"));
     }
-}
 
-fn make_replace_action(
-    uri: &Url,
-    diag: &Diagnostic,
-    title: String,
-    new_text: String,
-) -> CodeActionOrCommand {
-    let edit = WorkspaceEdit {
-        changes: Some(
-            [(
-                uri.clone(),
-                vec![TextEdit {
-                    range: diag.range,
-                    new_text,
-                }],
-            )]
-            .into_iter()
-            .collect(),
-        ),
-        ..Default::default()
-    };
-    CodeActionOrCommand::CodeAction(CodeAction {
-        title,
-        kind: Some(CodeActionKind::QUICKFIX),
-        diagnostics: Some(vec![diag.clone()]),
-        edit: Some(edit),
-        ..Default::default()
-    })
+    fn abstract_edit(content: &str, range: Range) -> Option<TextEdit> {
+        let actions = get_selection_actions(
+            &make_uri(),
+            content,
+            range,
+            &ZkLspConfig::default(),
+            parser::default_parser(),
+        );
+        let CodeActionOrCommand::CodeAction(action) = actions.into_iter().next()? else {
+            panic!("expected a code action");
+        };
+        assert_eq!(action.title, "ZK: Set as abstract");
+        let mut changes = action.edit?.changes?;
+        changes.remove(&make_uri())?.into_iter().next()
+    }
+
+    #[test]
+    fn test_selection_set_as_abstract_inserts_into_block() {
+        let content = format!("{NOTE_TOML_ACTIVE}Intro says \"hi\"\n  and more.\n");
+        // From `says` on line 12 through `and` on line 13.
+        let edit = abstract_edit(
+            &content,
+            Range::new(Position::new(12, 6), Position::new(13, 5)),
+        )
+        .unwrap();
+        assert_eq!(edit.range.start, Position::new(7, 0));
+        assert_eq!(edit.new_text, "  abstract = \"says \\\"hi\\\" and\"\n");
+        // Not on an empty selection, nor one reaching into the metadata.
+        let cursor = Range::new(Position::new(12, 6), Position::new(12, 6));
+        assert!(abstract_edit(&content, cursor).is_none());
+        let into_block = Range::new(Position::new(6, 0), Position::new(12, 5));
+        assert!(abstract_edit(&content, into_block).is_none());
+    }
+
+    #[test]
+    fn test_selection_set_as_abstract_creates_missing_block() {
+        let content = "#import \"../include.typ\": *\n= Bare <2603110000>\nKey idea.\n";
+        let edit = abstract_edit(
+            content,
+            Range::new(Position::new(2, 0), Position::new(2, 9)),
+        )
+        .unwrap();
+        assert_eq!(edit.range.start, Position::new(1, 0));
+        assert!(edit.new_text.starts_with("#let zk-metadata = toml(bytes("));
+        assert!(edit.new_text.contains("abstract = \"Key idea.\""));
+    }
+
+    fn reference_edits(content: &str, position: Position, done: bool) -> Vec<TextEdit> {
+        let deps = HashMap::from([("2603110001".to_string(), done)]);
+        let edit = insert_reference_edit(
+            &make_uri(),
+            content,
+            position,
+            "2603110001",
+            &deps,
+            parser::default_parser(),
+        );
+        edit.changes.unwrap().remove(&make_uri()).unwrap()
+    }
+
+    #[test]
+    fn test_insert_reference_uses_wikilink_style() {
+        let wikilinks = Parser::new(ParserConfig {
+            ref_style: RefStyle::Wikilink,
+            ..ParserConfig::default()
+        });
+        let content = "= Plan <2603110000>\n- [ ] ship \n";
+        let deps = HashMap::from([("2603110001".to_string(), true)]);
+        let edit = insert_reference_edit(
+            &make_uri(),
+            content,
+            Position::new(1, 11),
+            "2603110001",
+            &deps,
+            &wikilinks,
+        );
+        let edits = edit.changes.unwrap().remove(&make_uri()).unwrap();
+        assert_eq!(edits[0].new_text, "[[2603110001]]");
+        assert_eq!(edits[1].new_text, "x");
+    }
+
+    #[test]
+    fn test_insert_reference_checks_todo_for_done_note() {
+        let content = "= Plan <2603110000>\n- [ ] ship 中文 \n";
+        let edits = reference_edits(content, Position::new(1, 14), true);
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].range.start, Position::new(1, 14));
+        assert_eq!(edits[0].new_text, "@2603110001");
+        assert_eq!(
+            edits[1].range,
+            Range::new(Position::new(1, 3), Position::new(1, 4))
+        );
+        assert_eq!(edits[1].new_text, "x");
+
+        // A checked todo referencing a note that is not done is cleared.
+        let checked = "- [x] ship \n";
+        let edits = reference_edits(checked, Position::new(0, 11), false);
+        assert_eq!(edits[1].new_text, " ");
+    }
+
+    #[test]
+    fn test_insert_reference_plain_text_and_spacing() {
+        let content = "see\n";
+        let edits = reference_edits(content, Position::new(0, 3), true);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, " @2603110001");
+
+        // An unchecked todo whose reference is not done stays unchecked.
+        let todo = "- [ ] \n";
+        let edits = reference_edits(todo, Position::new(0, 6), false);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, "@2603110001");
+    }
+
+    #[test]
+    fn test_insert_reference_moves_out_of_checkbox_prefix() {
+        let content = "  - [ ] \n";
+        for character in [0, 4, 5] {
+            let edits = reference_edits(content, Position::new(0, character), true);
+            assert_eq!(edits.len(), 2);
+            assert_eq!(edits[0].range.start, Position::new(0, 8));
+            assert_eq!(edits[0].new_text, "@2603110001");
+            assert_eq!(
+                edits[1].range,
+                Range::new(Position::new(0, 5), Position::new(0, 6))
+            );
+        }
+    }
 }
@@ -1,9 +1,105 @@
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 
 use tower_lsp::lsp_types::*;
 
+use crate::config::{RefStyle, WikiConfig};
 use crate::index::NoteIndex;
-use crate::parser;
+use crate::note_ops;
+use crate::parser::{self, Parser};
+
+/// `@` reference completion in the note body: every indexed note, with the
+/// notes this document already cites ranked first (most cited first), then
+/// the rest by how many notes link to them. Offered when the text before the
+/// cursor is `@` plus zero or more digits, or `[[` plus digits under
+/// `ref_style = "wikilink"` (the item then also closes the `]]`). Each item
+/// documents the note's abstract, cut to `max_preview_chars`.
+pub fn get_reference_completions(
+    content: &str,
+    position: Position,
+    index: &NoteIndex,
+    note_parser: &Parser,
+    max_preview_chars: usize,
+) -> Vec<CompletionItem> {
+    let Some(line) = parser::split_lines(content).nth(position.line as usize) else {
+        return Vec::new();
+    };
+    let cursor = parser::utf16_to_byte(line, position.character);
+    let prefix = &line[..cursor];
+    let typed = prefix.trim_end_matches(|c: char| c.is_ascii_digit());
+    let close = match note_parser.config.ref_style {
+        RefStyle::At => {
+            let Some(before_at) = typed.strip_suffix('@') else {
+                return Vec::new();
+            };
+            // `name@host` is an e-mail address, not a reference.
+            if before_at.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+                return Vec::new();
+            }
+            ""
+        }
+        RefStyle::Wikilink => {
+            if !typed.ends_with("[[") {
+                return Vec::new();
+            }
+            if line[cursor..].starts_with("]]") {
+                ""
+            } else {
+                "]]"
+            }
+        }
+    };
+    let digits = &prefix[typed.len()..];
+    if digits.len() >= 10 {
+        return Vec::new();
+    }
+
+    let mut local: HashMap<String, usize> = HashMap::new();
+    for r in note_parser.find_all_refs_filtered(content) {
+        *local.entry(r.id).or_default() += 1;
+    }
+    let start = Position::new(position.line, position.character - digits.len() as u32);
+
+    index
+        .notes
+        .iter()
+        .filter(|entry| entry.key().starts_with(digits))
+        .map(|entry| {
+            let info = entry.value();
+            // Lower sorts first: cited-here notes (bucket 0) before the rest.
+            let sort_text = match local.get(&info.id) {
+                Some(&n) => format!("0{:010}{}", u32::MAX - n as u32, info.id),
+                None => {
+                    let linking = index.linking_notes(&info.id) as u32;
+                    format!("1{:010}{}", u32::MAX - linking, info.id)
+                }
+            };
+            CompletionItem {
+                label: info.id.clone(),
+                detail: Some(info.title.clone()),
+                documentation: info
+                    .abstract_text
+                    .as_deref()
+                    .filter(|text| !text.is_empty())
+                    .map(|text| {
+                        Documentation::String(parser::truncate_preview(text, max_preview_chars))
+                    }),
+                filter_text: Some(info.id.clone()),
+                sort_text: Some(sort_text),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range: Range::new(start, position),
+                    new_text: format!("{}{close}", info.id),
+                })),
+                kind: Some(CompletionItemKind::REFERENCE),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Stand-in for the title while the scaffold is escaped for snippet syntax.
+const TITLE_SENTINEL: &str = "\u{0}title\u{0}";
 
 /// Generate TOML metadata completions for the given cursor position.
 ///
@@ -12,6 +108,7 @@ use crate::parser;
 /// - `checklist-status = "` → enum values
 /// - `relation = "` → enum values
 /// - `relation-target = [` → note IDs from the index
+/// - `keywords = [` → keywords used in the wiki, most used first
 /// - blank / whitespace → missing field names
 pub fn get_completions(
     content: &str,
@@ -27,7 +124,7 @@ pub fn get_completions(
         return Vec::new();
     }
 
-    let lines: Vec<&str> = content.lines().collect();
+    let lines: Vec<&str> = parser::split_lines(content).collect();
     let current_line = lines.get(line_num).copied().unwrap_or("");
     let trimmed = current_line.trim_start();
 
@@ -56,19 +153,10 @@ pub fn get_completions(
             .collect();
     }
 
-    if trimmed.starts_with("relation-target") && trimmed.contains('[') {
-        // Determine whether the cursor sits inside an existing string literal.
-        // Count `"` characters between the last `[` or `,` and the cursor; if
-        // the count is odd the cursor is inside a string → insert bare id.
-        // Otherwise wrap with quotes so the result is valid TOML.
-        let col = position.character as usize;
-        let prefix = &current_line[..col.min(current_line.len())];
-        let after_bracket = prefix.rfind('[').map_or(prefix, |p| &prefix[p + 1..]);
-        let after_delim = after_bracket
-            .rfind(',')
-            .map_or(after_bracket, |p| &after_bracket[p + 1..]);
-        let inside_string = after_delim.chars().filter(|&c| c == '"').count() % 2 == 1;
-
+    let col = parser::utf16_to_byte(current_line, position.character);
+    let array = open_array(&lines, block.start_line, line_num, col);
+    if let Some(("relation-target", before)) = array.as_ref().map(|(k, b)| (*k, b.as_str())) {
+        let inside_string = inside_array_string(before);
         return index
             .notes
             .iter()
@@ -91,11 +179,43 @@ pub fn get_completions(
             .collect();
     }
 
+    if let Some(("keywords", before)) = array.as_ref().map(|(k, b)| (*k, b.as_str())) {
+        let inside_string = inside_array_string(before);
+        // Quoted strings in the array are the keywords already listed.
+        let rest = parser::split_lines(&current_line[col..])
+            .chain(lines.iter().skip(line_num + 1).copied())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let array_text = format!("{before}{}", &rest[..array_end(&rest)]);
+        let listed: Vec<String> = array_text
+            .split('"')
+            .skip(1)
+            .step_by(2)
+            .map(str::to_lowercase)
+            .collect();
+        return index
+            .keyword_counts()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (keyword, _))| !listed.contains(&keyword.to_lowercase()))
+            .map(|(rank, (keyword, count))| CompletionItem {
+                label: keyword.clone(),
+                insert_text: Some(if inside_string {
+                    keyword.clone()
+                } else {
+                    format!("\"{keyword}\"")
+                }),
+                detail: Some(format!("{count} note{}", if count == 1 { "" } else { "s" })),
+                sort_text: Some(format!("{rank:06}")),
+                kind: Some(CompletionItemKind::KEYWORD),
+                ..Default::default()
+            })
+            .collect();
+    }
+
     // Blank line → suggest missing fields
     if trimmed.is_empty() {
-        let present: Vec<&str> = block
-            .toml_content
-            .lines()
+        let present: Vec<&str> = parser::split_lines(&block.toml_content)
             .filter_map(|l| {
                 let t = l.trim_start();
                 if t.starts_with("schema-version") {
@@ -149,6 +269,111 @@ pub fn get_completions(
     Vec::new()
 }
 
+/// The `key = [...]` array that byte `col` of line `line_num` sits in, with
+/// the array text from after `[` up to the cursor. The array may have been
+/// opened on an earlier line of the block; `None` once it is closed.
+fn open_array<'a>(
+    lines: &[&'a str],
+    block_start: usize,
+    line_num: usize,
+    col: usize,
+) -> Option<(&'a str, String)> {
+    let mut before = Vec::new();
+    for i in (block_start..=line_num).rev() {
+        let line = lines.get(i).copied().unwrap_or("");
+        let text = if i == line_num {
+            &line[..col.min(line.len())]
+        } else {
+            line
+        };
+        if let Some((key, value)) = toml_key(text) {
+            before.push(value.trim_start().strip_prefix('[')?);
+            before.reverse();
+            let before = before.join("\n");
+            return (array_end(&before) == before.len()).then_some((key, before));
+        }
+        before.push(text);
+    }
+    None
+}
+
+/// `key` and the text after `=` when `line` starts a `key = value` pair.
+fn toml_key(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once('=')?;
+    let key = key.trim();
+    let bare = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    (!key.is_empty() && key.chars().all(bare)).then_some((key, value))
+}
+
+/// Byte offset of the `]` closing an array whose text starts at `s`
+/// (strings skipped), or `s.len()` if it is not closed.
+fn array_end(s: &str) -> usize {
+    let mut in_string = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ']' if !in_string => return i,
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+/// Whether the cursor, after array text `before`, sits inside a string
+/// literal: an odd number of `"` since the last `,`. Outside one,
+/// completions wrap their text in quotes so the result is valid TOML.
+fn inside_array_string(before: &str) -> bool {
+    let after_delim = before.rfind(',').map_or(before, |p| &before[p + 1..]);
+    after_delim.chars().filter(|&c| c == '"').count() % 2 == 1
+}
+
+/// Offer a `zettel-header` snippet that scaffolds a new note.
+///
+/// Only offered for a note file (`<ID>.typ` inside the note directory) that
+/// has no header yet and at most one non-blank line, i.e. the user has just
+/// started typing. The scaffold is what `zk-lsp new` writes (including a
+/// custom template), with the ID from the file name and a tab stop for the
+/// title.
+pub fn get_header_snippet(
+    content: &str,
+    path: &Path,
+    config: &WikiConfig,
+) -> Option<CompletionItem> {
+    let id = parser::note_id_from_path(path)?;
+    if !path.starts_with(&config.note_dir) || parser::parse_header(content).is_some() {
+        return None;
+    }
+    if parser::split_lines(content)
+        .filter(|l| !l.trim().is_empty())
+        .count()
+        > 1
+    {
+        return None;
+    }
+    let scaffold = note_ops::build_note_content(id, TITLE_SENTINEL, config);
+    let snippet = escape_snippet(&scaffold).replace(TITLE_SENTINEL, "${1:Title}") + "$0";
+    Some(CompletionItem {
+        label: "zettel-header".into(),
+        kind: Some(CompletionItemKind::SNIPPET),
+        detail: Some(format!("New note header <{id}>")),
+        insert_text: Some(snippet),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        ..Default::default()
+    })
+}
+
+/// Escape `$`, `}` and `\` so text is inserted literally by a snippet.
+fn escape_snippet(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '$' | '}' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -208,29 +433,174 @@ mod tests {
     }
 
     fn index_with_note(id: &str, title: &str) -> Arc<NoteIndex> {
+        index_with_notes(&[(id, title)])
+    }
+
+    fn index_with_notes(notes: &[(&str, &str)]) -> Arc<NoteIndex> {
         use crate::index::NoteInfo;
-        use std::path::PathBuf;
-        let idx = NoteIndex::new(Arc::new(tokio::sync::RwLock::new(WikiConfig::from_root(
-            PathBuf::from("/tmp"),
-        ))));
-        idx.notes.insert(
-            id.to_string(),
+        Arc::new(NoteIndex::with_notes(notes.iter().map(|(id, title)| {
             NoteInfo {
-                id: id.to_string(),
                 title: title.to_string(),
-                archived: false,
-                legacy: false,
-                alt_id: None,
-                evo_id: None,
-                relation_target: vec![],
-                aliases: vec![],
-                keywords: vec![],
-                abstract_text: None,
-                checklist_status: None,
-                path: PathBuf::from(format!("/tmp/{id}.typ")),
-            },
+                ..NoteInfo::stub(id)
+            }
+        })))
+    }
+
+    fn ranked(items: &[CompletionItem]) -> Vec<&str> {
+        let mut items: Vec<&CompletionItem> = items.iter().collect();
+        items.sort_by_key(|i| i.sort_text.clone());
+        items.iter().map(|i| i.label.as_str()).collect()
+    }
+
+    #[test]
+    fn test_reference_completion_ranks_cited_notes_first() {
+        use crate::index::BacklinkLocation;
+        let index = index_with_notes(&[
+            ("2603010000", "Hub"),
+            ("2603020000", "Cited twice"),
+            ("2603030000", "Cited once"),
+            ("2603040000", "Unrelated"),
+        ]);
+        // 01 is linked from elsewhere, so it leads the global ranking.
+        index
+            .backlinks
+            .entry("2603010000".into())
+            .or_default()
+            .push(BacklinkLocation {
+                file: "/tmp/2603090000.typ".into(),
+                line: 0,
+                start_char: 0,
+                end_char: 11,
+                start_byte: 0,
+                end_byte: 11,
+            });
+        let content = "= Draft <2603100000>\nSee @2603030000 and @2603020000.\n\
+                       Again @2603020000.\nNext: @\n";
+        let items = get_reference_completions(
+            content,
+            Position::new(3, 7),
+            &index,
+            parser::default_parser(),
+            280,
+        );
+        assert_eq!(
+            ranked(&items),
+            vec!["2603020000", "2603030000", "2603010000", "2603040000"]
+        );
+        let top = items.iter().find(|i| i.label == "2603020000").unwrap();
+        let Some(CompletionTextEdit::Edit(edit)) = &top.text_edit else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(3, 7), Position::new(3, 7))
+        );
+        assert_eq!(top.detail.as_deref(), Some("Cited twice"));
+    }
+
+    #[test]
+    fn test_reference_completion_documents_truncated_abstract() {
+        let index = index_with_notes(&[("2603010000", "Long"), ("2603020000", "Bare")]);
+        index.notes.get_mut("2603010000").unwrap().abstract_text =
+            Some("An abstract that goes on".into());
+        let items = get_reference_completions(
+            "@",
+            Position::new(0, 1),
+            &index,
+            parser::default_parser(),
+            11,
+        );
+        let docs = |id: &str| {
+            items
+                .iter()
+                .find(|i| i.label == id)
+                .unwrap()
+                .documentation
+                .clone()
+        };
+        assert_eq!(
+            docs("2603010000"),
+            Some(Documentation::String("An abstract…".into()))
         );
-        Arc::new(idx)
+        assert_eq!(docs("2603020000"), None);
+    }
+
+    #[test]
+    fn test_reference_completion_prefix_and_context() {
+        let index = index_with_notes(&[("2603010000", "A"), ("2604010000", "B")]);
+        // Empty document: nothing cited yet, still completes after `@`.
+        let items = get_reference_completions(
+            "@",
+            Position::new(0, 1),
+            &index,
+            parser::default_parser(),
+            280,
+        );
+        assert_eq!(items.len(), 2);
+        // Typed digits narrow the list and are replaced by the edit.
+        let items = get_reference_completions(
+            "x @2604",
+            Position::new(0, 7),
+            &index,
+            parser::default_parser(),
+            280,
+        );
+        assert_eq!(ranked(&items), vec!["2604010000"]);
+        let Some(CompletionTextEdit::Edit(edit)) = &items[0].text_edit else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(edit.range.start, Position::new(0, 3));
+        // Not after an e-mail style `name@`, nor without an `@`.
+        assert!(get_reference_completions(
+            "me@",
+            Position::new(0, 3),
+            &index,
+            parser::default_parser(),
+            280
+        )
+        .is_empty());
+        assert!(get_reference_completions(
+            "2603",
+            Position::new(0, 4),
+            &index,
+            parser::default_parser(),
+            280
+        )
+        .is_empty());
+        assert!(get_reference_completions(
+            "",
+            Position::new(0, 0),
+            &index,
+            parser::default_parser(),
+            280
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_reference_completion_wikilink_style() {
+        let index = index_with_notes(&[("2603010000", "A")]);
+        let wikilink = Parser::new(parser::ParserConfig {
+            ref_style: RefStyle::Wikilink,
+            ..Default::default()
+        });
+        let new_text = |content: &str, character| {
+            let items = get_reference_completions(
+                content,
+                Position::new(0, character),
+                &index,
+                &wikilink,
+                280,
+            );
+            let Some(CompletionTextEdit::Edit(edit)) = &items.first()?.text_edit else {
+                panic!("expected a text edit");
+            };
+            Some(edit.new_text.clone())
+        };
+        assert_eq!(new_text("See [[26", 8).as_deref(), Some("2603010000]]"));
+        // The closing brackets are already there.
+        assert_eq!(new_text("See [[]]", 6).as_deref(), Some("2603010000"));
+        assert_eq!(new_text("See @", 5), None);
     }
 
     #[test]
@@ -298,6 +668,64 @@ mod tests {
         assert_eq!(items[0].insert_text.as_deref(), Some("2603110001"));
     }
 
+    #[test]
+    fn test_completion_keywords_ranked_by_use() {
+        let content = concat!(
+            "#let zk-metadata = toml(bytes(\n",
+            "  ```toml\n",
+            "  schema-version = 1\n",
+            "  keywords = [\"lsp\", ]\n", // line 3, cursor after `, ` at col 21
+            "  ```.text,\n",
+            "))\n",
+            "= Test <2603110000>\n",
+        );
+        let index = index_with_notes(&[
+            ("2603010000", "A"),
+            ("2603020000", "B"),
+            ("2603030000", "C"),
+        ]);
+        let keywords = |id: &str, keywords: &[&str]| {
+            index.notes.get_mut(id).unwrap().keywords =
+                keywords.iter().map(|k| k.to_string()).collect();
+        };
+        keywords("2603010000", &["Rust", "LSP", "typst"]);
+        keywords("2603020000", &["rust", "typst"]);
+        keywords("2603030000", &["Rust"]);
+
+        let items = get_completions(content, Position::new(3, 21), &index);
+        assert_eq!(ranked(&items), vec!["Rust", "typst"]);
+        assert_eq!(items[0].insert_text.as_deref(), Some("\"Rust\""));
+        assert_eq!(items[0].detail.as_deref(), Some("3 notes"));
+
+        // Not offered on other lines of the block.
+        assert!(get_completions(content, Position::new(2, 20), &index).is_empty());
+    }
+
+    #[test]
+    fn test_completion_keywords_after_non_ascii_and_across_lines() {
+        let index = index_with_notes(&[("2603010000", "A")]);
+        index.notes.get_mut("2603010000").unwrap().keywords =
+            vec!["数学".to_string(), "rust".to_string()];
+        let content = |array: &str| {
+            format!(
+                "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n\
+                 {array}\n  ```.text,\n))\n= Test <2603110000>\n"
+            )
+        };
+
+        // The cursor column is in UTF-16 units; `数学` is 2 units, 6 bytes.
+        let one_line = content("  keywords = [\"数学\", ]");
+        let items = get_completions(&one_line, Position::new(3, 20), &index);
+        assert_eq!(ranked(&items), vec!["rust"]);
+
+        let multi_line = content("  keywords = [\n    \"数学\",\n    \n  ]");
+        let items = get_completions(&multi_line, Position::new(5, 4), &index);
+        assert_eq!(ranked(&items), vec!["rust"]);
+        assert_eq!(items[0].insert_text.as_deref(), Some("\"rust\""));
+        // Past the closing bracket nothing is offered.
+        assert!(get_completions(&multi_line, Position::new(6, 3), &index).is_empty());
+    }
+
     #[test]
     fn test_completion_outside_block_empty() {
         let index = empty_index();
@@ -331,4 +759,29 @@ mod tests {
             "already present field must not appear"
         );
     }
+
+    #[test]
+    fn test_header_snippet_for_empty_note() {
+        let config = WikiConfig::from_root(PathBuf::from("/tmp/zk_no_such_wiki"));
+        let path = config.note_dir.join("2603150000.typ");
+        let item = get_header_snippet("zet", &path, &config).unwrap();
+        assert_eq!(item.insert_text_format, Some(InsertTextFormat::SNIPPET));
+        let snippet = item.insert_text.unwrap();
+        assert!(snippet.contains("= ${1:Title} <2603150000>"));
+        assert!(snippet.starts_with("#import \"../include.typ\": *\n"));
+        assert!(snippet.ends_with("$0"));
+        // Only one tab stop: everything else is literal.
+        assert_eq!(snippet.matches("${").count(), 1);
+    }
+
+    #[test]
+    fn test_header_snippet_not_offered() {
+        let config = WikiConfig::from_root(PathBuf::from("/tmp/zk_no_such_wiki"));
+        let note = config.note_dir.join("2603110000.typ");
+        // Existing header, more than one line, non-ID file name, outside note dir.
+        assert!(get_header_snippet(NOTE_TOML, &note, &config).is_none());
+        assert!(get_header_snippet("a\nb\n", &note, &config).is_none());
+        assert!(get_header_snippet("", &config.note_dir.join("scratch.typ"), &config).is_none());
+        assert!(get_header_snippet("", Path::new("/tmp/2603110000.typ"), &config).is_none());
+    }
 }
@@ -1,26 +1,86 @@
+use std::path::Path;
 use std::sync::Arc;
 
 use tower_lsp::lsp_types::*;
 
+use crate::config::{RefStyle, WikiConfig};
+use crate::cross_wiki::{self, CrossRefTarget};
 use crate::index::NoteIndex;
-use crate::parser;
+use crate::parser::{self, Parser};
+use crate::title_links;
 
 /// Jump from a quoted note ID inside `relation-target = [...]` to the target
-/// note's title line.
+/// note's title line. `from` is the file being edited, which
+/// `reference_resolution = "relative"` resolves against.
 pub fn get_definition(
     content: &str,
     position: Position,
     index: &Arc<NoteIndex>,
+    from: &Path,
 ) -> Option<Location> {
-    get_definition_with_loader(content, position, index, |path| {
+    get_definition_with_loader(content, position, index, from, |path| {
         std::fs::read_to_string(path).ok()
     })
 }
 
+/// Jump from an `@<wiki>:<ID>` reference to the note's title line in the
+/// other wiki.
+pub fn get_cross_wiki_definition(
+    content: &str,
+    position: Position,
+    config: &WikiConfig,
+) -> Option<Location> {
+    let r = cross_wiki::cross_ref_at(content, position)?;
+    let CrossRefTarget::Found(path) = cross_wiki::resolve(config, &r.wiki, &r.id) else {
+        return None;
+    };
+    let note_content = std::fs::read_to_string(&path).ok()?;
+    title_location(&path, &note_content)
+}
+
+/// Jump from a `[[ID]]` body reference to the note's title line under
+/// `ref_style = "wikilink"`. `@ID` is left to the Typst language server,
+/// which resolves it as a label reference.
+pub fn get_wikilink_definition(
+    content: &str,
+    position: Position,
+    index: &Arc<NoteIndex>,
+    note_parser: &Parser,
+    from: &Path,
+) -> Option<Location> {
+    if note_parser.config.ref_style != RefStyle::Wikilink {
+        return None;
+    }
+    let line = parser::split_lines(content).nth(position.line as usize)?;
+    let id = note_parser.ref_at(line, parser::utf16_to_byte(line, position.character))?;
+    let path = index.path_from(id, from)?;
+    let note_content = std::fs::read_to_string(&path).ok()?;
+    title_location(&path, &note_content)
+}
+
+/// Jump from a `[[Note Title]]` link to the title line of the note with
+/// that title or alias (the first of several) under `title_links`.
+pub fn get_title_link_definition(
+    content: &str,
+    position: Position,
+    index: &Arc<NoteIndex>,
+    config: &WikiConfig,
+) -> Option<Location> {
+    if !config.zk_config.title_links {
+        return None;
+    }
+    let link = title_links::title_link_at(content, position)?;
+    let target = title_links::resolve(index, &link.title);
+    let path = index.path_of(target.first()?)?;
+    let note_content = std::fs::read_to_string(&path).ok()?;
+    title_location(&path, &note_content)
+}
+
 fn get_definition_with_loader<F>(
     content: &str,
     position: Position,
     index: &Arc<NoteIndex>,
+    from: &Path,
     load_note: F,
 ) -> Option<Location>
 where
@@ -32,7 +92,7 @@ where
         return None;
     }
 
-    let lines: Vec<&str> = content.lines().collect();
+    let lines: Vec<&str> = parser::split_lines(content).collect();
     let current_line = lines.get(line_num).copied()?;
     let trimmed = current_line.trim_start();
     if !trimmed.starts_with("relation-target") || !trimmed.contains('[') {
@@ -40,12 +100,21 @@ where
     }
 
     let id = find_id_at_col(current_line, position.character as usize)?;
-    let info = index.notes.get(&id)?;
-    let note_content = load_note(&info.path)?;
-    let title_line = parser::parse_header(&note_content)?.title_line_idx as u32;
+    // A file the index skipped (no valid header) is opened at its top.
+    let path = index.path_from(&id, from)?;
+    let note_content = load_note(&path)?;
+    title_location(&path, &note_content)
+}
+
+/// Start of the title line of the note at `path`, or of the file when its
+/// header does not parse.
+fn title_location(path: &Path, note_content: &str) -> Option<Location> {
+    let title_line = parser::parse_header(note_content)
+        .map(|h| h.title_line_idx as u32)
+        .unwrap_or(0);
 
     Some(Location {
-        uri: Url::from_file_path(&info.path).ok()?,
+        uri: Url::from_file_path(path).ok()?,
         range: Range {
             start: Position {
                 line: title_line,
@@ -69,10 +138,8 @@ fn find_id_at_col(line: &str, col: usize) -> Option<String> {
             let end = (start + 10).min(len);
             if end < len && bytes[end] == b'"' {
                 let candidate = &line[start..end];
-                if candidate.len() == 10 && candidate.bytes().all(|b| b.is_ascii_digit()) {
-                    if col >= i && col <= end {
-                        return Some(candidate.to_string());
-                    }
+                if parser::is_valid_id(candidate) && col >= i && col <= end {
+                    return Some(candidate.to_string());
                 }
             }
         }
@@ -89,29 +156,16 @@ mod tests {
     use std::path::PathBuf;
 
     fn make_index(id: &str, title: &str, path: PathBuf) -> Arc<NoteIndex> {
-        let idx = NoteIndex::new(Arc::new(tokio::sync::RwLock::new(WikiConfig::from_root(
-            PathBuf::from("/tmp"),
-        ))));
-        idx.notes.insert(
-            id.to_string(),
-            NoteInfo {
-                id: id.to_string(),
-                title: title.to_string(),
-                archived: false,
-                legacy: false,
-                alt_id: None,
-                evo_id: None,
-                relation_target: vec![],
-                aliases: vec![],
-                keywords: vec![],
-                abstract_text: None,
-                checklist_status: None,
-                path,
-            },
-        );
-        Arc::new(idx)
+        Arc::new(NoteIndex::with_notes([NoteInfo {
+            title: title.to_string(),
+            path,
+            ..NoteInfo::stub(id)
+        }]))
     }
 
+    /// Where `HOST_NOTE_CONTENT` is being edited.
+    const HOST_PATH: &str = "/virtual/2603110000.typ";
+
     const HOST_NOTE_CONTENT: &str = concat!(
         "#import \"../include.typ\": *\n",
         "#let zk-metadata = toml(bytes(\n",
@@ -149,13 +203,19 @@ mod tests {
             line: 5,
             character: 22,
         };
-        let loc = get_definition_with_loader(HOST_NOTE_CONTENT, pos, &index, |load_path| {
-            if load_path == path.as_path() {
-                Some(TARGET_NOTE_CONTENT.to_string())
-            } else {
-                None
-            }
-        })
+        let loc = get_definition_with_loader(
+            HOST_NOTE_CONTENT,
+            pos,
+            &index,
+            Path::new(HOST_PATH),
+            |load_path| {
+                if load_path == path.as_path() {
+                    Some(TARGET_NOTE_CONTENT.to_string())
+                } else {
+                    None
+                }
+            },
+        )
         .expect("expected definition");
 
         assert_eq!(loc.uri, Url::from_file_path(path).unwrap());
@@ -164,6 +224,29 @@ mod tests {
         assert_eq!(loc.range.end, loc.range.start);
     }
 
+    #[test]
+    fn test_definition_falls_back_to_headerless_note_file() {
+        let tmp = std::env::temp_dir().join("zk_definition_test_headerless");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let target = tmp.join("note/2603110001.typ");
+        std::fs::write(&target, "draft without a header\n").unwrap();
+        let index = Arc::new(NoteIndex::new(Arc::new(tokio::sync::RwLock::new(
+            WikiConfig::from_root(tmp.clone()),
+        ))));
+        let pos = Position {
+            line: 5,
+            character: 22,
+        };
+
+        let loc = get_definition(HOST_NOTE_CONTENT, pos, &index, Path::new(HOST_PATH));
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let loc = loc.expect("expected definition");
+        assert_eq!(loc.uri, Url::from_file_path(target).unwrap());
+        assert_eq!(loc.range.start, Position::new(0, 0));
+    }
+
     #[test]
     fn test_definition_outside_relation_target_returns_none() {
         let index = make_index("2603110001", "Target Note", PathBuf::from("/virtual/x.typ"));
@@ -171,6 +254,84 @@ mod tests {
             line: 5,
             character: 5,
         };
-        assert!(get_definition(HOST_NOTE_CONTENT, pos, &index).is_none());
+        assert!(get_definition(HOST_NOTE_CONTENT, pos, &index, Path::new(HOST_PATH)).is_none());
+    }
+
+    #[test]
+    fn test_cross_wiki_definition_opens_other_wikis_note() {
+        use crate::cross_wiki::tests::{home_config, two_wikis, WORK_NOTE};
+        let tmp = two_wikis("zk_cross_wiki_definition");
+        let config = home_config(&tmp);
+        let content = format!("See @work:{WORK_NOTE}.\n");
+        let loc = get_cross_wiki_definition(&content, Position::new(0, 8), &config);
+        let missing =
+            get_cross_wiki_definition("See @work:2602082038.\n", Position::new(0, 8), &config);
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let loc = loc.expect("expected definition");
+        assert!(loc
+            .uri
+            .path()
+            .ends_with(&format!("work/notes/{WORK_NOTE}.typ")));
+        assert_eq!(loc.range.start, Position::new(5, 0));
+        assert!(missing.is_none());
+    }
+
+    #[test]
+    fn test_wikilink_definition_only_under_wikilink_style() {
+        let tmp = std::env::temp_dir().join("zk_wikilink_definition");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(&tmp).unwrap();
+        let path = tmp.join("2603110001.typ");
+        std::fs::write(&path, TARGET_NOTE_CONTENT).unwrap();
+        let index = make_index("2603110001", "Target Note", path.clone());
+        let wikilink = Parser::new(parser::ParserConfig {
+            ref_style: RefStyle::Wikilink,
+            ..Default::default()
+        });
+        let content = "See [[2603110001]] and @2603110001.\n";
+        let at = |note_parser: &Parser, character| {
+            get_wikilink_definition(
+                content,
+                Position::new(0, character),
+                &index,
+                note_parser,
+                Path::new(HOST_PATH),
+            )
+        };
+        let loc = at(&wikilink, 6);
+        let on_at = at(&wikilink, 25);
+        let default_style = at(parser::default_parser(), 6);
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let loc = loc.expect("expected definition");
+        assert_eq!(loc.uri, Url::from_file_path(&path).unwrap());
+        assert_eq!(loc.range.start, Position::new(10, 0));
+        assert!(on_at.is_none());
+        assert!(default_style.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_title_link_definition_follows_first_match() {
+        let (index, tmp) = crate::title_links::tests::titled_wiki("zk_title_link_definition").await;
+        let config = index.config.read().await.clone();
+        let content = "[[Borrowing]] [[Draft]] [[Nowhere]]\n";
+        let at = |character| {
+            get_title_link_definition(content, Position::new(0, character), &index, &config)
+        };
+        let alias = at(3);
+        let ambiguous = at(16);
+        let missing = at(27);
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let alias = alias.expect("expected definition");
+        assert!(alias.uri.path().ends_with("note/2603150000.typ"));
+        assert_eq!(alias.range.start, Position::new(6, 0));
+        assert!(ambiguous
+            .expect("expected definition")
+            .uri
+            .path()
+            .ends_with("note/2603150001.typ"));
+        assert!(missing.is_none());
     }
 }
@@ -3,30 +3,50 @@ use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 use tower_lsp::lsp_types::*;
 
+use crate::config::{WikiConfig, ZkLspConfig};
+use crate::cross_wiki::{self, CrossRefTarget};
 use crate::index::NoteIndex;
-use crate::parser;
+use crate::note_ops::build_metadata_block;
+use crate::parser::{self, NoteDoc, Parser};
 use crate::reconcile::types::{DiagnosticSeverity as ReconcileSeverity, ReconcileDiagnostic};
+use crate::title_links::{self, TitleTarget};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiagnosticData {
-    pub kind: String, // "archived" | "legacy"
+    pub kind: String, // "archived" | "legacy" | "dead-link" | "missing-toml-field" | "missing-checkbox-target" | "missing-metadata"
     pub old_id: String,
     pub new_ids: Option<Vec<String>>,
     pub replacement: Option<String>,
 }
 
 /// Generate diagnostics for all @ID references in the document content.
-pub fn get_diagnostics(content: &str, index: &Arc<NoteIndex>, uri_path: &str) -> Vec<Diagnostic> {
+///
+/// `@<wiki>:<ID>` references into other wikis are checked too: an unknown
+/// wiki is an `unknown-wiki` error, a missing note a `dead-link`. With
+/// `title_links`, a `[[Note Title]]` no note answers to is a `dead-link`
+/// and one several notes answer to an `ambiguous-title` warning.
+///
+/// Each kind (`dead-link`, `unknown-wiki`, `ambiguous-title`, `archived`,
+/// `legacy`, `missing-checkbox-target`) is skipped or re-levelled per the
+/// `disabledDiagnostics` and `diagnosticSeverity` settings in `config`.
+pub fn get_diagnostics(
+    content: &str,
+    index: &Arc<NoteIndex>,
+    uri_path: &str,
+    config: &WikiConfig,
+) -> Vec<Diagnostic> {
+    let settings = &config.settings;
     let note_id = uri_path
         .rsplit('/')
         .next()
         .and_then(|s| s.strip_suffix(".typ"))
         .unwrap_or("");
+    let note_parser = config.parser();
     let mut diagnostics = Vec::new();
 
-    for (line_num, line) in content.lines().enumerate() {
-        let refs = parser::find_all_refs(line);
-        for r in refs {
+    for (line_num, line) in parser::split_lines(content).enumerate() {
+        let refs = note_parser.find_all_refs(line);
+        for r in &refs {
             let range = Range {
                 start: Position {
                     line: line_num as u32,
@@ -39,11 +59,36 @@ pub fn get_diagnostics(content: &str, index: &Arc<NoteIndex>, uri_path: &str) ->
             };
 
             let Some(info) = index.get(&r.id) else {
+                let Some(severity) = settings.severity_for("dead-link", DiagnosticSeverity::ERROR)
+                else {
+                    continue;
+                };
+                let suggestions = index.closest_ids(&r.id, 3);
+                let mut message = format!("Note @{} does not exist", r.id);
+                let mut data = None;
+                if !suggestions.is_empty() {
+                    let list = suggestions
+                        .iter()
+                        .map(|id| format!("@{id}"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    message.push_str(&format!(". Did you mean {list}?"));
+                    data = Some(
+                        serde_json::to_value(DiagnosticData {
+                            kind: "dead-link".into(),
+                            old_id: r.id.clone(),
+                            new_ids: Some(suggestions),
+                            replacement: None,
+                        })
+                        .unwrap(),
+                    );
+                }
                 diagnostics.push(Diagnostic {
                     range,
-                    severity: Some(DiagnosticSeverity::ERROR),
+                    severity: Some(severity),
                     source: Some("zk-lsp".into()),
-                    message: format!("Note @{} does not exist", r.id),
+                    message,
+                    data,
                     ..Default::default()
                 });
                 continue;
@@ -54,6 +99,10 @@ pub fn get_diagnostics(content: &str, index: &Arc<NoteIndex>, uri_path: &str) ->
                 if info.relation_target.iter().any(|t| t == note_id) {
                     continue;
                 }
+                let Some(severity) = settings.severity_for("archived", DiagnosticSeverity::WARNING)
+                else {
+                    continue;
+                };
                 let mut msg = format!("Note @{} is archived.", r.id);
                 if !info.relation_target.is_empty() {
                     let targets = info
@@ -72,25 +121,25 @@ pub fn get_diagnostics(content: &str, index: &Arc<NoteIndex>, uri_path: &str) ->
                 };
                 diagnostics.push(Diagnostic {
                     range,
-                    severity: Some(DiagnosticSeverity::WARNING),
+                    severity: Some(severity),
                     source: Some("zk-lsp".into()),
                     message: msg,
                     data: Some(serde_json::to_value(data).unwrap()),
                     ..Default::default()
                 });
             } else if info.legacy {
-                // Suppress if this note is a relation-target of the legacy note
-                if info.relation_target.iter().any(|t| t == note_id) {
+                // Suppress in a note the legacy note evolved into, and when
+                // the line already mentions one of them anywhere, so
+                // `@old @new` and `@new (was @old)` both count as migrated.
+                let successors = index.evolution_successors(&r.id);
+                if successors.contains(note_id)
+                    || refs.iter().any(|other| successors.contains(&other.id))
+                {
                     continue;
                 }
-                // Suppress if the same line already mentions any successor.
-                let after = &line[r.end_char as usize..];
-                let has_successor_on_same_line = parser::find_all_refs(after)
-                    .into_iter()
-                    .any(|next| info.relation_target.iter().any(|id| id == &next.id));
-                let should_warn = !has_successor_on_same_line;
-
-                if should_warn {
+                if let Some(severity) =
+                    settings.severity_for("legacy", DiagnosticSeverity::INFORMATION)
+                {
                     let mut msg = format!("Note @{} is legacy.", r.id);
                     if !info.relation_target.is_empty() {
                         let targets = info
@@ -109,7 +158,7 @@ pub fn get_diagnostics(content: &str, index: &Arc<NoteIndex>, uri_path: &str) ->
                     };
                     diagnostics.push(Diagnostic {
                         range,
-                        severity: Some(DiagnosticSeverity::INFORMATION),
+                        severity: Some(severity),
                         source: Some("zk-lsp".into()),
                         message: msg,
                         data: Some(serde_json::to_value(data).unwrap()),
@@ -118,8 +167,148 @@ pub fn get_diagnostics(content: &str, index: &Arc<NoteIndex>, uri_path: &str) ->
                 }
             }
         }
+        for r in parser::find_cross_refs(line) {
+            let (kind, message) = match cross_wiki::resolve(config, &r.wiki, &r.id) {
+                CrossRefTarget::Found(_) => continue,
+                CrossRefTarget::UnknownWiki => (
+                    "unknown-wiki",
+                    format!(
+                        "Unknown wiki `{}` in @{}:{}: add it under [wikis] in zk-lsp.toml",
+                        r.wiki, r.wiki, r.id
+                    ),
+                ),
+                CrossRefTarget::Missing => (
+                    "dead-link",
+                    format!("Note @{} does not exist in wiki `{}`", r.id, r.wiki),
+                ),
+            };
+            let Some(severity) = settings.severity_for(kind, DiagnosticSeverity::ERROR) else {
+                continue;
+            };
+            diagnostics.push(Diagnostic {
+                range: Range {
+                    start: Position {
+                        line: line_num as u32,
+                        character: parser::byte_to_utf16(line, r.start_char as usize),
+                    },
+                    end: Position {
+                        line: line_num as u32,
+                        character: parser::byte_to_utf16(line, r.end_char as usize),
+                    },
+                },
+                severity: Some(severity),
+                source: Some("zk-lsp".into()),
+                message,
+                ..Default::default()
+            });
+        }
+        if !config.zk_config.title_links {
+            continue;
+        }
+        for link in parser::find_title_links(line) {
+            let (kind, default, message) = match title_links::resolve(index, &link.title) {
+                TitleTarget::Found(_) => continue,
+                TitleTarget::Missing => (
+                    "dead-link",
+                    DiagnosticSeverity::ERROR,
+                    format!("No note is titled `{}`", link.title),
+                ),
+                TitleTarget::Ambiguous(ids) => (
+                    "ambiguous-title",
+                    DiagnosticSeverity::WARNING,
+                    format!(
+                        "`{}` matches {} notes: {}. Links go to @{}",
+                        link.title,
+                        ids.len(),
+                        ids.iter()
+                            .map(|id| format!("@{id}"))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        ids[0]
+                    ),
+                ),
+            };
+            let Some(severity) = settings.severity_for(kind, default) else {
+                continue;
+            };
+            diagnostics.push(Diagnostic {
+                range: Range {
+                    start: Position {
+                        line: line_num as u32,
+                        character: parser::byte_to_utf16(line, link.start_char as usize),
+                    },
+                    end: Position {
+                        line: line_num as u32,
+                        character: parser::byte_to_utf16(line, link.end_char as usize),
+                    },
+                },
+                severity: Some(severity),
+                source: Some("zk-lsp".into()),
+                message,
+                ..Default::default()
+            });
+        }
     }
 
+    if let Some(severity) =
+        settings.severity_for("missing-checkbox-target", DiagnosticSeverity::WARNING)
+    {
+        diagnostics.extend(get_checkbox_target_diagnostics(
+            content,
+            index,
+            &note_parser,
+            severity,
+        ));
+    }
+    diagnostics
+}
+
+/// Warn on checkbox items whose `@ID` does not resolve: reconcile can never
+/// evaluate them, so the box would stay unchecked forever. The warning sits on
+/// the `[ ]` marker; the dead `@ID` itself still gets the generic error.
+fn get_checkbox_target_diagnostics(
+    content: &str,
+    index: &Arc<NoteIndex>,
+    note_parser: &Parser,
+    severity: DiagnosticSeverity,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for item in note_parser.parse_checklist_items(content) {
+        let parser::ChecklistItemKind::Ref { targets } = &item.kind else {
+            continue;
+        };
+        // "- [" precedes the marker; the range covers "[ ]".
+        let line = item.line_idx as u32;
+        let range = Range {
+            start: Position {
+                line,
+                character: (item.indent + 2) as u32,
+            },
+            end: Position {
+                line,
+                character: (item.indent + 5) as u32,
+            },
+        };
+        for target in targets {
+            if index.contains(&target.target_id) {
+                continue;
+            }
+            let data = DiagnosticData {
+                kind: "missing-checkbox-target".into(),
+                old_id: target.target_id.clone(),
+                new_ids: None,
+                replacement: None,
+            };
+            diagnostics.push(Diagnostic {
+                range,
+                severity: Some(severity),
+                source: Some("zk-lsp".into()),
+                message: format!("Checkbox depends on nonexistent note @{}", target.target_id),
+                data: Some(serde_json::to_value(data).unwrap()),
+                ..Default::default()
+            });
+        }
+    }
     diagnostics
 }
 
@@ -134,8 +323,10 @@ fn extract_toml_field_name(trimmed_line: &str) -> Option<&str> {
 }
 
 /// Validate TOML metadata block fields and produce diagnostics.
-pub fn get_schema_diagnostics(content: &str, index: &Arc<NoteIndex>) -> Vec<Diagnostic> {
-    let lines: Vec<&str> = content.lines().collect();
+/// `relation-target` IDs are only looked up when `index` is given.
+pub fn get_schema_diagnostics(doc: &NoteDoc, index: Option<&Arc<NoteIndex>>) -> Vec<Diagnostic> {
+    let content = doc.content.as_str();
+    let lines: Vec<&str> = parser::split_lines(content).collect();
     let Some(block) = parser::find_toml_metadata_block(content) else {
         return vec![Diagnostic {
             range: Range {
@@ -155,11 +346,17 @@ pub fn get_schema_diagnostics(content: &str, index: &Arc<NoteIndex>) -> Vec<Diag
         }];
     };
 
-    let title_line_idx = lines
-        .iter()
-        .enumerate()
-        .skip(block.end_line + 1)
-        .find_map(|(idx, line)| parser::RE_TITLE.is_match(line).then_some(idx));
+    let title_line_idx =
+        lines
+            .iter()
+            .enumerate()
+            .skip(block.end_line + 1)
+            .find_map(|(idx, line)| {
+                parser::default_parser()
+                    .re_title
+                    .is_match(line)
+                    .then_some(idx)
+            });
 
     if title_line_idx.is_none() {
         return vec![Diagnostic {
@@ -201,7 +398,7 @@ pub fn get_schema_diagnostics(content: &str, index: &Arc<NoteIndex>) -> Vec<Diag
     }
 
     let mut diagnostics = Vec::new();
-    let toml_line_count = block.toml_content.lines().count();
+    let toml_line_count = parser::split_lines(&block.toml_content).count();
     let toml_start = block.end_line.saturating_sub(toml_line_count);
     let expected_fields = [
         ("schema-version", "  schema-version = 1\n"),
@@ -216,7 +413,7 @@ pub fn get_schema_diagnostics(content: &str, index: &Arc<NoteIndex>) -> Vec<Diag
     let mut present_fields = std::collections::HashMap::new();
 
     // Per-line field validation
-    for (i, toml_line) in block.toml_content.lines().enumerate() {
+    for (i, toml_line) in parser::split_lines(&block.toml_content).enumerate() {
         let file_line = toml_start + i;
         let file_line_text = lines.get(file_line).copied().unwrap_or("");
         let trimmed = toml_line.trim_start();
@@ -312,10 +509,14 @@ pub fn get_schema_diagnostics(content: &str, index: &Arc<NoteIndex>) -> Vec<Diag
 
         // relation != "active" but relation-target is empty → WARNING on relation line
         if parsed.relation != Relation::Active && parsed.relation_target.is_empty() {
-            if let Some((i, _)) = block.toml_content.lines().enumerate().find(|(_, l)| {
-                let t = l.trim_start();
-                t.starts_with("relation") && !t.starts_with("relation-target")
-            }) {
+            if let Some((i, _)) =
+                parser::split_lines(&block.toml_content)
+                    .enumerate()
+                    .find(|(_, l)| {
+                        let t = l.trim_start();
+                        t.starts_with("relation") && !t.starts_with("relation-target")
+                    })
+            {
                 let file_line = toml_start + i;
                 let file_line_text = lines.get(file_line).copied().unwrap_or("");
                 diagnostics.push(Diagnostic {
@@ -337,18 +538,44 @@ pub fn get_schema_diagnostics(content: &str, index: &Arc<NoteIndex>) -> Vec<Diag
             }
         }
 
+        // relation-target names the note itself → WARNING on relation-target line
+        let self_link = doc.header.as_ref().and_then(|h| h.self_link());
+        if let Some(kind) = self_link {
+            if let Some((i, _)) = parser::split_lines(&block.toml_content)
+                .enumerate()
+                .find(|(_, l)| l.trim_start().starts_with("relation-target"))
+            {
+                let file_line = toml_start + i;
+                let file_line_text = lines.get(file_line).copied().unwrap_or("");
+                diagnostics.push(Diagnostic {
+                    range: Range {
+                        start: Position {
+                            line: file_line as u32,
+                            character: 0,
+                        },
+                        end: Position {
+                            line: file_line as u32,
+                            character: file_line_text.len() as u32,
+                        },
+                    },
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    source: Some("zk-lsp".into()),
+                    message: format!("Note links to itself as {kind}"),
+                    ..Default::default()
+                });
+            }
+        }
+
         // relation-target IDs not found in index → WARNING on relation-target line
         let unknown_ids: Vec<&str> = parsed
             .relation_target
             .iter()
-            .filter(|id| !id.is_empty() && index.get(id).is_none())
+            .filter(|id| !id.is_empty() && index.is_some_and(|index| !index.contains(id)))
             .map(String::as_str)
             .collect();
 
         if !unknown_ids.is_empty() {
-            if let Some((i, _)) = block
-                .toml_content
-                .lines()
+            if let Some((i, _)) = parser::split_lines(&block.toml_content)
                 .enumerate()
                 .find(|(_, l)| l.trim_start().starts_with("relation-target"))
             {
@@ -385,11 +612,13 @@ pub fn get_schema_diagnostics(content: &str, index: &Arc<NoteIndex>) -> Vec<Diag
 /// 1. No other note references it (no backlinks in the index)
 /// 2. It has no outgoing `@ID` references itself
 ///
-/// Returns `None` if the note is not in the index or is not fully isolated.
+/// Returns `None` if the note is not in the index or is not fully isolated,
+/// or if it is marked `generated` and `exclude_generated` is set.
 pub fn get_orphan_diagnostic(
-    content: &str,
+    doc: &NoteDoc,
     uri_path: &str,
     index: &Arc<NoteIndex>,
+    exclude_generated: bool,
 ) -> Option<Diagnostic> {
     let note_id = uri_path
         .rsplit('/')
@@ -397,7 +626,8 @@ pub fn get_orphan_diagnostic(
         .and_then(|s| s.strip_suffix(".typ"))?;
 
     // Only flag notes that are in the index
-    if index.get(note_id).is_none() {
+    let info = index.get(note_id)?;
+    if exclude_generated && info.generated {
         return None;
     }
 
@@ -407,14 +637,13 @@ pub fn get_orphan_diagnostic(
     }
 
     // Not an orphan if it has outgoing links
-    if !parser::find_all_refs_filtered(content).is_empty() {
+    if !doc.refs.is_empty() {
         return None;
     }
 
     // Find the title line (contains `<{note_id}>`)
     let needle = format!("<{note_id}>");
-    let (line_num, _line_text) = content
-        .lines()
+    let (line_num, _line_text) = parser::split_lines(&doc.content)
         .enumerate()
         .find(|(_, l)| l.contains(&needle))?;
 
@@ -431,7 +660,50 @@ pub fn get_orphan_diagnostic(
         },
         severity: Some(DiagnosticSeverity::HINT),
         source: Some("zk-lsp".into()),
-        message: format!("Orphan note: no inbound or outbound @ID references"),
+        message: "Orphan note: no inbound or outbound @ID references".to_string(),
+        ..Default::default()
+    })
+}
+
+/// Generate a HINT for a note with a title heading but no TOML metadata block,
+/// which therefore cannot carry aliases, keywords or an abstract.
+///
+/// The diagnostic sits where the block belongs (the line after `#import`, or
+/// the top of the file); `data.replacement` holds the block built from
+/// `zk_config` for the "Add metadata block" quick fix. The server only calls
+/// this when the `missingMetadataDiagnostic` setting is on. `note_parser` is
+/// the wiki's configured parser.
+pub fn get_missing_metadata_diagnostic(
+    content: &str,
+    zk_config: &ZkLspConfig,
+    note_parser: &Parser,
+) -> Option<Diagnostic> {
+    if parser::find_toml_metadata_block(content).is_some() {
+        return None;
+    }
+    let note_id = parser::split_lines(content)
+        .find_map(|l| note_parser.re_title.captures(l))?
+        .get(1)?
+        .as_str()
+        .to_string();
+    let line = parser::split_lines(content)
+        .position(|l| note_parser.is_import_line(l))
+        .map_or(0, |i| i + 1) as u32;
+    let data = DiagnosticData {
+        kind: "missing-metadata".into(),
+        old_id: note_id,
+        new_ids: None,
+        replacement: Some(format!("{}\n", build_metadata_block(zk_config))),
+    };
+    Some(Diagnostic {
+        range: Range {
+            start: Position { line, character: 0 },
+            end: Position { line, character: 0 },
+        },
+        severity: Some(DiagnosticSeverity::HINT),
+        source: Some("zk-lsp".into()),
+        message: "Note has no metadata block".to_string(),
+        data: serde_json::to_value(data).ok(),
         ..Default::default()
     })
 }
@@ -456,7 +728,9 @@ pub fn get_reconcile_diagnostics(
             continue;
         }
 
-        let line_text = content.lines().nth(location.line).unwrap_or("");
+        let line_text = parser::split_lines(content)
+            .nth(location.line)
+            .unwrap_or("");
         diagnostics.push(Diagnostic {
             range: Range {
                 start: Position {
@@ -481,12 +755,17 @@ pub fn get_reconcile_diagnostics(
                         .iter()
                         .map(|related| {
                             let related_line = if related.file_path == file_path {
-                                content.lines().nth(related.line).unwrap_or("").to_string()
+                                parser::split_lines(content)
+                                    .nth(related.line)
+                                    .unwrap_or("")
+                                    .to_string()
                             } else {
                                 std::fs::read_to_string(&related.file_path)
                                     .ok()
                                     .and_then(|content| {
-                                        content.lines().nth(related.line).map(str::to_string)
+                                        parser::split_lines(&content)
+                                            .nth(related.line)
+                                            .map(str::to_string)
                                     })
                                     .unwrap_or_default()
                             };
@@ -527,7 +806,7 @@ pub fn get_reconcile_diagnostics(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::WikiConfig;
+    use crate::config::ClientSettings;
     use crate::index::{BacklinkLocation, NoteIndex, NoteInfo};
     use std::path::PathBuf;
     use std::sync::Arc;
@@ -539,22 +818,18 @@ mod tests {
         Arc::new(NoteIndex::new(config))
     }
 
+    /// Config for the `make_index` wiki with client `settings` applied.
+    fn wiki_config(settings: serde_json::Value) -> WikiConfig {
+        WikiConfig::from_root(PathBuf::from("/tmp/wiki"))
+            .with_settings(ClientSettings::from_json(&settings))
+    }
+
     fn insert_note(index: &Arc<NoteIndex>, id: &str) {
         index.notes.insert(
             id.to_string(),
             NoteInfo {
-                id: id.to_string(),
-                title: format!("Note {id}"),
-                archived: false,
-                legacy: false,
-                alt_id: None,
-                evo_id: None,
-                relation_target: vec![],
-                aliases: vec![],
-                keywords: vec![],
-                abstract_text: None,
-                checklist_status: None,
                 path: PathBuf::from(format!("/tmp/wiki/note/{id}.typ")),
+                ..NoteInfo::stub(id)
             },
         );
     }
@@ -563,18 +838,12 @@ mod tests {
         index.notes.insert(
             id.to_string(),
             NoteInfo {
-                id: id.to_string(),
-                title: format!("Note {id}"),
-                archived: false,
                 legacy: true,
                 alt_id: targets.first().map(|s| s.to_string()),
                 evo_id: targets.first().map(|s| s.to_string()),
                 relation_target: targets.iter().map(|s| s.to_string()).collect(),
-                aliases: vec![],
-                keywords: vec![],
-                abstract_text: None,
-                checklist_status: None,
                 path: PathBuf::from(format!("/tmp/wiki/note/{id}.typ")),
+                ..NoteInfo::stub(id)
             },
         );
     }
@@ -589,6 +858,8 @@ mod tests {
                 line: 0,
                 start_char: 0,
                 end_char: 11,
+                start_byte: 0,
+                end_byte: 11,
             });
     }
 
@@ -596,13 +867,69 @@ mod tests {
     fn test_dead_link_produces_error() {
         let index = make_index();
         // Note 1111111111 is NOT in the index → dead link
-        let content = "- [ ] @1111111111\n";
-        let diags = get_diagnostics(content, &index, "/wiki/note/9999999999.typ");
+        // (a checkbox line would add the checkbox-target warning too)
+        let content = "See @1111111111\n";
+        let diags = get_diagnostics(
+            content,
+            &index,
+            "/wiki/note/9999999999.typ",
+            &wiki_config(serde_json::json!({})),
+        );
         assert_eq!(diags.len(), 1);
         assert_eq!(diags[0].severity, Some(DiagnosticSeverity::ERROR));
         assert!(diags[0].message.contains("does not exist"));
     }
 
+    #[test]
+    fn test_dead_link_suggests_close_ids() {
+        let index = make_index();
+        insert_note(&index, "2603150930");
+        let content = "See @2603150390\n";
+        let diags = get_diagnostics(
+            content,
+            &index,
+            "/wiki/note/9999999999.typ",
+            &wiki_config(serde_json::json!({})),
+        );
+        assert_eq!(diags.len(), 1);
+        assert_eq!(
+            diags[0].message,
+            "Note @2603150390 does not exist. Did you mean @2603150930?"
+        );
+        let data: DiagnosticData = serde_json::from_value(diags[0].data.clone().unwrap()).unwrap();
+        assert_eq!(data.kind, "dead-link");
+        assert_eq!(data.new_ids, Some(vec!["2603150930".to_string()]));
+    }
+
+    #[test]
+    fn test_checkbox_with_missing_target_warns_on_marker() {
+        let index = make_index();
+        insert_note(&index, "1111111111");
+        let content = "  - [ ] @1111111111 and @2699999999\n- [ ] plain task\n";
+        let diags = get_diagnostics(
+            content,
+            &index,
+            "/tmp/wiki/note/2222222222.typ",
+            &wiki_config(serde_json::json!({})),
+        );
+        let checkbox: Vec<&Diagnostic> = diags
+            .iter()
+            .filter(|d| d.message.starts_with("Checkbox depends"))
+            .collect();
+        assert_eq!(checkbox.len(), 1);
+        assert_eq!(
+            checkbox[0].message,
+            "Checkbox depends on nonexistent note @2699999999"
+        );
+        assert_eq!(checkbox[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(checkbox[0].range.start, Position::new(0, 4));
+        assert_eq!(checkbox[0].range.end, Position::new(0, 7));
+        // The generic dead-link error is still reported on the ID itself.
+        assert!(diags
+            .iter()
+            .any(|d| d.message == "Note @2699999999 does not exist"));
+    }
+
     #[test]
     fn test_missing_metadata_block_produces_error() {
         let index = make_index();
@@ -612,7 +939,7 @@ mod tests {
             "\n",
             "= Note <2603110000>\n",
         );
-        let diags = get_schema_diagnostics(content, &index);
+        let diags = get_schema_diagnostics(&NoteDoc::parse(content.to_string()), Some(&index));
         assert_eq!(diags.len(), 1);
         assert_eq!(diags[0].severity, Some(DiagnosticSeverity::ERROR));
         assert_eq!(diags[0].message, "Missing `zk-metadata` TOML block");
@@ -637,7 +964,7 @@ mod tests {
             "))\n",
             "#show: zettel.with(metadata: zk-metadata)\n",
         );
-        let diags = get_schema_diagnostics(content, &index);
+        let diags = get_schema_diagnostics(&NoteDoc::parse(content.to_string()), Some(&index));
         assert_eq!(diags.len(), 1);
         assert_eq!(diags[0].severity, Some(DiagnosticSeverity::ERROR));
         assert_eq!(
@@ -652,13 +979,44 @@ mod tests {
         insert_note(&index, "1111111111");
         // No backlinks, no outgoing refs → orphan
         let content = "= My Note <1111111111>\n";
-        let diag = get_orphan_diagnostic(content, "/wiki/note/1111111111.typ", &index);
+        let diag = get_orphan_diagnostic(
+            &NoteDoc::parse(content.to_string()),
+            "/wiki/note/1111111111.typ",
+            &index,
+            false,
+        );
         assert!(diag.is_some());
         let d = diag.unwrap();
         assert_eq!(d.severity, Some(DiagnosticSeverity::HINT));
         assert!(d.message.contains("Orphan note"));
     }
 
+    #[test]
+    fn test_missing_metadata_hint_and_quick_fix() {
+        let content = "#import \"../include.typ\": *\n#show: zettel\n\n= Old <1111111111>\n";
+        let zk = ZkLspConfig::default();
+        let d = get_missing_metadata_diagnostic(content, &zk, parser::default_parser()).unwrap();
+        assert_eq!(d.severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(d.range.start, Position::new(1, 0));
+
+        let uri = Url::parse("file:///wiki/note/1111111111.typ").unwrap();
+        let actions = crate::handlers::code_actions::get_code_actions(
+            &uri,
+            &[d],
+            crate::config::RefStyle::At,
+        );
+        let Some(CodeActionOrCommand::CodeAction(action)) = actions.first() else {
+            panic!("expected a code action");
+        };
+        assert_eq!(action.title, "Add metadata block");
+        let edit = &action.edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri][0];
+        let mut lines: Vec<&str> = content.lines().collect();
+        lines.insert(1, edit.new_text.trim_end());
+        let fixed = lines.join("\n");
+        assert!(parser::parse_header(&fixed).is_some());
+        assert!(get_missing_metadata_diagnostic(&fixed, &zk, parser::default_parser()).is_none());
+    }
+
     #[test]
     fn test_non_orphan_no_hint_inbound() {
         let index = make_index();
@@ -666,7 +1024,12 @@ mod tests {
         add_backlink(&index, "1111111111", "2222222222");
         // Has inbound backlink → not orphan
         let content = "= My Note <1111111111>\n";
-        let diag = get_orphan_diagnostic(content, "/wiki/note/1111111111.typ", &index);
+        let diag = get_orphan_diagnostic(
+            &NoteDoc::parse(content.to_string()),
+            "/wiki/note/1111111111.typ",
+            &index,
+            false,
+        );
         assert!(diag.is_none());
     }
 
@@ -676,7 +1039,12 @@ mod tests {
         insert_note(&index, "1111111111");
         // No backlinks, but note has outgoing ref → not orphan
         let content = "= My Note <1111111111>\n- [ ] @2222222222\n";
-        let diag = get_orphan_diagnostic(content, "/wiki/note/1111111111.typ", &index);
+        let diag = get_orphan_diagnostic(
+            &NoteDoc::parse(content.to_string()),
+            "/wiki/note/1111111111.typ",
+            &index,
+            false,
+        );
         assert!(diag.is_none());
     }
 
@@ -695,7 +1063,7 @@ mod tests {
             "\n",
             "= Note <2603110000>\n",
         );
-        let diags = get_schema_diagnostics(content, &index);
+        let diags = get_schema_diagnostics(&NoteDoc::parse(content.to_string()), Some(&index));
         assert!(diags
             .iter()
             .any(|d| d.message == "Missing TOML field `aliases`"));
@@ -708,6 +1076,33 @@ mod tests {
             .all(|d| d.severity == Some(DiagnosticSeverity::INFORMATION)));
     }
 
+    #[test]
+    fn test_schema_flags_self_relation_target() {
+        let content = concat!(
+            "#import \"../include.typ\": *\n",
+            "#let zk-metadata = toml(bytes(\n",
+            "  ```toml\n",
+            "  schema-version = 1\n",
+            "  relation = \"legacy\"\n",
+            "  relation-target = [\"2602082037\"]\n",
+            "  ```.text,\n",
+            "))\n",
+            "#show: zettel.with(metadata: zk-metadata)\n",
+            "\n",
+            "= Copy <2602082037>\n",
+        );
+        let index = make_index();
+        insert_note(&index, "2602082037");
+        let diags = get_schema_diagnostics(&NoteDoc::parse(content.to_string()), Some(&index));
+        let self_link: Vec<_> = diags
+            .iter()
+            .filter(|d| d.message == "Note links to itself as evolution")
+            .collect();
+        assert_eq!(self_link.len(), 1);
+        assert_eq!(self_link[0].range.start.line, 5);
+        assert_eq!(self_link[0].severity, Some(DiagnosticSeverity::WARNING));
+    }
+
     #[test]
     fn test_schema_does_not_flag_existing_relation_target() {
         let index = make_index();
@@ -729,17 +1124,103 @@ mod tests {
             "\n",
             "= Note <2603110000>\n",
         );
-        let diags = get_schema_diagnostics(content, &index);
+        let diags = get_schema_diagnostics(&NoteDoc::parse(content.to_string()), Some(&index));
         assert!(!diags
             .iter()
             .any(|d| d.message == "Missing TOML field `relation-target`"));
     }
 
+    #[test]
+    fn test_cross_wiki_refs_report_unknown_wiki_and_missing_note() {
+        use crate::cross_wiki::tests::{home_config, two_wikis, WORK_NOTE};
+        let tmp = two_wikis("zk_cross_wiki_diagnostics");
+        let config = home_config(&tmp);
+        let content = format!("@work:{WORK_NOTE} @work:2602082038\n@play:{WORK_NOTE}\n");
+        let diags = get_diagnostics(
+            &content,
+            &make_index(),
+            "/wiki/note/9999999999.typ",
+            &config,
+        );
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let found: Vec<(u32, u32, &str)> = diags
+            .iter()
+            .map(|d| {
+                (
+                    d.range.start.line,
+                    d.range.start.character,
+                    d.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (0, 17, "Note @2602082038 does not exist in wiki `work`"),
+                (
+                    1,
+                    0,
+                    "Unknown wiki `play` in @play:2602082037: add it under [wikis] in zk-lsp.toml"
+                ),
+            ]
+        );
+        assert!(diags
+            .iter()
+            .all(|d| d.severity == Some(DiagnosticSeverity::ERROR)));
+    }
+
+    #[tokio::test]
+    async fn test_title_links_report_unresolved_and_ambiguous() {
+        let (index, tmp) = crate::title_links::tests::titled_wiki("zk_title_links_diags").await;
+        let config = index.config.read().await.clone();
+        let off = WikiConfig::from_root(tmp.join("elsewhere"));
+        let content = "[[rust ownership]] [[Nowhere]]\nSee [[Draft]].\n";
+        let diags = get_diagnostics(content, &index, "/wiki/note/9999999999.typ", &config);
+        let disabled = get_diagnostics(content, &index, "/wiki/note/9999999999.typ", &off);
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let found: Vec<(u32, u32, Option<DiagnosticSeverity>, &str)> = diags
+            .iter()
+            .map(|d| {
+                (
+                    d.range.start.line,
+                    d.range.start.character,
+                    d.severity,
+                    d.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    0,
+                    19,
+                    Some(DiagnosticSeverity::ERROR),
+                    "No note is titled `Nowhere`"
+                ),
+                (
+                    1,
+                    4,
+                    Some(DiagnosticSeverity::WARNING),
+                    "`Draft` matches 2 notes: @2603150001, @2603150002. Links go to @2603150001"
+                ),
+            ]
+        );
+        assert!(disabled.is_empty(), "title links checked without the flag");
+    }
+
     #[test]
     fn test_legacy_diagnostic_lists_all_relation_targets() {
         let index = make_index();
         insert_legacy_note(&index, "1111111111", &["2222222222", "3333333333"]);
-        let diags = get_diagnostics("- [ ] @1111111111\n", &index, "/wiki/note/9999999999.typ");
+        let diags = get_diagnostics(
+            "- [ ] @1111111111\n",
+            &index,
+            "/wiki/note/9999999999.typ",
+            &wiki_config(serde_json::json!({})),
+        );
         assert_eq!(diags.len(), 1);
         assert!(diags[0].message.contains("@2222222222"));
         assert!(diags[0].message.contains("@3333333333"));
@@ -760,7 +1241,119 @@ mod tests {
             "- [ ] @1111111111 @3333333333\n",
             &index,
             "/wiki/note/9999999999.typ",
+            &wiki_config(serde_json::json!({})),
         );
         assert!(diags.is_empty());
     }
+
+    #[test]
+    fn test_legacy_suppression_ignores_ref_order_and_spacing() {
+        let index = make_index();
+        insert_legacy_note(&index, "1111111111", &["2222222222"]);
+        insert_note(&index, "2222222222");
+        insert_note(&index, "4444444444");
+        let path = "/wiki/note/9999999999.typ";
+        let config = wiki_config(serde_json::json!({}));
+        for line in [
+            "- [ ] @1111111111 @4444444444 @2222222222\n",
+            "- [ ] @2222222222 (was @1111111111)\n",
+            "@4444444444,@2222222222,@1111111111\n",
+        ] {
+            assert!(
+                get_diagnostics(line, &index, path, &config).is_empty(),
+                "{line:?}"
+            );
+        }
+        // The successor on another line does not count.
+        let diags = get_diagnostics(
+            "- [ ] @1111111111 @4444444444\n@2222222222\n",
+            &index,
+            path,
+            &config,
+        );
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].range.start.line, 0);
+    }
+
+    #[test]
+    fn test_legacy_suppression_follows_evolution_chain() {
+        // 1111111111 → 2222222222 (also legacy) → 3333333333.
+        let index = make_index();
+        insert_legacy_note(&index, "1111111111", &["2222222222"]);
+        insert_legacy_note(&index, "2222222222", &["3333333333"]);
+        insert_note(&index, "3333333333");
+        let config = wiki_config(serde_json::json!({}));
+        let diags = get_diagnostics(
+            "@3333333333 @1111111111\n",
+            &index,
+            "/wiki/note/9999999999.typ",
+            &config,
+        );
+        assert!(diags.is_empty(), "{diags:?}");
+        // Inside the final successor, the old note is expected.
+        let diags = get_diagnostics(
+            "From @1111111111.\n",
+            &index,
+            "/wiki/note/3333333333.typ",
+            &config,
+        );
+        assert!(diags.is_empty(), "{diags:?}");
+        // The intermediate note on the line is still reported on its own.
+        let diags = get_diagnostics(
+            "@2222222222 @1111111111\n",
+            &index,
+            "/wiki/note/9999999999.typ",
+            &config,
+        );
+        assert_eq!(diags.len(), 1);
+        assert!(diags[0].message.starts_with("Note @2222222222 is legacy."));
+    }
+
+    #[test]
+    fn test_evolution_successors_stop_at_cycles() {
+        let index = make_index();
+        insert_legacy_note(&index, "1111111111", &["2222222222"]);
+        insert_legacy_note(&index, "2222222222", &["1111111111"]);
+        let successors = index.evolution_successors("1111111111");
+        assert_eq!(successors.len(), 1);
+        assert!(successors.contains("2222222222"));
+        assert!(index.evolution_successors("3333333333").is_empty());
+    }
+
+    #[test]
+    fn test_disabled_and_remapped_diagnostic_kinds() {
+        let index = make_index();
+        insert_legacy_note(&index, "1111111111", &["2222222222"]);
+        insert_note(&index, "3333333333");
+        if let Some(mut info) = index.notes.get_mut("3333333333") {
+            info.archived = true;
+        }
+        let content = "See @1111111111 and @3333333333.\n";
+        let path = "/wiki/note/9999999999.typ";
+
+        let all = get_diagnostics(content, &index, path, &wiki_config(serde_json::json!({})));
+        assert_eq!(all.len(), 2);
+
+        let config = wiki_config(serde_json::json!({"disabledDiagnostics": ["legacy"]}));
+        let diags = get_diagnostics(content, &index, path, &config);
+        assert_eq!(diags.len(), 1);
+        assert!(diags[0].message.contains("@3333333333 is archived"));
+        assert_eq!(diags[0].severity, Some(DiagnosticSeverity::WARNING));
+
+        let config = wiki_config(serde_json::json!({
+            "diagnosticSeverity": {"archived": "error", "legacy": "hint"}
+        }));
+        let mut severities: Vec<_> = get_diagnostics(content, &index, path, &config)
+            .into_iter()
+            .map(|d| (d.range.start.character, d.severity))
+            .collect();
+        severities.sort_by_key(|(c, _)| *c);
+        assert_eq!(
+            severities,
+            vec![
+                (4, Some(DiagnosticSeverity::HINT)),
+                (20, Some(DiagnosticSeverity::ERROR))
+            ]
+        );
+    }
 }
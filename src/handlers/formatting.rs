@@ -1,12 +1,19 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::Arc;
 
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use tower_lsp::lsp_types::*;
+use tracing::instrument;
 
 use crate::config::WikiConfig;
+use crate::documents::DocumentStore;
 use crate::hooks::apply::apply_hook_result;
 use crate::hooks::lua::{build_hook_note_input, HookRunner};
-use crate::parser;
+use crate::note_ops;
+use crate::parser::{self, Parser};
+use crate::reconcile::writeback;
 
 /// Default hooks embedded at compile time.
 const DEFAULT_CHECKLIST_HOOK: &str = include_str!("../../examples/hooks/checklist.lua");
@@ -81,7 +88,7 @@ pub fn apply_metadata_patch(
     let block = parser::find_toml_metadata_block(content)
         .ok_or_else(|| anyhow::anyhow!("no TOML metadata block found"))?;
 
-    let lines: Vec<&str> = content.lines().collect();
+    let lines: Vec<&str> = parser::split_lines(content).collect();
     let trailing_newline = content.ends_with('\n');
     let mut result_lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
 
@@ -107,31 +114,338 @@ pub fn apply_metadata_patch(
 }
 
 /// Format `content` by running hooks in sequence:
+/// 0. `sync_ref_checkboxes`, unless `checkbox_ref_depth` is 0.
 /// 1. Built-in default hooks (checklist.lua + relation_status.lua, embedded at compile time),
 ///    unless `config.zk_config.disable_default_hooks` is true.
 /// 2. User-configured file hooks from `config.zk_config.hooks`, loaded at runtime.
+/// 3. `normalize_whitespace`, if `config.zk_config.format_whitespace` is true.
 ///
-/// Cross-file ref-checkbox sync (`@ID` items) is intentionally NOT performed here;
-/// that is the exclusive responsibility of the `reconcile` command.
+/// Step 0 only reads the notes this one references, to the configured depth,
+/// from their `open` buffers when there are any and from disk otherwise;
+/// workspace-wide reconciliation is still the `reconcile` command's job.
 ///
 /// On any hook error the step is skipped and a warning is emitted; the original
 /// content (or the output of the previous step) is passed through unchanged.
-pub async fn format_content(content: &str, config: &WikiConfig) -> String {
+#[instrument(level = "debug", skip_all, fields(bytes = content.len()))]
+pub async fn format_content(
+    content: &str,
+    config: &WikiConfig,
+    open: Option<&DocumentStore>,
+) -> String {
     let zk = &config.zk_config;
+    let note_parser = config.parser();
     let mut current = content.to_string();
+    if config.checkbox_ref_depth() > 0 {
+        current = sync_ref_checkboxes(&current, config, &note_parser, open).await;
+    }
     if !zk.disable_default_hooks {
-        current = run_default_hooks(&current);
+        current = run_default_hooks(&current, &note_parser);
+    }
+    current = run_hooks(&current, &zk.hooks, &note_parser);
+    if zk.format_whitespace {
+        current = normalize_whitespace(&current, &note_parser);
     }
-    current = run_hooks(&current, &zk.hooks);
     current
 }
 
+/// Set the `@ID` checkboxes in `content` from the done-state of the notes
+/// they reference, following references `checkbox_ref_depth` levels deep.
+/// Boxes whose target note cannot be found or read are left as they are.
+async fn sync_ref_checkboxes(
+    content: &str,
+    config: &WikiConfig,
+    note_parser: &Parser,
+    open: Option<&DocumentStore>,
+) -> String {
+    let depth = config.checkbox_ref_depth();
+    let own_id = note_parser.parse_header(content).map(|h| h.id);
+    let mut notes: HashMap<String, String> = HashMap::new();
+    let mut frontier = writeback::ref_target_ids(content, note_parser);
+    for _ in 0..depth {
+        let mut next = HashSet::new();
+        for id in frontier {
+            if own_id.as_deref() == Some(id.as_str()) || notes.contains_key(&id) {
+                continue;
+            }
+            let Some(path) = config.locate_note(&id) else {
+                continue;
+            };
+            let text = match open.and_then(|docs| docs.get(&path)) {
+                Some(text) => text,
+                None => {
+                    let zk = &config.zk_config;
+                    let read =
+                        note_ops::read_note_text(&path, zk.lossy_reads, config.max_note_bytes());
+                    let Ok(text) = read.await else {
+                        continue;
+                    };
+                    text
+                }
+            };
+            next.extend(writeback::ref_target_ids(&text, note_parser));
+            notes.insert(id, text);
+        }
+        frontier = next;
+    }
+    let own_id = own_id.unwrap_or_default();
+    let dep_states = writeback::resolve_dep_states(&own_id, content, &notes, depth, note_parser);
+    writeback::sync_ref_checkboxes(content, &dep_states, note_parser)
+}
+
+/// Trim trailing spaces and tabs, and collapse three or more consecutive
+/// blank lines into one. The header (through the title line) and lines in
+/// ```` ``` ```` fences are left as they are; line endings are kept.
+pub fn normalize_whitespace(content: &str, note_parser: &Parser) -> String {
+    let body_start = note_parser
+        .parse_header(content)
+        .map_or(0, |h| h.title_line_idx + 1);
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut out = String::with_capacity(content.len());
+    let mut in_fence = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if i < body_start {
+            out.push_str(line);
+            i += 1;
+            continue;
+        }
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            out.push_str(line);
+            i += 1;
+            continue;
+        }
+        if in_fence {
+            out.push_str(line);
+            i += 1;
+            continue;
+        }
+        let blank = |l: &str| l.trim().is_empty();
+        let run = lines[i..].iter().take_while(|l| blank(l)).count();
+        if run >= 3 {
+            // Keep the last line of the run, which carries the final line
+            // ending (or none at end of file).
+            i += run - 1;
+        }
+        let line = lines[i];
+        let text = line.trim_end_matches(['\n', '\r']);
+        out.push_str(text.trim_end_matches([' ', '\t']));
+        out.push_str(&line[text.len()..]);
+        i += 1;
+    }
+    out
+}
+
+/// A single `TextEdit` replacing the lines where `old` and `new` differ
+/// (common leading and trailing lines are kept), or none if they are equal.
+pub fn format_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    if old == new {
+        return Vec::new();
+    }
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let (prefix, suffix) = common_lines(&old_lines, &new_lines);
+    let bytes = |lines: &[&str]| lines.iter().map(|l| l.len()).sum::<usize>();
+    let start = bytes(&old_lines[..prefix]);
+    let old_end = old.len() - bytes(&old_lines[old_lines.len() - suffix..]);
+    let new_end = new.len() - bytes(&new_lines[new_lines.len() - suffix..]);
+    vec![TextEdit {
+        range: Range {
+            start: byte_to_position(old, start),
+            end: byte_to_position(old, old_end),
+        },
+        new_text: new[start..new_end].to_string(),
+    }]
+}
+
+/// Unified diff from `old` to `new` with three lines of context, headed
+/// `a/<label>` / `b/<label>`, or an empty string if they are equal. Changes
+/// more than six unchanged lines apart get separate hunks.
+pub fn unified_diff(old: &str, new: &str, label: &str) -> String {
+    const CONTEXT: usize = 3;
+    if old == new {
+        return String::new();
+    }
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let ops = diff_lines(&old_lines, &new_lines);
+
+    // `[first, last]` op indices of each hunk's changes.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (i, _) in ops.iter().enumerate().filter(|(_, (mark, _))| *mark != ' ') {
+        match hunks.last_mut() {
+            Some((_, last)) if i - *last <= 2 * CONTEXT + 1 => *last = i,
+            _ => hunks.push((i, i)),
+        }
+    }
+
+    let mut out = format!("--- a/{label}\n+++ b/{label}\n");
+    for (first, last) in hunks {
+        let start = first.saturating_sub(CONTEXT);
+        let end = (last + CONTEXT + 1).min(ops.len());
+        // Lines of `ops` on the old (not `+`) or new (not `-`) side.
+        let side =
+            |ops: &[(char, &str)], other: char| ops.iter().filter(|(m, _)| *m != other).count();
+        let (old_before, new_before) = (side(&ops[..start], '+'), side(&ops[..start], '-'));
+        let hunk = &ops[start..end];
+        let (old_len, new_len) = (side(hunk, '+'), side(hunk, '-'));
+        // An empty side is numbered by the line before it, as `diff -u` does.
+        let range = |before: usize, len: usize| match len {
+            0 => format!("{before},0"),
+            len => format!("{},{len}", before + 1),
+        };
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_before, old_len),
+            range(new_before, new_len)
+        ));
+        for (mark, line) in hunk {
+            out.push(*mark);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// Line-level edit script from `old` to `new`: `' '` for kept lines, `'-'`
+/// and `'+'` for removed and added ones, removals first within a change.
+/// The lines between the common prefix and suffix are matched by longest
+/// common subsequence; past `MAX_CELLS` they are replaced as one block.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(char, &'a str)> {
+    const MAX_CELLS: usize = 4_000_000;
+    let (prefix, suffix) = common_lines(old, new);
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+    let mut ops: Vec<(char, &str)> = old[..prefix].iter().map(|l| (' ', *l)).collect();
+    if (a.len() + 1) * (b.len() + 1) > MAX_CELLS {
+        ops.extend(a.iter().map(|l| ('-', *l)));
+        ops.extend(b.iter().map(|l| ('+', *l)));
+    } else {
+        // lcs[i][j]: length of the longest common subsequence of a[i..], b[j..].
+        let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = if a[i] == b[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push((' ', a[i]));
+                i += 1;
+                j += 1;
+            } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push(('-', a[i]));
+                i += 1;
+            } else {
+                ops.push(('+', b[j]));
+                j += 1;
+            }
+        }
+    }
+    ops.extend(old[old.len() - suffix..].iter().map(|l| (' ', *l)));
+    ops
+}
+
+/// Number of equal lines at the start and, after those, at the end of
+/// `old` and `new`.
+fn common_lines(old: &[&str], new: &[&str]) -> (usize, usize) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    (prefix, suffix)
+}
+
+/// LSP position of byte `offset` in `content`, counting `\n`, `\r\n` and a
+/// lone `\r` as line breaks (see `parser::split_lines`).
+fn byte_to_position(content: &str, offset: usize) -> Position {
+    let before = &content[..offset];
+    let mut line = 0;
+    let mut line_start = 0;
+    let bytes = before.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        let is_break = b == b'\n' || (b == b'\r' && bytes.get(i + 1) != Some(&b'\n'));
+        if is_break {
+            line += 1;
+            line_start = i + 1;
+        }
+    }
+    Position::new(
+        line,
+        parser::byte_to_utf16(&before[line_start..], offset - line_start),
+    )
+}
+
+/// Files seen by `format_wiki`: the notes whose content changed, sorted, and
+/// the total number of notes checked.
+#[derive(Debug, Default)]
+pub struct FormatWikiStats {
+    pub changed: Vec<PathBuf>,
+    pub total: usize,
+}
+
+/// Run `format_content` over every note in `note_dir`, at most one note per
+/// available CPU at a time. Unless `check`, changed notes are written back
+/// (tmp → rename); unchanged notes are never rewritten.
+pub async fn format_wiki(config: Arc<WikiConfig>, check: bool) -> anyhow::Result<FormatWikiStats> {
+    let limit = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let semaphore = Arc::new(Semaphore::new(limit));
+    let mut tasks = JoinSet::new();
+    for path in note_ops::indexable_note_files(&config).await? {
+        let config = Arc::clone(&config);
+        let permit = Arc::clone(&semaphore).acquire_owned().await?;
+        tasks.spawn(async move {
+            let _permit = permit;
+            let content = tokio::fs::read_to_string(&path).await?;
+            let formatted = format_content(&content, &config, None).await;
+            if formatted == content {
+                return anyhow::Ok(None);
+            }
+            if !check {
+                let tmp = path.with_extension("typ.tmp");
+                tokio::fs::write(&tmp, formatted.as_bytes()).await?;
+                tokio::fs::rename(&tmp, &path).await?;
+            }
+            Ok(Some(path))
+        });
+    }
+
+    let mut stats = FormatWikiStats::default();
+    while let Some(joined) = tasks.join_next().await {
+        stats.total += 1;
+        if let Some(path) = joined?? {
+            stats.changed.push(path);
+        }
+    }
+    stats.changed.sort();
+    Ok(stats)
+}
+
 /// Run the built-in embedded hooks (checklist.lua + relation_status.lua).
-pub(crate) fn run_default_hooks(content: &str) -> String {
+pub(crate) fn run_default_hooks(content: &str, note_parser: &Parser) -> String {
     let hooks: &[(&str, &str)] = &[
         ("checklist", DEFAULT_CHECKLIST_HOOK),
         ("relation_status", DEFAULT_RELATION_HOOK),
     ];
+    // A note with `auto-status = false` keeps its `checklist-status`; the
+    // hooks' checkbox edits still apply.
+    let auto_status = note_parser
+        .parse_header(content)
+        .is_none_or(|h| h.auto_status);
     let mut current = content.to_string();
     for (name, src) in hooks {
         let runner = match HookRunner::load_str(src) {
@@ -141,14 +455,17 @@ pub(crate) fn run_default_hooks(content: &str) -> String {
                 continue;
             }
         };
-        let input = build_hook_note_input(&current);
-        let result = match runner.run(&input) {
+        let input = build_hook_note_input(&current, note_parser);
+        let mut result = match runner.run(&input) {
             Ok(r) => r,
             Err(e) => {
                 tracing::warn!("default hook '{name}' run error: {e}");
                 continue;
             }
         };
+        if !auto_status {
+            result.metadata.remove("checklist-status");
+        }
         match apply_hook_result(&result, &current) {
             Ok(out) => current = out,
             Err(e) => tracing::warn!("default hook '{name}' apply error: {e}"),
@@ -158,7 +475,7 @@ pub(crate) fn run_default_hooks(content: &str) -> String {
 }
 
 /// Run user-configured file hooks loaded at runtime. No-op if `hook_paths` is empty.
-pub(crate) fn run_hooks(content: &str, hook_paths: &[PathBuf]) -> String {
+pub(crate) fn run_hooks(content: &str, hook_paths: &[PathBuf], note_parser: &Parser) -> String {
     let mut current = content.to_string();
     for path in hook_paths {
         let name = path
@@ -172,7 +489,7 @@ pub(crate) fn run_hooks(content: &str, hook_paths: &[PathBuf]) -> String {
                 continue;
             }
         };
-        let input = build_hook_note_input(&current);
+        let input = build_hook_note_input(&current, note_parser);
         let result = match runner.run(&input) {
             Ok(r) => r,
             Err(e) => {
@@ -192,7 +509,7 @@ pub(crate) fn run_hooks(content: &str, hook_paths: &[PathBuf]) -> String {
 /// block to `new_status`. Returns None if not found or already correct.
 pub fn compute_toml_status_edit(content: &str, new_status: &str) -> Option<TextEdit> {
     let block = parser::find_toml_metadata_block(content)?;
-    let lines: Vec<&str> = content.lines().collect();
+    let lines: Vec<&str> = parser::split_lines(content).collect();
 
     for i in block.start_line..=block.end_line {
         let line = lines.get(i)?;
@@ -218,3 +535,308 @@ pub fn compute_toml_status_edit(content: &str, new_status: &str) -> Option<TextE
     }
     None
 }
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(content: &str, edit: &TextEdit) -> String {
+        let offset = |pos: Position| {
+            let line_start: usize = content
+                .split_inclusive('\n')
+                .take(pos.line as usize)
+                .map(str::len)
+                .sum();
+            line_start + pos.character as usize
+        };
+        let (start, end) = (offset(edit.range.start), offset(edit.range.end));
+        format!("{}{}{}", &content[..start], edit.new_text, &content[end..])
+    }
+
+    #[test]
+    fn test_format_edits_replace_only_changed_lines() {
+        assert!(format_edits("a\nb\n", "a\nb\n").is_empty());
+
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nB\nx\nc\nd\n";
+        let edits = format_edits(old, new);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range.start, Position::new(1, 0));
+        assert_eq!(edits[0].range.end, Position::new(2, 0));
+        assert_eq!(edits[0].new_text, "B\nx\n");
+        assert_eq!(apply(old, &edits[0]), new);
+
+        // Missing trailing newline: the edit reaches the end of the document.
+        let edits = format_edits("a\nb", "a\nb\n");
+        assert_eq!(edits[0].range.start, Position::new(1, 0));
+        assert_eq!(edits[0].range.end, Position::new(1, 1));
+        assert_eq!(apply("a\nb", &edits[0]), "a\nb\n");
+
+        // Identical lines on both sides of a deletion are not double-counted.
+        let edits = format_edits("x\nx\nx\n", "x\nx\n");
+        assert_eq!(apply("x\nx\nx\n", &edits[0]), "x\nx\n");
+    }
+
+    #[test]
+    fn test_unified_diff_single_hunk_with_context() {
+        assert_eq!(unified_diff("a\n", "a\n", "n.typ"), "");
+
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n";
+        assert_eq!(
+            unified_diff(old, new, "n.typ"),
+            "--- a/n.typ\n+++ b/n.typ\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n"
+        );
+
+        // Pure insertion at the top; a missing final newline is marked.
+        assert_eq!(
+            unified_diff("x", "new\nx", "n.typ"),
+            "--- a/n.typ\n+++ b/n.typ\n@@ -1,1 +1,2 @@\n+new\n x\n\\ No newline at end of file\n"
+        );
+        assert_eq!(
+            unified_diff("a\nb\n", "a\n", "n.typ"),
+            "--- a/n.typ\n+++ b/n.typ\n@@ -1,2 +1,1 @@\n a\n-b\n"
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_separates_distant_changes() {
+        let old: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        let new = old
+            .replacen("2\n", "two\n", 1)
+            .replace("18\n", "eighteen\n");
+        assert_eq!(
+            unified_diff(&old, &new, "n.typ"),
+            concat!(
+                "--- a/n.typ\n+++ b/n.typ\n",
+                "@@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n",
+                "@@ -15,6 +15,6 @@\n 15\n 16\n 17\n-18\n+eighteen\n 19\n 20\n",
+            )
+        );
+
+        // Unchanged lines between two changes stay context, not -/+ pairs.
+        let old = "a\nb\nc\nd\n";
+        let new = "A\nb\nc\nD\n";
+        assert_eq!(
+            unified_diff(old, new, "n.typ"),
+            "--- a/n.typ\n+++ b/n.typ\n@@ -1,4 +1,4 @@\n-a\n+A\n b\n c\n-d\n+D\n"
+        );
+    }
+
+    #[test]
+    fn test_normalize_whitespace_trims_and_collapses_outside_fences() {
+        let header = "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1  \n  ```.text,\n))\n\n\n\n= Note <2603150000>  \n";
+        let body = concat!(
+            "Text.  \n",
+            "\n",
+            "  \n",
+            "\t\n",
+            "More.\t\r\n",
+            "\n",
+            "\n",
+            "```rust\n",
+            "let x = 1;   \n",
+            "\n",
+            "\n",
+            "\n",
+            "```\n",
+            "End.\n",
+            "\n",
+            "\n",
+            "\n",
+        );
+        let out = normalize_whitespace(&format!("{header}{body}"), parser::default_parser());
+
+        assert_eq!(
+            out,
+            format!(
+                "{header}{}",
+                concat!(
+                    "Text.\n",
+                    "\n",
+                    "More.\r\n",
+                    "\n",
+                    "\n",
+                    "```rust\n",
+                    "let x = 1;   \n",
+                    "\n",
+                    "\n",
+                    "\n",
+                    "```\n",
+                    "End.\n",
+                    "\n",
+                )
+            )
+        );
+        assert_eq!(normalize_whitespace(&out, parser::default_parser()), out);
+    }
+
+    #[tokio::test]
+    async fn test_format_content_normalizes_whitespace_only_when_enabled() {
+        let content = "#import \"../include.typ\": *\n\
+                       #let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n\
+                       #show: zettel.with(metadata: zk-metadata)\n\n= Note <2603150000>\nProse.  \n";
+        let mut config = WikiConfig::from_root(PathBuf::from("/nonexistent"));
+        let off = format_content(content, &config, None).await;
+        config.zk_config.format_whitespace = true;
+        let on = format_content(content, &config, None).await;
+
+        assert!(off.ends_with("Prose.  \n"));
+        assert!(on.ends_with("Prose.\n"));
+        let edits = format_edits(&off, &on);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, "Prose.\n");
+    }
+
+    #[tokio::test]
+    async fn test_format_content_syncs_ref_checkboxes_to_depth() {
+        let tmp = std::env::temp_dir().join("zk_format_ref_depth");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let note = |id: &str, body: &str| {
+            format!(
+                "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+                 checklist-status = \"none\"\n  ```.text,\n))\n= Note <{id}>\n{body}"
+            )
+        };
+        // A -> B -> C; C is done, but B's box for C is still unchecked.
+        std::fs::write(
+            tmp.join("note/2603020000.typ"),
+            note("2603020000", "- [ ] @2603030000\n"),
+        )
+        .unwrap();
+        std::fs::write(
+            tmp.join("note/2603030000.typ"),
+            note("2603030000", "- [x] shipped\n"),
+        )
+        .unwrap();
+        let a = note("2603010000", "- [ ] @2603020000\n- [x] @2603990000\n");
+        let mut config = WikiConfig::from_root(tmp.clone());
+        config.zk_config.checkbox_ref_depth = Some(1);
+
+        let out = format_content(&a, &config, None).await;
+        assert!(out.contains("- [ ] @2603020000"), "{out}");
+        // A missing note never clears a manual check.
+        assert!(out.contains("- [x] @2603990000"), "{out}");
+
+        config.zk_config.checkbox_ref_depth = Some(2);
+        let out = format_content(&a, &config, None).await;
+        assert!(out.contains("- [x] @2603020000"), "{out}");
+
+        std::fs::write(
+            tmp.join("note/2603020000.typ"),
+            note("2603020000", "- [x] @2603030000\n"),
+        )
+        .unwrap();
+        // Off by default: formatting stays per-file.
+        for depth in [None, Some(0)] {
+            config.zk_config.checkbox_ref_depth = depth;
+            let out = format_content(&a, &config, None).await;
+            assert!(out.contains("- [ ] @2603020000"), "{out}");
+        }
+        config.zk_config.checkbox_ref_depth = Some(1);
+        let out = format_content(&a, &config, None).await;
+        assert!(out.contains("- [x] @2603020000"), "{out}");
+
+        // An open buffer overrides the note on disk.
+        let open = DocumentStore::new();
+        open.open(
+            tmp.join("note/2603020000.typ"),
+            note("2603020000", "- [ ] @2603030000\n"),
+        );
+        let out = format_content(&a, &config, Some(&open)).await;
+        assert!(out.contains("- [ ] @2603020000"), "{out}");
+
+        let _ = std::fs::remove_dir_all(&tmp);
+    }
+
+    #[test]
+    fn test_default_hooks_keep_status_of_auto_status_false_notes() {
+        let note = |extra: &str| {
+            format!(
+                "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+                 checklist-status = \"todo\"\n{extra}  ```.text,\n))\n\
+                 = Note <2603010000>\n- [ ] parent\n  - [x] child\n"
+            )
+        };
+        let opted_out = note("  auto-status = false\n");
+        let out = run_default_hooks(&opted_out, parser::default_parser());
+        assert!(out.contains("checklist-status = \"todo\""), "{out}");
+        // Checkbox propagation is not status management and still runs.
+        assert!(out.contains("- [x] parent"), "{out}");
+
+        let managed = run_default_hooks(&note(""), parser::default_parser());
+        assert!(managed.contains("checklist-status = \"done\""), "{managed}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_format_wiki_follows_symlinked_date_dirs() {
+        let tmp = std::env::temp_dir().join("zk_format_wiki_symlinks");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::create_dir_all(tmp.join("shared/2026/03")).unwrap();
+        let stale = "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+                     checklist-status = \"todo\"\n  ```.text,\n))\n\
+                     = Note <2603010000>\n- [x] done\n";
+        std::fs::write(tmp.join("shared/2026/03/2603010000.typ"), stale).unwrap();
+        std::os::unix::fs::symlink(tmp.join("shared/2026"), tmp.join("note/2026")).unwrap();
+        let mut config = WikiConfig::from_root(tmp.clone());
+
+        let stats = format_wiki(Arc::new(config.clone()), true).await.unwrap();
+        assert_eq!(stats.total, 0);
+        config.zk_config.follow_symlinks = true;
+        let stats = format_wiki(Arc::new(config), true).await.unwrap();
+        assert_eq!(stats.total, 1);
+        assert_eq!(stats.changed.len(), 1);
+
+        let _ = std::fs::remove_dir_all(&tmp);
+    }
+
+    #[tokio::test]
+    async fn test_format_wiki_check_then_write() {
+        let tmp = std::env::temp_dir().join("zk_format_wiki");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let note = |status: &str, body: &str| {
+            format!(
+                "#import \"../include.typ\": *\n\
+                 #let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+                 checklist-status = \"{status}\"\n  ```.text,\n))\n\
+                 #show: zettel.with(metadata: zk-metadata)\n\n= Note <2603010000>\n{body}"
+            )
+        };
+        let stale = tmp.join("note/2603010000.typ");
+        let stale_content = note("todo", "- [x] done\n");
+        std::fs::write(&stale, &stale_content).unwrap();
+        let clean = tmp.join("note/2603020000.typ");
+        let clean_content = format_content(
+            &note("none", "Prose only.\n"),
+            &WikiConfig::from_root(tmp.clone()),
+            None,
+        )
+        .await;
+        std::fs::write(&clean, &clean_content).unwrap();
+        let config = Arc::new(WikiConfig::from_root(tmp.clone()));
+
+        let stats = format_wiki(Arc::clone(&config), true).await.unwrap();
+        assert_eq!(stats.total, 2);
+        assert_eq!(stats.changed, vec![stale.clone()]);
+        assert_eq!(std::fs::read_to_string(&stale).unwrap(), stale_content);
+
+        let stats = format_wiki(Arc::clone(&config), false).await.unwrap();
+        assert_eq!(stats.changed, vec![stale.clone()]);
+        assert!(std::fs::read_to_string(&stale)
+            .unwrap()
+            .contains("checklist-status = \"done\""));
+        assert_eq!(std::fs::read_to_string(&clean).unwrap(), clean_content);
+
+        let stats = format_wiki(config, true).await.unwrap();
+        assert!(stats.changed.is_empty());
+        let _ = std::fs::remove_dir_all(&tmp);
+    }
+}
@@ -2,24 +2,113 @@ use std::sync::Arc;
 
 use tower_lsp::lsp_types::*;
 
+use crate::config::{ClientSettings, RefStyle, WikiConfig};
+use crate::cross_wiki::{self, CrossRefTarget};
 use crate::index::NoteIndex;
-use crate::parser;
+use crate::parser::{self, Parser};
+use crate::title_links::{self, TitleTarget};
+
+/// Notes listed under "See also" when `hoverSeeAlso` is on.
+const SEE_ALSO_LIMIT: usize = 3;
 
 /// Return hover content when the cursor is over a quoted note ID inside a
 /// `relation-target = [...]` value within the TOML metadata block.
 ///
-/// The hover body is the full file content of the referenced note, rendered as
-/// a fenced Typst code block so editors can apply syntax highlighting.
-pub fn get_hover(content: &str, position: Position, index: &Arc<NoteIndex>) -> Option<Hover> {
-    get_hover_with_loader(content, position, index, |path| {
+/// The hover body is the note's abstract (cut to `maxPreviewChars`), then
+/// the full file content of the referenced note, rendered as a fenced Typst
+/// code block so editors can apply syntax highlighting. With `hoverSeeAlso`,
+/// notes sharing keywords with it follow.
+pub fn get_hover(
+    content: &str,
+    position: Position,
+    index: &Arc<NoteIndex>,
+    settings: &ClientSettings,
+) -> Option<Hover> {
+    get_hover_with_loader(content, position, index, settings, |path| {
         std::fs::read_to_string(path).ok()
     })
 }
 
+/// Hover for an `@<wiki>:<ID>` reference: the other wiki's note title and
+/// body, read from disk.
+pub fn get_cross_wiki_hover(
+    content: &str,
+    position: Position,
+    config: &WikiConfig,
+) -> Option<Hover> {
+    let r = cross_wiki::cross_ref_at(content, position)?;
+    let CrossRefTarget::Found(path) = cross_wiki::resolve(config, &r.wiki, &r.id) else {
+        return None;
+    };
+    let note_content = std::fs::read_to_string(path).ok()?;
+    let title = parser::parse_header(&note_content)
+        .map(|h| h.title)
+        .unwrap_or_default();
+    Some(markdown_hover(format!(
+        "**{title}** `{}:{}`\n\n```typst\n{}\n```",
+        r.wiki,
+        r.id,
+        extract_preview_body(&note_content).trim_end()
+    )))
+}
+
+/// Hover for a `[[ID]]` body reference under `ref_style = "wikilink"`, the
+/// same as for a `relation-target` ID.
+pub fn get_wikilink_hover(
+    content: &str,
+    position: Position,
+    index: &Arc<NoteIndex>,
+    note_parser: &Parser,
+    settings: &ClientSettings,
+) -> Option<Hover> {
+    if note_parser.config.ref_style != RefStyle::Wikilink {
+        return None;
+    }
+    let line = parser::split_lines(content).nth(position.line as usize)?;
+    let id = note_parser.ref_at(line, parser::utf16_to_byte(line, position.character))?;
+    note_hover(id, index, settings, |path| {
+        std::fs::read_to_string(path).ok()
+    })
+}
+
+/// Hover for a `[[Note Title]]` link under `title_links`: the note it
+/// resolves to, after a list of the other candidates when it is ambiguous.
+pub fn get_title_link_hover(
+    content: &str,
+    position: Position,
+    index: &Arc<NoteIndex>,
+    config: &WikiConfig,
+) -> Option<Hover> {
+    if !config.zk_config.title_links {
+        return None;
+    }
+    let link = title_links::title_link_at(content, position)?;
+    let target = title_links::resolve(index, &link.title);
+    let hover = note_hover(target.first()?, index, &config.settings, |path| {
+        std::fs::read_to_string(path).ok()
+    })?;
+    let TitleTarget::Ambiguous(ids) = &target else {
+        return Some(hover);
+    };
+    let HoverContents::Markup(body) = hover.contents else {
+        return Some(hover);
+    };
+    let others = ids[1..]
+        .iter()
+        .map(|id| format!("`{id}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(markdown_hover(format!(
+        "*Ambiguous title; also:* {others}\n\n{}",
+        body.value
+    )))
+}
+
 fn get_hover_with_loader<F>(
     content: &str,
     position: Position,
     index: &Arc<NoteIndex>,
+    settings: &ClientSettings,
     load_note: F,
 ) -> Option<Hover>
 where
@@ -32,7 +121,7 @@ where
         return None;
     }
 
-    let lines: Vec<&str> = content.lines().collect();
+    let lines: Vec<&str> = parser::split_lines(content).collect();
     let current_line = lines.get(line_num).copied()?;
     let trimmed = current_line.trim_start();
 
@@ -44,25 +133,68 @@ where
     // column falls within one of them (quotes inclusive for a generous range).
     let col = position.character as usize;
     let id = find_id_at_col(current_line, col)?;
+    note_hover(&id, index, settings, load_note)
+}
 
-    let info = index.notes.get(&id)?;
+/// Hover body for the note `id`.
+fn note_hover<F>(
+    id: &str,
+    index: &Arc<NoteIndex>,
+    settings: &ClientSettings,
+    load_note: F,
+) -> Option<Hover>
+where
+    F: Fn(&std::path::Path) -> Option<String>,
+{
+    let Some(info) = index.get(id) else {
+        // Present on disk but not indexed: show the start of the raw file.
+        let note_content = load_note(&index.path_if_exists(id)?)?;
+        return Some(markdown_hover(format!(
+            "`{id}` *(not indexed: no valid note header)*\n\n```typst\n{}\n```",
+            parser::truncate_preview(note_content.trim_end(), settings.max_preview_chars)
+        )));
+    };
     let note_content = load_note(&info.path)?;
     let preview_content = extract_preview_body(&note_content);
 
-    let markdown = format!(
-        "**{}** `{}`\n\n```typst\n{}\n```",
+    let abstract_text = match info.abstract_text.as_deref() {
+        Some(text) if !text.is_empty() => {
+            format!(
+                "> {}\n\n",
+                parser::truncate_preview(text, settings.max_preview_chars)
+            )
+        }
+        _ => String::new(),
+    };
+    let mut markdown = format!(
+        "**{}** `{}`\n\n{abstract_text}```typst\n{}\n```",
         info.title,
         info.id,
         preview_content.trim_end()
     );
+    let see_also = if settings.hover_see_also {
+        index.see_also(&info.id, SEE_ALSO_LIMIT)
+    } else {
+        Vec::new()
+    };
+    if !see_also.is_empty() {
+        markdown.push_str("\n\n**See also**\n");
+        for (note, _) in see_also {
+            markdown.push_str(&format!("\n- {} `{}`", note.title, note.id));
+        }
+    }
+
+    Some(markdown_hover(markdown))
+}
 
-    Some(Hover {
+fn markdown_hover(markdown: String) -> Hover {
+    Hover {
         contents: HoverContents::Markup(MarkupContent {
             kind: MarkupKind::Markdown,
             value: markdown,
         }),
         range: None,
-    })
+    }
 }
 
 /// Scan `line` for `"XXXXXXXXXX"` patterns (quoted 10-digit ASCII IDs) and
@@ -78,7 +210,7 @@ fn find_id_at_col(line: &str, col: usize) -> Option<String> {
             let end = (start + 10).min(len);
             if end < len && bytes[end] == b'"' {
                 let candidate = &line[start..end];
-                if candidate.len() == 10 && candidate.bytes().all(|b| b.is_ascii_digit()) {
+                if parser::is_valid_id(candidate) {
                     // Span is [i, end] inclusive (the two quote chars).
                     if col >= i && col <= end {
                         return Some(candidate.to_string());
@@ -97,7 +229,7 @@ fn extract_preview_body(content: &str) -> String {
     let Some(header) = parser::parse_header(content) else {
         return content.to_string();
     };
-    let lines: Vec<&str> = content.lines().collect();
+    let lines: Vec<&str> = parser::split_lines(content).collect();
     lines[header.title_line_idx..].join("\n")
 }
 
@@ -110,27 +242,11 @@ mod tests {
     use std::sync::Arc;
 
     fn make_index(id: &str, title: &str, path: PathBuf) -> Arc<NoteIndex> {
-        let idx = NoteIndex::new(Arc::new(tokio::sync::RwLock::new(WikiConfig::from_root(
-            PathBuf::from("/tmp"),
-        ))));
-        idx.notes.insert(
-            id.to_string(),
-            NoteInfo {
-                id: id.to_string(),
-                title: title.to_string(),
-                archived: false,
-                legacy: false,
-                alt_id: None,
-                evo_id: None,
-                relation_target: vec![],
-                aliases: vec![],
-                keywords: vec![],
-                abstract_text: None,
-                checklist_status: None,
-                path,
-            },
-        );
-        Arc::new(idx)
+        Arc::new(NoteIndex::with_notes([NoteInfo {
+            title: title.to_string(),
+            path,
+            ..NoteInfo::stub(id)
+        }]))
     }
 
     const NOTE_CONTENT: &str = concat!(
@@ -172,13 +288,19 @@ mod tests {
             line: 5,
             character: 22,
         };
-        let hover = get_hover_with_loader(NOTE_CONTENT, pos, &index, |path| {
-            if path == PathBuf::from("/virtual/2603110001.typ").as_path() {
-                Some(TARGET_NOTE_CONTENT.to_string())
-            } else {
-                None
-            }
-        });
+        let hover = get_hover_with_loader(
+            NOTE_CONTENT,
+            pos,
+            &index,
+            &ClientSettings::default(),
+            |path| {
+                if path == PathBuf::from("/virtual/2603110001.typ").as_path() {
+                    Some(TARGET_NOTE_CONTENT.to_string())
+                } else {
+                    None
+                }
+            },
+        );
         assert!(hover.is_some());
         let HoverContents::Markup(mc) = hover.unwrap().contents else {
             panic!()
@@ -193,6 +315,115 @@ mod tests {
             .contains("#show: zettel.with(metadata: zk-metadata)"));
     }
 
+    #[test]
+    fn test_hover_shows_truncated_abstract() {
+        let path = PathBuf::from("/virtual/2603110001.typ");
+        let index = make_index("2603110001", "Target Note", path);
+        index.notes.get_mut("2603110001").unwrap().abstract_text = Some("摘要很长很长".into());
+        let pos = Position {
+            line: 5,
+            character: 22,
+        };
+        let settings = ClientSettings {
+            max_preview_chars: 2,
+            ..Default::default()
+        };
+        let hover = get_hover_with_loader(NOTE_CONTENT, pos, &index, &settings, |_| {
+            Some(TARGET_NOTE_CONTENT.to_string())
+        })
+        .unwrap();
+        let HoverContents::Markup(mc) = hover.contents else {
+            panic!()
+        };
+        assert!(mc
+            .value
+            .starts_with("**Target Note** `2603110001`\n\n> 摘要…\n\n```typst\n"));
+    }
+
+    #[test]
+    fn test_hover_see_also_is_toggleable() {
+        let path = PathBuf::from("/virtual/2603110001.typ");
+        let index = make_index("2603110001", "Target Note", path);
+        let target = index.notes.get("2603110001").unwrap().clone();
+        for (id, title, keyword) in [
+            ("2603120000", "Shares", "zettel"),
+            ("2603130000", "Unrelated", "other"),
+        ] {
+            let mut note = target.clone();
+            note.id = id.into();
+            note.title = title.into();
+            note.keywords = vec![keyword.into()];
+            index.notes.insert(id.into(), note);
+            index
+                .keywords
+                .entry(keyword.into())
+                .or_default()
+                .push(id.into());
+        }
+        index.notes.get_mut("2603110001").unwrap().keywords = vec!["zettel".into()];
+        let pos = Position {
+            line: 5,
+            character: 22,
+        };
+        let hover_text = |settings: &ClientSettings| {
+            let hover = get_hover_with_loader(NOTE_CONTENT, pos, &index, settings, |_| {
+                Some(TARGET_NOTE_CONTENT.to_string())
+            })
+            .unwrap();
+            let HoverContents::Markup(mc) = hover.contents else {
+                panic!()
+            };
+            mc.value
+        };
+
+        assert!(!hover_text(&ClientSettings::default()).contains("See also"));
+        let on = hover_text(&ClientSettings {
+            hover_see_also: true,
+            ..Default::default()
+        });
+        assert!(on.ends_with("```\n\n**See also**\n\n- Shares `2603120000`"));
+    }
+
+    #[test]
+    fn test_hover_on_headerless_note_shows_raw_file() {
+        let tmp = std::env::temp_dir().join("zk_hover_test_headerless");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::write(tmp.join("note/2603110001.typ"), "draft without a header\n").unwrap();
+        let index = Arc::new(NoteIndex::new(Arc::new(tokio::sync::RwLock::new(
+            WikiConfig::from_root(tmp.clone()),
+        ))));
+        let pos = Position {
+            line: 5,
+            character: 22,
+        };
+
+        let hover = get_hover(NOTE_CONTENT, pos, &index, &ClientSettings::default());
+        std::fs::write(tmp.join("note/2603110001.typ"), "x".repeat(10_000)).unwrap();
+        let long = get_hover(
+            NOTE_CONTENT,
+            pos,
+            &index,
+            &ClientSettings {
+                max_preview_chars: 20,
+                ..Default::default()
+            },
+        );
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let HoverContents::Markup(markup) = hover.expect("expected hover").contents else {
+            panic!("expected markup");
+        };
+        assert_eq!(
+            markup.value,
+            "`2603110001` *(not indexed: no valid note header)*\n\n```typst\ndraft without a header\n```"
+        );
+        let HoverContents::Markup(long) = long.expect("expected hover").contents else {
+            panic!("expected markup");
+        };
+        assert!(long.value.ends_with(&format!("\n{}…\n```", "x".repeat(20))));
+    }
+
     #[test]
     fn test_hover_outside_id_returns_none() {
         let index = make_index("2603110001", "Target Note", PathBuf::from("/tmp/x.typ"));
@@ -201,7 +432,7 @@ mod tests {
             line: 5,
             character: 5,
         };
-        assert!(get_hover(NOTE_CONTENT, pos, &index).is_none());
+        assert!(get_hover(NOTE_CONTENT, pos, &index, &ClientSettings::default()).is_none());
     }
 
     #[test]
@@ -220,4 +451,40 @@ mod tests {
         let content = "= Legacy <2603110002>\nBody\n";
         assert_eq!(extract_preview_body(content), content);
     }
+
+    #[test]
+    fn test_cross_wiki_hover_shows_other_wikis_note() {
+        use crate::cross_wiki::tests::{home_config, two_wikis, WORK_NOTE};
+        let tmp = two_wikis("zk_cross_wiki_hover");
+        let config = home_config(&tmp);
+        let content = format!("See @work:{WORK_NOTE}.\n");
+        let hover = get_cross_wiki_hover(&content, Position::new(0, 8), &config);
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let HoverContents::Markup(markup) = hover.expect("expected hover").contents else {
+            panic!("expected markup");
+        };
+        assert!(markup
+            .value
+            .starts_with(&format!("**Work Plan** `work:{WORK_NOTE}`")));
+        assert!(markup.value.contains("Quarterly goals."));
+    }
+
+    #[tokio::test]
+    async fn test_title_link_hover_lists_other_candidates() {
+        let (index, tmp) = crate::title_links::tests::titled_wiki("zk_title_link_hover").await;
+        let config = index.config.read().await.clone();
+        let content = "[[Draft]] [[Rust Ownership]]\n";
+        let ambiguous = get_title_link_hover(content, Position::new(0, 2), &index, &config);
+        let unique = get_title_link_hover(content, Position::new(0, 12), &index, &config);
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let value = |hover: Option<Hover>| match hover.expect("expected hover").contents {
+            HoverContents::Markup(markup) => markup.value,
+            other => panic!("unexpected contents {other:?}"),
+        };
+        assert!(value(ambiguous)
+            .starts_with("*Ambiguous title; also:* `2603150002`\n\n**Draft** `2603150001`"));
+        assert!(value(unique).starts_with("**Rust Ownership** `2603150000`"));
+    }
 }
@@ -2,29 +2,45 @@ use std::sync::Arc;
 
 use tower_lsp::lsp_types::*;
 
-use crate::index::NoteIndex;
-use crate::parser;
+use crate::config::{ClientSettings, HintFilter, HintLabel};
+use crate::index::{NoteIndex, NoteInfo};
+use crate::parser::{self, Parser};
 
-/// Produce inlay hints for all @ID references in the given line range.
-pub fn get_inlay_hints(content: &str, range: Range, index: &Arc<NoteIndex>) -> Vec<InlayHint> {
+/// Produce inlay hints for the @ID references in the given line range that
+/// pass `settings.inlay_hint_filter`.
+pub fn get_inlay_hints(
+    content: &str,
+    range: Range,
+    index: &Arc<NoteIndex>,
+    note_parser: &Parser,
+    settings: &ClientSettings,
+) -> Vec<InlayHint> {
+    let filter = settings.inlay_hint_filter;
+    if filter == HintFilter::Off {
+        return Vec::new();
+    }
     let start_line = range.start.line as usize;
     let end_line = range.end.line as usize;
-    let lines: Vec<&str> = content.lines().collect();
+    let lines: Vec<&str> = parser::split_lines(content).take(end_line + 1).collect();
 
     let mut hints = Vec::new();
-    for r in parser::find_all_refs_filtered(content) {
+    for r in note_parser.find_refs_filtered_in(content, start_line..=end_line) {
         let ln = r.line as usize;
-        if ln < start_line || ln > end_line {
-            continue;
-        }
         if let Some(info) = index.get(&r.id) {
+            let mut text = hint_label(&info, settings.inlay_hint_label);
+            if filter == HintFilter::ArchivedLegacy {
+                match stale_marker(&info) {
+                    Some(marker) => text = format!("{text} [{marker}]"),
+                    None => continue,
+                }
+            }
             let line = lines[ln];
             hints.push(InlayHint {
                 position: Position {
                     line: r.line,
                     character: parser::byte_to_utf16(line, r.end_char as usize),
                 },
-                label: InlayHintLabel::String(info.title.clone()),
+                label: InlayHintLabel::String(text),
                 kind: Some(InlayHintKind::TYPE),
                 padding_left: Some(true),
                 padding_right: None,
@@ -36,3 +52,127 @@ pub fn get_inlay_hints(content: &str, range: Range, index: &Arc<NoteIndex>) -> V
     }
     hints
 }
+
+/// Why a reference target counts as stale, if it does.
+fn stale_marker(info: &NoteInfo) -> Option<&'static str> {
+    if info.archived {
+        Some("archived")
+    } else if info.legacy {
+        Some("legacy")
+    } else {
+        None
+    }
+}
+
+fn hint_label(info: &NoteInfo, label: HintLabel) -> String {
+    match (label, info.aliases.first()) {
+        (HintLabel::Alias, Some(alias)) => alias.clone(),
+        (HintLabel::Both, Some(alias)) => format!("{} ({alias})", info.title),
+        _ => info.title.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_index() -> Arc<NoteIndex> {
+        let notes = [
+            (
+                "2603110001",
+                "Linear Algebra",
+                vec!["LA".to_string(), "Matrices".into()],
+            ),
+            ("2603110002", "Topology", vec![]),
+            ("2603110003", "Old Calculus", vec![]),
+            ("2603110004", "Legacy Sets", vec![]),
+        ];
+        Arc::new(NoteIndex::with_notes(notes.into_iter().map(
+            |(id, title, aliases)| NoteInfo {
+                title: title.to_string(),
+                archived: id == "2603110003",
+                legacy: id == "2603110004",
+                aliases,
+                ..NoteInfo::stub(id)
+            },
+        )))
+    }
+
+    fn settings(label: HintLabel, filter: HintFilter) -> ClientSettings {
+        ClientSettings {
+            inlay_hint_label: label,
+            inlay_hint_filter: filter,
+            ..ClientSettings::default()
+        }
+    }
+
+    fn labels_in(content: &str, settings: &ClientSettings) -> Vec<String> {
+        let range = Range::new(Position::new(0, 0), Position::new(1, 0));
+        get_inlay_hints(
+            content,
+            range,
+            &make_index(),
+            parser::default_parser(),
+            settings,
+        )
+        .into_iter()
+        .map(|hint| match hint.label {
+            InlayHintLabel::String(s) => s,
+            other => panic!("unexpected label {other:?}"),
+        })
+        .collect()
+    }
+
+    fn labels(label: HintLabel) -> Vec<String> {
+        let content = "See @2603110001 and @2603110002.\n";
+        labels_in(content, &settings(label, HintFilter::All))
+    }
+
+    #[test]
+    fn test_inlay_hint_label_modes() {
+        assert_eq!(labels(HintLabel::Title), vec!["Linear Algebra", "Topology"]);
+        assert_eq!(labels(HintLabel::Alias), vec!["LA", "Topology"]);
+        assert_eq!(
+            labels(HintLabel::Both),
+            vec!["Linear Algebra (LA)", "Topology"]
+        );
+    }
+
+    #[test]
+    fn test_inlay_hint_filter_modes() {
+        let content = "@2603110001, @2603110003 and @2603110004.\n";
+        let hints = |filter| labels_in(content, &settings(HintLabel::Title, filter));
+        assert_eq!(
+            hints(HintFilter::All),
+            vec!["Linear Algebra", "Old Calculus", "Legacy Sets"]
+        );
+        assert_eq!(
+            hints(HintFilter::ArchivedLegacy),
+            vec!["Old Calculus [archived]", "Legacy Sets [legacy]"]
+        );
+        assert!(hints(HintFilter::Off).is_empty());
+    }
+
+    #[test]
+    fn test_range_request_on_large_document_stays_in_range() {
+        let mut lines: Vec<String> = (0..5000).map(|i| format!("{i}: @2603110001")).collect();
+        // A block comment opened before the range still hides its refs.
+        lines[1999] = "/* @2603110002".into();
+        lines[2000] = "hidden @2603110002 */ shown @2603110002".into();
+        let content = lines.join("\n");
+        let range = Range::new(Position::new(2000, 0), Position::new(2002, 0));
+
+        let hints = get_inlay_hints(
+            &content,
+            range,
+            &make_index(),
+            parser::default_parser(),
+            &ClientSettings::default(),
+        );
+        let found: Vec<(u32, u32)> = hints
+            .iter()
+            .map(|h| (h.position.line, h.position.character))
+            .collect();
+        assert_eq!(found, vec![(2000, 39), (2001, 17), (2002, 17)]);
+    }
+}
@@ -3,11 +3,17 @@ use std::sync::Arc;
 use tower_lsp::lsp_types::*;
 
 use crate::index::NoteIndex;
+use crate::parser::{self, Parser};
 
 /// Find all references to the note whose ID appears at the cursor position.
-pub fn find_references(index: &Arc<NoteIndex>, uri: &Url, line_text: &str) -> Vec<Location> {
+pub fn find_references(
+    index: &Arc<NoteIndex>,
+    uri: &Url,
+    line_text: &str,
+    note_parser: &Parser,
+) -> Vec<Location> {
     // Extract the ID from the title label `<ID>` on the cursor line, or from any `@ID`
-    let id = extract_id_from_line(line_text);
+    let id = extract_id_from_line(line_text, note_parser);
     let id = match id {
         Some(id) => id,
         None => return vec![],
@@ -32,36 +38,29 @@ pub fn find_references(index: &Arc<NoteIndex>, uri: &Url, line_text: &str) -> Ve
         .collect()
 }
 
-fn extract_id_from_line(line: &str) -> Option<String> {
+fn extract_id_from_line(line: &str, note_parser: &Parser) -> Option<String> {
     // Try `<ID>` first (title line format)
     if let Some(id) = extract_angle_id(line) {
         return Some(id);
     }
-    // Try `@ID` at any position (the first one on the line)
-    extract_at_id(line)
+    // Try `@ID` (or `[[ID]]`) at any position (the first one on the line)
+    extract_ref_id(line, note_parser)
 }
 
 fn extract_angle_id(line: &str) -> Option<String> {
     let start = line.rfind('<')?;
     let end = line[start..].find('>')? + start;
     let candidate = &line[start + 1..end];
-    if candidate.len() == 10 && candidate.chars().all(|c| c.is_ascii_digit()) {
+    if parser::is_valid_id(candidate) {
         Some(candidate.to_string())
     } else {
         None
     }
 }
 
-fn extract_at_id(line: &str) -> Option<String> {
-    let at = line.find('@')?;
-    let rest = &line[at + 1..];
-    let end = rest
-        .find(|c: char| !c.is_ascii_digit())
-        .unwrap_or(rest.len());
-    let candidate = &rest[..end];
-    if candidate.len() == 10 {
-        Some(candidate.to_string())
-    } else {
-        None
-    }
+fn extract_ref_id(line: &str, note_parser: &Parser) -> Option<String> {
+    note_parser
+        .id_refs(line)
+        .next()
+        .map(|(_, id)| id.to_string())
 }
@@ -5,7 +5,7 @@ use mlua::{Lua, Value as LuaValue};
 use super::types::{
     HookCheckbox, HookHeading, HookNoteInput, HookResult, HookSpan, HookTextEdit, HookTitle,
 };
-use crate::parser::{self, ChecklistItemKind};
+use crate::parser::{self, ChecklistItemKind, Parser};
 
 /// A loaded Lua hook script that exposes a `run(note) -> result` function.
 pub struct HookRunner {
@@ -77,7 +77,8 @@ fn span_for_line(line_offsets: &[usize], line_idx: usize, line_len: usize) -> Ho
 // Build HookNoteInput from raw content
 // ---------------------------------------------------------------------------
 
-pub fn build_hook_note_input(content: &str) -> HookNoteInput {
+/// `note_parser` decides which checklist items reference other notes.
+pub fn build_hook_note_input(content: &str, note_parser: &Parser) -> HookNoteInput {
     let lines: Vec<&str> = content.lines().collect();
     let line_offsets = build_line_byte_offsets(content);
 
@@ -106,7 +107,8 @@ pub fn build_hook_note_input(content: &str) -> HookNoteInput {
         .unwrap_or_default();
 
     // Parse checkboxes
-    let checkboxes = parser::parse_checklist_items(content)
+    let checkboxes = note_parser
+        .parse_checklist_items(content)
         .into_iter()
         .map(|item| {
             let line_len = lines.get(item.line_idx).map(|l| l.len()).unwrap_or(0);
@@ -407,8 +409,10 @@ mod tests {
     #[test]
     fn test_load_and_call_empty_run() {
         let runner = HookRunner::load_str("function run(n) return {} end").unwrap();
-        let input =
-            build_hook_note_input(&make_toml_note("Test", "2601010000", "none", "active", ""));
+        let input = build_hook_note_input(
+            &make_toml_note("Test", "2601010000", "none", "active", ""),
+            parser::default_parser(),
+        );
         let result = runner.run(&input).unwrap();
         assert!(result.metadata.is_empty());
         assert!(result.edits.is_empty());
@@ -420,8 +424,10 @@ mod tests {
             r#"function run(n) return { metadata = { ["checklist-status"] = n.id } } end"#,
         )
         .unwrap();
-        let input =
-            build_hook_note_input(&make_toml_note("Test", "2601010001", "none", "active", ""));
+        let input = build_hook_note_input(
+            &make_toml_note("Test", "2601010001", "none", "active", ""),
+            parser::default_parser(),
+        );
         let result = runner.run(&input).unwrap();
         assert_eq!(
             result.metadata.get("checklist-status"),
@@ -438,7 +444,7 @@ mod tests {
                end"#,
         )
         .unwrap();
-        let input = build_hook_note_input(&note);
+        let input = build_hook_note_input(&note, parser::default_parser());
         let result = runner.run(&input).unwrap();
         if let Some(toml::Value::String(s)) = result.metadata.get("checklist-status") {
             let line: usize = s.parse().expect("should be a line number");
@@ -458,7 +464,7 @@ mod tests {
                end"#,
         )
         .unwrap();
-        let input = build_hook_note_input(&note);
+        let input = build_hook_note_input(&note, parser::default_parser());
         let result = runner.run(&input).unwrap();
         if let Some(toml::Value::String(s)) = result.metadata.get("checklist-status") {
             let byte: usize = s.parse().expect("should be a byte offset");
@@ -486,7 +492,7 @@ mod tests {
                end"#,
         )
         .unwrap();
-        let input = build_hook_note_input(&note);
+        let input = build_hook_note_input(&note, parser::default_parser());
         let result = runner.run(&input).unwrap();
         if let Some(toml::Value::String(s)) = result.metadata.get("checklist-status") {
             let parts: Vec<&str> = s.splitn(2, ':').collect();
@@ -510,7 +516,7 @@ mod tests {
         // Verify line_idx is strictly increasing and indent values are correct.
         let body = "- [ ] parent\n  - [ ] child one\n  - [x] child two\n";
         let note = make_toml_note("Test", "2601010011", "none", "active", body);
-        let input = build_hook_note_input(&note);
+        let input = build_hook_note_input(&note, parser::default_parser());
 
         assert_eq!(input.checkboxes.len(), 3, "expected 3 checkboxes");
 
@@ -583,7 +589,7 @@ function run(n)
 end
 "#;
         let runner = HookRunner::load_str(lua_script).unwrap();
-        let input = build_hook_note_input(&note);
+        let input = build_hook_note_input(&note, parser::default_parser());
         let result = runner.run(&input).unwrap();
 
         // The hook should have emitted an edit unchecking the parent ref item.
@@ -604,8 +610,10 @@ end
             r#"function run(n) return { metadata = { ["checklist-status"] = "done" } } end"#,
         )
         .unwrap();
-        let input =
-            build_hook_note_input(&make_toml_note("Test", "2601010004", "none", "active", ""));
+        let input = build_hook_note_input(
+            &make_toml_note("Test", "2601010004", "none", "active", ""),
+            parser::default_parser(),
+        );
         let result = runner.run(&input).unwrap();
         assert_eq!(
             result.metadata.get("checklist-status"),
@@ -626,7 +634,7 @@ end
             end_b = edit_end,
         );
         let runner = HookRunner::load_str(&lua_src).unwrap();
-        let input = build_hook_note_input(&note);
+        let input = build_hook_note_input(&note, parser::default_parser());
         let result = runner.run(&input).unwrap();
         let output = apply_hook_result(&result, &note).unwrap();
         assert!(output.contains("- [x] my task"), "edit was applied");
@@ -680,8 +688,10 @@ end
     #[test]
     fn test_invalid_return_type_errors() {
         let runner = HookRunner::load_str(r#"function run(n) return "not a table" end"#).unwrap();
-        let input =
-            build_hook_note_input(&make_toml_note("Test", "2601010009", "none", "active", ""));
+        let input = build_hook_note_input(
+            &make_toml_note("Test", "2601010009", "none", "active", ""),
+            parser::default_parser(),
+        );
         assert!(
             runner.run(&input).is_err(),
             "should error on non-table return"
@@ -697,7 +707,7 @@ end
             r#"function run(n) return { metadata = { ["checklist-status"] = "todo" } } end"#,
         )
         .unwrap();
-        let input = build_hook_note_input(&note);
+        let input = build_hook_note_input(&note, parser::default_parser());
         let result = runner.run(&input).unwrap();
         let output = apply_hook_result(&result, &note).unwrap();
         // The hook's value is applied as-is — no Rust normalizer overrides it
@@ -727,7 +737,7 @@ end
     fn default_hooks_all_children_done_parent_becomes_checked() {
         let body = "- [ ] parent\n  - [x] child one\n  - [x] child two\n";
         let note = make_toml_note("Test", "2601020001", "none", "active", body);
-        let out = run_default_hooks(&note);
+        let out = run_default_hooks(&note, parser::default_parser());
         assert!(out.contains("- [x] parent"), "parent should be checked");
         assert!(
             out.contains("checklist-status = \"done\""),
@@ -739,7 +749,7 @@ end
     fn default_hooks_any_child_incomplete_parent_unchecked() {
         let body = "- [x] parent\n  - [x] child one\n  - [ ] child two\n";
         let note = make_toml_note("Test", "2601020002", "none", "active", body);
-        let out = run_default_hooks(&note);
+        let out = run_default_hooks(&note, parser::default_parser());
         assert!(out.contains("- [ ] parent"), "parent should be unchecked");
     }
 
@@ -747,7 +757,7 @@ end
     fn default_hooks_three_level_propagates() {
         let body = "- [ ] grandparent\n  - [ ] parent\n    - [x] grandchild\n";
         let note = make_toml_note("Test", "2601020003", "none", "active", body);
-        let out = run_default_hooks(&note);
+        let out = run_default_hooks(&note, parser::default_parser());
         assert!(
             out.contains("- [x] grandparent"),
             "grandparent propagated to done"
@@ -759,7 +769,7 @@ end
     fn default_hooks_archived_status_is_done() {
         let body = "- [ ] unfinished task\n";
         let note = make_toml_note("Test", "2601020004", "none", "archived", body);
-        let out = run_default_hooks(&note);
+        let out = run_default_hooks(&note, parser::default_parser());
         assert!(
             out.contains("checklist-status = \"done\""),
             "archived note → done"
@@ -769,7 +779,7 @@ end
     #[test]
     fn default_hooks_legacy_status_is_done() {
         let note = make_toml_note("Test", "2601020005", "none", "legacy", "");
-        let out = run_default_hooks(&note);
+        let out = run_default_hooks(&note, parser::default_parser());
         assert!(
             out.contains("checklist-status = \"done\""),
             "legacy note → done"
@@ -780,8 +790,8 @@ end
     fn default_hooks_idempotent() {
         let body = "- [ ] parent\n  - [x] child\n";
         let note = make_toml_note("Test", "2601020006", "none", "active", body);
-        let first = run_default_hooks(&note);
-        let second = run_default_hooks(&first);
+        let first = run_default_hooks(&note, parser::default_parser());
+        let second = run_default_hooks(&first, parser::default_parser());
         assert_eq!(first, second, "default hooks must be idempotent");
     }
 
@@ -792,11 +802,11 @@ end
         let note_with = make_toml_note("Test", "2601020007", "none", "active", body_with);
         let note_without = make_toml_note("Test", "2601020008", "none", "active", body_without);
         assert!(
-            run_default_hooks(&note_with).ends_with('\n'),
+            run_default_hooks(&note_with, parser::default_parser()).ends_with('\n'),
             "trailing newline preserved"
         );
         assert!(
-            !run_default_hooks(&note_without).ends_with('\n'),
+            !run_default_hooks(&note_without, parser::default_parser()).ends_with('\n'),
             "no spurious newline added"
         );
     }
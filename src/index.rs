@@ -1,13 +1,20 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
+use chrono::NaiveDateTime;
 use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
 use tokio::fs;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{error, instrument, Span};
 
-use crate::config::WikiConfig;
-use crate::parser::{self, ChecklistStatus};
+use crate::cancel::CancelToken;
+use crate::config::{ReferenceResolution, WikiConfig};
+use crate::note_ops;
+use crate::parser::{self, ChecklistStatus, NoteDoc};
 
 #[derive(Debug, Clone)]
 pub struct NoteInfo {
@@ -25,21 +32,205 @@ pub struct NoteInfo {
     pub abstract_text: Option<String>,
     #[allow(dead_code)]
     pub checklist_status: Option<ChecklistStatus>,
+    /// Topic tags such as `tag.project` / `topic.rust` (status tags excluded).
+    pub tags: Vec<String>,
+    /// `generated = true` in the metadata block.
+    pub generated: bool,
     pub path: PathBuf,
 }
 
-#[derive(Debug, Clone)]
+/// A reference to a note. `start_char`/`end_char` are UTF-16 code-unit
+/// offsets (what LSP positions use); `start_byte`/`end_byte` are byte offsets
+/// within the same line, for CLI consumers and external tools.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct BacklinkLocation {
     pub file: PathBuf,
     pub line: u32,
     pub start_char: u32,
     pub end_char: u32,
+    pub start_byte: u32,
+    pub end_byte: u32,
+}
+
+/// Backlinks to one note from a single source file (see `backlinks_by_source`).
+#[derive(Debug, Clone)]
+pub struct BacklinkGroup {
+    pub source_id: String,
+    pub source_title: String,
+    pub file: PathBuf,
+    pub locations: Vec<BacklinkLocation>,
+}
+
+/// Largest edit distance at which `closest_ids` still suggests an ID: enough
+/// for one swapped pair of digits or two typos.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Canonical `tag.*` form: `#foo` and `foo` become `tag.foo`.
+fn normalize_tag(tag: &str) -> String {
+    let tag = tag.trim().trim_start_matches('#');
+    if tag.contains('.') {
+        tag.to_string()
+    } else {
+        format!("tag.{tag}")
+    }
+}
+
+/// Title key for duplicate detection: trimmed, lowercased, inner whitespace
+/// collapsed to single spaces.
+fn normalize_title(title: &str) -> String {
+    title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Levenshtein distance in characters (so a CJK character counts once).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = substitute.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// `edit_distance(a, b)` when it is at most `max`, else `None`. Stops as
+/// soon as every entry of a row exceeds `max`, so most pairs cost a few
+/// rows instead of the full table.
+fn edit_distance_within(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = substitute.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        if cur.iter().all(|&d| d > max) {
+            return None;
+        }
+        prev = cur;
+    }
+    Some(prev[b.len()]).filter(|&d| d <= max)
+}
+
+/// Search relevance of `note` for the lowercased query `q`, lower is better:
+/// exact ID or title, ID or title prefix, title substring, alias, keyword,
+/// then abstract. `None` when nothing matches.
+fn match_rank(note: &NoteInfo, q: &str) -> Option<u8> {
+    let title = note.title.to_lowercase();
+    if note.id == q || title == q {
+        Some(0)
+    } else if note.id.starts_with(q) || title.starts_with(q) {
+        Some(1)
+    } else if note.id.contains(q) || title.contains(q) {
+        Some(2)
+    } else if note.aliases.iter().any(|a| a.to_lowercase().contains(q)) {
+        Some(3)
+    } else if note.keywords.iter().any(|k| k.to_lowercase().contains(q)) {
+        Some(4)
+    } else if note
+        .abstract_text
+        .as_deref()
+        .is_some_and(|a| a.to_lowercase().contains(q))
+    {
+        Some(5)
+    } else {
+        None
+    }
+}
+
+/// A change to the set of indexed notes, broadcast to subscribers of
+/// `NoteIndex::subscribe` (the server forwards it as `$/zk/indexChanged`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexChange {
+    pub kind: IndexChangeKind,
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexChangeKind {
+    Added,
+    Removed,
+    Retitled,
+    /// The whole index was rebuilt; `ids` is empty.
+    Rebuilt,
 }
 
 pub struct NoteIndex {
     pub notes: Arc<DashMap<String, NoteInfo>>,
     pub backlinks: Arc<DashMap<String, Vec<BacklinkLocation>>>,
+    /// Lowercased keyword → IDs of the notes listing it, sorted.
+    pub keywords: Arc<DashMap<String, Vec<String>>>,
+    /// Lowercased title or alias → IDs of the notes carrying it, sorted.
+    pub titles: Arc<DashMap<String, Vec<String>>>,
     pub config: Arc<RwLock<WikiConfig>>,
+    changes: broadcast::Sender<IndexChange>,
+    /// Where to keep the `links.json` sidecar, taken from the config on each
+    /// rebuild and update; `None` while `writeLinksJson` is off.
+    links_json: Arc<Mutex<Option<PathBuf>>>,
+    /// Held while `links.json` is written, so two updates never share its
+    /// temp file.
+    links_json_sync: Arc<tokio::sync::Mutex<()>>,
+    /// Canonical target → in-wiki path of each symlinked note, recorded on
+    /// rebuild while `follow_symlinks` is on.
+    symlinks: Arc<DashMap<PathBuf, PathBuf>>,
+    /// The `keywords` and `titles` keys each file added, so an update
+    /// removes just those instead of scanning every key.
+    lookup_keys: Arc<DashMap<PathBuf, LookupKeys>>,
+    /// Held for writing while a change touches several maps, and for
+    /// reading by `snapshot`, so a snapshot sees all of a change or none.
+    /// Never held across an await.
+    writing: Arc<std::sync::RwLock<()>>,
+    /// One lock per wiki path, held for a whole `update_file`/`update_doc`
+    /// so two updates of the same file (say `did_open` and the watcher)
+    /// run one after the other while other files update in parallel.
+    updating: Arc<DashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+    /// Paths updated or removed while a rebuild scans, one set per running
+    /// rebuild. Its merge keeps their live entries rather than the content
+    /// the scan read, which may be older. Written with `writing` held.
+    touched: Arc<Mutex<HashMap<u64, HashSet<PathBuf>>>>,
+    next_rebuild: Arc<AtomicU64>,
+}
+
+/// Point-in-time copy of the index made by `NoteIndex::snapshot`.
+#[derive(Debug, Clone)]
+pub struct IndexSnapshot {
+    pub notes: HashMap<String, NoteInfo>,
+    pub backlinks: HashMap<String, Vec<BacklinkLocation>>,
+    /// `NoteIndex::link_graph` as of the snapshot.
+    pub links: BTreeMap<String, NoteLinks>,
+}
+
+impl IndexSnapshot {
+    pub fn get(&self, id: &str) -> Option<&NoteInfo> {
+        self.notes.get(id)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.notes.contains_key(id)
+    }
+}
+
+/// One note's entry in `links.json`: the IDs linking to it and the IDs it
+/// links to, sorted, self-links left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct NoteLinks {
+    pub backlinks: BTreeSet<String>,
+    pub outlinks: BTreeSet<String>,
 }
 
 impl NoteIndex {
@@ -47,76 +238,419 @@ impl NoteIndex {
         NoteIndex {
             notes: Arc::new(DashMap::new()),
             backlinks: Arc::new(DashMap::new()),
+            keywords: Arc::new(DashMap::new()),
+            titles: Arc::new(DashMap::new()),
             config,
+            changes: broadcast::channel(256).0,
+            links_json: Arc::new(Mutex::new(None)),
+            links_json_sync: Arc::new(tokio::sync::Mutex::new(())),
+            symlinks: Arc::new(DashMap::new()),
+            lookup_keys: Arc::new(DashMap::new()),
+            writing: Arc::new(std::sync::RwLock::new(())),
+            updating: Arc::new(DashMap::new()),
+            touched: Arc::new(Mutex::new(HashMap::new())),
+            next_rebuild: Arc::new(AtomicU64::new(0)),
         }
     }
 
+    /// Receive every later index change. Changes made while nobody is
+    /// subscribed are dropped.
+    pub fn subscribe(&self) -> broadcast::Receiver<IndexChange> {
+        self.changes.subscribe()
+    }
+
+    fn notify(&self, kind: IndexChangeKind, id: Option<&str>) {
+        let ids = id.map(|id| vec![id.to_string()]).unwrap_or_default();
+        // No receivers is fine: nobody is listening.
+        let _ = self.changes.send(IndexChange { kind, ids });
+    }
+
     /// Rebuild the full index by scanning all notes in note_dir.
+    ///
+    /// Notes are indexed into fresh maps and merged in only at the end, so
+    /// concurrent readers keep seeing the previous index, never an empty or
+    /// half-built one. Files updated while the scan runs keep their updated
+    /// entries.
     pub async fn rebuild_full(&self) -> Result<usize> {
-        self.notes.clear();
-        self.backlinks.clear();
+        self.rebuild_full_with_progress(|_, _| {}).await
+    }
 
-        let note_dir = {
-            let config = self.config.read().await;
-            config.note_dir.clone()
-        };
-        let mut entries = fs::read_dir(&note_dir).await?;
-        let mut paths = Vec::new();
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.extension().and_then(|e| e.to_str()) == Some("typ") {
-                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                    if stem.len() == 10 && stem.chars().all(|c| c.is_ascii_digit()) {
-                        paths.push(path);
-                    }
-                }
+    /// `rebuild_full`, calling `progress(done, total)` after each candidate
+    /// file is read, so callers can show how far a large scan has got.
+    #[instrument(level = "debug", skip_all, fields(notes))]
+    pub async fn rebuild_full_with_progress(
+        &self,
+        progress: impl Fn(usize, usize),
+    ) -> Result<usize> {
+        let config = self.config.read().await.clone();
+        let rebuild = self.next_rebuild.fetch_add(1, Ordering::Relaxed);
+        self.touched.lock().unwrap().insert(rebuild, HashSet::new());
+        let scanned = scan(&config, progress).await;
+        {
+            // The touched set is taken with `writing` held, so no update
+            // lands between it and the merge unrecorded.
+            let _writing = self.writing.write().unwrap();
+            let touched = self.touched.lock().unwrap().remove(&rebuild);
+            let fresh = scanned?;
+            for path in touched.iter().flatten() {
+                self.carry_over(path, &fresh);
             }
+            replace_entries(&self.notes, fresh.notes);
+            replace_entries(&self.backlinks, fresh.backlinks);
+            replace_entries(&self.keywords, fresh.keywords);
+            replace_entries(&self.titles, fresh.titles);
+            replace_entries(&self.symlinks, fresh.symlinks);
+            replace_entries(&self.lookup_keys, fresh.lookup_keys);
         }
+        self.set_links_json(&config);
+        self.sync_links_json().await;
 
-        for path in &paths {
-            let _ = self.index_file(path).await;
-        }
-
+        Span::current().record("notes", self.notes.len());
+        self.notify(IndexChangeKind::Rebuilt, None);
         Ok(self.notes.len())
     }
 
     /// Update a single file in the index.
+    #[instrument(level = "debug", skip_all, fields(path = %path.display()))]
     pub async fn update_file(&self, path: &Path) -> Result<()> {
-        // Remove old backlinks contributed by this file
-        self.remove_backlinks_from(path);
-        self.index_file(path).await
+        self.update(path, None).await
+    }
+
+    /// `update_file` with content the caller already read and parsed (the
+    /// saved or opened buffer), skipping the disk read and re-parse.
+    pub async fn update_doc(&self, path: &Path, doc: &NoteDoc) -> Result<()> {
+        self.update(path, Some(doc)).await
+    }
+
+    async fn update(&self, path: &Path, doc: Option<&NoteDoc>) -> Result<()> {
+        let path = &self.wiki_path(path);
+        let file_lock = Arc::clone(self.updating.entry(path.clone()).or_default().value());
+        let result = {
+            let _updating = file_lock.lock().await;
+            self.update_locked(path, doc).await
+        };
+        // Drop the lock once nobody else holds or waits for it (the map and
+        // `file_lock` are the only references), so the map does not keep one
+        // per path ever seen.
+        self.updating
+            .remove_if(path, |_, lock| Arc::strong_count(lock) == 2);
+        result
+    }
+
+    /// `update` for the in-wiki `path`, with its `updating` lock held.
+    async fn update_locked(&self, path: &Path, doc: Option<&NoteDoc>) -> Result<()> {
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        let links_before = self.links_from(path);
+        let (parser, count_self, lossy, max_bytes, retarget) = {
+            let config = self.config.read().await;
+            let retarget = self.set_links_json(&config);
+            if config.is_ignored(path) {
+                let removed = {
+                    let _writing = self.writing.write().unwrap();
+                    self.touch(path);
+                    self.remove_backlinks_from(path);
+                    self.remove_lookups_of(path);
+                    self.notes.remove(stem).is_some()
+                };
+                if removed {
+                    self.notify(IndexChangeKind::Removed, Some(stem));
+                }
+                if retarget || self.links_from(path) != links_before {
+                    self.sync_links_json().await;
+                }
+                return Ok(());
+            }
+            (
+                config.parser(),
+                config.settings.count_self_references,
+                config.zk_config.lossy_reads,
+                config.max_note_bytes(),
+                retarget,
+            )
+        };
+        // Read before taking the write lock, which is never held across an
+        // await.
+        let (read, read_error) = match doc {
+            Some(_) => (None, None),
+            None => match note_ops::read_note_text(path, lossy, max_bytes).await {
+                Ok(content) => (Some(NoteDoc::parse_with(&parser, content)), None),
+                Err(e) => (None, Some(e)),
+            },
+        };
+        let old_title = self.notes.get(stem).map(|n| n.title.clone());
+        {
+            let _writing = self.writing.write().unwrap();
+            self.touch(path);
+            // Remove old backlinks, keywords and titles contributed by this file
+            self.remove_backlinks_from(path);
+            self.remove_lookups_of(path);
+            if let Some(doc) = doc.or(read.as_ref()) {
+                self.index_doc(path, doc, count_self);
+            }
+        }
+        match (old_title, self.notes.get(stem).map(|n| n.title.clone())) {
+            (None, Some(_)) => self.notify(IndexChangeKind::Added, Some(stem)),
+            (Some(old), Some(new)) if old != new => {
+                self.notify(IndexChangeKind::Retitled, Some(stem))
+            }
+            _ => {}
+        }
+        if retarget || self.links_from(path) != links_before {
+            self.sync_links_json().await;
+        }
+        match read_error {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
     }
 
     /// Remove a note from the index by its path.
-    pub fn remove_by_path(&self, path: &Path) {
-        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-            self.notes.remove(stem);
+    pub async fn remove_by_path(&self, path: &Path) {
+        let path = &self.wiki_path(path);
+        let stem = path.file_stem().and_then(|s| s.to_str());
+        let links_before = self.links_from(path);
+        let removed = {
+            let _writing = self.writing.write().unwrap();
+            self.touch(path);
+            let removed = stem.is_some_and(|stem| self.notes.remove(stem).is_some());
+            self.remove_lookups_of(path);
+            self.remove_backlinks_from(path);
+            removed
+        };
+        self.updating
+            .remove_if(path, |_, lock| Arc::strong_count(lock) == 1);
+        if removed {
+            self.notify(IndexChangeKind::Removed, stem);
+        }
+        if self.links_from(path) != links_before {
+            self.sync_links_json().await;
+        }
+    }
+
+    /// The in-wiki link for `path` when it is the target of a symlinked
+    /// note, so edits made through either path index under the same file.
+    fn wiki_path(&self, path: &Path) -> PathBuf {
+        if !self.symlinks.is_empty() {
+            if let Ok(real) = std::fs::canonicalize(path) {
+                if let Some(link) = self.symlinks.get(&real) {
+                    return link.clone();
+                }
+            }
+        }
+        path.to_path_buf()
+    }
+
+    /// Every indexed note with the notes linking to it and the IDs it links
+    /// to (missing notes included). Sources are file stems of backlinks.
+    pub fn link_graph(&self) -> BTreeMap<String, NoteLinks> {
+        self.snapshot().links
+    }
+
+    /// A copy of the notes and links as they are now, for graph walks that
+    /// read them many times and must not see an update land halfway
+    /// through. Costs one clone of the notes and backlinks.
+    pub fn snapshot(&self) -> IndexSnapshot {
+        let (notes, backlinks) = {
+            let _reading = self.writing.read().unwrap();
+            let notes: HashMap<String, NoteInfo> = self
+                .notes
+                .iter()
+                .map(|e| (e.key().clone(), e.value().clone()))
+                .collect();
+            let backlinks: HashMap<String, Vec<BacklinkLocation>> = self
+                .backlinks
+                .iter()
+                .map(|e| (e.key().clone(), e.value().clone()))
+                .collect();
+            (notes, backlinks)
+        };
+        let links = link_graph_of(&notes, &backlinks);
+        IndexSnapshot {
+            notes,
+            backlinks,
+            links,
+        }
+    }
+
+    /// Take the `links.json` target from `config`. True when it changed, so
+    /// the file is due a write even if no link did.
+    fn set_links_json(&self, config: &WikiConfig) -> bool {
+        let target = config
+            .settings
+            .write_links_json
+            .then(|| config.links_json_path());
+        let mut current = self.links_json.lock().unwrap();
+        let changed = *current != target;
+        *current = target;
+        changed
+    }
+
+    /// The part of the link graph `path` contributes: the ID of the note it
+    /// holds and the IDs it references. Updates that leave both unchanged
+    /// skip rewriting `links.json`.
+    fn links_from(&self, path: &Path) -> (Option<String>, BTreeSet<String>) {
+        let id = self.lookup_keys.get(path).map(|keys| keys.id.clone());
+        let targets = self
+            .backlinks
+            .iter()
+            .filter(|entry| entry.value().iter().any(|loc| loc.file == path))
+            .map(|entry| entry.key().clone())
+            .collect();
+        (id, targets)
+    }
+
+    /// Rewrite `links.json` from the current index if it is enabled. The
+    /// file is replaced atomically, so readers never see a partial write.
+    /// Writes run one at a time, each taking the graph once it is its turn,
+    /// so the last one to finish has the newest graph.
+    async fn sync_links_json(&self) {
+        let Some(path) = self.links_json.lock().unwrap().clone() else {
+            return;
+        };
+        let _syncing = self.links_json_sync.lock().await;
+        let graph = self.link_graph();
+        let target = path.clone();
+        let write = move || -> Result<()> {
+            let json = serde_json::to_string_pretty(&graph)?;
+            let tmp = target.with_extension("json.tmp");
+            std::fs::write(&tmp, json + "\n")?;
+            std::fs::rename(&tmp, &target)?;
+            Ok(())
+        };
+        let written = tokio::task::spawn_blocking(write)
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|r| r);
+        if let Err(e) = written {
+            error!("writing {}: {e}", path.display());
         }
-        self.remove_backlinks_from(path);
     }
 
     pub fn get(&self, id: &str) -> Option<NoteInfo> {
         self.notes.get(id).map(|r| r.clone())
     }
 
-    /// Simple fuzzy search over title, aliases, keywords.
-    pub fn search(&self, query: &str) -> Vec<NoteInfo> {
+    /// Absolute path of an indexed note. A relative wiki root (e.g.
+    /// `--wiki-root notes`) is resolved against the current directory.
+    pub fn path_of(&self, id: &str) -> Option<PathBuf> {
+        let path = self.notes.get(id)?.path.clone();
+        Some(std::path::absolute(&path).unwrap_or(path))
+    }
+
+    /// `path_of`, falling back to a note file on disk that the index lacks
+    /// (e.g. one without a valid header), so navigation still works. The
+    /// fallback is skipped while the config is being replaced.
+    pub fn path_if_exists(&self, id: &str) -> Option<PathBuf> {
+        self.path_of(id).or_else(|| {
+            let path = self.config.try_read().ok()?.locate_note(id)?;
+            Some(std::path::absolute(&path).unwrap_or(path))
+        })
+    }
+
+    /// `path_if_exists` for an `@ID` written in the note at `from`.
+    ///
+    /// Under `reference_resolution = "relative"`, a `<id>.typ` in the
+    /// directory of `from`, or in a parent of it up to `note_dir`, is
+    /// preferred, so collections that reuse IDs resolve to their own note.
+    pub fn path_from(&self, id: &str, from: &Path) -> Option<PathBuf> {
+        let relative = self.config.try_read().ok().and_then(|config| {
+            let resolution = config.zk_config.reference_resolution.unwrap_or_default();
+            if resolution != ReferenceResolution::Relative {
+                return None;
+            }
+            let note_dir = std::path::absolute(&config.note_dir).ok()?;
+            let from = std::path::absolute(from).ok()?;
+            from.ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(&note_dir))
+                .map(|dir| dir.join(format!("{id}.typ")))
+                .find(|path| path.is_file())
+        });
+        relative.or_else(|| self.path_if_exists(id))
+    }
+
+    /// Every note `id` has evolved into: the relation targets of a legacy
+    /// note, and theirs in turn while they are legacy too. Empty unless `id`
+    /// is an indexed legacy note; cycles stop at the first repeat.
+    pub fn evolution_successors(&self, id: &str) -> HashSet<String> {
+        let mut successors = HashSet::new();
+        let mut pending = vec![id.to_string()];
+        while let Some(current) = pending.pop() {
+            let Some(info) = self.notes.get(&current) else {
+                continue;
+            };
+            if !info.legacy {
+                continue;
+            }
+            for next in info.evo_id.iter().chain(&info.relation_target) {
+                if next != id && successors.insert(next.clone()) {
+                    pending.push(next.clone());
+                }
+            }
+        }
+        successors
+    }
+
+    /// Whether a note with this ID is indexed, without cloning its info.
+    pub fn contains(&self, id: &str) -> bool {
+        self.notes.contains_key(id)
+    }
+
+    /// All indexed note IDs, sorted.
+    #[allow(dead_code)]
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.notes.iter().map(|e| e.key().clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    /// Simple fuzzy search over title, aliases, keywords. Notes marked
+    /// `generated` are skipped when `exclude_generated`.
+    ///
+    /// Results are ranked by `match_rank` (ID and title hits first), then
+    /// newest ID first. Stops early, returning the matches found so far, once
+    /// `cancel` fires.
+    #[instrument(level = "debug", skip(self, cancel), fields(results))]
+    pub fn search(
+        &self,
+        query: &str,
+        exclude_generated: bool,
+        cancel: &CancelToken,
+    ) -> Vec<NoteInfo> {
         let q = query.to_lowercase();
-        self.notes
+        let mut ranked: Vec<(u8, NoteInfo)> = self
+            .notes
             .iter()
-            .filter(|entry| {
+            .take_while(|_| !cancel.is_cancelled())
+            .filter(|entry| !(exclude_generated && entry.value().generated))
+            .filter_map(|entry| {
                 let n = entry.value();
-                n.title.to_lowercase().contains(&q)
-                    || n.id.contains(&q)
-                    || n.aliases.iter().any(|a| a.to_lowercase().contains(&q))
-                    || n.keywords.iter().any(|k| k.to_lowercase().contains(&q))
-                    || n.abstract_text
-                        .as_deref()
-                        .map(|a| a.to_lowercase().contains(&q))
-                        .unwrap_or(false)
+                match_rank(n, &q).map(|rank| (rank, n.clone()))
             })
-            .map(|entry| entry.value().clone())
-            .collect()
+            .collect();
+        ranked.sort_by(|(ra, a), (rb, b)| ra.cmp(rb).then_with(|| b.id.cmp(&a.id)));
+        let results: Vec<NoteInfo> = ranked.into_iter().map(|(_, n)| n).collect();
+        Span::current().record("results", results.len());
+        results
+    }
+
+    /// Notes whose ID timestamp lies in `[start, end)`, oldest first.
+    /// IDs that are not valid timestamps are skipped.
+    pub fn created_between(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Vec<(NaiveDateTime, NoteInfo)> {
+        let mut notes: Vec<(NaiveDateTime, NoteInfo)> = self
+            .notes
+            .iter()
+            .filter_map(|entry| {
+                let created = parser::id_to_datetime(entry.key())?;
+                (created >= start && created < end).then(|| (created, entry.value().clone()))
+            })
+            .collect();
+        notes.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.id.cmp(&b.1.id)));
+        notes
     }
 
     /// Get all backlink locations for an ID.
@@ -127,46 +661,368 @@ impl NoteIndex {
             .unwrap_or_default()
     }
 
+    /// Backlinks to `id` grouped by source file, sorted by source ID, with
+    /// locations in document order. The source note is found by file stem;
+    /// a file that is not indexed uses its stem as both ID and title.
+    pub fn backlinks_by_source(&self, id: &str) -> Vec<BacklinkGroup> {
+        let mut by_file: HashMap<PathBuf, Vec<BacklinkLocation>> = HashMap::new();
+        for loc in self.get_backlinks(id) {
+            by_file.entry(loc.file.clone()).or_default().push(loc);
+        }
+        let mut groups: Vec<BacklinkGroup> = by_file
+            .into_iter()
+            .map(|(file, mut locations)| {
+                locations.sort_by_key(|l| (l.line, l.start_char));
+                let stem = file
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let source_title = self
+                    .notes
+                    .get(&stem)
+                    .map(|n| n.title.clone())
+                    .unwrap_or_else(|| stem.clone());
+                BacklinkGroup {
+                    source_id: stem,
+                    source_title,
+                    file,
+                    locations,
+                }
+            })
+            .collect();
+        groups.sort_by(|a, b| a.source_id.cmp(&b.source_id).then(a.file.cmp(&b.file)));
+        groups
+    }
+
+    /// Files that reference `id`, sorted and without the note's own file:
+    /// what would be left with dead links if the note were removed.
+    pub fn referencing_files(&self, id: &str) -> Vec<PathBuf> {
+        let own_path = self.notes.get(id).map(|n| n.path.clone());
+        let mut files: Vec<PathBuf> = self
+            .get_backlinks(id)
+            .into_iter()
+            .map(|loc| loc.file)
+            .filter(|file| Some(file) != own_path.as_ref())
+            .collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// Number of distinct notes that reference `id`, not counting the note
+    /// itself. Several `@id` occurrences in one note count once.
+    pub fn linking_notes(&self, id: &str) -> usize {
+        let own_path = self.notes.get(id).map(|n| n.path.clone());
+        self.backlinks
+            .get(id)
+            .map(|locs| {
+                locs.iter()
+                    .map(|loc| &loc.file)
+                    .filter(|file| Some(*file) != own_path.as_ref())
+                    .collect::<HashSet<_>>()
+                    .len()
+            })
+            .unwrap_or(0)
+    }
+
+    /// `get_backlinks(id).len()` for each of `ids`, without copying the
+    /// locations. IDs nothing references map to 0.
+    pub fn backlink_counts(&self, ids: &[String]) -> HashMap<String, usize> {
+        ids.iter()
+            .map(|id| {
+                let count = self.backlinks.get(id).map_or(0, |locs| locs.len());
+                (id.clone(), count)
+            })
+            .collect()
+    }
+
+    /// `linking_notes` for each of `ids`.
+    pub fn linking_note_counts(&self, ids: &[String]) -> HashMap<String, usize> {
+        ids.iter()
+            .map(|id| (id.clone(), self.linking_notes(id)))
+            .collect()
+    }
+
+    /// The `limit` most-referenced existing notes by `linking_notes`, highest
+    /// first, ties broken by ID. Notes nobody links to are left out.
+    pub fn hub_notes(&self, limit: usize) -> Vec<(NoteInfo, usize)> {
+        let mut hubs: Vec<(NoteInfo, usize)> = self
+            .notes
+            .iter()
+            .map(|entry| (entry.value().clone(), self.linking_notes(entry.key())))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        hubs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.id.cmp(&b.0.id)));
+        hubs.truncate(limit);
+        hubs
+    }
+
+    /// Up to `n` indexed IDs within `MAX_SUGGESTION_DISTANCE` edits of `id`,
+    /// closest first (ties by ID). Used to suggest fixes for mistyped refs.
+    pub fn closest_ids(&self, id: &str, n: usize) -> Vec<String> {
+        let mut candidates: Vec<(usize, String)> = self
+            .notes
+            .iter()
+            .filter(|entry| entry.key() != id)
+            .filter_map(|entry| {
+                let d = edit_distance_within(id, entry.key(), MAX_SUGGESTION_DISTANCE)?;
+                Some((d, entry.key().clone()))
+            })
+            .collect();
+        candidates.sort();
+        candidates.into_iter().take(n).map(|(_, id)| id).collect()
+    }
+
+    /// Notes that no other note links to (they may still link out), sorted
+    /// by ID. Archived notes are skipped unless `include_archived`.
+    pub fn leaf_notes(&self, include_archived: bool) -> Vec<NoteInfo> {
+        let mut leaves: Vec<NoteInfo> = self
+            .notes
+            .iter()
+            .filter(|entry| include_archived || !entry.value().archived)
+            .filter(|entry| self.linking_notes(entry.key()) == 0)
+            .map(|entry| entry.value().clone())
+            .collect();
+        leaves.sort_by(|a, b| a.id.cmp(&b.id));
+        leaves
+    }
+
+    /// Groups of notes sharing a normalized title (see `normalize_title`).
+    /// With `max_distance > 0`, titles within that many character edits of
+    /// each other are grouped too (transitively). Notes are sorted by ID
+    /// within a group, groups by their first ID; singletons are left out.
+    /// Archived notes are skipped unless `include_archived`. Stops early,
+    /// returning no groups, once `cancel` fires.
+    pub fn duplicate_titles(
+        &self,
+        include_archived: bool,
+        max_distance: usize,
+        cancel: &CancelToken,
+    ) -> Vec<Vec<NoteInfo>> {
+        // Distinct normalized titles, each with its notes.
+        let mut by_title: HashMap<String, Vec<NoteInfo>> = HashMap::new();
+        for entry in self.notes.iter() {
+            let info = entry.value();
+            let key = normalize_title(&info.title);
+            if key.is_empty() || (info.archived && !include_archived) {
+                continue;
+            }
+            by_title.entry(key).or_default().push(info.clone());
+        }
+        let mut titles: Vec<(String, Vec<NoteInfo>)> = by_title.into_iter().collect();
+        titles.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // Union-find over distinct titles.
+        let mut parent: Vec<usize> = (0..titles.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        if max_distance > 0 {
+            let lens: Vec<usize> = titles.iter().map(|(t, _)| t.chars().count()).collect();
+            for i in 0..titles.len() {
+                if cancel.is_cancelled() {
+                    return Vec::new();
+                }
+                for j in i + 1..titles.len() {
+                    if lens[i].abs_diff(lens[j]) <= max_distance
+                        && edit_distance(&titles[i].0, &titles[j].0) <= max_distance
+                    {
+                        let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                        parent[a] = b;
+                    }
+                }
+            }
+        }
+
+        let mut groups: HashMap<usize, Vec<NoteInfo>> = HashMap::new();
+        for (i, (_, notes)) in titles.into_iter().enumerate() {
+            groups
+                .entry(root(&mut parent, i))
+                .or_default()
+                .extend(notes);
+        }
+        let mut groups: Vec<Vec<NoteInfo>> = groups
+            .into_values()
+            .filter(|g| g.len() > 1)
+            .map(|mut g| {
+                g.sort_by(|a, b| a.id.cmp(&b.id));
+                g
+            })
+            .collect();
+        groups.sort_by(|a, b| a[0].id.cmp(&b[0].id));
+        groups
+    }
+
+    /// Notes carrying topic tag `tag`, sorted by ID. A leading `#` is
+    /// ignored and a bare name means `tag.<name>`, so `project`,
+    /// `tag.project` and `#tag.project` are the same tag.
+    pub fn by_tag(&self, tag: &str) -> Vec<NoteInfo> {
+        let tag = normalize_tag(tag);
+        let mut notes: Vec<NoteInfo> = self
+            .notes
+            .iter()
+            .filter(|entry| entry.value().tags.contains(&tag))
+            .map(|entry| entry.value().clone())
+            .collect();
+        notes.sort_by(|a, b| a.id.cmp(&b.id));
+        notes
+    }
+
+    /// IDs of the notes whose title or one of whose aliases is `title`
+    /// (compared lowercased), sorted.
+    pub fn ids_with_title(&self, title: &str) -> Vec<String> {
+        self.titles
+            .get(&title.to_lowercase())
+            .map(|ids| ids.clone())
+            .unwrap_or_default()
+    }
+
+    /// Other notes sharing at least one keyword with `id` (compared
+    /// lowercased), with the shared keywords; most shared first, then newest.
+    pub fn notes_sharing_keywords(&self, id: &str) -> Vec<(NoteInfo, Vec<String>)> {
+        let Some(keywords) = self.notes.get(id).map(|n| n.keywords.clone()) else {
+            return Vec::new();
+        };
+        let mut shared: HashMap<String, Vec<String>> = HashMap::new();
+        for keyword in keywords.iter().map(|k| k.to_lowercase()) {
+            let Some(ids) = self.keywords.get(&keyword) else {
+                continue;
+            };
+            for other in ids.iter().filter(|other| *other != id) {
+                let list = shared.entry(other.clone()).or_default();
+                if !list.contains(&keyword) {
+                    list.push(keyword.clone());
+                }
+            }
+        }
+        let mut related: Vec<(NoteInfo, Vec<String>)> = shared
+            .into_iter()
+            .filter_map(|(other, keywords)| Some((self.get(&other)?, keywords)))
+            .collect();
+        related.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| b.0.id.cmp(&a.0.id)));
+        related
+    }
+
+    /// Up to `limit` non-archived `notes_sharing_keywords`, with the
+    /// shared-keyword count.
+    pub fn see_also(&self, id: &str, limit: usize) -> Vec<(NoteInfo, usize)> {
+        self.notes_sharing_keywords(id)
+            .into_iter()
+            .filter(|(note, _)| !note.archived)
+            .take(limit)
+            .map(|(note, shared)| (note, shared.len()))
+            .collect()
+    }
+
+    /// Every topic tag with the number of notes carrying it, most used
+    /// first; ties by tag name.
+    pub fn tag_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for entry in self.notes.iter() {
+            for tag in &entry.value().tags {
+                *counts.entry(tag.clone()).or_default() += 1;
+            }
+        }
+        let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// Every keyword with the number of notes listing it (compared
+    /// lowercased), most used first; ties by keyword. Each keyword is shown
+    /// in its most common spelling.
+    pub fn keyword_counts(&self) -> Vec<(String, usize)> {
+        let mut spellings: HashMap<String, HashMap<String, usize>> = HashMap::new();
+        for entry in self.notes.iter() {
+            let mut seen = HashSet::new();
+            for keyword in &entry.value().keywords {
+                if seen.insert(keyword.to_lowercase()) {
+                    *spellings
+                        .entry(keyword.to_lowercase())
+                        .or_default()
+                        .entry(keyword.clone())
+                        .or_default() += 1;
+                }
+            }
+        }
+        let mut counts: Vec<(String, usize)> = spellings
+            .into_values()
+            .filter_map(|variants| {
+                let total = variants.values().sum();
+                let (spelling, _) = variants
+                    .into_iter()
+                    .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))?;
+                Some((spelling, total))
+            })
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
     // -----------------------------------------------------------------------
     // Private helpers
     // -----------------------------------------------------------------------
 
-    async fn index_file(&self, path: &Path) -> Result<()> {
-        let content = fs::read_to_string(path).await?;
-        if let Some(header) = parser::parse_header(&content) {
-            let info = NoteInfo {
-                id: header.id.clone(),
-                title: header.title.clone(),
-                archived: header.archived,
-                legacy: header.legacy,
-                alt_id: header.alt_id.clone(),
-                evo_id: header.evo_id.clone(),
-                relation_target: header.relation_target.clone(),
-                aliases: header.aliases.clone(),
-                keywords: header.keywords.clone(),
-                abstract_text: header.abstract_text.clone(),
-                checklist_status: header.checklist_status.clone(),
-                path: path.to_path_buf(),
-            };
-            self.notes.insert(header.id.clone(), info);
-        }
-
-        // Update backlinks from this file.
-        // Convert byte offsets to UTF-16 code-unit offsets (required by LSP) here,
-        // while the line text is available.
-        let lines: Vec<&str> = content.lines().collect();
-        let refs = parser::find_all_refs_filtered(&content);
-        for r in refs {
-            let line_text = lines.get(r.line as usize).copied().unwrap_or("");
-            let loc = BacklinkLocation {
-                file: path.to_path_buf(),
-                line: r.line,
-                start_char: parser::byte_to_utf16(line_text, r.start_char as usize),
-                end_char: parser::byte_to_utf16(line_text, r.end_char as usize),
-            };
-            self.backlinks.entry(r.id).or_default().push(loc);
+    fn index_doc(&self, path: &Path, doc: &NoteDoc, count_self: bool) {
+        index_doc_into(
+            &self.notes,
+            &self.backlinks,
+            &self.keywords,
+            &self.titles,
+            &self.lookup_keys,
+            path,
+            doc,
+            count_self,
+        );
+    }
+
+    /// Record that `path` changed for every running rebuild. Call with
+    /// `writing` held.
+    fn touch(&self, path: &Path) {
+        for paths in self.touched.lock().unwrap().values_mut() {
+            paths.insert(path.to_path_buf());
+        }
+    }
+
+    /// Make `fresh` hold the live entries of `path`, replacing what the
+    /// rebuild scan read from it. Call with `writing` held.
+    fn carry_over(&self, path: &Path, fresh: &Scanned) {
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            return;
+        };
+        fresh.notes.remove(stem);
+        remove_lookups_into(&fresh.keywords, &fresh.titles, &fresh.lookup_keys, path);
+        for mut entry in fresh.backlinks.iter_mut() {
+            entry.value_mut().retain(|loc| loc.file != path);
+        }
+        fresh.backlinks.retain(|_, v| !v.is_empty());
+
+        if let Some(info) = self.notes.get(stem) {
+            fresh.notes.insert(stem.to_string(), info.clone());
+        }
+        if let Some(keys) = self.lookup_keys.get(path) {
+            add_to_lookup(&fresh.keywords, &keys.id, &keys.keywords);
+            add_to_lookup(&fresh.titles, &keys.id, &keys.titles);
+            fresh.lookup_keys.insert(path.to_path_buf(), keys.clone());
+        }
+        for entry in self.backlinks.iter() {
+            for loc in entry.value().iter().filter(|loc| loc.file == path) {
+                fresh
+                    .backlinks
+                    .entry(entry.key().clone())
+                    .or_default()
+                    .push(loc.clone());
+            }
         }
-        Ok(())
+    }
+
+    fn remove_lookups_of(&self, path: &Path) {
+        remove_lookups_into(&self.keywords, &self.titles, &self.lookup_keys, path);
     }
 
     fn remove_backlinks_from(&self, path: &Path) {
@@ -177,3 +1033,1421 @@ impl NoteIndex {
         self.backlinks.retain(|_, v| !v.is_empty());
     }
 }
+
+/// Maps built by a rebuild scan, merged into the live index at its end.
+#[derive(Default)]
+struct Scanned {
+    notes: DashMap<String, NoteInfo>,
+    backlinks: DashMap<String, Vec<BacklinkLocation>>,
+    keywords: DashMap<String, Vec<String>>,
+    titles: DashMap<String, Vec<String>>,
+    symlinks: DashMap<PathBuf, PathBuf>,
+    lookup_keys: DashMap<PathBuf, LookupKeys>,
+}
+
+/// The lookup keys one file added, under the header ID it added them for.
+#[derive(Clone)]
+struct LookupKeys {
+    id: String,
+    /// Lowercased, as stored in `keywords`.
+    keywords: Vec<String>,
+    /// Lowercased title and aliases, as stored in `titles`.
+    titles: Vec<String>,
+}
+
+/// Index every note `config` covers into fresh maps, calling
+/// `progress(done, total)` after each file.
+async fn scan(config: &WikiConfig, progress: impl Fn(usize, usize)) -> Result<Scanned> {
+    let paths = note_ops::indexable_note_files(config).await?;
+    let parser = config.parser();
+    let count_self = config.settings.count_self_references;
+    let lossy = config.zk_config.lossy_reads;
+    let max_bytes = config.max_note_bytes();
+    let fresh = Scanned::default();
+    for (done, path) in paths.iter().enumerate() {
+        if config.zk_config.follow_symlinks {
+            if let Ok(real) = fs::canonicalize(path).await {
+                if fs::symlink_metadata(path)
+                    .await
+                    .is_ok_and(|m| m.file_type().is_symlink())
+                {
+                    fresh.symlinks.insert(real, path.clone());
+                }
+            }
+        }
+        if let Ok(content) = note_ops::read_note_text(path, lossy, max_bytes).await {
+            let doc = NoteDoc::parse_with(&parser, content);
+            index_doc_into(
+                &fresh.notes,
+                &fresh.backlinks,
+                &fresh.keywords,
+                &fresh.titles,
+                &fresh.lookup_keys,
+                path,
+                &doc,
+                count_self,
+            );
+        }
+        progress(done + 1, paths.len());
+    }
+    Ok(fresh)
+}
+
+/// Add `doc` (the content of `path`) to `notes`, `backlinks`, `keywords` and
+/// `titles`, recording the lookup keys it added in `lookup_keys`.
+#[allow(clippy::too_many_arguments)]
+fn index_doc_into(
+    notes: &DashMap<String, NoteInfo>,
+    backlinks: &DashMap<String, Vec<BacklinkLocation>>,
+    keywords: &DashMap<String, Vec<String>>,
+    titles: &DashMap<String, Vec<String>>,
+    lookup_keys: &DashMap<PathBuf, LookupKeys>,
+    path: &Path,
+    doc: &NoteDoc,
+    count_self: bool,
+) {
+    let own_id = doc.header.as_ref().map(|h| h.id.as_str());
+    if let Some(header) = &doc.header {
+        let lower =
+            |keys: &[String]| -> Vec<String> { keys.iter().map(|k| k.to_lowercase()).collect() };
+        let keys = LookupKeys {
+            id: header.id.clone(),
+            keywords: lower(&header.keywords),
+            titles: lower(&[std::slice::from_ref(&header.title), &header.aliases[..]].concat()),
+        };
+        add_to_lookup(keywords, &keys.id, &keys.keywords);
+        add_to_lookup(titles, &keys.id, &keys.titles);
+        lookup_keys.insert(path.to_path_buf(), keys);
+        let info = NoteInfo {
+            id: header.id.clone(),
+            title: header.title.clone(),
+            archived: header.archived,
+            legacy: header.legacy,
+            alt_id: header.alt_id.clone(),
+            evo_id: header.evo_id.clone(),
+            relation_target: header.relation_target.clone(),
+            aliases: header.aliases.clone(),
+            keywords: header.keywords.clone(),
+            abstract_text: header.abstract_text.clone(),
+            checklist_status: header.checklist_status.clone(),
+            tags: header.tags.clone(),
+            generated: header.generated,
+            path: path.to_path_buf(),
+        };
+        notes.insert(header.id.clone(), info);
+    }
+
+    // Update backlinks from this file.
+    // Convert byte offsets to UTF-16 code-unit offsets (required by LSP) here,
+    // while the line text is available.
+    let lines: Vec<&str> = parser::split_lines(&doc.content).collect();
+    for r in &doc.refs {
+        // `@<own ID>` in the body is not a backlink unless `countSelfReferences`.
+        if !count_self && own_id == Some(r.id.as_str()) {
+            continue;
+        }
+        let line_text = lines.get(r.line as usize).copied().unwrap_or("");
+        let loc = BacklinkLocation {
+            file: path.to_path_buf(),
+            line: r.line,
+            start_char: parser::byte_to_utf16(line_text, r.start_char as usize),
+            end_char: parser::byte_to_utf16(line_text, r.end_char as usize),
+            start_byte: r.start_char,
+            end_byte: r.end_char,
+        };
+        backlinks.entry(r.id.clone()).or_default().push(loc);
+    }
+}
+
+/// Record note `id` under each of `keys` (lowercased) in `lookup`.
+fn add_to_lookup(lookup: &DashMap<String, Vec<String>>, id: &str, keys: &[String]) {
+    for key in keys {
+        let mut ids = lookup.entry(key.to_lowercase()).or_default();
+        if let Err(pos) = ids.binary_search_by(|note| note.as_str().cmp(id)) {
+            ids.insert(pos, id.to_string());
+        }
+    }
+}
+
+/// Remove the `keywords` and `titles` entries `path` added, as recorded in
+/// `lookup_keys`.
+fn remove_lookups_into(
+    keywords: &DashMap<String, Vec<String>>,
+    titles: &DashMap<String, Vec<String>>,
+    lookup_keys: &DashMap<PathBuf, LookupKeys>,
+    path: &Path,
+) {
+    let Some((_, added)) = lookup_keys.remove(path) else {
+        return;
+    };
+    for (lookup, keys) in [(keywords, &added.keywords), (titles, &added.titles)] {
+        for key in keys {
+            if let Some(mut ids) = lookup.get_mut(key) {
+                ids.retain(|note| *note != added.id);
+            }
+            lookup.remove_if(key, |_, ids| ids.is_empty());
+        }
+    }
+}
+
+fn link_graph_of(
+    notes: &HashMap<String, NoteInfo>,
+    backlinks: &HashMap<String, Vec<BacklinkLocation>>,
+) -> BTreeMap<String, NoteLinks> {
+    let mut graph: BTreeMap<String, NoteLinks> = notes
+        .keys()
+        .map(|id| (id.clone(), NoteLinks::default()))
+        .collect();
+    for (target, locs) in backlinks {
+        for loc in locs {
+            let Some(source) = loc.file.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if source == target {
+                continue;
+            }
+            if let Some(links) = graph.get_mut(target) {
+                links.backlinks.insert(source.to_string());
+            }
+            if let Some(links) = graph.get_mut(source) {
+                links.outlinks.insert(target.clone());
+            }
+        }
+    }
+    graph
+}
+
+/// Make `live` hold exactly `fresh`'s entries: overwrite, then drop stale
+/// keys, so `live` is never empty while `fresh` has entries.
+fn replace_entries<K, V>(live: &DashMap<K, V>, fresh: DashMap<K, V>)
+where
+    K: Eq + std::hash::Hash + Clone,
+{
+    let keys: HashSet<K> = fresh.iter().map(|e| e.key().clone()).collect();
+    for (key, value) in fresh {
+        live.insert(key, value);
+    }
+    live.retain(|key, _| keys.contains(key));
+}
+
+#[cfg(test)]
+impl NoteInfo {
+    /// An active note titled `Note {id}` at `/tmp/note/{id}.typ`; tests
+    /// override fields with struct update syntax.
+    pub fn stub(id: &str) -> Self {
+        NoteInfo {
+            id: id.to_string(),
+            title: format!("Note {id}"),
+            archived: false,
+            legacy: false,
+            alt_id: None,
+            evo_id: None,
+            relation_target: vec![],
+            aliases: vec![],
+            keywords: vec![],
+            abstract_text: None,
+            checklist_status: None,
+            tags: vec![],
+            generated: false,
+            path: PathBuf::from(format!("/tmp/note/{id}.typ")),
+        }
+    }
+}
+
+#[cfg(test)]
+impl NoteIndex {
+    /// An index over a wiki rooted at `/tmp` holding `notes` and no backlinks.
+    pub fn with_notes(notes: impl IntoIterator<Item = NoteInfo>) -> Self {
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(PathBuf::from(
+            "/tmp",
+        )))));
+        for info in notes {
+            idx.notes.insert(info.id.clone(), info);
+        }
+        idx
+    }
+
+    /// Record an `@{to}` reference at the start of `/tmp/note/{from}.typ`.
+    pub fn add_link(&self, from: &str, to: &str) {
+        self.backlinks
+            .entry(to.to_string())
+            .or_default()
+            .push(BacklinkLocation {
+                file: PathBuf::from(format!("/tmp/note/{from}.typ")),
+                line: 0,
+                start_char: 0,
+                end_char: 11,
+                start_byte: 0,
+                end_byte: 11,
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_index(ids: &[&str]) -> NoteIndex {
+        NoteIndex::with_notes(ids.iter().map(|id| NoteInfo::stub(id)))
+    }
+
+    #[tokio::test]
+    async fn test_backlinks_store_utf16_and_byte_offsets() {
+        let tmp = std::env::temp_dir().join("zk_index_test_offsets");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let path = tmp.join("note/2603150000.typ");
+        // "é" is 2 bytes but 1 UTF-16 unit; "😀" is 4 bytes but 2 UTF-16 units.
+        std::fs::write(&path, "é😀 @2603140000\n").unwrap();
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone()))));
+        idx.update_file(&path).await.unwrap();
+        let locs = idx.get_backlinks("2603140000");
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(locs.len(), 1);
+        assert_eq!((locs[0].start_char, locs[0].end_char), (4, 15));
+        assert_eq!((locs[0].start_byte, locs[0].end_byte), (7, 18));
+    }
+
+    #[tokio::test]
+    async fn test_label_refs_config_indexes_body_labels() {
+        let tmp = std::env::temp_dir().join("zk_index_test_label_refs");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::write(tmp.join("zk-lsp.toml"), "label_refs = true\n").unwrap();
+        let path = tmp.join("note/2603150000.typ");
+        std::fs::write(
+            &path,
+            "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n\
+             = Own <2603150000>\nSee <2603140000> and @2603130000.\n",
+        )
+        .unwrap();
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone()))));
+        idx.rebuild_full().await.unwrap();
+        let label = idx.get_backlinks("2603140000");
+        let at = idx.get_backlinks("2603130000");
+        let own = idx.get_backlinks("2603150000");
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(label.len(), 1);
+        assert_eq!((label[0].line, label[0].start_char), (6, 4));
+        assert_eq!(at.len(), 1);
+        assert!(own.is_empty(), "title label counted as a self-backlink");
+    }
+
+    #[tokio::test]
+    async fn test_wikilink_ref_style_indexes_bracketed_refs() {
+        let tmp = std::env::temp_dir().join("zk_index_test_wikilink");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::write(tmp.join("zk-lsp.toml"), "ref_style = \"wikilink\"\n").unwrap();
+        let path = tmp.join("note/2603150000.typ");
+        std::fs::write(
+            &path,
+            "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n\
+             = Own <2603150000>\nSee [[2603140000]] and @2603130000.\n",
+        )
+        .unwrap();
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone()))));
+        idx.rebuild_full().await.unwrap();
+        let linked = idx.get_backlinks("2603140000");
+        let at = idx.get_backlinks("2603130000");
+        let style = idx.config.read().await.ref_style();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(style, crate::config::RefStyle::Wikilink);
+        assert_eq!(linked.len(), 1);
+        assert_eq!(
+            (linked[0].line, linked[0].start_char, linked[0].end_char),
+            (6, 4, 18)
+        );
+        assert!(at.is_empty(), "@ID counted under ref_style = \"wikilink\"");
+    }
+
+    #[tokio::test]
+    async fn test_links_json_follows_the_index() {
+        let tmp = std::env::temp_dir().join("zk_index_test_links_json");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let write = |id: &str, body: &str| {
+            let path = tmp.join(format!("note/{id}.typ"));
+            std::fs::write(
+                &path,
+                format!(
+                    "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n\
+                     = Note <{id}>\n{body}"
+                ),
+            )
+            .unwrap();
+            path
+        };
+        write("2603150000", "Self @2603150000, see @2603150001.\n");
+        let b = write("2603150001", "Back to @2603150000 and @2603159999.\n");
+        let settings = crate::config::ClientSettings {
+            write_links_json: true,
+            ..Default::default()
+        };
+        let config = WikiConfig::from_root(tmp.clone()).with_settings(settings);
+        let idx = NoteIndex::new(Arc::new(RwLock::new(config)));
+        idx.rebuild_full().await.unwrap();
+        let read = || {
+            serde_json::from_str::<serde_json::Value>(
+                &std::fs::read_to_string(tmp.join("links.json")).unwrap(),
+            )
+            .unwrap()
+        };
+        let built = read();
+        std::fs::remove_file(&b).unwrap();
+        idx.remove_by_path(&b).await;
+        let after_remove = read();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(
+            built,
+            serde_json::json!({
+                "2603150000": {"backlinks": ["2603150001"], "outlinks": ["2603150001"]},
+                "2603150001": {"backlinks": ["2603150000"], "outlinks": ["2603150000", "2603159999"]},
+            })
+        );
+        assert_eq!(
+            after_remove,
+            serde_json::json!({
+                "2603150000": {"backlinks": [], "outlinks": ["2603150001"]},
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_links_json_rewritten_only_when_links_change() {
+        let tmp = std::env::temp_dir().join("zk_index_test_links_json_unchanged");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let path = tmp.join("note/2603150000.typ");
+        let write = |body: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n\
+                     = Note <2603150000>\n{body}"
+                ),
+            )
+            .unwrap();
+        };
+        write("See @2603150001.\n");
+        let settings = crate::config::ClientSettings {
+            write_links_json: true,
+            ..Default::default()
+        };
+        let config = WikiConfig::from_root(tmp.clone()).with_settings(settings);
+        let idx = NoteIndex::new(Arc::new(RwLock::new(config)));
+        idx.rebuild_full().await.unwrap();
+        let links_json = tmp.join("links.json");
+        std::fs::remove_file(&links_json).unwrap();
+
+        // Same link, new prose: nothing to rewrite.
+        write("See @2603150001, again.\n");
+        idx.update_file(&path).await.unwrap();
+        let after_prose = links_json.exists();
+        write("See @2603150002.\n");
+        idx.update_file(&path).await.unwrap();
+        let after_link = links_json.exists();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert!(!after_prose, "links.json rewritten for a prose-only edit");
+        assert!(after_link, "links.json not rewritten after a link changed");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_parallel_updates_write_whole_links_json() {
+        let tmp = std::env::temp_dir().join("zk_index_test_links_json_parallel");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let paths: Vec<PathBuf> = (0..32u64)
+            .map(|i| {
+                let id = 2603150000 + i;
+                let path = tmp.join(format!("note/{id}.typ"));
+                std::fs::write(
+                    &path,
+                    format!(
+                        "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+                         ```.text,\n))\n= Note <{id}>\nSee @{}.\n",
+                        id + 1
+                    ),
+                )
+                .unwrap();
+                path
+            })
+            .collect();
+        let settings = crate::config::ClientSettings {
+            write_links_json: true,
+            ..Default::default()
+        };
+        let config = WikiConfig::from_root(tmp.clone()).with_settings(settings);
+        let idx = Arc::new(NoteIndex::new(Arc::new(RwLock::new(config))));
+        let tasks: Vec<_> = paths
+            .into_iter()
+            .map(|path| {
+                let idx = Arc::clone(&idx);
+                tokio::spawn(async move { idx.update_file(&path).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(tmp.join("links.json")).unwrap())
+                .unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(written, serde_json::to_value(idx.link_graph()).unwrap());
+        assert_eq!(written.as_object().unwrap().len(), 32);
+    }
+
+    #[tokio::test]
+    async fn test_searches_during_rebuild_never_see_an_empty_index() {
+        let tmp = std::env::temp_dir().join("zk_index_test_rebuild_readers");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        for i in 0..200u64 {
+            let id = 2603150000 + i;
+            std::fs::write(
+                tmp.join(format!("note/{id}.typ")),
+                format!(
+                    "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+                     ```.text,\n))\n= Note {i} <{id}>\n"
+                ),
+            )
+            .unwrap();
+        }
+        let idx = Arc::new(NoteIndex::new(Arc::new(RwLock::new(
+            WikiConfig::from_root(tmp.clone()),
+        ))));
+        assert_eq!(idx.rebuild_full().await.unwrap(), 200);
+
+        // Every file read inside the rebuild yields, letting the reader run.
+        let rebuild = tokio::spawn({
+            let idx = Arc::clone(&idx);
+            async move { idx.rebuild_full().await.unwrap() }
+        });
+        let mut min_seen = usize::MAX;
+        while !rebuild.is_finished() {
+            min_seen = min_seen.min(idx.search("Note", false, &CancelToken::new()).len());
+            tokio::task::yield_now().await;
+        }
+        let rebuilt = rebuild.await.unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(rebuilt, 200);
+        assert_eq!(min_seen, 200);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_update_during_rebuild_is_not_rolled_back() {
+        let tmp = std::env::temp_dir().join("zk_index_test_rebuild_update");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let write = |id: &str, title: &str, body: &str| {
+            let path = tmp.join(format!("note/{id}.typ"));
+            std::fs::write(
+                &path,
+                format!(
+                    "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+                     ```.text,\n))\n= {title} <{id}>\n{body}"
+                ),
+            )
+            .unwrap();
+            path
+        };
+        let a = write("2603150000", "Old A", "See @2603150002.\n");
+        let b = write("2603150001", "Old B", "See @2603150002.\n");
+        let idx = Arc::new(NoteIndex::new(Arc::new(RwLock::new(
+            WikiConfig::from_root(tmp.clone()),
+        ))));
+
+        // The rebuild stops after reading its first file until both notes
+        // have been rewritten and re-indexed, then merges what it read.
+        let (read_tx, read_rx) = std::sync::mpsc::channel();
+        let (updated_tx, updated_rx) = std::sync::mpsc::channel::<()>();
+        let rebuild = tokio::spawn({
+            let idx = Arc::clone(&idx);
+            async move {
+                idx.rebuild_full_with_progress(move |done, _| {
+                    if done == 1 {
+                        read_tx.send(()).unwrap();
+                        updated_rx.recv().unwrap();
+                    }
+                })
+                .await
+                .unwrap()
+            }
+        });
+        tokio::task::spawn_blocking(move || read_rx.recv().unwrap())
+            .await
+            .unwrap();
+        for (path, id, title) in [(&a, "2603150000", "New A"), (&b, "2603150001", "New B")] {
+            write(id, title, "See @2603150003.\n");
+            idx.update_file(path).await.unwrap();
+        }
+        updated_tx.send(()).unwrap();
+        assert_eq!(rebuild.await.unwrap(), 2);
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(idx.get("2603150000").unwrap().title, "New A");
+        assert_eq!(idx.get("2603150001").unwrap().title, "New B");
+        assert_eq!(idx.ids_with_title("old a"), Vec::<String>::new());
+        assert_eq!(idx.ids_with_title("new a"), vec!["2603150000".to_string()]);
+        assert!(idx.get_backlinks("2603150002").is_empty());
+        assert_eq!(idx.get_backlinks("2603150003").len(), 2);
+        assert!(idx.touched.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_see_also_ranks_by_shared_keywords() {
+        let idx = make_index(&[
+            "2603010000",
+            "2603020000",
+            "2603030000",
+            "2603040000",
+            "2603050000",
+        ]);
+        let set = |id: &str, keywords: &[&str], archived: bool| {
+            let mut note = idx.notes.get_mut(id).unwrap();
+            note.keywords = keywords.iter().map(|k| k.to_string()).collect();
+            note.archived = archived;
+            add_to_lookup(&idx.keywords, id, &note.keywords);
+        };
+        set("2603010000", &["rust", "lsp", "typst"], false);
+        set("2603020000", &["Rust"], false);
+        set("2603030000", &["rust", "typst"], false);
+        set("2603040000", &["rust", "lsp", "typst"], true);
+        set("2603050000", &["python"], false);
+
+        let ids = |limit| -> Vec<(String, usize)> {
+            idx.see_also("2603010000", limit)
+                .into_iter()
+                .map(|(n, shared)| (n.id, shared))
+                .collect()
+        };
+        assert_eq!(
+            ids(3),
+            vec![("2603030000".into(), 2), ("2603020000".into(), 1)]
+        );
+        assert_eq!(ids(1), vec![("2603030000".into(), 2)]);
+        assert!(idx.see_also("2603050000", 3).is_empty());
+        assert!(idx.see_also("2603990000", 3).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lookup_removal_uses_header_id() {
+        let tmp = std::env::temp_dir().join("zk_index_test_lookup_header_id");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        // The file's stem and its title label disagree.
+        let path = tmp.join("note/2603050000.typ");
+        let write = |keyword: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+                     keywords = [\"{keyword}\"]\n  ```.text,\n))\n= {keyword} <2603059999>\n"
+                ),
+            )
+            .unwrap();
+        };
+        write("Solo");
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone()))));
+        idx.rebuild_full().await.unwrap();
+        let before = idx.keywords.get("solo").map(|ids| ids.clone());
+
+        write("Other");
+        idx.update_file(&path).await.unwrap();
+        let keywords: Vec<String> = idx.keywords.iter().map(|e| e.key().clone()).collect();
+        let titles: Vec<String> = idx.titles.iter().map(|e| e.key().clone()).collect();
+        idx.remove_by_path(&path).await;
+        let removed = (idx.keywords.len(), idx.titles.len());
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(before, Some(vec!["2603059999".to_string()]));
+        assert_eq!(keywords, vec!["other".to_string()]);
+        assert_eq!(titles, vec!["other".to_string()]);
+        assert_eq!(removed, (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_keyword_index_follows_updates_and_removal() {
+        let tmp = std::env::temp_dir().join("zk_index_test_keywords");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let write = |id: &str, keywords: &str| {
+            let path = tmp.join(format!("note/{id}.typ"));
+            std::fs::write(
+                &path,
+                format!(
+                    "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+                     keywords = [{keywords}]\n  ```.text,\n))\n= Note <{id}>\n"
+                ),
+            )
+            .unwrap();
+            path
+        };
+        write("2603010000", r#""Rust", "lsp""#);
+        let b = write("2603020000", r#""rust""#);
+        let c = write("2603030000", r#""rust", "LSP""#);
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone()))));
+        idx.rebuild_full().await.unwrap();
+        let related = |id: &str| -> Vec<(String, Vec<String>)> {
+            idx.notes_sharing_keywords(id)
+                .into_iter()
+                .map(|(n, shared)| (n.id, shared))
+                .collect()
+        };
+        let before = related("2603010000");
+        let rust_before = idx.keywords.get("rust").map(|ids| ids.clone());
+
+        // B drops "rust"; C is deleted.
+        write("2603020000", r#""typst""#);
+        idx.update_file(&b).await.unwrap();
+        std::fs::remove_file(&c).unwrap();
+        idx.remove_by_path(&c).await;
+        let after = related("2603010000");
+        let rust_after = idx.keywords.get("rust").map(|ids| ids.clone());
+        let lsp_after = idx.keywords.get("lsp").map(|ids| ids.clone());
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(
+            before,
+            vec![
+                ("2603030000".into(), vec!["rust".into(), "lsp".into()]),
+                ("2603020000".into(), vec!["rust".into()]),
+            ]
+        );
+        assert_eq!(
+            rust_before,
+            Some(vec![
+                "2603010000".into(),
+                "2603020000".into(),
+                "2603030000".into()
+            ])
+        );
+        assert!(after.is_empty());
+        assert_eq!(rust_after, Some(vec!["2603010000".into()]));
+        assert_eq!(lsp_after, Some(vec!["2603010000".into()]));
+    }
+
+    fn add_ref(idx: &NoteIndex, target: &str, from: &str) {
+        idx.backlinks
+            .entry(target.to_string())
+            .or_default()
+            .push(BacklinkLocation {
+                file: PathBuf::from(format!("/tmp/note/{from}.typ")),
+                line: 0,
+                start_char: 0,
+                end_char: 11,
+                start_byte: 0,
+                end_byte: 11,
+            });
+    }
+
+    #[test]
+    fn test_backlinks_by_source_groups_per_file() {
+        let idx = make_index(&["2603010000", "2603020000", "2603030000"]);
+        let at = |from: &str, line: u32, start_char: u32| BacklinkLocation {
+            file: PathBuf::from(format!("/tmp/note/{from}.typ")),
+            line,
+            start_char,
+            end_char: start_char + 11,
+            start_byte: 0,
+            end_byte: 11,
+        };
+        idx.backlinks.insert(
+            "2603010000".into(),
+            vec![
+                at("2603030000", 4, 0),
+                at("2603020000", 9, 2),
+                at("2603030000", 1, 6),
+                at("drafts", 0, 0),
+            ],
+        );
+
+        let groups = idx.backlinks_by_source("2603010000");
+        let summary: Vec<(&str, &str, Vec<u32>)> = groups
+            .iter()
+            .map(|g| {
+                (
+                    g.source_id.as_str(),
+                    g.source_title.as_str(),
+                    g.locations.iter().map(|l| l.line).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("2603020000", "Note 2603020000", vec![9]),
+                ("2603030000", "Note 2603030000", vec![1, 4]),
+                ("drafts", "drafts", vec![0]),
+            ]
+        );
+        assert!(idx.backlinks_by_source("2603020000").is_empty());
+    }
+
+    #[test]
+    fn test_hub_notes_count_distinct_linking_notes() {
+        let idx = make_index(&["2603010000", "2603020000", "2603030000", "2603040000"]);
+        // 01 is cited three times by one note and once by itself: one linker.
+        for _ in 0..3 {
+            add_ref(&idx, "2603010000", "2603040000");
+        }
+        add_ref(&idx, "2603010000", "2603010000");
+        add_ref(&idx, "2603020000", "2603030000");
+        add_ref(&idx, "2603020000", "2603040000");
+        add_ref(&idx, "2603030000", "2603040000");
+
+        let hubs: Vec<(String, usize)> = idx
+            .hub_notes(10)
+            .into_iter()
+            .map(|(n, c)| (n.id, c))
+            .collect();
+        assert_eq!(
+            hubs,
+            vec![
+                ("2603020000".to_string(), 2),
+                ("2603010000".to_string(), 1),
+                ("2603030000".to_string(), 1),
+            ]
+        );
+        assert_eq!(idx.hub_notes(1).len(), 1);
+    }
+
+    #[test]
+    fn test_referencing_files_skip_self_and_duplicates() {
+        let idx = make_index(&["2603010000", "2603020000", "2603030000"]);
+        add_ref(&idx, "2603010000", "2603030000");
+        add_ref(&idx, "2603010000", "2603010000");
+        add_ref(&idx, "2603010000", "2603020000");
+        add_ref(&idx, "2603010000", "2603030000");
+
+        assert_eq!(
+            idx.referencing_files("2603010000"),
+            vec![
+                PathBuf::from("/tmp/note/2603020000.typ"),
+                PathBuf::from("/tmp/note/2603030000.typ"),
+            ]
+        );
+        assert!(idx.referencing_files("2603020000").is_empty());
+    }
+
+    #[test]
+    fn test_leaf_notes_ignore_outlinks_and_self_links() {
+        let idx = make_index(&["2603010000", "2603020000", "2603030000", "2603040000"]);
+        idx.notes.get_mut("2603040000").unwrap().archived = true;
+        add_ref(&idx, "2603020000", "2603010000");
+        add_ref(&idx, "2603030000", "2603030000");
+
+        let ids = |leaves: Vec<NoteInfo>| leaves.into_iter().map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(ids(idx.leaf_notes(false)), vec!["2603010000", "2603030000"]);
+        assert_eq!(
+            ids(idx.leaf_notes(true)),
+            vec!["2603010000", "2603030000", "2603040000"]
+        );
+    }
+
+    #[test]
+    fn test_path_if_exists_finds_unindexed_files() {
+        let tmp = std::env::temp_dir().join("zk_index_test_path_if_exists");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let headerless = tmp.join("note/2603150000.typ");
+        std::fs::write(&headerless, "just text, no header\n").unwrap();
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone()))));
+
+        let found = idx.path_if_exists("2603150000");
+        let missing = idx.path_if_exists("2603150001");
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert!(!idx.contains("2603150000"));
+        assert_eq!(found, Some(headerless));
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn test_path_from_global_and_relative() {
+        let tmp = std::env::temp_dir().join("zk_index_test_path_from");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note/physics/mechanics")).unwrap();
+        let top = tmp.join("note/2603150000.typ");
+        let local = tmp.join("note/physics/2603150000.typ");
+        let from = tmp.join("note/physics/mechanics/2603150001.typ");
+        for path in [&top, &local, &from] {
+            std::fs::write(path, "").unwrap();
+        }
+        let mut config = WikiConfig::from_root(tmp.clone());
+        let idx = NoteIndex::new(Arc::new(RwLock::new(config.clone())));
+        let global = idx.path_from("2603150000", &from);
+
+        config.zk_config.reference_resolution = Some(ReferenceResolution::Relative);
+        *idx.config.try_write().unwrap() = config;
+        let relative = idx.path_from("2603150000", &from);
+        let outside = idx.path_from("2603150000", &tmp.join("elsewhere/2603150001.typ"));
+        let fallback = idx.path_from("2603150000", &top);
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(global, Some(top.clone()));
+        assert_eq!(relative, Some(local));
+        assert_eq!(outside, Some(top.clone()));
+        assert_eq!(fallback, Some(top));
+    }
+
+    #[test]
+    fn test_path_of_is_absolute() {
+        let idx = make_index(&["2603150000"]);
+        idx.notes.alter("2603150000", |_, mut info| {
+            info.path = PathBuf::from("wiki/note/2603150000.typ");
+            info
+        });
+        let path = idx.path_of("2603150000").unwrap();
+        assert!(path.is_absolute());
+        assert!(path.ends_with("wiki/note/2603150000.typ"));
+        assert_eq!(idx.path_of("2603159999"), None);
+    }
+
+    #[test]
+    fn test_duplicate_titles() {
+        let idx = make_index(&[
+            "2603010000",
+            "2603020000",
+            "2603030000",
+            "2603040000",
+            "2603050000",
+            "2603060000",
+        ]);
+        let titles = [
+            ("2603010000", "Rust  Ownership"),
+            ("2603020000", " rust ownership"),
+            ("2603030000", "Rust ownerships"),
+            ("2603040000", "Borrowing"),
+            ("2603050000", "借用检查"),
+            ("2603060000", "借用检查器"),
+        ];
+        for (id, title) in titles {
+            idx.notes.alter(id, |_, mut info| {
+                info.title = title.into();
+                info
+            });
+        }
+        let cancel = CancelToken::new();
+        let ids = |groups: Vec<Vec<NoteInfo>>| -> Vec<Vec<String>> {
+            groups
+                .into_iter()
+                .map(|g| g.into_iter().map(|n| n.id).collect())
+                .collect()
+        };
+
+        assert_eq!(
+            ids(idx.duplicate_titles(false, 0, &cancel)),
+            vec![vec!["2603010000", "2603020000"]]
+        );
+        // One character edit also joins the plural and the CJK pair.
+        assert_eq!(
+            ids(idx.duplicate_titles(false, 1, &cancel)),
+            vec![
+                vec!["2603010000", "2603020000", "2603030000"],
+                vec!["2603050000", "2603060000"],
+            ]
+        );
+
+        idx.notes.alter("2603020000", |_, mut info| {
+            info.archived = true;
+            info
+        });
+        assert!(idx.duplicate_titles(false, 0, &cancel).is_empty());
+        assert_eq!(idx.duplicate_titles(true, 0, &cancel).len(), 1);
+    }
+
+    #[test]
+    fn test_search_can_exclude_generated() {
+        let idx = make_index(&["2603150000", "2603150001"]);
+        idx.notes.alter("2603150001", |_, mut info| {
+            info.generated = true;
+            info
+        });
+        let cancel = CancelToken::new();
+        assert_eq!(idx.search("note", false, &cancel).len(), 2);
+        let hits = idx.search("note", true, &cancel);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "2603150000");
+    }
+
+    #[test]
+    fn test_search_ranks_title_matches_first() {
+        let idx = make_index(&["2603150000", "2603150001", "2603150002"]);
+        idx.notes.alter("2603150000", |_, mut info| {
+            info.title = "Rust".into();
+            info
+        });
+        idx.notes.alter("2603150001", |_, mut info| {
+            info.title = "Borrowing".into();
+            info.keywords = vec!["rust".into()];
+            info
+        });
+        idx.notes.alter("2603150002", |_, mut info| {
+            info.title = "Rust traits".into();
+            info
+        });
+        let hits: Vec<String> = idx
+            .search("rust", false, &CancelToken::new())
+            .into_iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(hits, vec!["2603150000", "2603150002", "2603150001"]);
+    }
+
+    #[test]
+    fn test_ids_and_contains() {
+        let idx = make_index(&["2603150002", "2603150000", "2603150001"]);
+        assert_eq!(idx.ids(), vec!["2603150000", "2603150001", "2603150002"]);
+        assert!(idx.contains("2603150001"));
+        assert!(!idx.contains("2603159999"));
+    }
+
+    #[test]
+    fn test_by_tag_and_tag_counts() {
+        let idx = make_index(&["2603010000", "2603020000", "2603030000"]);
+        let tag = |id: &str, tags: &[&str]| {
+            idx.notes.get_mut(id).unwrap().tags = tags.iter().map(|t| t.to_string()).collect();
+        };
+        tag("2603010000", &["tag.project", "topic.rust"]);
+        tag("2603020000", &["topic.rust"]);
+        tag("2603030000", &["tag.project", "topic.rust"]);
+
+        let ids = |notes: Vec<NoteInfo>| notes.into_iter().map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(ids(idx.by_tag("project")), vec!["2603010000", "2603030000"]);
+        assert_eq!(
+            ids(idx.by_tag("#tag.project")),
+            ids(idx.by_tag("tag.project"))
+        );
+        assert_eq!(idx.by_tag("topic.rust").len(), 3);
+        assert!(idx.by_tag("rust").is_empty());
+        assert_eq!(
+            idx.tag_counts(),
+            vec![
+                ("topic.rust".to_string(), 3),
+                ("tag.project".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keyword_counts_merge_case_variants() {
+        let idx = make_index(&["2603010000", "2603020000", "2603030000"]);
+        let keywords = |id: &str, keywords: &[&str]| {
+            idx.notes.get_mut(id).unwrap().keywords =
+                keywords.iter().map(|k| k.to_string()).collect();
+        };
+        keywords("2603010000", &["Rust", "lsp", "rust"]);
+        keywords("2603020000", &["Rust"]);
+        keywords("2603030000", &["rust", "typst"]);
+        assert_eq!(
+            idx.keyword_counts(),
+            vec![
+                ("Rust".to_string(), 3),
+                ("lsp".to_string(), 1),
+                ("typst".to_string(), 1)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_self_references_skipped_unless_counted() {
+        let tmp = std::env::temp_dir().join("zk_index_test_self_refs");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let path = tmp.join("note/2603150000.typ");
+        std::fs::write(
+            &path,
+            "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n\
+             = Loop <2603150000>\nAs said in @2603150000, see @2603150001.\n",
+        )
+        .unwrap();
+        let config = WikiConfig::from_root(tmp.clone());
+        let idx = NoteIndex::new(Arc::new(RwLock::new(config.clone())));
+        idx.rebuild_full().await.unwrap();
+        let skipped = idx.get_backlinks("2603150000").len();
+        let other = idx.get_backlinks("2603150001").len();
+
+        let counting = config.with_settings(crate::config::ClientSettings::from_json(
+            &serde_json::json!({"countSelfReferences": true}),
+        ));
+        *idx.config.write().await = counting;
+        idx.update_file(&path).await.unwrap();
+        let counted = idx.get_backlinks("2603150000").len();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(skipped, 0);
+        assert_eq!(other, 1);
+        assert_eq!(counted, 1);
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_reports_progress_per_file() {
+        let tmp = std::env::temp_dir().join("zk_index_test_progress");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        for id in ["2603150000", "2603150001"] {
+            let note = format!(
+                "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n= Title <{id}>\n"
+            );
+            std::fs::write(tmp.join(format!("note/{id}.typ")), note).unwrap();
+        }
+        // Read and counted, though its header does not parse.
+        std::fs::write(tmp.join("note/2603150002.typ"), "no header\n").unwrap();
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone()))));
+
+        let calls = Mutex::new(Vec::new());
+        let n = idx
+            .rebuild_full_with_progress(|done, total| calls.lock().unwrap().push((done, total)))
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(n, 2);
+        assert_eq!(calls.into_inner().unwrap(), vec![(1, 3), (2, 3), (3, 3)]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_follow_symlinks_indexes_linked_notes_under_wiki_path() {
+        use std::os::unix::fs::symlink;
+        let tmp = std::env::temp_dir().join("zk_index_test_symlinks");
+        let _ = std::fs::remove_dir_all(&tmp);
+        let shared = tmp.join("shared");
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::create_dir_all(shared.join("2026/03")).unwrap();
+        let note = |id: &str, body: &str| {
+            format!(
+                "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n= Title <{id}>\n{body}\n"
+            )
+        };
+        std::fs::write(tmp.join("note/2603150000.typ"), note("2603150000", "")).unwrap();
+        // A note file linked in from elsewhere.
+        let real = shared.join("2603150001.typ");
+        std::fs::write(&real, note("2603150001", "See @2603150000.")).unwrap();
+        let link = tmp.join("note/2603150001.typ");
+        symlink(&real, &link).unwrap();
+        // A linked date directory, with a link back to itself inside.
+        std::fs::write(
+            shared.join("2026/03/2603150002.typ"),
+            note("2603150002", "Also @2603150000."),
+        )
+        .unwrap();
+        symlink(shared.join("2026"), tmp.join("note/2026")).unwrap();
+        symlink(shared.join("2026"), shared.join("2026/03/12")).unwrap();
+
+        let config = Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone())));
+        let idx = NoteIndex::new(config.clone());
+        // Off: linked files are read as before, linked directories skipped.
+        assert_eq!(idx.rebuild_full().await.unwrap(), 2);
+
+        config.write().await.zk_config.follow_symlinks = true;
+        assert_eq!(idx.rebuild_full().await.unwrap(), 3);
+        // An edit through the real path lands on the in-wiki path, replacing
+        // the old backlink rather than adding a second one.
+        std::fs::write(&real, note("2603150001", "Still @2603150000.")).unwrap();
+        idx.update_file(&real).await.unwrap();
+        let files: Vec<PathBuf> = idx
+            .backlinks
+            .get("2603150000")
+            .unwrap()
+            .iter()
+            .map(|loc| loc.file.clone())
+            .collect();
+        assert_eq!(files.iter().filter(|f| **f == link).count(), 1);
+        assert!(!files.contains(&real));
+        assert_eq!(idx.get("2603150001").unwrap().path, link);
+        idx.remove_by_path(&real).await;
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert!(!idx.contains("2603150001"));
+        assert_eq!(idx.backlinks.get("2603150000").unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_unaffected_by_later_update() {
+        let tmp = std::env::temp_dir().join("zk_index_test_snapshot");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let note = |id: &str, title: &str, body: &str| {
+            format!(
+                "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n= {title} <{id}>\n{body}\n"
+            )
+        };
+        let a = tmp.join("note/2603150000.typ");
+        std::fs::write(&a, note("2603150000", "A", "See @2603150001.")).unwrap();
+        std::fs::write(tmp.join("note/2603150001.typ"), note("2603150001", "B", "")).unwrap();
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone()))));
+        idx.rebuild_full().await.unwrap();
+
+        let snap = idx.snapshot();
+        std::fs::write(&a, note("2603150000", "A renamed", "No links now.")).unwrap();
+        idx.update_file(&a).await.unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(snap.get("2603150000").unwrap().title, "A");
+        assert_eq!(snap.backlinks["2603150001"].len(), 1);
+        assert!(snap.links["2603150000"].outlinks.contains("2603150001"));
+        // The live index moved on.
+        assert_eq!(idx.get("2603150000").unwrap().title, "A renamed");
+        assert!(idx.get_backlinks("2603150001").is_empty());
+        assert!(idx.link_graph()["2603150000"].outlinks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lossy_reads_index_invalid_utf8_notes() {
+        let tmp = std::env::temp_dir().join("zk_index_test_lossy_reads");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let path = tmp.join("note/2603150000.typ");
+        let mut bytes = b"#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n= Caf\xe9 <2603150000>\nSee @2603150001.\n".to_vec();
+        bytes.push(0xff);
+        std::fs::write(&path, bytes).unwrap();
+        let index = |lossy: bool| {
+            let mut config = WikiConfig::from_root(tmp.clone());
+            config.zk_config.lossy_reads = lossy;
+            NoteIndex::new(Arc::new(RwLock::new(config)))
+        };
+
+        let strict = index(false);
+        strict.rebuild_full().await.unwrap();
+        let update_error = strict.update_file(&path).await.unwrap_err();
+        let lossy = index(true);
+        lossy.rebuild_full().await.unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert!(strict.get("2603150000").is_none());
+        assert_eq!(
+            update_error
+                .downcast_ref::<std::io::Error>()
+                .unwrap()
+                .kind(),
+            std::io::ErrorKind::InvalidData
+        );
+        assert_eq!(lossy.get("2603150000").unwrap().title, "Caf\u{fffd}");
+        assert_eq!(lossy.get_backlinks("2603150001").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_notes_over_max_note_bytes_skipped_with_warning() {
+        #[derive(Clone, Default)]
+        struct Log(Arc<Mutex<Vec<u8>>>);
+        impl std::io::Write for Log {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let tmp = std::env::temp_dir().join("zk_index_test_max_note_bytes");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let note = |id: &str, body: &str| {
+            format!(
+                "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n= Note <{id}>\n{body}\n"
+            )
+        };
+        std::fs::write(tmp.join("note/2603150000.typ"), note("2603150000", "")).unwrap();
+        let big = tmp.join("note/2603150001.typ");
+        std::fs::write(&big, note("2603150001", &"x".repeat(500))).unwrap();
+        std::fs::write(tmp.join("zk-lsp.toml"), "max_note_bytes = 200\n").unwrap();
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone()))));
+
+        let log = Log::default();
+        let writer = log.clone();
+        let _subscriber = tracing::subscriber::set_default(
+            tracing_subscriber::fmt()
+                .with_writer(move || writer.clone())
+                .with_ansi(false)
+                .finish(),
+        );
+        idx.rebuild_full().await.unwrap();
+        let update = idx.update_file(&big).await;
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert!(idx.get("2603150000").is_some());
+        assert!(idx.get("2603150001").is_none());
+        assert!(update.is_err());
+        let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        assert!(log.contains("WARN"), "{log}");
+        assert!(
+            log.contains(&format!(
+                "{} is {} bytes, over max_note_bytes (200); skipped",
+                big.display(),
+                note("2603150001", &"x".repeat(500)).len()
+            )),
+            "{log}"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_updates_of_one_file_are_serialized() {
+        let tmp = std::env::temp_dir().join("zk_index_test_concurrent_update");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let path = tmp.join("note/2603150000.typ");
+        std::fs::write(
+            &path,
+            "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n= A <2603150000>\nSee @2603150001 and @2603150002.\n",
+        )
+        .unwrap();
+        let idx = Arc::new(NoteIndex::new(Arc::new(RwLock::new(
+            WikiConfig::from_root(tmp.clone()),
+        ))));
+        let mut rx = idx.subscribe();
+
+        for _ in 0..20 {
+            let tasks: Vec<_> = (0..16)
+                .map(|_| {
+                    let idx = Arc::clone(&idx);
+                    let path = path.clone();
+                    tokio::spawn(async move { idx.update_file(&path).await })
+                })
+                .collect();
+            for task in tasks {
+                task.await.unwrap().unwrap();
+            }
+            assert_eq!(idx.get_backlinks("2603150001").len(), 1);
+            assert_eq!(idx.get_backlinks("2603150002").len(), 1);
+        }
+        // Every update finished, so none of their locks is kept.
+        assert!(idx.updating.is_empty());
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        // Only the first update saw the note as new.
+        let mut added = 0;
+        while let Ok(change) = rx.try_recv() {
+            assert_eq!(change.kind, IndexChangeKind::Added);
+            added += 1;
+        }
+        assert_eq!(added, 1);
+    }
+
+    #[tokio::test]
+    async fn test_batch_backlink_counts_match_per_id_queries() {
+        let tmp = std::env::temp_dir().join("zk_index_test_backlink_counts");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let note = |id: &str, body: &str| {
+            format!(
+                "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n= Note <{id}>\n{body}\n"
+            )
+        };
+        for (id, body) in [
+            (
+                "2603150000",
+                "See @2603150001 and @2603150001 and @2603150002.",
+            ),
+            ("2603150001", "Back to @2603150000; also @2603150002."),
+            ("2603150002", "Self @2603150002."),
+        ] {
+            std::fs::write(tmp.join(format!("note/{id}.typ")), note(id, body)).unwrap();
+        }
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone()))));
+        idx.rebuild_full().await.unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let ids: Vec<String> = ["2603150000", "2603150001", "2603150002", "2603159999"]
+            .map(String::from)
+            .to_vec();
+        let references = idx.backlink_counts(&ids);
+        let linking = idx.linking_note_counts(&ids);
+        for id in &ids {
+            assert_eq!(references[id], idx.get_backlinks(id).len(), "{id}");
+            assert_eq!(linking[id], idx.linking_notes(id), "{id}");
+        }
+        assert_eq!(references["2603150001"], 2);
+        assert_eq!(linking["2603150001"], 1);
+        assert_eq!(references["2603159999"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_update_and_remove_broadcast_changes() {
+        let tmp = std::env::temp_dir().join("zk_index_test_changes");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let path = tmp.join("note/2603150000.typ");
+        let note = |title: &str| {
+            format!(
+                "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n= {title} <2603150000>\n"
+            )
+        };
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone()))));
+        let mut rx = idx.subscribe();
+
+        std::fs::write(&path, note("First")).unwrap();
+        idx.update_file(&path).await.unwrap();
+        idx.update_file(&path).await.unwrap(); // unchanged: no event
+        std::fs::write(&path, note("Second")).unwrap();
+        idx.update_file(&path).await.unwrap();
+        idx.remove_by_path(&path).await;
+        idx.remove_by_path(&path).await; // already gone: no event
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let id = vec!["2603150000".to_string()];
+        let mut kinds = Vec::new();
+        while let Ok(change) = rx.try_recv() {
+            assert_eq!(change.ids, id);
+            kinds.push(change.kind);
+        }
+        assert_eq!(
+            kinds,
+            vec![
+                IndexChangeKind::Added,
+                IndexChangeKind::Retitled,
+                IndexChangeKind::Removed
+            ]
+        );
+    }
+
+    #[test]
+    fn test_closest_ids_within_distance() {
+        let idx = make_index(&["2603150930", "2603150939", "2603151039", "2511010000"]);
+        // Swapped "93" → "39": every 26031 candidate is two edits away (ties
+        // by ID); 2511010000 is too far.
+        assert_eq!(
+            idx.closest_ids("2603150390", 5),
+            vec!["2603150930", "2603150939", "2603151039"]
+        );
+        // One typo: the single-edit neighbour wins over two-edit ones.
+        assert_eq!(idx.closest_ids("2603150949", 1), vec!["2603150939"]);
+        assert!(idx.closest_ids("1999999999", 3).is_empty());
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance_within("kitten", "sitting", 3), Some(3));
+        assert_eq!(edit_distance_within("kitten", "sitting", 2), None);
+        assert_eq!(edit_distance_within("2603150390", "9999999999", 2), None);
+    }
+
+    #[test]
+    fn test_created_between_filters_and_sorts() {
+        let idx = make_index(&["2603120800", "2603100900", "2603110930", "2604010000"]);
+        let start = parser::parse_date_bound("2026-03-10", false).unwrap();
+        let end = parser::parse_date_bound("2026-03-12", true).unwrap();
+        let ids: Vec<String> = idx
+            .created_between(start, end)
+            .into_iter()
+            .map(|(_, n)| n.id)
+            .collect();
+        assert_eq!(ids, vec!["2603100900", "2603110930", "2603120800"]);
+    }
+
+    #[test]
+    fn test_created_between_skips_invalid_timestamps() {
+        // Month 13 is a valid ID shape but not a valid date.
+        let idx = make_index(&["2613010000", "2603110930"]);
+        let start = parser::parse_date_bound("2000-01-01", false).unwrap();
+        let end = parser::parse_date_bound("2099-12-31", true).unwrap();
+        let found = idx.created_between(start, end);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1.id, "2603110930");
+    }
+}
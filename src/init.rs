@@ -10,6 +10,8 @@ use crate::note_ops;
 /// Provides only what zk-lsp itself requires:
 ///   - `zettel`    — render function called by every note
 ///   - `zk_entry` — called by `link.typ` to include each note
+///   - `zk_alias` — called by `link.typ` for each alias of a note when
+///     `include_aliases_in_link_typ` is on
 ///
 /// Extend this file freely with your own imports and styling.
 const INCLUDE_TYP: &str = r#"// zk-lsp include — extend with your own imports and styling
@@ -23,6 +25,10 @@ const INCLUDE_TYP: &str = r#"// zk-lsp include — extend with your own imports
 #let zk_entry(id, path) = {
   include path
 }
+
+/// One alias of note `id`, listed by link.typ when
+/// `include_aliases_in_link_typ` is on. Define it to build alias lookups.
+#let zk_alias(alias, id) = none
 "#;
 
 /// Human-editable entry point for the compiled wiki document.
@@ -1,51 +1,152 @@
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde::Serialize;
 use tokio::fs;
+use tokio::sync::Mutex;
 
 use crate::config::WikiConfig;
+use crate::{note_ops, parser};
 
 const HEADER: &str = "#import \"include.typ\": *\n// Auto-generated by zk-lsp — do not edit manually.\n// Run `zk-lsp generate` to rebuild.\n\n";
 
+/// Serializes every read-modify-write of link.typ in this process, so the
+/// watcher, `zk.generateLinkTyp` and note creation/deletion cannot interleave
+/// writes (they share one tmp file) or lose each other's updates.
+static LINK_TYP_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// How the on-disk link.typ differs from what `generate_link_typ` would write.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkTypReport {
+    /// The file exists and matches the expected content byte for byte.
+    pub up_to_date: bool,
+    pub exists: bool,
+    /// Notes with no entry in link.typ, sorted.
+    pub missing: Vec<String>,
+    /// Entries in link.typ without a (non-ignored) note, sorted.
+    pub extra: Vec<String>,
+}
+
+/// What `normalize_link_typ` changed, counted per `#zk_entry` line.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizeReport {
+    /// link.typ was rewritten (it differed from `compute_link_typ`).
+    pub changed: bool,
+    /// Notes that had no entry.
+    pub added: usize,
+    /// Entries without a (non-ignored) note, and duplicate entries.
+    pub removed: usize,
+    /// Kept entries that had to move to restore ID order.
+    pub reordered: usize,
+    /// Kept entries whose note path changed (e.g. moved to a date subdirectory).
+    pub repathed: usize,
+}
+
 /// Regenerate link.typ from scratch by scanning note_dir.
 pub async fn generate_link_typ(config: &WikiConfig) -> Result<()> {
-    let mut ids = collect_note_ids(&config.note_dir).await?;
-    ids.sort();
-    write_link_typ(&config.link_file, &ids).await
+    let _guard = LINK_TYP_LOCK.lock().await;
+    regenerate(config).await
 }
 
-/// Append a single entry to link.typ (used when a new note is created).
+/// The link.typ content `generate_link_typ` would write, without writing it.
+pub async fn compute_link_typ(config: &WikiConfig) -> Result<String> {
+    let mut notes = collect_notes(config).await?;
+    notes.sort();
+    let aliases = if config.zk_config.include_aliases_in_link_typ {
+        collect_aliases(config, &notes).await
+    } else {
+        HashMap::new()
+    };
+    Ok(render_link_typ(config, &notes, &aliases))
+}
+
+/// Compare the on-disk link.typ with `compute_link_typ`.
+///
+/// `missing` / `extra` list IDs by `#zk_entry` line; a stale file can also
+/// differ only in order, header or note-dir path, with both lists empty.
+pub async fn verify_link_typ(config: &WikiConfig) -> Result<LinkTypReport> {
+    let expected = compute_link_typ(config).await?;
+    let actual = match fs::read_to_string(&config.link_file).await {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).with_context(|| format!("reading {}", config.link_file.display())),
+    };
+    let expected_ids = entry_ids(&expected);
+    let actual_ids = entry_ids(actual.as_deref().unwrap_or(""));
+    let diff = |a: &[String], b: &[String]| -> Vec<String> {
+        let mut ids: Vec<String> = a.iter().filter(|id| !b.contains(id)).cloned().collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    };
+    Ok(LinkTypReport {
+        up_to_date: actual.as_deref() == Some(expected.as_str()),
+        exists: actual.is_some(),
+        missing: diff(&expected_ids, &actual_ids),
+        extra: diff(&actual_ids, &expected_ids),
+    })
+}
+
+/// Rewrite link.typ as `compute_link_typ` would and report what that changed.
+/// The file is left alone when it is already up to date.
+pub async fn normalize_link_typ(config: &WikiConfig) -> Result<NormalizeReport> {
+    let _guard = LINK_TYP_LOCK.lock().await;
+    let expected = compute_link_typ(config).await?;
+    let actual = match fs::read_to_string(&config.link_file).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", config.link_file.display())),
+    };
+    let report = compare_entries(&actual, &expected);
+    if actual != expected {
+        atomic_write(&config.link_file, &expected).await?;
+    }
+    Ok(NormalizeReport {
+        changed: actual != expected,
+        ..report
+    })
+}
+
+/// Append a single entry to link.typ (used when a note is created or saved).
+///
+/// With `include_aliases_in_link_typ`, an existing entry whose `#zk_alias`
+/// lines no longer match the note's `aliases` is rewritten too.
 pub async fn add_entry(id: &str, config: &WikiConfig) -> Result<()> {
+    let _guard = LINK_TYP_LOCK.lock().await;
     let link_file = &config.link_file;
     if !link_file.exists() {
         // Bootstrap from scratch
-        return generate_link_typ(config).await;
+        return regenerate(config).await;
     }
     let content = fs::read_to_string(link_file).await?;
-    let entry = format_entry(id);
-    if content.contains(&entry) {
+    if entry_ids(&content).iter().any(|entry| entry == id)
+        && aliases_current(id, &content, config).await
+    {
         return Ok(());
     }
     // Rebuild sorted to keep deterministic order
-    let mut ids = collect_note_ids(&config.note_dir).await?;
-    ids.sort();
-    write_link_typ(link_file, &ids).await
+    regenerate(config).await
 }
 
 /// Remove an entry from link.typ (used when a note is deleted).
 pub async fn remove_entry(id: &str, config: &WikiConfig) -> Result<()> {
+    let _guard = LINK_TYP_LOCK.lock().await;
     let link_file = &config.link_file;
     if !link_file.exists() {
         return Ok(());
     }
     let content = fs::read_to_string(link_file).await?;
-    let entry = format_entry(id);
-    if !content.contains(&entry) {
+    let of_note = |l: &str| entry_id(l) == Some(id) || alias_id(l) == Some(id);
+    if !content.lines().any(of_note) {
         return Ok(());
     }
     let new_content: String = content
         .lines()
-        .filter(|l| *l != entry.trim_end_matches('\n'))
+        .filter(|l| !of_note(l))
         .collect::<Vec<_>>()
         .join("\n")
         + "\n";
@@ -56,35 +157,177 @@ pub async fn remove_entry(id: &str, config: &WikiConfig) -> Result<()> {
 // Helpers
 // ---------------------------------------------------------------------------
 
-async fn collect_note_ids(note_dir: &Path) -> Result<Vec<String>> {
-    let mut ids = Vec::new();
-    let mut entries = fs::read_dir(note_dir)
+/// `generate_link_typ` for callers already holding `LINK_TYP_LOCK`.
+async fn regenerate(config: &WikiConfig) -> Result<()> {
+    let content = compute_link_typ(config).await?;
+    atomic_write(&config.link_file, &content).await
+}
+
+/// `(id, path)` of every non-ignored note, flat or in date subdirectories.
+async fn collect_notes(config: &WikiConfig) -> Result<Vec<(String, PathBuf)>> {
+    let note_dir = &config.note_dir;
+    let mut notes = Vec::new();
+    let files = note_ops::wiki_note_files(config)
         .await
         .with_context(|| format!("reading note dir {}", note_dir.display()))?;
-    while let Some(entry) = entries.next_entry().await? {
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) != Some("typ") {
+    for path in files {
+        if config.is_ignored(&path) {
             continue;
         }
-        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-            if stem.len() == 10 && stem.chars().all(|c| c.is_ascii_digit()) {
-                ids.push(stem.to_string());
+        if let Some(id) = parser::note_id_from_path(&path) {
+            notes.push((id.to_string(), path.clone()));
+        }
+    }
+    Ok(notes)
+}
+
+/// Aliases of each note in `notes` that has any, read from its header.
+/// Unreadable files and files without a header are skipped.
+async fn collect_aliases(
+    config: &WikiConfig,
+    notes: &[(String, PathBuf)],
+) -> HashMap<String, Vec<String>> {
+    let parser = config.parser();
+    let mut aliases = HashMap::new();
+    for (id, path) in notes {
+        let Ok(content) = fs::read_to_string(path).await else {
+            continue;
+        };
+        if let Some(header) = parser.parse_header(&content) {
+            if !header.aliases.is_empty() {
+                aliases.insert(id.clone(), header.aliases);
             }
         }
     }
-    Ok(ids)
+    aliases
 }
 
-fn format_entry(id: &str) -> String {
-    format!("#zk_entry(\"{id}\", \"note/{id}.typ\")\n")
+/// Whether the `#zk_alias` lines for `id` in link.typ `content` are the ones
+/// its note's `aliases` produce. Always true without
+/// `include_aliases_in_link_typ`.
+async fn aliases_current(id: &str, content: &str, config: &WikiConfig) -> bool {
+    if !config.zk_config.include_aliases_in_link_typ {
+        return true;
+    }
+    let Some(path) = config.locate_note(id) else {
+        return true;
+    };
+    let aliases = collect_aliases(config, &[(id.to_string(), path)]).await;
+    let expected: String = aliases
+        .get(id)
+        .into_iter()
+        .flatten()
+        .map(|alias| format_alias(alias, id))
+        .collect();
+    let actual: String = content
+        .lines()
+        .filter(|l| alias_id(l) == Some(id))
+        .map(|l| format!("{l}\n"))
+        .collect();
+    actual == expected
+}
+
+fn format_entry(id: &str, path: &Path, config: &WikiConfig) -> String {
+    // link.typ lives at the wiki root, so entries use root-relative paths.
+    let rel = path.strip_prefix(&config.root).unwrap_or(path);
+    format!("#zk_entry(\"{id}\", \"{}\")\n", rel.display())
+}
+
+/// `#zk_alias("<alias>", "<ID>")`, with `"` and `\` escaped for Typst.
+fn format_alias(alias: &str, id: &str) -> String {
+    let escaped = alias.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("#zk_alias(\"{escaped}\", \"{id}\")\n")
 }
 
-async fn write_link_typ(link_file: &Path, ids: &[String]) -> Result<()> {
+/// One `#zk_entry` line per note, in `notes` order, each followed by the
+/// note's `#zk_alias` lines from `aliases`.
+fn render_link_typ(
+    config: &WikiConfig,
+    notes: &[(String, PathBuf)],
+    aliases: &HashMap<String, Vec<String>>,
+) -> String {
     let mut content = HEADER.to_string();
-    for id in ids {
-        content.push_str(&format_entry(id));
+    for (id, path) in notes {
+        content.push_str(&format_entry(id, path, config));
+        for alias in aliases.get(id).into_iter().flatten() {
+            content.push_str(&format_alias(alias, id));
+        }
+    }
+    content
+}
+
+/// ID of a `#zk_entry("<ID>", …)` line.
+fn entry_id(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix("#zk_entry(\"")
+        .and_then(|rest| rest.split('"').next())
+}
+
+/// ID of a `#zk_alias("<alias>", "<ID>")` line.
+fn alias_id(line: &str) -> Option<&str> {
+    let args = line.trim().strip_prefix("#zk_alias(")?.strip_suffix(')')?;
+    args.strip_suffix('"')?.rsplit('"').next()
+}
+
+/// `(id, path)` of a `#zk_entry("<ID>", "<path>")` line.
+fn parse_entry(line: &str) -> Option<(&str, &str)> {
+    let id = entry_id(line)?;
+    let rest = line.trim().strip_prefix("#zk_entry(\"")?[id.len()..].trim_start_matches("\",");
+    let path = rest.trim_start().strip_prefix('"')?.split('"').next()?;
+    Some((id, path))
+}
+
+/// Entry-level differences between an existing link.typ and the expected one
+/// (`changed` is left to the caller).
+fn compare_entries(actual: &str, expected: &str) -> NormalizeReport {
+    let expected: Vec<(&str, &str)> = expected.lines().filter_map(parse_entry).collect();
+    let position: HashMap<&str, usize> = expected
+        .iter()
+        .enumerate()
+        .map(|(i, (id, _))| (*id, i))
+        .collect();
+    let mut report = NormalizeReport::default();
+    let mut seen = HashSet::new();
+    // Expected positions of the kept entries, in their current order.
+    let mut kept = Vec::new();
+    for (id, path) in actual.lines().filter_map(parse_entry) {
+        match position.get(id) {
+            Some(&i) if seen.insert(id) => {
+                kept.push(i);
+                if expected[i].1 != path {
+                    report.repathed += 1;
+                }
+            }
+            _ => report.removed += 1,
+        }
+    }
+    report.added = expected.len() - kept.len();
+    report.reordered = kept.len() - longest_increasing_run(&kept);
+    report
+}
+
+/// Length of the longest increasing subsequence: the entries that can stay
+/// put while the others move.
+fn longest_increasing_run(values: &[usize]) -> usize {
+    let mut tails: Vec<usize> = Vec::new();
+    for &v in values {
+        let i = tails.partition_point(|&t| t < v);
+        if i == tails.len() {
+            tails.push(v);
+        } else {
+            tails[i] = v;
+        }
     }
-    atomic_write(link_file, &content).await
+    tails.len()
+}
+
+/// IDs of the `#zk_entry("<ID>", …)` lines in a link.typ.
+fn entry_ids(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(entry_id)
+        .map(String::from)
+        .collect()
 }
 
 async fn atomic_write(dest: &Path, content: &str) -> Result<()> {
@@ -97,3 +340,205 @@ async fn atomic_write(dest: &Path, content: &str) -> Result<()> {
         .with_context(|| format!("renaming {} -> {}", tmp.display(), dest.display()))?;
     Ok(())
 }
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_verify_link_typ_reports_drift() {
+        let tmp = std::env::temp_dir().join("zk_link_gen_test_verify");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::write(tmp.join("note/2603010000.typ"), "").unwrap();
+        std::fs::write(tmp.join("note/2603020000.typ"), "").unwrap();
+        let config = WikiConfig::from_root(tmp.clone());
+
+        let absent = verify_link_typ(&config).await.unwrap();
+        generate_link_typ(&config).await.unwrap();
+        let fresh = verify_link_typ(&config).await.unwrap();
+        std::fs::remove_file(tmp.join("note/2603020000.typ")).unwrap();
+        std::fs::write(tmp.join("note/2603030000.typ"), "").unwrap();
+        let stale = verify_link_typ(&config).await.unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert!(!absent.exists && !absent.up_to_date);
+        assert_eq!(absent.missing, vec!["2603010000", "2603020000"]);
+        assert!(fresh.up_to_date && fresh.missing.is_empty() && fresh.extra.is_empty());
+        assert!(!stale.up_to_date);
+        assert_eq!(stale.missing, vec!["2603030000"]);
+        assert_eq!(stale.extra, vec!["2603020000"]);
+    }
+
+    #[tokio::test]
+    async fn test_normalize_link_typ_counts_changes() {
+        let tmp = std::env::temp_dir().join("zk_link_gen_test_normalize");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note/2026/03")).unwrap();
+        for id in ["2603010000", "2603020000", "2603030000", "2603040000"] {
+            std::fs::write(tmp.join(format!("note/{id}.typ")), "").unwrap();
+        }
+        std::fs::write(tmp.join("note/2026/03/2603050000.typ"), "").unwrap();
+        let config = WikiConfig::from_root(tmp.clone());
+        // 04 before 01..03, 05 at its old flat path, 09 gone, 02 listed
+        // twice and 03 missing.
+        let drifted = concat!(
+            "#import \"include.typ\": *\n",
+            "#zk_entry(\"2603040000\", \"note/2603040000.typ\")\n",
+            "#zk_entry(\"2603010000\", \"note/2603010000.typ\")\n",
+            "#zk_entry(\"2603090000\", \"note/2603090000.typ\")\n",
+            "#zk_entry(\"2603020000\", \"note/2603020000.typ\")\n",
+            "#zk_entry(\"2603020000\", \"note/2603020000.typ\")\n",
+            "#zk_entry(\"2603050000\", \"note/2603050000.typ\")\n",
+        );
+        std::fs::write(&config.link_file, drifted).unwrap();
+
+        let first = normalize_link_typ(&config).await.unwrap();
+        let verified = verify_link_typ(&config).await.unwrap();
+        let second = normalize_link_typ(&config).await.unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(
+            first,
+            NormalizeReport {
+                changed: true,
+                added: 1,
+                removed: 2,
+                reordered: 1,
+                repathed: 1,
+            }
+        );
+        assert!(verified.up_to_date);
+        assert_eq!(second, NormalizeReport::default());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_add_remove_leaves_consistent_file() {
+        let tmp = std::env::temp_dir().join("zk_link_gen_test_concurrent");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let note = |i: usize| tmp.join(format!("note/26030100{i:02}.typ"));
+        for i in 0..20 {
+            std::fs::write(note(i), "").unwrap();
+        }
+        let config = std::sync::Arc::new(WikiConfig::from_root(tmp.clone()));
+        generate_link_typ(&config).await.unwrap();
+
+        // Delete the even notes and create ten new ones, then update
+        // link.typ for all of them at once.
+        let mut tasks = Vec::new();
+        for i in 0..30 {
+            if i < 20 && i % 2 == 1 {
+                continue;
+            }
+            let id = format!("26030100{i:02}");
+            let cfg = std::sync::Arc::clone(&config);
+            if i < 20 {
+                std::fs::remove_file(note(i)).unwrap();
+                tasks.push(tokio::spawn(async move { remove_entry(&id, &cfg).await }));
+            } else {
+                std::fs::write(note(i), "").unwrap();
+                tasks.push(tokio::spawn(async move { add_entry(&id, &cfg).await }));
+            }
+            let cfg = std::sync::Arc::clone(&config);
+            tasks.push(tokio::spawn(async move { generate_link_typ(&cfg).await }));
+        }
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        let report = verify_link_typ(&config).await.unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+        assert!(report.up_to_date, "{report:?}");
+    }
+
+    #[tokio::test]
+    async fn test_aliases_listed_when_enabled() {
+        let tmp = std::env::temp_dir().join("zk_link_gen_test_aliases");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::write(
+            tmp.join("note/2603010000.typ"),
+            "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  aliases = [\"Borrowing\", \"The \\\"Borrow\\\" Checker\"]\n  ```.text,\n))\n= Rust Ownership <2603010000>\n",
+        )
+        .unwrap();
+        std::fs::write(tmp.join("note/2603020000.typ"), "").unwrap();
+
+        let off = compute_link_typ(&WikiConfig::from_root(tmp.clone()))
+            .await
+            .unwrap();
+        std::fs::write(
+            tmp.join("zk-lsp.toml"),
+            "include_aliases_in_link_typ = true\n",
+        )
+        .unwrap();
+        let config = WikiConfig::from_root(tmp.clone());
+        generate_link_typ(&config).await.unwrap();
+        let on = std::fs::read_to_string(&config.link_file).unwrap();
+        let verified = verify_link_typ(&config).await.unwrap();
+        remove_entry("2603010000", &config).await.unwrap();
+        let removed = std::fs::read_to_string(&config.link_file).unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert!(!off.contains("#zk_alias"));
+        assert_eq!(
+            on.strip_prefix(HEADER).unwrap(),
+            concat!(
+                "#zk_entry(\"2603010000\", \"note/2603010000.typ\")\n",
+                "#zk_alias(\"Borrowing\", \"2603010000\")\n",
+                "#zk_alias(\"The \\\"Borrow\\\" Checker\", \"2603010000\")\n",
+                "#zk_entry(\"2603020000\", \"note/2603020000.typ\")\n",
+            )
+        );
+        assert!(verified.up_to_date);
+        assert_eq!(
+            removed.strip_prefix(HEADER).unwrap(),
+            "#zk_entry(\"2603020000\", \"note/2603020000.typ\")\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_entry_picks_up_edited_aliases() {
+        let tmp = std::env::temp_dir().join("zk_link_gen_test_alias_edits");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::write(
+            tmp.join("zk-lsp.toml"),
+            "include_aliases_in_link_typ = true\n",
+        )
+        .unwrap();
+        let note = tmp.join("note/2603010000.typ");
+        let write_note = |aliases: &str| {
+            std::fs::write(
+                &note,
+                format!(
+                    "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  aliases = [{aliases}]\n  ```.text,\n))\n= Ownership <2603010000>\n"
+                ),
+            )
+            .unwrap();
+        };
+        let config = WikiConfig::from_root(tmp.clone());
+        write_note("");
+        add_entry("2603010000", &config).await.unwrap();
+        let created = std::fs::read_to_string(&config.link_file).unwrap();
+
+        write_note("\"Borrowing\"");
+        add_entry("2603010000", &config).await.unwrap();
+        let added = std::fs::read_to_string(&config.link_file).unwrap();
+        let verified = verify_link_typ(&config).await.unwrap();
+
+        write_note("\"Moves\"");
+        add_entry("2603010000", &config).await.unwrap();
+        let edited = std::fs::read_to_string(&config.link_file).unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert!(!created.contains("#zk_alias"));
+        assert!(added.contains("#zk_alias(\"Borrowing\", \"2603010000\")\n"));
+        assert!(verified.up_to_date);
+        assert!(edited.contains("#zk_alias(\"Moves\", \"2603010000\")\n"));
+        assert!(!edited.contains("Borrowing"));
+    }
+}
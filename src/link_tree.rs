@@ -0,0 +1,152 @@
+/// Map-of-content outline: the notes reachable from a root by outgoing links,
+/// as a tree.
+///
+/// `link_tree` walks the links of an `IndexSnapshot` depth-first. A note is expanded
+/// the first time the walk reaches it; later occurrences (shared children
+/// and cycles back to an ancestor) become leaves marked `revisited`.
+use std::collections::{BTreeMap, HashSet};
+
+use serde::Serialize;
+
+use crate::index::{IndexSnapshot, NoteLinks};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkTreeNode {
+    pub id: String,
+    pub title: String,
+    /// Already shown earlier in the tree; not expanded again.
+    pub revisited: bool,
+    /// Has outlinks that were cut off by the depth limit.
+    pub truncated: bool,
+    /// Linked notes in ID order; missing notes are left out.
+    pub children: Vec<LinkTreeNode>,
+}
+
+/// Tree of notes within `depth` outgoing-link hops of `root`. Depth 0 yields
+/// the root alone. Returns `None` when `root` is not indexed.
+pub fn link_tree(index: &IndexSnapshot, root: &str, depth: usize) -> Option<LinkTreeNode> {
+    if !index.contains(root) {
+        return None;
+    }
+    let mut visited = HashSet::new();
+    Some(walk(index, &index.links, root, depth, &mut visited))
+}
+
+fn walk(
+    index: &IndexSnapshot,
+    graph: &BTreeMap<String, NoteLinks>,
+    id: &str,
+    depth: usize,
+    visited: &mut HashSet<String>,
+) -> LinkTreeNode {
+    let mut node = LinkTreeNode {
+        id: id.to_string(),
+        title: index.get(id).map(|n| n.title.clone()).unwrap_or_default(),
+        revisited: false,
+        truncated: false,
+        children: Vec::new(),
+    };
+    if !visited.insert(id.to_string()) {
+        node.revisited = true;
+        return node;
+    }
+    let outlinks = graph
+        .get(id)
+        .into_iter()
+        .flat_map(|links| &links.outlinks)
+        .filter(|target| index.contains(target));
+    for target in outlinks {
+        if depth == 0 {
+            node.truncated = true;
+            break;
+        }
+        node.children
+            .push(walk(index, graph, target, depth - 1, visited));
+    }
+    node
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{NoteIndex, NoteInfo};
+
+    /// Index over `ids` with one `from → to` link per pair in `links`.
+    fn make_index(ids: &[&str], links: &[(&str, &str)]) -> NoteIndex {
+        let idx = NoteIndex::with_notes(ids.iter().map(|id| NoteInfo::stub(id)));
+        for (from, to) in links {
+            idx.add_link(from, to);
+        }
+        idx
+    }
+
+    /// `id` plus markers, indented two spaces per level.
+    fn outline(node: &LinkTreeNode) -> Vec<String> {
+        fn go(node: &LinkTreeNode, level: usize, out: &mut Vec<String>) {
+            let mut line = format!("{}{}", "  ".repeat(level), node.id);
+            if node.revisited {
+                line.push_str(" (revisited)");
+            }
+            if node.truncated {
+                line.push_str(" …");
+            }
+            out.push(line);
+            for child in &node.children {
+                go(child, level + 1, out);
+            }
+        }
+        let mut out = Vec::new();
+        go(node, 0, &mut out);
+        out
+    }
+
+    const A: &str = "2603010000";
+    const B: &str = "2603020000";
+    const C: &str = "2603030000";
+    const D: &str = "2603040000";
+
+    // a → b, a → c, b → d, c → d, d → a, plus a link to a missing note.
+    fn diamond() -> NoteIndex {
+        make_index(
+            &[A, B, C, D],
+            &[(A, B), (A, C), (B, D), (C, D), (D, A), (A, "2603990000")],
+        )
+    }
+
+    #[test]
+    fn test_shared_children_and_cycles_are_marked_revisited() {
+        let tree = link_tree(&diamond().snapshot(), A, 5).unwrap();
+        assert_eq!(tree.title, format!("Note {A}"));
+        assert_eq!(
+            outline(&tree),
+            vec![
+                A.to_string(),
+                format!("  {B}"),
+                format!("    {D}"),
+                format!("      {A} (revisited)"),
+                format!("  {C}"),
+                format!("    {D} (revisited)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_depth_limit_marks_truncated_nodes() {
+        let idx = diamond().snapshot();
+        let tree = link_tree(&idx, A, 1).unwrap();
+        assert_eq!(
+            outline(&tree),
+            vec![A.to_string(), format!("  {B} …"), format!("  {C} …")]
+        );
+        let root_only = link_tree(&idx, A, 0).unwrap();
+        assert_eq!(outline(&root_only), vec![format!("{A} …")]);
+        // A note without outlinks is not truncated.
+        let leaf = link_tree(&make_index(&[A], &[]).snapshot(), A, 0).unwrap();
+        assert!(!leaf.truncated);
+        assert!(link_tree(&idx, "2603990000", 2).is_none());
+    }
+}
@@ -0,0 +1,416 @@
+/// End-to-end tests that drive `ZkLspServer` over JSON-RPC.
+///
+/// `Harness` runs the real `tower_lsp::Server` on an in-memory duplex pipe,
+/// so requests travel through the same framing and dispatch as in the editor.
+/// Requests from the server (`workspace/inlayHint/refresh`,
+/// `client/registerCapability`, ...) are answered with `null` and recorded;
+/// notifications are buffered until a test waits for them.
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::sync::RwLock;
+use tower_lsp::lsp_types::Url;
+use tower_lsp::{LspService, Server};
+
+use crate::config::WikiConfig;
+use crate::server::ZkLspServer;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Harness {
+    root: PathBuf,
+    writer: DuplexStream,
+    reader: BufReader<DuplexStream>,
+    next_id: u64,
+    notifications: Vec<Value>,
+    server_requests: Vec<String>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl Harness {
+    /// Start a server for the wiki at `root`, then `initialize`, `initialized`
+    /// and wait for the first index build (signalled by the inlay hint refresh).
+    async fn start(root: PathBuf) -> Self {
+        Self::start_with(root, json!({})).await
+    }
+
+    /// `start` with the given client `capabilities`.
+    async fn start_with(root: PathBuf, capabilities: Value) -> Self {
+        let (client_write, server_read) = tokio::io::duplex(1 << 16);
+        let (server_write, client_read) = tokio::io::duplex(1 << 16);
+        let config = Arc::new(RwLock::new(WikiConfig::from_root(root.clone())));
+        let (service, socket) = LspService::new(|client| ZkLspServer::new(client, config, None));
+        let server = tokio::spawn(async move {
+            Server::new(server_read, server_write, socket)
+                .serve(service)
+                .await;
+        });
+        let mut harness = Self {
+            root,
+            writer: client_write,
+            reader: BufReader::new(client_read),
+            next_id: 0,
+            notifications: Vec::new(),
+            server_requests: Vec::new(),
+            server,
+        };
+        let root_uri = Url::from_directory_path(&harness.root).unwrap();
+        let init = harness
+            .request(
+                "initialize",
+                json!({"processId": null, "rootUri": root_uri, "capabilities": capabilities}),
+            )
+            .await;
+        assert!(init["capabilities"]["referencesProvider"]
+            .as_bool()
+            .unwrap());
+        harness.notify("initialized", json!({})).await;
+        harness
+            .wait_for_server_request("workspace/inlayHint/refresh")
+            .await;
+        harness
+    }
+
+    fn uri(&self, id: &str) -> Url {
+        Url::from_file_path(self.root.join(format!("note/{id}.typ"))).unwrap()
+    }
+
+    async fn send(&mut self, msg: Value) {
+        let body = msg.to_string();
+        let frame = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+        self.writer.write_all(frame.as_bytes()).await.unwrap();
+    }
+
+    async fn recv(&mut self) -> Value {
+        let mut len = 0;
+        loop {
+            let mut header = String::new();
+            self.reader.read_line(&mut header).await.unwrap();
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(n) = header.strip_prefix("Content-Length: ") {
+                len = n.parse().unwrap();
+            }
+        }
+        let mut body = vec![0; len];
+        self.reader.read_exact(&mut body).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    /// Read one message, answering and recording server-to-client requests
+    /// and buffering notifications. Returns responses only.
+    async fn next_response(&mut self) -> Option<Value> {
+        let msg = tokio::time::timeout(TIMEOUT, self.recv())
+            .await
+            .expect("timed out waiting for the server");
+        match (msg.get("method"), msg.get("id")) {
+            (Some(method), Some(id)) => {
+                self.server_requests
+                    .push(method.as_str().unwrap_or_default().to_string());
+                let id = id.clone();
+                self.send(json!({"jsonrpc": "2.0", "id": id, "result": null}))
+                    .await;
+                None
+            }
+            (Some(_), None) => {
+                self.notifications.push(msg);
+                None
+            }
+            _ => Some(msg),
+        }
+    }
+
+    async fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        let mut msg = json!({"jsonrpc": "2.0", "id": id, "method": method});
+        if !params.is_null() {
+            msg["params"] = params;
+        }
+        self.send(msg).await;
+        loop {
+            if let Some(resp) = self.next_response().await {
+                assert_eq!(resp["id"], json!(id));
+                assert!(resp.get("error").is_none(), "{method} failed: {resp}");
+                return resp["result"].clone();
+            }
+        }
+    }
+
+    async fn notify(&mut self, method: &str, params: Value) {
+        let mut msg = json!({"jsonrpc": "2.0", "method": method});
+        if !params.is_null() {
+            msg["params"] = params;
+        }
+        self.send(msg).await;
+    }
+
+    async fn wait_for_server_request(&mut self, method: &str) {
+        while !self.server_requests.iter().any(|m| m == method) {
+            self.next_response().await;
+        }
+    }
+
+    /// The first buffered or incoming `method` notification matching `pred`.
+    async fn wait_for_notification(
+        &mut self,
+        method: &str,
+        pred: impl Fn(&Value) -> bool,
+    ) -> Value {
+        loop {
+            if let Some(i) = self
+                .notifications
+                .iter()
+                .position(|n| n["method"] == method && pred(&n["params"]))
+            {
+                return self.notifications.remove(i)["params"].clone();
+            }
+            self.next_response().await;
+        }
+    }
+
+    async fn open(&mut self, id: &str) {
+        let text = std::fs::read_to_string(self.root.join(format!("note/{id}.typ"))).unwrap();
+        let uri = self.uri(id);
+        self.notify(
+            "textDocument/didOpen",
+            json!({"textDocument": {
+                "uri": uri, "languageId": "typst", "version": 1, "text": text
+            }}),
+        )
+        .await;
+    }
+
+    async fn shutdown(mut self) {
+        self.request("shutdown", Value::Null).await;
+        self.notify("exit", Value::Null).await;
+        // The server stops once its input is closed.
+        drop(self.writer);
+        tokio::time::timeout(TIMEOUT, self.server)
+            .await
+            .expect("server did not exit")
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+fn note(id: &str, title: &str, body: &str) -> String {
+    format!(
+        "#import \"../include.typ\": *\n\
+         #let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n\
+         #show: zettel.with(metadata: zk-metadata)\n\n= {title} <{id}>\n{body}"
+    )
+}
+
+const ALPHA: &str = "2603150000";
+const BETA: &str = "2603150001";
+const GAMMA: &str = "2603150002";
+/// Line of the title heading in `note`; body lines follow.
+const TITLE_LINE: u32 = 8;
+
+/// Alpha links to Beta and to a missing note one digit away from Alpha;
+/// Beta and Gamma both link back to Alpha.
+fn wiki(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("note")).unwrap();
+    let write = |dir: &Path, id: &str, text: String| {
+        std::fs::write(dir.join(format!("note/{id}.typ")), text).unwrap();
+    };
+    write(
+        &root,
+        ALPHA,
+        note(ALPHA, "Alpha", "See @2603150001 and @2603150009.\n"),
+    );
+    write(&root, BETA, note(BETA, "Beta", "Back to @2603150000.\n"));
+    write(&root, GAMMA, note(GAMMA, "Gamma", "Also @2603150000.\n"));
+    root
+}
+
+#[tokio::test]
+async fn test_references_to_title_note() {
+    let mut h = Harness::start(wiki("zk_lsp_lifecycle_refs")).await;
+    h.open(ALPHA).await;
+    let uri = h.uri(ALPHA);
+    let refs = h
+        .request(
+            "textDocument/references",
+            json!({
+                "textDocument": {"uri": uri},
+                "position": {"line": TITLE_LINE, "character": 2},
+                "context": {"includeDeclaration": false}
+            }),
+        )
+        .await;
+    let mut found: Vec<(String, u64, u64)> = refs
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|loc| {
+            (
+                loc["uri"].as_str().unwrap().to_string(),
+                loc["range"]["start"]["line"].as_u64().unwrap(),
+                loc["range"]["start"]["character"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    found.sort();
+    let body = u64::from(TITLE_LINE) + 1;
+    assert_eq!(
+        found,
+        vec![
+            (h.uri(BETA).to_string(), body, 8),
+            (h.uri(GAMMA).to_string(), body, 5),
+        ]
+    );
+    h.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_diagnostics_inlay_hints_and_code_actions() {
+    let mut h = Harness::start(wiki("zk_lsp_lifecycle_diags")).await;
+    h.open(ALPHA).await;
+    let uri = h.uri(ALPHA);
+    let published = h
+        .wait_for_notification("textDocument/publishDiagnostics", |p| {
+            p["uri"] == json!(uri)
+        })
+        .await;
+    let diags = published["diagnostics"].as_array().unwrap();
+    let dead: Vec<&Value> = diags.iter().filter(|d| d["severity"] == json!(1)).collect();
+    assert_eq!(dead.len(), 1, "{diags:?}");
+    let body = TITLE_LINE + 1;
+    assert_eq!(
+        dead[0]["range"]["start"],
+        json!({"line": body, "character": 20})
+    );
+    assert!(dead[0]["message"].as_str().unwrap().contains("2603150009"));
+
+    let hints = h
+        .request(
+            "textDocument/inlayHint",
+            json!({
+                "textDocument": {"uri": uri},
+                "range": {"start": {"line": 0, "character": 0},
+                          "end": {"line": body + 1, "character": 0}}
+            }),
+        )
+        .await;
+    let labels: Vec<&str> = hints
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hint| hint["label"].as_str().unwrap())
+        .collect();
+    assert_eq!(labels, vec!["Beta"]);
+
+    let actions = h
+        .request(
+            "textDocument/codeAction",
+            json!({
+                "textDocument": {"uri": uri},
+                "range": dead[0]["range"],
+                "context": {"diagnostics": [dead[0]]}
+            }),
+        )
+        .await;
+    let titles: Vec<&str> = actions
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| a["title"].as_str().unwrap())
+        .collect();
+    assert!(
+        titles.iter().any(|t| t.contains(ALPHA)),
+        "expected a replace-with-{ALPHA} fix, got {titles:?}"
+    );
+    h.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_initial_index_build_reports_progress() {
+    let mut h = Harness::start_with(
+        wiki("zk_lsp_lifecycle_progress"),
+        json!({"window": {"workDoneProgress": true}}),
+    )
+    .await;
+    assert!(h
+        .server_requests
+        .iter()
+        .any(|m| m == "window/workDoneProgress/create"));
+    let begin = h
+        .wait_for_notification("$/progress", |p| p["value"]["kind"] == "begin")
+        .await;
+    assert_eq!(begin["token"], "zk-lsp/index");
+    assert_eq!(begin["value"]["title"], "Indexing notes");
+    let end = h
+        .wait_for_notification("$/progress", |p| p["value"]["kind"] == "end")
+        .await;
+    assert_eq!(end["value"]["message"], "3 notes");
+    h.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_code_actions_use_unsaved_buffer_and_only_filter() {
+    let mut h = Harness::start(wiki("zk_lsp_lifecycle_context_actions")).await;
+    h.open(ALPHA).await;
+    let uri = h.uri(ALPHA);
+    // An unsaved line below the body; the file on disk ends before it.
+    let text = note(
+        ALPHA,
+        "Alpha",
+        "See @2603150001 and @2603150009.\nFresh idea.\n",
+    );
+    h.notify(
+        "textDocument/didChange",
+        json!({"textDocument": {"uri": uri, "version": 2},
+               "contentChanges": [{"text": text}]}),
+    )
+    .await;
+    let line = TITLE_LINE + 2;
+    let selection = json!({"start": {"line": line, "character": 0},
+                           "end": {"line": line, "character": 11}});
+    let titles = |actions: Value| -> Vec<String> {
+        actions
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|a| a["title"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let all = h
+        .request(
+            "textDocument/codeAction",
+            json!({"textDocument": {"uri": uri}, "range": selection,
+                   "context": {"diagnostics": []}}),
+        )
+        .await;
+    let abstract_action = all
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|a| a["title"] == "ZK: Set as abstract")
+        .expect("expected Set as abstract")
+        .clone();
+    let edit = &abstract_action["edit"]["changes"][uri.as_str()][0];
+    assert!(edit["newText"]
+        .as_str()
+        .unwrap()
+        .contains("abstract = \"Fresh idea.\""));
+
+    let quickfix_only = h
+        .request(
+            "textDocument/codeAction",
+            json!({"textDocument": {"uri": uri}, "range": selection,
+                   "context": {"diagnostics": [], "only": ["quickfix"]}}),
+        )
+        .await;
+    assert!(titles(quickfix_only).is_empty());
+    h.shutdown().await;
+}
@@ -1,8 +1,13 @@
+mod abstract_gen;
+mod archive_migration;
+mod cancel;
 mod cli;
 mod config;
 mod context_export;
+mod cross_wiki;
 mod cycle;
 mod dependency_graph;
+mod documents;
 mod graph_check;
 mod handlers;
 #[allow(dead_code)]
@@ -10,17 +15,28 @@ mod hooks;
 mod index;
 mod init;
 mod link_gen;
+mod link_tree;
+#[cfg(test)]
+mod lsp_tests;
+mod malformed;
 mod migrate;
+mod neighborhood;
+mod note_check;
 mod note_ops;
 mod parser;
 mod reconcile;
 mod server;
+mod stale_tags;
+mod timeline;
+mod title_links;
+mod vocabulary;
 mod watcher;
 
 use anyhow::Context;
 use clap::Parser;
 use tokio::sync::RwLock;
 use tower_lsp::{LspService, Server};
+use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::{fmt, EnvFilter};
 
 use cli::{Cli, Command};
@@ -29,11 +45,14 @@ use server::ZkLspServer;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Tracing writes to stderr (stdout reserved for JSON-RPC)
+    // Tracing writes to stderr (stdout reserved for JSON-RPC).
+    // Closing spans log their duration (`time.busy`), so `ZK_LSP_LOG=debug`
+    // shows operation timings.
     fmt()
         .with_env_filter(
             EnvFilter::try_from_env("ZK_LSP_LOG").unwrap_or_else(|_| EnvFilter::new("info")),
         )
+        .with_span_events(FmtSpan::CLOSE)
         .with_writer(std::io::stderr)
         .init();
 
@@ -51,6 +70,26 @@ async fn main() -> anyhow::Result<()> {
 
     let config = std::sync::Arc::new(WikiConfig::resolve(cli.wiki_root.clone(), None));
 
+    // Everything but the server, stdin formatting and checking, and note
+    // creation (which creates the directory) reads the note directory; fail
+    // loudly rather than reporting an empty wiki.
+    if !matches!(
+        cli.command,
+        None | Some(Command::Lsp)
+            | Some(Command::Format)
+            | Some(Command::New)
+            | Some(Command::Daily)
+            | Some(Command::Check { input: Some(_), .. })
+    ) {
+        let state = note_ops::NoteDirState::probe(&config.note_dir).await;
+        if !state.is_usable() {
+            if let Some(problem) = state.describe(&config.note_dir) {
+                eprintln!("zk-lsp: {problem}");
+            }
+            std::process::exit(1);
+        }
+    }
+
     match cli.command.unwrap_or(Command::Lsp) {
         Command::Lsp => {
             run_lsp(cli.wiki_root).await?;
@@ -59,21 +98,74 @@ async fn main() -> anyhow::Result<()> {
             link_gen::generate_link_typ(&config).await?;
             eprintln!("link.typ regenerated at {}", config.link_file.display());
         }
+        Command::NormalizeLinks => {
+            let report = link_gen::normalize_link_typ(&config).await?;
+            if report.changed {
+                println!(
+                    "{}: {} added, {} removed, {} reordered, {} re-pathed",
+                    config.link_file.display(),
+                    report.added,
+                    report.removed,
+                    report.reordered,
+                    report.repathed
+                );
+            } else {
+                println!("{} is already up to date", config.link_file.display());
+            }
+        }
         Command::New => {
             let path = note_ops::create_note(&config).await?;
             println!("{}", path.display());
         }
-        Command::Remove { id } => {
-            note_ops::delete_note(&id, &config).await?;
-            eprintln!("Note {id} removed.");
+        Command::Daily => {
+            let path = note_ops::daily_note(&config).await?;
+            println!("{}", path.display());
+        }
+        Command::Remove { id, trash, force } => {
+            let index = build_index(&config).await?;
+            let referrers = index.referencing_files(&id);
+            if !referrers.is_empty() {
+                eprintln!("warning: {} note(s) still link to {id}:", referrers.len());
+                for file in &referrers {
+                    eprintln!("  {}", file.display());
+                }
+                if !force {
+                    eprintln!("Not removed; pass --force to remove it anyway.");
+                    std::process::exit(1);
+                }
+            }
+            let trash = trash || config.zk_config.trash_on_delete;
+            match note_ops::delete_note(&id, &config, trash).await? {
+                Some(path) => eprintln!("Note {id} moved to {}.", path.display()),
+                None => eprintln!("Note {id} removed."),
+            }
+        }
+        Command::Restore { id } => {
+            let path = note_ops::restore_note(&id, &config).await?;
+            println!("{}", path.display());
         }
         Command::Format => {
             use std::io::Read;
             let mut content = String::new();
             std::io::stdin().read_to_string(&mut content)?;
-            let formatted = handlers::formatting::format_content(&content, &config).await;
+            let formatted = handlers::formatting::format_content(&content, &config, None).await;
             print!("{formatted}");
         }
+        Command::FormatAll { check } => {
+            let stats = handlers::formatting::format_wiki(config.clone(), check).await?;
+            for path in &stats.changed {
+                println!("{}", path.display());
+            }
+            let changed = stats.changed.len();
+            if check {
+                eprintln!("{changed} of {} note(s) need formatting.", stats.total);
+                if changed > 0 {
+                    std::process::exit(1);
+                }
+            } else {
+                eprintln!("Formatted {changed} of {} note(s).", stats.total);
+            }
+        }
         Command::Migrate => {
             eprintln!("Migrating legacy notes in {} …", config.note_dir.display());
             let stats = migrate::migrate_wiki(&config).await?;
@@ -92,14 +184,64 @@ async fn main() -> anyhow::Result<()> {
                 std::process::exit(1);
             }
         },
-        Command::Export { id, depth, inverse } => {
-            let out = context_export::export_context(&id, depth, inverse, &config).await?;
+        Command::Export {
+            id,
+            depth,
+            inverse,
+            both,
+            max_chars,
+        } => {
+            let direction = if both {
+                context_export::Direction::Both
+            } else if inverse {
+                context_export::Direction::Incoming
+            } else {
+                context_export::Direction::Outgoing
+            };
+            let out =
+                context_export::export_context(&id, depth, direction, max_chars, &config).await?;
             print!("{out}");
         }
+        Command::Neighborhood { id, depth, format } => {
+            let index = build_index(&config).await?;
+            let Some(hood) = neighborhood::neighborhood(&index.snapshot(), &id, depth) else {
+                eprintln!("Note {id} not found in {}", config.note_dir.display());
+                std::process::exit(1);
+            };
+            if format == "json" {
+                println!("{}", neighborhood::render_json(&hood)?);
+            } else {
+                print!("{}", neighborhood::render_dot(&hood));
+            }
+        }
         Command::Init => unreachable!("handled above"),
+        Command::Check {
+            input: Some(input),
+            no_index,
+            ..
+        } => {
+            if input != "-" {
+                anyhow::bail!("check: expected `-` to read a note from stdin, got `{input}`");
+            }
+            use std::io::Read;
+            let mut content = String::new();
+            std::io::stdin().read_to_string(&mut content)?;
+            let index = if no_index {
+                None
+            } else {
+                Some(build_index(&config).await?)
+            };
+            let diags = note_check::check_note(&content, &config, index.as_ref());
+            print!("{}", note_check::render_note_diagnostics(&diags));
+            if note_check::has_errors(&diags) {
+                std::process::exit(1);
+            }
+        }
         Command::Check {
             no_orphans,
             no_dead_links,
+            exclude_generated,
+            ..
         } => {
             let mut report = graph_check::check_graph(&config).await?;
             let has_dead_links = !report.dead_links.is_empty();
@@ -109,6 +251,9 @@ async fn main() -> anyhow::Result<()> {
             if no_orphans {
                 report.orphans.clear();
             }
+            if exclude_generated {
+                report.orphans.retain(|o| !o.generated);
+            }
             let rendered = graph_check::render_check_report(&report);
             print!("{rendered}");
             if has_dead_links && !no_dead_links {
@@ -116,11 +261,10 @@ async fn main() -> anyhow::Result<()> {
             }
         }
         Command::NoteInfo { id } => {
-            let path = config.note_dir.join(format!("{id}.typ"));
-            if !path.exists() {
-                eprintln!("Note {id} not found at {}", path.display());
+            let Some(path) = config.locate_note(&id) else {
+                eprintln!("Note {id} not found in {}", config.note_dir.display());
                 std::process::exit(1);
-            }
+            };
             let content = tokio::fs::read_to_string(&path)
                 .await
                 .with_context(|| format!("reading {}", path.display()))?;
@@ -135,6 +279,177 @@ async fn main() -> anyhow::Result<()> {
             let json = build_note_info_json(&id, &path, &header, &parsed_toml)?;
             println!("{json}");
         }
+        Command::Path { id } => {
+            let index = build_index(&config).await?;
+            let Some(path) = index.path_of(&id) else {
+                eprintln!("Note {id} not found in {}", config.note_dir.display());
+                std::process::exit(1);
+            };
+            println!("{}", path.display());
+        }
+        Command::Backlinks { id, json } => {
+            let index = build_index(&config).await?;
+            let mut locs = index.get_backlinks(&id);
+            locs.sort_by(|a, b| {
+                (&a.file, a.line, a.start_byte).cmp(&(&b.file, b.line, b.start_byte))
+            });
+            if json {
+                println!("{}", serde_json::to_string_pretty(&locs)?);
+            } else {
+                for loc in locs {
+                    println!(
+                        "{}:{}:{}",
+                        loc.file.display(),
+                        loc.line + 1,
+                        loc.start_byte + 1
+                    );
+                }
+            }
+        }
+        Command::Hubs { limit, json } => {
+            let index = build_index(&config).await?;
+            let hubs = index.hub_notes(limit);
+            if json {
+                let out: Vec<serde_json::Value> = hubs
+                    .iter()
+                    .map(|(note, count)| {
+                        serde_json::json!({ "id": note.id, "title": note.title, "count": count })
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&out)?);
+            } else {
+                for (note, count) in hubs {
+                    println!("{count:>5}  {}  {}", note.id, note.title);
+                }
+            }
+        }
+        Command::Leaves {
+            include_archived,
+            json,
+        } => {
+            let index = build_index(&config).await?;
+            let leaves = index.leaf_notes(include_archived);
+            if json {
+                let out: Vec<serde_json::Value> = leaves
+                    .iter()
+                    .map(|note| serde_json::json!({ "id": note.id, "title": note.title }))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&out)?);
+            } else {
+                for note in leaves {
+                    println!("{}  {}", note.id, note.title);
+                }
+            }
+        }
+        Command::DupTitles {
+            distance,
+            include_archived,
+            json,
+        } => {
+            let index = build_index(&config).await?;
+            let groups =
+                index.duplicate_titles(include_archived, distance, &cancel::CancelToken::new());
+            if json {
+                let out: Vec<Vec<serde_json::Value>> = groups
+                    .iter()
+                    .map(|group| {
+                        group
+                            .iter()
+                            .map(|note| {
+                                serde_json::json!({
+                                    "id": note.id,
+                                    "title": note.title,
+                                    "path": note.path,
+                                })
+                            })
+                            .collect()
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&out)?);
+            } else {
+                for (i, group) in groups.iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+                    for note in group {
+                        println!("{}  {}  {}", note.id, note.title, note.path.display());
+                    }
+                }
+            }
+        }
+        Command::Since { date } => {
+            let Some(start) = parser::parse_date_bound(&date, false) else {
+                eprintln!("Invalid date {date:?}: expected YYYY-MM-DD or \"YYYY-MM-DD HH:MM\"");
+                std::process::exit(2);
+            };
+            let index = build_index(&config).await?;
+            let end = chrono::Local::now().naive_local() + chrono::Duration::minutes(1);
+            for (created, note) in index.created_between(start, end) {
+                println!(
+                    "{}  {}  {}",
+                    note.id,
+                    created.format("%Y-%m-%d %H:%M"),
+                    note.title
+                );
+            }
+        }
+        Command::Timeline { by, json } => {
+            let period = timeline::TimelinePeriod::parse(&by).expect("checked by clap");
+            let index = build_index(&config).await?;
+            let entries = timeline::notes_timeline(&index, period);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else {
+                print!("{}", timeline::render_timeline(&entries));
+            }
+        }
+        Command::Vocab => {
+            let index = build_index(&config).await?;
+            let vocab = vocabulary::vocabulary(&index);
+            println!("{}", serde_json::to_string_pretty(&vocab)?);
+        }
+        Command::StaleTags { fix, json } => {
+            let stale = stale_tags::find_stale_tags(&config).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stale)?);
+            } else {
+                print!("{}", stale_tags::render_stale_tags(&stale));
+            }
+            if fix {
+                let written = stale_tags::fix_stale_tags(&stale).await?;
+                eprintln!("Fixed {written} note(s).");
+            } else if !stale.is_empty() {
+                eprintln!(
+                    "{} note(s) with stale status tags; re-run with --fix to update them.",
+                    stale.len()
+                );
+            }
+        }
+        Command::Malformed { json } => {
+            let malformed = malformed::find_malformed_notes(&config).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&malformed)?);
+            } else {
+                print!("{}", malformed::render_malformed(&malformed));
+            }
+            if !malformed.is_empty() {
+                eprintln!(
+                    "{} note(s) cannot be indexed; fix their headers to include them.",
+                    malformed.len()
+                );
+                std::process::exit(1);
+            }
+        }
+        Command::Migrations { json } => {
+            let index = build_index(&config).await?;
+            let pending =
+                archive_migration::pending_migrations(&index, &cancel::CancelToken::new()).await;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&pending)?);
+            } else {
+                print!("{}", archive_migration::render_pending_migrations(&pending));
+            }
+        }
     }
     Ok(())
 }
@@ -190,6 +505,10 @@ fn build_note_info_json(
     );
     metadata.insert("keywords".into(), json!(parsed.keywords));
     metadata.insert("generated".into(), json!(parsed.generated));
+    // Optional; shown only when a note opts out.
+    if !parsed.auto_status {
+        metadata.insert("auto-status".into(), json!(false));
+    }
     metadata.insert("checklist-status".into(), json!(checklist_status_str));
     metadata.insert("relation".into(), json!(relation_str));
     metadata.insert("relation-target".into(), json!(parsed.relation_target));
@@ -209,6 +528,30 @@ fn build_note_info_json(
     Ok(serde_json::to_string_pretty(&output)?)
 }
 
+/// Build the note index for a CLI command, with progress on stderr.
+async fn build_index(config: &WikiConfig) -> anyhow::Result<std::sync::Arc<index::NoteIndex>> {
+    let index = index::NoteIndex::new(std::sync::Arc::new(RwLock::new(config.clone())));
+    index.rebuild_full_with_progress(print_progress).await?;
+    Ok(std::sync::Arc::new(index))
+}
+
+/// `rebuild_full` progress for CLI commands: an `Indexing notes done/total`
+/// line on stderr, redrawn in place and cleared at the end. Silent when
+/// stderr is not a terminal, so piped output and logs stay clean.
+fn print_progress(done: usize, total: usize) {
+    use std::io::{IsTerminal, Write};
+    let mut stderr = std::io::stderr();
+    if !stderr.is_terminal() {
+        return;
+    }
+    if done < total {
+        let _ = write!(stderr, "\rIndexing notes {done}/{total}");
+    } else {
+        let _ = write!(stderr, "\r\x1b[2K");
+    }
+    let _ = stderr.flush();
+}
+
 async fn run_lsp(cli_root: Option<std::path::PathBuf>) -> anyhow::Result<()> {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
@@ -0,0 +1,159 @@
+/// Notes the index silently skips.
+///
+/// `find_malformed_notes` runs `parse_header` over every file `rebuild_full`
+/// would index and lists those it rejects, with `diagnose` giving a best
+/// guess at the cause. `render_malformed` formats the report for the CLI.
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::config::WikiConfig;
+use crate::parser::{self, ParseError, Parser};
+use crate::{migrate, note_ops};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MalformedNote {
+    /// The ID from the file name; the note itself did not yield one.
+    pub id: String,
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Every indexable file in `note_dir` without a parseable header, by ID.
+pub async fn find_malformed_notes(config: &WikiConfig) -> Result<Vec<MalformedNote>> {
+    let parser = config.parser();
+    let files = note_ops::indexable_note_files(config)
+        .await
+        .with_context(|| format!("reading note dir {}", config.note_dir.display()))?;
+    let mut malformed = Vec::new();
+    for path in files {
+        let Some(id) = parser::note_id_from_path(&path).map(str::to_string) else {
+            continue;
+        };
+        let reason = match tokio::fs::read(&path).await {
+            Ok(bytes) => match String::from_utf8(bytes) {
+                Ok(content) => match diagnose(&content, &parser) {
+                    Some(reason) => reason.to_string(),
+                    None => continue,
+                },
+                // Still listed under `lossy_reads`: the index sees the note,
+                // but with U+FFFD where the bad bytes were.
+                Err(e) if config.zk_config.lossy_reads => {
+                    let content = String::from_utf8_lossy(e.as_bytes());
+                    match diagnose(&content, &parser) {
+                        Some(reason) => format!("not valid UTF-8; {reason}"),
+                        None => "not valid UTF-8; indexed lossily (lossy_reads)".to_string(),
+                    }
+                }
+                Err(_) => "not valid UTF-8".to_string(),
+            },
+            Err(e) => format!("unreadable: {e}"),
+        };
+        malformed.push(MalformedNote { id, path, reason });
+    }
+    malformed.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(malformed)
+}
+
+/// Why `parse_header` rejects `content`, or `None` if it does not.
+pub fn diagnose(content: &str, parser: &Parser) -> Option<&'static str> {
+    let error = parser.parse_header_detailed(content).err()?;
+    let no_block = matches!(error, ParseError::NoImport | ParseError::NoMetadata);
+    if no_block && migrate::migrate_note(content, parser).is_some() {
+        return Some("legacy comment-format note; run `zk-lsp migrate`");
+    }
+    Some(error.reason())
+}
+
+/// One `ID  path  reason` line per malformed note.
+pub fn render_malformed(malformed: &[MalformedNote]) -> String {
+    malformed
+        .iter()
+        .map(|m| format!("{}  {}  {}\n", m.id, m.path.display(), m.reason))
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: &str =
+        "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n";
+
+    #[test]
+    fn test_diagnose_guesses_the_cause() {
+        let parser = Parser::default();
+        let diagnose = |content: &str| diagnose(content, &parser);
+        assert_eq!(diagnose(&format!("{BLOCK}= Fine <2603150000>\n")), None);
+        assert_eq!(diagnose("  \n"), Some("empty file"));
+        assert_eq!(
+            diagnose("= Title <2603150000>\nbody\n"),
+            Some("no #import line and no zk-metadata block")
+        );
+        assert_eq!(
+            diagnose("#import \"../include.typ\": *\n= Title\n"),
+            Some("no zk-metadata block")
+        );
+        assert_eq!(
+            diagnose("#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n"),
+            Some("unterminated zk-metadata block (missing ```toml fence or closing ```)")
+        );
+        assert_eq!(
+            diagnose(&format!("= Early <2603150000>\n{BLOCK}")),
+            Some("title heading comes before the zk-metadata block")
+        );
+        assert_eq!(
+            diagnose(&format!("{BLOCK}= No label\n")),
+            Some("title heading has no <ID> label")
+        );
+        assert_eq!(
+            diagnose(&format!("{BLOCK}just text\n")),
+            Some("no `= Title <ID>` heading after the zk-metadata block")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_malformed_notes_skips_good_and_non_note_files() {
+        let tmp = std::env::temp_dir().join("zk_malformed_scan");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let write = |name: &str, content: String| {
+            std::fs::write(tmp.join("note").join(name), content).unwrap();
+        };
+        write("2603150000.typ", format!("{BLOCK}= Fine <2603150000>\n"));
+        write("2603150001.typ", format!("{BLOCK}= No label\n"));
+        write("scratch.typ", "not a note".to_string());
+        let mut config = WikiConfig::from_root(tmp.clone());
+
+        let malformed = find_malformed_notes(&config).await.unwrap();
+        let mut bad = format!("{BLOCK}= Caf").into_bytes();
+        bad.extend_from_slice(b"\xe9 <2603150002>\n");
+        std::fs::write(tmp.join("note/2603150002.typ"), bad).unwrap();
+        let strict = find_malformed_notes(&config).await.unwrap();
+        config.zk_config.lossy_reads = true;
+        let lossy = find_malformed_notes(&config).await.unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(strict[1].reason, "not valid UTF-8");
+        assert_eq!(
+            lossy[1].reason,
+            "not valid UTF-8; indexed lossily (lossy_reads)"
+        );
+
+        assert_eq!(malformed.len(), 1);
+        assert_eq!(malformed[0].id, "2603150001");
+        assert_eq!(
+            render_malformed(&malformed),
+            format!(
+                "2603150001  {}  title heading has no <ID> label\n",
+                tmp.join("note/2603150001.typ").display()
+            )
+        );
+    }
+}
@@ -1,9 +1,9 @@
 /// Migration from legacy comment-format notes to TOML schema v1.
 ///
 /// Legacy format:
-///   /* Metadata:          ← optional 6-line block
+///   /* Metadata:          ← optional block
 ///   Aliases: ...
-///   Abstract: ...
+///   Abstract: ...          ← non-key lines continue the previous value
 ///   Keyword: ...
 ///   Generated: true
 ///   */
@@ -37,7 +37,8 @@ use anyhow::Result;
 use tokio::fs;
 
 use crate::config::WikiConfig;
-use crate::parser::{find_toml_metadata_block, RE_ALT, RE_EVO, RE_TITLE};
+use crate::note_ops;
+use crate::parser::{find_toml_metadata_block, note_id_from_path, Parser};
 
 pub struct MigrateStats {
     pub migrated: usize,
@@ -53,18 +54,9 @@ pub async fn migrate_wiki(config: &WikiConfig) -> Result<MigrateStats> {
         skipped: 0,
     };
 
-    let mut entries = fs::read_dir(&config.note_dir).await?;
-    let mut paths = Vec::new();
-    while let Some(entry) = entries.next_entry().await? {
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) == Some("typ") {
-            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                if stem.len() == 10 && stem.chars().all(|c| c.is_ascii_digit()) {
-                    paths.push(path);
-                }
-            }
-        }
-    }
+    let parser = config.parser();
+    let mut paths = note_ops::wiki_note_files(config).await?;
+    paths.retain(|path| note_id_from_path(path).is_some());
 
     for path in &paths {
         let content = match fs::read_to_string(path).await {
@@ -81,7 +73,7 @@ pub async fn migrate_wiki(config: &WikiConfig) -> Result<MigrateStats> {
             continue;
         }
 
-        match migrate_note(&content) {
+        match migrate_note(&content, &parser) {
             Some(new_content) => {
                 // Atomic write via tmp → rename
                 let tmp = path.with_extension("typ.migrate_tmp");
@@ -102,21 +94,26 @@ pub async fn migrate_wiki(config: &WikiConfig) -> Result<MigrateStats> {
 
 /// Convert a single legacy note to TOML schema v1.
 /// Returns `None` if the content does not look like a legacy note.
-pub fn migrate_note(content: &str) -> Option<String> {
+///
+/// The `#import` anchor line is located with `parser.is_import_line` and
+/// carried over unchanged.
+pub fn migrate_note(content: &str, parser: &Parser) -> Option<String> {
     let lines: Vec<&str> = content.lines().collect();
 
     // Locate the #import line — mandatory for both legacy variants.
-    let import_idx = lines
-        .iter()
-        .position(|l| l.trim() == r#"#import "../include.typ": *"#)?;
+    let import_idx = lines.iter().position(|l| parser.is_import_line(l))?;
 
     // ── Parse legacy comment metadata (optional block before import) ──────
     let mut aliases: Vec<String> = Vec::new();
     let mut abstract_text = String::new();
     let mut keywords: Vec<String> = Vec::new();
+    // Migrated notes count as generated unless the comment says otherwise.
+    let mut generated = true;
 
     if import_idx > 0 {
         let mut in_meta = false;
+        // Field that non-key lines continue; any other `Key:` line ends it.
+        let mut current: Option<&str> = None;
         for line in &lines[..import_idx] {
             if line.trim() == "/* Metadata:" {
                 in_meta = true;
@@ -125,21 +122,34 @@ pub fn migrate_note(content: &str) -> Option<String> {
             if line.trim() == "*/" {
                 break;
             }
-            if in_meta {
-                if let Some(val) = line.strip_prefix("Aliases:") {
-                    aliases = val
-                        .split(',')
-                        .map(|s| s.trim().to_string())
-                        .filter(|s| !s.is_empty())
-                        .collect();
-                } else if let Some(val) = line.strip_prefix("Abstract:") {
-                    abstract_text = val.trim().to_string();
-                } else if let Some(val) = line.strip_prefix("Keyword:") {
-                    keywords = val
-                        .split(',')
-                        .map(|s| s.trim().to_string())
-                        .filter(|s| !s.is_empty())
-                        .collect();
+            if !in_meta {
+                continue;
+            }
+            if let Some(val) = line.strip_prefix("Aliases:") {
+                aliases = split_list(val);
+                current = Some("Aliases");
+            } else if let Some(val) = line.strip_prefix("Abstract:") {
+                abstract_text = val.trim().to_string();
+                current = Some("Abstract");
+            } else if let Some(val) = line.strip_prefix("Keyword:") {
+                keywords = split_list(val);
+                current = Some("Keyword");
+            } else if let Some(val) = line.strip_prefix("Generated:") {
+                generated = val.trim() != "false";
+                current = None;
+            } else if is_meta_key(line) {
+                current = None;
+            } else if !line.trim().is_empty() {
+                match current {
+                    Some("Aliases") => aliases.extend(split_list(line)),
+                    Some("Keyword") => keywords.extend(split_list(line)),
+                    Some("Abstract") => {
+                        if !abstract_text.is_empty() {
+                            abstract_text.push(' ');
+                        }
+                        abstract_text.push_str(line.trim());
+                    }
+                    _ => {}
                 }
             }
         }
@@ -158,7 +168,7 @@ pub fn migrate_note(content: &str) -> Option<String> {
 
     let title_line = lines.get(title_line_idx)?;
     // Reject if this line doesn't look like a Typst heading with an ID.
-    RE_TITLE.captures(title_line)?;
+    parser.re_title.captures(title_line)?;
 
     // ── Tag line ──────────────────────────────────────────────────────────
     let tag_line = lines.get(tag_line_idx).copied().unwrap_or("");
@@ -179,11 +189,13 @@ pub fn migrate_note(content: &str) -> Option<String> {
 
     // ── Optional link line ────────────────────────────────────────────────
     let link_line = lines.get(link_line_idx).copied().unwrap_or("");
-    let alt_id = RE_ALT
+    let alt_id = parser
+        .re_alt
         .captures(link_line)
         .and_then(|c| c.get(1))
         .map(|m| m.as_str().to_string());
-    let evo_id = RE_EVO
+    let evo_id = parser
+        .re_evo
         .captures(link_line)
         .and_then(|c| c.get(1))
         .map(|m| m.as_str().to_string());
@@ -212,14 +224,15 @@ pub fn migrate_note(content: &str) -> Option<String> {
     // ── Assemble new content ──────────────────────────────────────────────
     let mut out = String::with_capacity(content.len() + 256);
 
-    out.push_str("#import \"../include.typ\": *\n");
+    out.push_str(lines[import_idx].trim());
+    out.push('\n');
     out.push_str("#let zk-metadata = toml(bytes(\n");
     out.push_str("  ```toml\n");
     out.push_str("  schema-version = 1\n");
     out.push_str(&format!("  aliases = {}\n", toml_string_array(&aliases)));
     out.push_str(&format!("  abstract = {}\n", toml_quoted(&abstract_text)));
     out.push_str(&format!("  keywords = {}\n", toml_string_array(&keywords)));
-    out.push_str("  generated = true\n");
+    out.push_str(&format!("  generated = {generated}\n"));
     out.push_str(&format!(
         "  checklist-status = {}\n",
         toml_quoted(checklist_status)
@@ -250,6 +263,21 @@ pub fn migrate_note(content: &str) -> Option<String> {
     Some(out)
 }
 
+/// Comma-separated legacy list value, trimmed, empty items dropped.
+fn split_list(val: &str) -> Vec<String> {
+    val.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// An unindented `Key:` line in the legacy metadata comment.
+fn is_meta_key(line: &str) -> bool {
+    line.split_once(':').is_some_and(|(key, _)| {
+        !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    })
+}
+
 /// Remove status/relation tags from a tag line, preserving all other tags.
 ///
 /// Stripped: `#tag.archived`, `#tag.legacy`, `#tag.todo`, `#tag.done`, `#tag.wip`
@@ -292,11 +320,13 @@ fn toml_string_array(items: &[String]) -> String {
 mod tests {
     use super::*;
     use crate::parser::tests::{NOTE_NO_META, NOTE_WITH_META};
-    use crate::parser::{find_toml_metadata_block, parse_header, parse_toml_metadata};
+    use crate::parser::{
+        find_toml_metadata_block, parse_header, parse_toml_metadata, ParserConfig,
+    };
 
     #[test]
     fn migrate_with_meta_round_trips() {
-        let migrated = migrate_note(NOTE_WITH_META).expect("migration failed");
+        let migrated = migrate_note(NOTE_WITH_META, &Parser::default()).expect("migration failed");
 
         // Must parse as a valid TOML-format note.
         let header = parse_header(&migrated).expect("migrated note not parseable");
@@ -330,7 +360,7 @@ mod tests {
 
     #[test]
     fn migrate_no_meta_round_trips() {
-        let migrated = migrate_note(NOTE_NO_META).expect("migration failed");
+        let migrated = migrate_note(NOTE_NO_META, &Parser::default()).expect("migration failed");
 
         let header = parse_header(&migrated).expect("migrated note not parseable");
         assert_eq!(header.id, "2602082106");
@@ -352,6 +382,39 @@ mod tests {
         assert!(!migrated.contains("#tag.todo"));
     }
 
+    #[test]
+    fn migrate_joins_continuation_lines() {
+        let note = concat!(
+            "/* Metadata:\n",
+            "Aliases: One,\n",
+            "  Two\n",
+            "Abstract: First half of a long abstract\n",
+            "  that continues here.\n",
+            "Keyword: a, b\n",
+            "Generated: true\n",
+            "  ignored\n",
+            "*/\n",
+            "#import \"../include.typ\": *\n",
+            "#show: zettel\n",
+            "\n",
+            "= Long <2602082037>\n",
+            "#tag.todo\n",
+        );
+        let migrated = migrate_note(note, &Parser::default()).expect("migration failed");
+        let header = parse_header(&migrated).unwrap();
+        assert_eq!(
+            header.abstract_text.as_deref(),
+            Some("First half of a long abstract that continues here.")
+        );
+        assert_eq!(header.aliases, vec!["One", "Two"]);
+        assert_eq!(header.keywords, vec!["a", "b"]);
+        assert!(header.generated);
+
+        let hand_written = note.replace("Generated: true", "Generated: false");
+        let migrated = migrate_note(&hand_written, &Parser::default()).unwrap();
+        assert!(!parse_header(&migrated).unwrap().generated);
+    }
+
     #[test]
     fn migrate_preserves_non_status_tags() {
         let note = concat!(
@@ -364,7 +427,7 @@ mod tests {
             "\n",
             "Body.\n",
         );
-        let migrated = migrate_note(note).expect("migration failed");
+        let migrated = migrate_note(note, &Parser::default()).expect("migration failed");
 
         // Status/relation tags stripped.
         assert!(!migrated.contains("#tag.archived"));
@@ -378,6 +441,20 @@ mod tests {
         assert!(research_pos > title_pos);
     }
 
+    #[test]
+    fn migrate_uses_configured_import_marker() {
+        let note = NOTE_NO_META.replace("\"../include.typ\"", "\"../../lib/include.typ\"");
+        assert!(migrate_note(&note, &Parser::default()).is_none());
+
+        let parser = Parser::new(ParserConfig {
+            import_marker: regex::Regex::new(r#"^#import ".*include\.typ": \*$"#).unwrap(),
+            ..ParserConfig::default()
+        });
+        let migrated = migrate_note(&note, &parser).expect("migration failed");
+        assert!(migrated.starts_with("#import \"../../lib/include.typ\": *\n"));
+        assert_eq!(parse_header(&migrated).unwrap().id, "2602082106");
+    }
+
     #[test]
     fn migrate_already_toml_skipped_by_caller() {
         let toml_note = concat!(
@@ -0,0 +1,218 @@
+/// Focused subgraph export around one note.
+///
+/// `neighborhood` collects the notes within `depth` link hops of a center
+/// note, following outgoing links and backlinks together (BFS over the
+/// backlinks of an `IndexSnapshot`). `render_dot` / `render_json` serialise the result for
+/// per-note visualisations.
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use serde::Serialize;
+
+use crate::index::IndexSnapshot;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NeighborhoodNode {
+    pub id: String,
+    pub title: String,
+    /// Hops from the center note (0 for the center itself).
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct NeighborhoodEdge {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Neighborhood {
+    pub center: String,
+    pub depth: usize,
+    /// Sorted by hop count, then ID; the center comes first.
+    pub nodes: Vec<NeighborhoodNode>,
+    /// Links between the collected notes, sorted.
+    pub edges: Vec<NeighborhoodEdge>,
+}
+
+/// Distinct `source → target` links between indexed notes, self-links
+/// excluded. The source is the file stem of each backlink location.
+fn link_edges(index: &IndexSnapshot) -> BTreeSet<NeighborhoodEdge> {
+    let mut edges = BTreeSet::new();
+    for (target, locs) in &index.backlinks {
+        if !index.contains(target) {
+            continue;
+        }
+        for loc in locs {
+            let Some(source) = loc.file.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if source != target && index.contains(source) {
+                edges.insert(NeighborhoodEdge {
+                    from: source.to_string(),
+                    to: target.clone(),
+                });
+            }
+        }
+    }
+    edges
+}
+
+/// The subgraph within `depth` hops of `center`, in both link directions.
+///
+/// Depth 0 yields just the center note; a note without links yields itself
+/// with no edges. Returns `None` when `center` is not indexed.
+pub fn neighborhood(index: &IndexSnapshot, center: &str, depth: usize) -> Option<Neighborhood> {
+    let center_info = index.get(center)?;
+    let edges = link_edges(index);
+    let mut adjacent: HashMap<&str, Vec<&str>> = HashMap::new();
+    for edge in &edges {
+        adjacent.entry(&edge.from).or_default().push(&edge.to);
+        adjacent.entry(&edge.to).or_default().push(&edge.from);
+    }
+
+    let mut nodes = vec![NeighborhoodNode {
+        id: center.to_string(),
+        title: center_info.title.clone(),
+        depth: 0,
+    }];
+    let mut visited: HashSet<&str> = HashSet::from([center]);
+    let mut queue: VecDeque<(&str, usize)> = VecDeque::from([(center, 0)]);
+    while let Some((id, d)) = queue.pop_front() {
+        if d >= depth {
+            continue;
+        }
+        for &next in adjacent.get(id).into_iter().flatten() {
+            if visited.insert(next) {
+                queue.push_back((next, d + 1));
+                nodes.push(NeighborhoodNode {
+                    id: next.to_string(),
+                    title: index.get(next).map(|n| n.title.clone()).unwrap_or_default(),
+                    depth: d + 1,
+                });
+            }
+        }
+    }
+    nodes.sort_by(|a, b| (a.depth, &a.id).cmp(&(b.depth, &b.id)));
+
+    let edges = edges
+        .iter()
+        .filter(|e| visited.contains(e.from.as_str()) && visited.contains(e.to.as_str()))
+        .cloned()
+        .collect();
+    Some(Neighborhood {
+        center: center.to_string(),
+        depth,
+        nodes,
+        edges,
+    })
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Graphviz DOT for a neighborhood; the center node is filled and bold.
+pub fn render_dot(hood: &Neighborhood) -> String {
+    let mut out = format!("digraph \"neighborhood_{}\" {{\n", hood.center);
+    out.push_str("  node [shape=box];\n");
+    for node in &hood.nodes {
+        let label = dot_escape(&format!("{}\n{}", node.id, node.title)).replace('\n', "\\n");
+        let style = if node.id == hood.center {
+            ", style=\"filled,bold\", fillcolor=\"lightyellow\""
+        } else {
+            ""
+        };
+        out.push_str(&format!("  \"{}\" [label=\"{label}\"{style}];\n", node.id));
+    }
+    for edge in &hood.edges {
+        out.push_str(&format!("  \"{}\" -> \"{}\";\n", edge.from, edge.to));
+    }
+    out.push_str("}\n");
+    out
+}
+
+pub fn render_json(hood: &Neighborhood) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(hood)
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{NoteIndex, NoteInfo};
+
+    /// Index over `ids` with one `from → to` link per pair in `links`.
+    fn make_index(ids: &[&str], links: &[(&str, &str)]) -> NoteIndex {
+        let idx = NoteIndex::with_notes(ids.iter().map(|id| NoteInfo {
+            title: format!("Note \"{id}\""),
+            ..NoteInfo::stub(id)
+        }));
+        for (from, to) in links {
+            idx.add_link(from, to);
+        }
+        idx
+    }
+
+    fn ids(hood: &Neighborhood) -> Vec<(&str, usize)> {
+        hood.nodes
+            .iter()
+            .map(|n| (n.id.as_str(), n.depth))
+            .collect()
+    }
+
+    // Chain a → b ← c → d, plus e unconnected.
+    const A: &str = "2603010000";
+    const B: &str = "2603020000";
+    const C: &str = "2603030000";
+    const D: &str = "2603040000";
+    const E: &str = "2603050000";
+
+    fn chain() -> IndexSnapshot {
+        make_index(&[A, B, C, D, E], &[(A, B), (C, B), (C, D), (A, A)]).snapshot()
+    }
+
+    #[test]
+    fn test_bfs_follows_both_directions_up_to_depth() {
+        let idx = chain();
+        let hood = neighborhood(&idx, B, 1).unwrap();
+        assert_eq!(ids(&hood), vec![(B, 0), (A, 1), (C, 1)]);
+        assert_eq!(hood.edges.len(), 2);
+
+        let hood = neighborhood(&idx, A, 3).unwrap();
+        assert_eq!(ids(&hood), vec![(A, 0), (B, 1), (C, 2), (D, 3)]);
+        // The self-link on A is not an edge.
+        assert!(hood.edges.iter().all(|e| e.from != e.to));
+    }
+
+    #[test]
+    fn test_depth_zero_and_disconnected_notes() {
+        let idx = chain();
+        let hood = neighborhood(&idx, B, 0).unwrap();
+        assert_eq!(ids(&hood), vec![(B, 0)]);
+        assert!(hood.edges.is_empty());
+
+        let hood = neighborhood(&idx, E, 2).unwrap();
+        assert_eq!(ids(&hood), vec![(E, 0)]);
+        assert!(hood.edges.is_empty());
+
+        assert!(neighborhood(&idx, "2603990000", 1).is_none());
+    }
+
+    #[test]
+    fn test_render_dot_highlights_center() {
+        let idx = chain();
+        let dot = render_dot(&neighborhood(&idx, B, 1).unwrap());
+        assert!(dot.starts_with("digraph \"neighborhood_2603020000\" {\n"));
+        assert!(dot.contains(
+            "\"2603020000\" [label=\"2603020000\\nNote \\\"2603020000\\\"\", style=\"filled,bold\""
+        ));
+        assert!(
+            dot.contains("  \"2603010000\" [label=\"2603010000\\nNote \\\"2603010000\\\"\"];\n")
+        );
+        assert!(dot.contains("  \"2603030000\" -> \"2603020000\";\n"));
+        assert!(!dot.contains(D));
+    }
+}
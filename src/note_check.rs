@@ -0,0 +1,126 @@
+/// Checks on a single note's text, for `zk-lsp check -` (note on stdin).
+///
+/// `check_note` runs the same diagnostics the LSP server publishes for an
+/// open buffer. Without an index only the note itself is checked: the
+/// metadata block and the title heading. With one, references are resolved
+/// against the wiki too. `render_note_diagnostics` formats the result as
+/// `-:<line>:<column>: <severity>: <message>` lines.
+use std::sync::Arc;
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use crate::config::WikiConfig;
+use crate::handlers::diagnostics;
+use crate::index::NoteIndex;
+use crate::parser::NoteDoc;
+
+/// Diagnostics for `content`, sorted by position.
+pub fn check_note(
+    content: &str,
+    config: &WikiConfig,
+    index: Option<&Arc<NoteIndex>>,
+) -> Vec<Diagnostic> {
+    let doc = NoteDoc::parse_with(&config.parser(), content.to_string());
+    let mut diags = diagnostics::get_schema_diagnostics(&doc, index);
+    if let Some(index) = index {
+        // The note's own ID, for self-reference checks; the text may not be
+        // saved anywhere yet.
+        let path = doc
+            .header
+            .as_ref()
+            .map(|h| config.note_path(&h.id).display().to_string())
+            .unwrap_or_default();
+        diags.extend(diagnostics::get_diagnostics(content, index, &path, config));
+    }
+    diags.sort_by_key(|d| (d.range.start.line, d.range.start.character));
+    diags
+}
+
+/// One `-:<line>:<column>: <severity>: <message>` line per diagnostic,
+/// 1-based, columns in UTF-16 units as LSP reports them.
+pub fn render_note_diagnostics(diags: &[Diagnostic]) -> String {
+    diags
+        .iter()
+        .map(|d| {
+            let severity = match d.severity {
+                Some(DiagnosticSeverity::ERROR) => "error",
+                Some(DiagnosticSeverity::WARNING) => "warning",
+                Some(DiagnosticSeverity::INFORMATION) => "info",
+                _ => "hint",
+            };
+            format!(
+                "-:{}:{}: {severity}: {}\n",
+                d.range.start.line + 1,
+                d.range.start.character + 1,
+                d.message
+            )
+        })
+        .collect()
+}
+
+/// True if any diagnostic is an error.
+pub fn has_errors(diags: &[Diagnostic]) -> bool {
+    diags
+        .iter()
+        .any(|d| d.severity == Some(DiagnosticSeverity::ERROR))
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::RwLock;
+
+    const BLOCK: &str = concat!(
+        "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n",
+        "  aliases = []\n  abstract = \"\"\n  keywords = []\n  generated = false\n",
+        "  checklist-status = \"none\"\n  relation = \"archived\"\n",
+        "  relation-target = [\"2603159999\"]\n  ```.text,\n))\n",
+    );
+
+    #[tokio::test]
+    async fn test_check_note_with_and_without_index() {
+        let tmp = std::env::temp_dir().join("zk_note_check");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::write(
+            tmp.join("note/2603150001.typ"),
+            "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n= Other <2603150001>\n",
+        )
+        .unwrap();
+        let config = WikiConfig::from_root(tmp.clone());
+        let index = Arc::new(NoteIndex::new(Arc::new(RwLock::new(config.clone()))));
+        index.rebuild_full().await.unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let content = format!("{BLOCK}= Plan <2603150000>\nSee @2603150001 and @2603150002.\n");
+        let header_only = check_note(&content, &config, None);
+        let full = check_note(&content, &config, Some(&index));
+
+        assert!(header_only.is_empty(), "{header_only:?}");
+        assert_eq!(
+            render_note_diagnostics(&full),
+            concat!(
+                "-:10:1: warning: Note @2603159999 does not exist in the index\n",
+                "-:14:21: error: Note @2603150002 does not exist. Did you mean @2603150001?\n",
+            )
+        );
+        assert!(has_errors(&full));
+    }
+
+    #[test]
+    fn test_check_note_reports_header_problems() {
+        let config = WikiConfig::from_root(std::path::PathBuf::from("/nonexistent"));
+        let unlabelled = check_note(&format!("{BLOCK}= Plan\n"), &config, None);
+        assert_eq!(
+            render_note_diagnostics(&unlabelled),
+            "-:11:1: error: Missing note title heading (`= Title <ID>`)\n"
+        );
+        let no_block = check_note("= Plan <2603150000>\n", &config, None);
+        assert_eq!(no_block.len(), 1);
+        assert!(has_errors(&no_block));
+    }
+}
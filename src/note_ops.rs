@@ -1,11 +1,190 @@
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
-use chrono::Local;
+use chrono::{Duration, Local};
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
 
 use crate::config::{WikiConfig, ZkLspConfig};
-use crate::link_gen;
+use crate::{link_gen, parser};
+
+/// Every `.typ` file directly in `note_dir` or in its date subdirectories
+/// (`YYYY/MM/`, see `SubdirLayout`), unsorted. Callers filter by ID and
+/// `is_ignored` themselves.
+pub async fn note_files(note_dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    note_files_following(note_dir, false).await
+}
+
+/// `note_files` under the config's `follow_symlinks`: the note files
+/// `link.typ` and the index are built from.
+pub async fn wiki_note_files(config: &WikiConfig) -> std::io::Result<Vec<PathBuf>> {
+    note_files_following(&config.note_dir, config.zk_config.follow_symlinks).await
+}
+
+/// `note_files`, also descending into symlinked date directories when
+/// `follow_symlinks` is set. Each directory is listed once by canonical
+/// path, so a link back to an enclosing directory cannot loop.
+async fn note_files_following(
+    note_dir: &Path,
+    follow_symlinks: bool,
+) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![note_dir.to_path_buf()];
+    let mut seen = HashSet::new();
+    while let Some(dir) = dirs.pop() {
+        if follow_symlinks && !seen.insert(fs::canonicalize(&dir).await?) {
+            continue;
+        }
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let file_type = entry.file_type().await?;
+            let is_dir = if file_type.is_symlink() && follow_symlinks {
+                fs::metadata(&path).await.is_ok_and(|m| m.is_dir())
+            } else {
+                file_type.is_dir()
+            };
+            if is_dir {
+                if is_date_dir(&path) {
+                    dirs.push(path);
+                }
+            } else if path.extension().and_then(|e| e.to_str()) == Some("typ") {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// The files `NoteIndex::rebuild_full` tries to index: `wiki_note_files`
+/// minus ignored files and files whose stem is not a note ID.
+pub async fn indexable_note_files(config: &WikiConfig) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = wiki_note_files(config).await?;
+    paths.retain(|path| !config.is_ignored(path) && parser::note_id_from_path(path).is_some());
+    Ok(paths)
+}
+
+/// Read a note as text. A file over `max_bytes` is logged and not read. A
+/// file that is not valid UTF-8 is logged; with `lossy` its bad bytes become
+/// U+FFFD. Both are otherwise `InvalidData` errors.
+pub async fn read_note_text(path: &Path, lossy: bool, max_bytes: u64) -> std::io::Result<String> {
+    if let Some(len) = oversized(path, max_bytes).await {
+        tracing::warn!(
+            "{} is {len} bytes, over max_note_bytes ({max_bytes}); skipped",
+            path.display()
+        );
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{len} bytes, over max_note_bytes ({max_bytes})"),
+        ));
+    }
+    let bytes = fs::read(path).await?;
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok(text),
+        Err(e) => {
+            tracing::warn!(
+                "{} is not valid UTF-8{}",
+                path.display(),
+                if lossy {
+                    "; reading lossily"
+                } else {
+                    "; skipped"
+                }
+            );
+            if lossy {
+                Ok(String::from_utf8_lossy(e.as_bytes()).into_owned())
+            } else {
+                Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            }
+        }
+    }
+}
+
+/// Size of `path` if it is larger than `max_bytes`, checked from metadata
+/// without reading the file.
+pub async fn oversized(path: &Path, max_bytes: u64) -> Option<u64> {
+    let len = fs::metadata(path).await.ok()?.len();
+    (len > max_bytes).then_some(len)
+}
+
+/// True if `path` lies in `note_dir` itself or one of its date subdirectories.
+pub fn is_in_note_dir(note_dir: &Path, path: &Path) -> bool {
+    let Some(rel) = path.parent().and_then(|p| p.strip_prefix(note_dir).ok()) else {
+        return false;
+    };
+    rel.components()
+        .all(|c| c.as_os_str().to_str().is_some_and(is_date_component))
+}
+
+fn is_date_dir(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(is_date_component)
+}
+
+/// What is at the configured note directory, checked before indexing so a
+/// misconfigured wiki root is reported instead of silently indexing nothing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NoteDirState {
+    /// Nothing exists at the path.
+    Missing,
+    /// The path exists but cannot be listed (not a directory, no permission).
+    Inaccessible(String),
+    /// A readable directory without any `.typ` files.
+    Empty,
+    /// A readable directory with this many `.typ` files.
+    Ready(usize),
+}
+
+impl NoteDirState {
+    pub async fn probe(note_dir: &Path) -> Self {
+        match fs::metadata(note_dir).await {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::Missing,
+            Err(e) => return Self::Inaccessible(e.to_string()),
+            Ok(meta) if !meta.is_dir() => return Self::Inaccessible("not a directory".into()),
+            Ok(_) => {}
+        }
+        match note_files(note_dir).await {
+            Ok(files) if files.is_empty() => Self::Empty,
+            Ok(files) => Self::Ready(files.len()),
+            Err(e) => Self::Inaccessible(e.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::Inaccessible(_) => "inaccessible",
+            Self::Empty => "empty",
+            Self::Ready(_) => "ok",
+        }
+    }
+
+    /// True when notes can be read from the directory (even if there are none).
+    pub fn is_usable(&self) -> bool {
+        matches!(self, Self::Empty | Self::Ready(_))
+    }
+
+    /// A one-line explanation for the user, or `None` when there is nothing to report.
+    pub fn describe(&self, note_dir: &Path) -> Option<String> {
+        let dir = note_dir.display();
+        match self {
+            Self::Missing => Some(format!(
+                "note directory {dir} does not exist; check --wiki-root, WIKI_ROOT or note_dir"
+            )),
+            Self::Inaccessible(e) => Some(format!("note directory {dir} is not readable: {e}")),
+            Self::Empty => Some(format!("note directory {dir} contains no notes yet")),
+            Self::Ready(_) => None,
+        }
+    }
+}
+
+/// `YYYY` or `MM`: a path component of the `yearMonth` layout.
+fn is_date_component(name: &str) -> bool {
+    matches!(name.len(), 2 | 4) && name.bytes().all(|b| b.is_ascii_digit())
+}
 
 /// Render a TOML default value as an inline TOML string.
 fn toml_default_inline(v: &toml::Value) -> String {
@@ -71,11 +250,12 @@ pub fn build_metadata_block(config: &ZkLspConfig) -> String {
     lines.join("\n")
 }
 
-fn build_note_content(id: &str, config: &WikiConfig) -> String {
+pub(crate) fn build_note_content(id: &str, title: &str, config: &WikiConfig) -> String {
     let metadata_block = build_metadata_block(&config.zk_config);
     if let Some(tmpl) = &config.zk_config.new_note_template {
         return tmpl
             .replace("{{id}}", id)
+            .replace("{{title}}", title)
             .replace("{{metadata}}", &metadata_block);
     }
     format!(
@@ -83,38 +263,272 @@ fn build_note_content(id: &str, config: &WikiConfig) -> String {
          {metadata_block}\n\
          #show: zettel.with(metadata: zk-metadata)\n\
          \n\
-         =  <{id}>\n"
+         = {title} <{id}>\n"
     )
 }
 
 /// Create a new note with the current timestamp as ID.
 /// Returns the path to the new file.
+/// Fails instead of reusing a note that already has this minute's ID.
 pub async fn create_note(config: &WikiConfig) -> Result<PathBuf> {
     let id = Local::now().format("%y%m%d%H%M").to_string();
-    fs::create_dir_all(&config.note_dir).await?;
+    create_fresh_note(config, &id, "").await
+}
 
-    let path = config.note_dir.join(format!("{id}.typ"));
-    if !path.exists() {
-        let content = build_note_content(&id, config);
-        fs::write(&path, &content)
-            .await
-            .with_context(|| format!("writing note {}", path.display()))?;
-    }
+/// Find or create today's daily note, whose ID is today's date at 00:00
+/// (local time) and whose title is the date.
+pub async fn daily_note(config: &WikiConfig) -> Result<PathBuf> {
+    let today = Local::now().date_naive();
+    let id = today.format("%y%m%d0000").to_string();
+    let title = today.format("%Y-%m-%d").to_string();
+    create_note_with_id(config, &id, &title).await
+}
+
+/// Create a note with the given ID and title, unless it already exists.
+/// Either way the note gets a link.typ entry. Returns the path to the note.
+pub async fn create_note_with_id(config: &WikiConfig, id: &str, title: &str) -> Result<PathBuf> {
+    let path = match config.locate_note(id) {
+        Some(existing) => existing,
+        None => {
+            write_new_note(config, id, title).await?;
+            config.note_path(id)
+        }
+    };
+    link_gen::add_entry(id, config).await?;
+    Ok(path)
+}
 
-    link_gen::add_entry(&id, config).await?;
+/// Create a note with the given ID and title; fails if a note with that ID
+/// already exists, which is left untouched.
+pub async fn create_fresh_note(config: &WikiConfig, id: &str, title: &str) -> Result<PathBuf> {
+    let path = config.note_path(id);
+    if !write_new_note(config, id, title).await? {
+        let existing = config.locate_note(id).unwrap_or(path);
+        anyhow::bail!(
+            "note {id} already exists at {}; refusing to overwrite it",
+            existing.display()
+        );
+    }
+    link_gen::add_entry(id, config).await?;
     Ok(path)
 }
 
+/// Write a new note file at `note_path`. Returns `false` without touching
+/// anything when the note already exists, here or under the other layout;
+/// the create itself is atomic, so a concurrent writer cannot be clobbered.
+async fn write_new_note(config: &WikiConfig, id: &str, title: &str) -> Result<bool> {
+    ensure_valid_id(id)?;
+    if config.locate_note(id).is_some() {
+        return Ok(false);
+    }
+    let path = config.note_path(id);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    let mut file = match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .await
+    {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => return Ok(false),
+        Err(e) => return Err(e).with_context(|| format!("creating note {}", path.display())),
+    };
+    let content = build_note_content(id, title, config);
+    file.write_all(content.as_bytes())
+        .await
+        .with_context(|| format!("writing note {}", path.display()))?;
+    Ok(true)
+}
+
+/// Result of `split_note`: the note that was created and the edit that
+/// replaces the moved lines in the original with a reference to it.
+#[derive(Debug, Clone)]
+pub struct SplitNote {
+    pub id: String,
+    pub path: PathBuf,
+    pub edit: TextEdit,
+}
+
+/// Move body lines `start_line..=end_line` (0-based) of `content` into a new
+/// note titled `title`, created with the usual header scaffold and the next
+/// free minute ID from now. The original file is not touched: the returned
+/// edit replaces the lines with `@newId` and is applied by the caller.
+///
+/// Fails unless the range lies entirely below the title heading.
+pub async fn split_note(
+    config: &WikiConfig,
+    content: &str,
+    start_line: usize,
+    end_line: usize,
+    title: &str,
+) -> Result<SplitNote> {
+    let header =
+        parser::parse_header(content).context("not a TOML note; run `zk-lsp migrate` first")?;
+    let lines: Vec<&str> = parser::split_lines(content).collect();
+    if start_line <= header.title_line_idx || end_line < start_line || end_line >= lines.len() {
+        anyhow::bail!(
+            "lines {start_line}..={end_line} are not within the body (lines {}..{})",
+            header.title_line_idx + 1,
+            lines.len()
+        );
+    }
+
+    let mut stamp = Local::now();
+    let mut id = stamp.format("%y%m%d%H%M").to_string();
+    while config.locate_note(&id).is_some() {
+        stamp += Duration::minutes(1);
+        id = stamp.format("%y%m%d%H%M").to_string();
+    }
+    let path = create_fresh_note(config, &id, title).await?;
+    let mut new_content = fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("reading note {}", path.display()))?;
+    new_content.push('\n');
+    for line in &lines[start_line..=end_line] {
+        new_content.push_str(line);
+        new_content.push('\n');
+    }
+    // Atomic write: tmp → rename
+    let tmp = path.with_extension("typ.tmp");
+    fs::write(&tmp, new_content.as_bytes()).await?;
+    fs::rename(&tmp, &path).await?;
+
+    // Replace whole lines; the last line of a file without a trailing
+    // newline ends at its last character instead of the next line.
+    let reference = config.ref_style().format(&id);
+    let (end, new_text) = if end_line + 1 < lines.len() || content.ends_with(['\n', '\r']) {
+        (
+            Position::new(end_line as u32 + 1, 0),
+            format!("{reference}\n"),
+        )
+    } else {
+        let last = lines[end_line];
+        (
+            Position::new(end_line as u32, parser::byte_to_utf16(last, last.len())),
+            reference,
+        )
+    };
+    Ok(SplitNote {
+        edit: TextEdit {
+            range: Range::new(Position::new(start_line as u32, 0), end),
+            new_text,
+        },
+        id,
+        path,
+    })
+}
+
+fn ensure_valid_id(id: &str) -> Result<()> {
+    if !parser::is_valid_id(id) {
+        anyhow::bail!(
+            "invalid note ID {id:?}: expected {} ASCII digits",
+            parser::ID_DIGITS
+        );
+    }
+    Ok(())
+}
+
 /// Delete a note and remove its entry from link.typ.
-pub async fn delete_note(id: &str, config: &WikiConfig) -> Result<()> {
-    let path = config.note_dir.join(format!("{id}.typ"));
-    if path.exists() {
-        fs::remove_file(&path)
-            .await
-            .with_context(|| format!("deleting note {}", path.display()))?;
+///
+/// With `trash`, the file is moved to `WikiConfig::trash_path` instead and
+/// the new location is returned, so `restore_note` can bring it back. If
+/// the trash already holds that ID, the copy is named
+/// `<ID>.<timestamp>.typ` so the older one is kept too.
+pub async fn delete_note(id: &str, config: &WikiConfig, trash: bool) -> Result<Option<PathBuf>> {
+    ensure_valid_id(id)?;
+    let mut trashed = None;
+    if let Some(path) = config.locate_note(id) {
+        if trash {
+            let dest = free_trash_path(config, id);
+            if let Some(dir) = dest.parent() {
+                fs::create_dir_all(dir)
+                    .await
+                    .with_context(|| format!("creating {}", dir.display()))?;
+            }
+            fs::rename(&path, &dest)
+                .await
+                .with_context(|| format!("moving {} to {}", path.display(), dest.display()))?;
+            trashed = Some(dest);
+        } else {
+            fs::remove_file(&path)
+                .await
+                .with_context(|| format!("deleting note {}", path.display()))?;
+        }
     }
     link_gen::remove_entry(id, config).await?;
-    Ok(())
+    Ok(trashed)
+}
+
+/// Where to trash `id`: `WikiConfig::trash_path`, or beside it as
+/// `<ID>.<timestamp>.typ` (`-<n>` added within the same second) when that
+/// is taken.
+fn free_trash_path(config: &WikiConfig, id: &str) -> PathBuf {
+    let plain = config.trash_path(id);
+    if !plain.exists() {
+        return plain;
+    }
+    let dir = plain.parent().map(Path::to_path_buf).unwrap_or_default();
+    let stamp = Local::now().format("%Y%m%d%H%M%S");
+    (0..)
+        .map(|n| match n {
+            0 => dir.join(format!("{id}.{stamp}.typ")),
+            n => dir.join(format!("{id}.{stamp}-{n}.typ")),
+        })
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// The most recently trashed copy of `id`, if any.
+fn newest_trashed(config: &WikiConfig, id: &str) -> Option<PathBuf> {
+    let plain = config.trash_path(id);
+    let dir = plain.parent()?;
+    let prefix = format!("{id}.");
+    let stamped = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            let stamp = name.strip_prefix(&prefix)?.strip_suffix(".typ")?;
+            let (time, n) = match stamp.split_once('-') {
+                Some((time, n)) => (time.to_string(), n.parse::<u32>().ok()?),
+                None => (stamp.to_string(), 0),
+            };
+            time.bytes()
+                .all(|b| b.is_ascii_digit())
+                .then(|| ((time, n), dir.join(&name)))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, path)| path);
+    stamped.or_else(|| plain.is_file().then_some(plain))
+}
+
+/// Move a note back from `.trash/` to where the current layout puts it and
+/// add it to link.typ again. Of several trashed copies, the newest comes
+/// back. Fails if the note is not in the trash or its ID is in use again.
+pub async fn restore_note(id: &str, config: &WikiConfig) -> Result<PathBuf> {
+    ensure_valid_id(id)?;
+    let Some(src) = newest_trashed(config, id) else {
+        let trash = config.trash_path(id);
+        anyhow::bail!(
+            "note {id} is not in {}",
+            trash.parent().unwrap_or(&trash).display()
+        );
+    };
+    if let Some(existing) = config.locate_note(id) {
+        anyhow::bail!("note {id} already exists at {}", existing.display());
+    }
+    let dest = config.note_path(id);
+    if let Some(dir) = dest.parent() {
+        fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("creating {}", dir.display()))?;
+    }
+    fs::rename(&src, &dest)
+        .await
+        .with_context(|| format!("restoring {} to {}", src.display(), dest.display()))?;
+    link_gen::add_entry(id, config).await?;
+    Ok(dest)
 }
 
 #[cfg(test)]
@@ -126,11 +540,27 @@ mod tests {
     fn config_with_fields(fields: Vec<MetadataFieldConfig>) -> ZkLspConfig {
         ZkLspConfig {
             new_note_template: None,
+            note_dir: None,
+            ignore: Vec::new(),
             metadata: MetadataConfig { fields },
             hooks: Vec::new(),
             reconcile_rules: Vec::new(),
             disable_default_hooks: false,
             disable_default_reconcile_rules: false,
+            import_marker: None,
+            label_refs: false,
+            subdir_layout: None,
+            trash_on_delete: false,
+            reference_resolution: None,
+            follow_symlinks: false,
+            wikis: Default::default(),
+            ref_style: None,
+            title_links: false,
+            include_aliases_in_link_typ: false,
+            lossy_reads: false,
+            max_note_bytes: None,
+            checkbox_ref_depth: None,
+            format_whitespace: false,
         }
     }
 
@@ -184,6 +614,186 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_create_note_with_id_keeps_existing_note() {
+        let tmp = std::env::temp_dir().join("zk_note_ops_test_with_id");
+        let _ = std::fs::remove_dir_all(&tmp);
+        let config = WikiConfig::from_root(tmp.clone());
+
+        let path = create_note_with_id(&config, "2603150000", "2026-03-15")
+            .await
+            .unwrap();
+        let created = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, "edited").unwrap();
+        let again = create_note_with_id(&config, "2603150000", "2026-03-15")
+            .await
+            .unwrap();
+        let kept = std::fs::read_to_string(&again).unwrap();
+        let link_typ = std::fs::read_to_string(&config.link_file).unwrap_or_default();
+        let invalid = create_note_with_id(&config, "26031500", "x").await;
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert!(created.contains("= 2026-03-15 <2603150000>"));
+        assert_eq!(path, again);
+        assert_eq!(kept, "edited");
+        assert!(link_typ.contains("2603150000"));
+        assert!(invalid.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_create_fresh_note_refuses_occupied_id() {
+        let tmp = std::env::temp_dir().join("zk_note_ops_test_fresh");
+        let _ = std::fs::remove_dir_all(&tmp);
+        let config = WikiConfig::from_root(tmp.clone());
+
+        let path = create_fresh_note(&config, "2603150000", "First")
+            .await
+            .unwrap();
+        std::fs::write(&path, "edited").unwrap();
+        let again = create_fresh_note(&config, "2603150000", "Second").await;
+        let kept = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let err = again.unwrap_err().to_string();
+        assert!(err.contains("already exists"), "{err}");
+        assert_eq!(kept, "edited");
+    }
+
+    #[tokio::test]
+    async fn test_year_month_layout_creates_and_finds_dated_notes() {
+        let tmp = std::env::temp_dir().join("zk_note_ops_test_year_month");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::write(tmp.join("zk-lsp.toml"), "subdir_layout = \"yearMonth\"\n").unwrap();
+        // Made before the layout change: stays flat and is still found.
+        let flat = tmp.join("note/2602010000.typ");
+        std::fs::write(&flat, "old").unwrap();
+        let config = WikiConfig::from_root(tmp.clone());
+
+        let dated = create_fresh_note(&config, "2603150000", "New")
+            .await
+            .unwrap();
+        let existing = create_note_with_id(&config, "2602010000", "Old")
+            .await
+            .unwrap();
+        let mut files = note_files(&config.note_dir).await.unwrap();
+        files.sort();
+        let link_typ = std::fs::read_to_string(&config.link_file).unwrap();
+        let index = crate::index::NoteIndex::new(std::sync::Arc::new(tokio::sync::RwLock::new(
+            config.clone(),
+        )));
+        index.rebuild_full().await.unwrap();
+        let indexed = index.path_of("2603150000");
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(dated, tmp.join("note/2026/03/2603150000.typ"));
+        assert_eq!(existing, flat);
+        assert_eq!(files, vec![dated.clone(), flat.clone()]);
+        assert!(link_typ.contains("#zk_entry(\"2603150000\", \"note/2026/03/2603150000.typ\")"));
+        assert!(link_typ.contains("#zk_entry(\"2602010000\", \"note/2602010000.typ\")"));
+        assert_eq!(indexed, Some(dated.clone()));
+        assert!(is_in_note_dir(&config.note_dir, &dated));
+        assert!(is_in_note_dir(&config.note_dir, &flat));
+        assert!(!is_in_note_dir(
+            &config.note_dir,
+            &tmp.join("note/drafts/2603150000.typ")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_trash_and_restore_note() {
+        let tmp = std::env::temp_dir().join("zk_note_ops_test_trash");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let config = WikiConfig::from_root(tmp.clone());
+        let path = create_fresh_note(&config, "2603150000", "Keep")
+            .await
+            .unwrap();
+        create_fresh_note(&config, "2603150001", "Drop")
+            .await
+            .unwrap();
+
+        let trashed = delete_note("2603150000", &config, true).await.unwrap();
+        let in_trash = config.trash_path("2603150000").is_file();
+        let link_after_trash = std::fs::read_to_string(&config.link_file).unwrap();
+        let restored = restore_note("2603150000", &config).await.unwrap();
+        let link_after_restore = std::fs::read_to_string(&config.link_file).unwrap();
+        let again = restore_note("2603150000", &config).await;
+        let hard = delete_note("2603150001", &config, false).await.unwrap();
+        let hard_in_trash = config.trash_path("2603150001").exists();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(trashed, Some(tmp.join(".trash/2603150000.typ")));
+        assert!(in_trash);
+        assert!(!link_after_trash.contains("2603150000"));
+        assert_eq!(restored, path);
+        assert!(link_after_restore.contains("2603150000"));
+        assert!(again.unwrap_err().to_string().contains("not in"));
+        assert_eq!(hard, None);
+        assert!(!hard_in_trash);
+    }
+
+    #[tokio::test]
+    async fn test_trashing_an_id_twice_keeps_both_copies() {
+        let tmp = std::env::temp_dir().join("zk_note_ops_test_trash_twice");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let config = WikiConfig::from_root(tmp.clone());
+        let mut trashed = Vec::new();
+        for title in ["First", "Second", "Third"] {
+            create_fresh_note(&config, "2603150000", title)
+                .await
+                .unwrap();
+            trashed.push(
+                delete_note("2603150000", &config, true)
+                    .await
+                    .unwrap()
+                    .unwrap(),
+            );
+        }
+        let in_trash = std::fs::read_dir(tmp.join(".trash")).unwrap().count();
+        let mut restored_titles = Vec::new();
+        for _ in 0..3 {
+            let path = restore_note("2603150000", &config).await.unwrap();
+            let content = std::fs::read_to_string(&path).unwrap();
+            restored_titles.push(parser::parse_header(&content).unwrap().title);
+            std::fs::remove_file(&path).unwrap();
+        }
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(trashed[0], tmp.join(".trash/2603150000.typ"));
+        assert_ne!(trashed[1], trashed[2]);
+        assert_eq!(in_trash, 3);
+        assert_eq!(restored_titles, ["Third", "Second", "First"]);
+    }
+
+    #[tokio::test]
+    async fn test_note_dir_state_tells_missing_from_empty() {
+        let tmp = std::env::temp_dir().join("zk_note_ops_test_dir_state");
+        let _ = std::fs::remove_dir_all(&tmp);
+        let note_dir = tmp.join("note");
+
+        let missing = NoteDirState::probe(&note_dir).await;
+        std::fs::create_dir_all(&note_dir).unwrap();
+        let empty = NoteDirState::probe(&note_dir).await;
+        std::fs::write(note_dir.join("2603150000.typ"), "").unwrap();
+        let ready = NoteDirState::probe(&note_dir).await;
+        let file = NoteDirState::probe(&note_dir.join("2603150000.typ")).await;
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(missing, NoteDirState::Missing);
+        assert!(!missing.is_usable());
+        assert!(missing
+            .describe(&note_dir)
+            .unwrap()
+            .contains("does not exist"));
+        assert_eq!(empty, NoteDirState::Empty);
+        assert!(empty.is_usable());
+        assert_eq!(ready, NoteDirState::Ready(1));
+        assert_eq!(ready.describe(&note_dir), None);
+        assert!(matches!(file, NoteDirState::Inaccessible(_)));
+    }
+
     /// Extract the TOML content from between ```toml and ``` fences.
     fn extract_toml_from_block(block: &str) -> Option<String> {
         let lines: Vec<&str> = block.lines().collect();
@@ -197,4 +807,52 @@ mod tests {
         }
         Some(toml_lines.join("\n"))
     }
+
+    #[tokio::test]
+    async fn test_split_note_moves_body_lines() {
+        let tmp = std::env::temp_dir().join("zk_note_ops_test_split");
+        let _ = std::fs::remove_dir_all(&tmp);
+        let config = WikiConfig::from_root(tmp.clone());
+        let original = build_note_content("2603150000", "Big", &config)
+            + "Intro.\n== Details\nLong section.\nOutro.";
+        let title_line = parser::parse_header(&original).unwrap().title_line_idx;
+
+        let header_err = split_note(&config, &original, title_line, title_line + 1, "X").await;
+        let range_err = split_note(&config, &original, title_line + 3, title_line + 2, "X").await;
+        let split = split_note(
+            &config,
+            &original,
+            title_line + 2,
+            title_line + 3,
+            "Details",
+        )
+        .await
+        .unwrap();
+        let created = std::fs::read_to_string(&split.path).unwrap();
+        let tail = split_note(&config, &original, title_line + 4, title_line + 4, "Outro")
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert!(header_err.is_err());
+        assert!(range_err.is_err());
+        let header = parser::parse_header(&created).unwrap();
+        assert_eq!(header.id, split.id);
+        assert_eq!(header.title, "Details");
+        assert!(created.ends_with("\n== Details\nLong section.\n"));
+        assert_eq!(
+            split.edit.range,
+            Range::new(
+                Position::new(title_line as u32 + 2, 0),
+                Position::new(title_line as u32 + 4, 0)
+            )
+        );
+        assert_eq!(split.edit.new_text, format!("@{}\n", split.id));
+
+        // A second split in the same minute takes the next free ID; the
+        // unterminated last line is replaced up to its end.
+        assert_ne!(tail.id, split.id);
+        assert_eq!(tail.edit.range.end, Position::new(title_line as u32 + 4, 6));
+        assert_eq!(tail.edit.new_text, format!("@{}", tail.id));
+    }
 }
@@ -1,13 +1,621 @@
 /// Stateless parsing of Zettelkasten note headers and content.
+use std::ops::RangeInclusive;
+
 use once_cell::sync::Lazy;
 use regex::Regex;
 
-pub(crate) static RE_ID_REF: Lazy<Regex> = Lazy::new(|| Regex::new(r"@(\d{10})").unwrap());
-pub(crate) static RE_TITLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^=\s+.*<(\d{10})>").unwrap());
-pub(crate) static RE_EVO: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"#evolution_link\s*\(\s*<(\d{10})>\s*\)").unwrap());
-pub(crate) static RE_ALT: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"#alternative_link\s*\(\s*<(\d{10})>\s*\)").unwrap());
+use crate::config::RefStyle;
+
+/// `@` followed by ten digits. Group 2 captures a trailing label character
+/// (Typst labels may continue with letters, digits, `-`, `_`, or an inner `.`
+/// / `:`); a match with group 2 set is a Typst `@label` ref, not a note ref.
+/// Use `Parser::id_refs` rather than iterating this regex directly.
+const ID_REF_PATTERN: &str = r"@(\d{10})([\w-]|[.:][\w-])?";
+/// A Typst label naming a note, `<YYMMDDHHMM>`, optionally written `@<ID>`.
+/// Only treated as a reference when `ParserConfig::label_refs` is set.
+const LABEL_REF_PATTERN: &str = r"@?<(\d{10})>";
+/// `[[YYMMDDHHMM]]`, the note reference under `ref_style = "wikilink"`.
+const WIKILINK_REF_PATTERN: &str = r"\[\[(\d{10})\]\]";
+/// `@<wiki>:<ID>`, a note in another wiki listed under `[wikis]`. Group 3
+/// plays the same role as in `ID_REF_PATTERN`.
+const CROSS_REF_PATTERN: &str = r"@([A-Za-z][\w-]*):(\d{10})([\w-]|[.:][\w-])?";
+/// `[[Note Title]]`, a link by title or alias under `title_links`.
+const TITLE_LINK_PATTERN: &str = r"\[\[([^\[\]\n]+)\]\]";
+const TITLE_PATTERN: &str = r"^=\s+.*<(\d{10})>";
+const EVO_PATTERN: &str = r"#evolution_link\s*\(\s*<(\d{10})>\s*\)";
+const ALT_PATTERN: &str = r"#alternative_link\s*\(\s*<(\d{10})>\s*\)";
+const TOPIC_TAG_PATTERN: &str = r"#((?:tag|topic)\.[\w-]+)";
+
+/// `#tag.*` names that encode status or relation, not topics.
+const STATUS_TAGS: &[&str] = &[
+    "tag.todo",
+    "tag.wip",
+    "tag.done",
+    "tag.archived",
+    "tag.legacy",
+];
+
+/// Default import-anchor pattern: the line written into every new note.
+pub const DEFAULT_IMPORT_MARKER: &str = r#"^#import "\.\./include\.typ": \*$"#;
+
+/// Number of digits in a note ID (`YYMMDDHHMM`).
+pub const ID_DIGITS: usize = 10;
+
+/// True if `s` is a well-formed note ID: exactly `ID_DIGITS` ASCII digits.
+///
+/// This matches what `Parser` accepts (`\d` is ASCII-only there).
+pub fn is_valid_id(s: &str) -> bool {
+    s.len() == ID_DIGITS && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// True if `s` has the shape of a note ID but uses non-ASCII digits
+/// (e.g. full-width `２６０３１１０９００`), so it is never treated as a note.
+pub fn is_lookalike_id(s: &str) -> bool {
+    !is_valid_id(s) && s.chars().count() == ID_DIGITS && s.chars().all(char::is_numeric)
+}
+
+/// `Parser::id_refs` with the default configuration.
+pub fn id_refs(s: &str) -> impl Iterator<Item = (std::ops::Range<usize>, &str)> {
+    default_parser().id_refs(s)
+}
+
+/// Creation time encoded in a note ID (`YYMMDDHHMM`).
+/// Returns `None` for IDs that are not valid timestamps (e.g. month 13).
+pub fn id_to_datetime(id: &str) -> Option<chrono::NaiveDateTime> {
+    if !is_valid_id(id) {
+        return None;
+    }
+    chrono::NaiveDateTime::parse_from_str(id, "%y%m%d%H%M").ok()
+}
+
+/// Parse a date range bound: `YYYY-MM-DD`, `YYYY-MM-DD HH:MM` or `YYYY-MM-DDTHH:MM`.
+///
+/// A date without a time is the start of that day, or — when `end` is set —
+/// the start of the next day, so `end` bounds include the whole day.
+pub fn parse_date_bound(s: &str, end: bool) -> Option<chrono::NaiveDateTime> {
+    let s = s.trim();
+    for fmt in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"] {
+        if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(s, fmt) {
+            return Some(dt);
+        }
+    }
+    let date = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+    let date = if end { date.succ_opt()? } else { date };
+    date.and_hms_opt(0, 0, 0)
+}
+
+/// Note ID of a `<ID>.typ` path, or `None` if the file is not a note.
+pub fn note_id_from_path(path: &std::path::Path) -> Option<&str> {
+    if path.extension().and_then(|e| e.to_str()) != Some("typ") {
+        return None;
+    }
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .filter(|s| is_valid_id(s))
+}
+
+// ---------------------------------------------------------------------------
+// Configured parser
+// ---------------------------------------------------------------------------
+
+/// Wiki-specific parsing settings, taken from `ZkLspConfig`.
+#[derive(Debug, Clone)]
+pub struct ParserConfig {
+    /// Matched against trimmed lines to find a note's `#import` anchor.
+    pub import_marker: Regex,
+    /// Also count body `<ID>` / `@<ID>` labels as note references.
+    pub label_refs: bool,
+    /// Whether body references are written `@ID` or `[[ID]]`.
+    pub ref_style: RefStyle,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            import_marker: Regex::new(DEFAULT_IMPORT_MARKER).unwrap(),
+            label_refs: false,
+            ref_style: RefStyle::At,
+        }
+    }
+}
+
+impl ParserConfig {
+    pub fn from_zk_config(zk_config: &crate::config::ZkLspConfig) -> Self {
+        let mut config = Self::default();
+        if let Some(re) = &zk_config.import_marker {
+            config.import_marker = re.clone();
+        }
+        config.label_refs = zk_config.label_refs;
+        config.ref_style = zk_config.ref_style.unwrap_or_default();
+        config
+    }
+}
+
+/// Note parser holding its configuration and compiled regexes.
+///
+/// The free functions of this module (`parse_header`, `find_all_refs`, …)
+/// are wrappers over `default_parser()`.
+#[derive(Debug, Clone)]
+pub struct Parser {
+    pub config: ParserConfig,
+    re_id_ref: Regex,
+    re_label_ref: Regex,
+    re_wikilink_ref: Regex,
+    pub(crate) re_title: Regex,
+    pub(crate) re_evo: Regex,
+    pub(crate) re_alt: Regex,
+    re_topic_tag: Regex,
+}
+
+static DEFAULT_PARSER: Lazy<Parser> = Lazy::new(|| Parser::new(ParserConfig::default()));
+
+/// Shared parser with the default configuration.
+pub fn default_parser() -> &'static Parser {
+    &DEFAULT_PARSER
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        DEFAULT_PARSER.clone()
+    }
+}
+
+impl Parser {
+    pub fn new(config: ParserConfig) -> Self {
+        Self {
+            config,
+            re_id_ref: Regex::new(ID_REF_PATTERN).unwrap(),
+            re_label_ref: Regex::new(LABEL_REF_PATTERN).unwrap(),
+            re_wikilink_ref: Regex::new(WIKILINK_REF_PATTERN).unwrap(),
+            re_title: Regex::new(TITLE_PATTERN).unwrap(),
+            re_evo: Regex::new(EVO_PATTERN).unwrap(),
+            re_alt: Regex::new(ALT_PATTERN).unwrap(),
+            re_topic_tag: Regex::new(TOPIC_TAG_PATTERN).unwrap(),
+        }
+    }
+
+    /// True if `line` is the note's `#import` anchor line.
+    pub fn is_import_line(&self, line: &str) -> bool {
+        self.config.import_marker.is_match(line.trim())
+    }
+
+    /// Note-ID references (`@YYMMDDHHMM`) in `s`, as (byte range of `@ID`, ID).
+    ///
+    /// Typst `@label` references that merely start with ten digits, such as
+    /// `@2602082037-fig` or `@26020820371`, are skipped; so are ordinary labels
+    /// like `@figure-1`. A trailing `.` or `:` ends the reference, as in Typst.
+    ///
+    /// Under `RefStyle::Wikilink` the references are `[[ID]]` instead (range
+    /// covers the brackets) and `@ID` is not a reference.
+    ///
+    /// With `label_refs`, `<ID>` and `@<ID>` labels count too (range covers
+    /// the whole label), except on heading lines, where `<ID>` names the
+    /// heading itself rather than linking to it.
+    pub fn id_refs<'a>(
+        &'a self,
+        s: &'a str,
+    ) -> impl Iterator<Item = (std::ops::Range<usize>, &'a str)> + 'a {
+        self.id_refs_in(s, s.trim_start().starts_with('='))
+    }
+
+    /// `id_refs` over `s`, a comment-free segment of a line; `heading` says
+    /// whether the whole line is a heading.
+    fn id_refs_in<'a>(
+        &'a self,
+        s: &'a str,
+        heading: bool,
+    ) -> impl Iterator<Item = (std::ops::Range<usize>, &'a str)> + 'a {
+        let mut refs: Vec<_> = match self.config.ref_style {
+            RefStyle::At => self
+                .re_id_ref
+                .captures_iter(s)
+                .filter_map(|c| {
+                    if c.get(2).is_some() {
+                        return None;
+                    }
+                    let id = c.get(1).unwrap();
+                    Some((c.get(0).unwrap().start()..id.end(), id.as_str()))
+                })
+                .collect(),
+            RefStyle::Wikilink => self
+                .re_wikilink_ref
+                .captures_iter(s)
+                .map(|c| (c.get(0).unwrap().range(), c.get(1).unwrap().as_str()))
+                .collect(),
+        };
+        if self.config.label_refs && !heading {
+            refs.extend(self.re_label_ref.captures_iter(s).map(|c| {
+                let label = c.get(0).unwrap();
+                (label.range(), c.get(1).unwrap().as_str())
+            }));
+            refs.sort_by_key(|(range, _)| range.start);
+        }
+        refs.into_iter()
+    }
+
+    /// The ID of the reference in `line` whose range contains byte column
+    /// `col` (end inclusive, so a cursor just after the reference counts).
+    pub fn ref_at<'a>(&'a self, line: &'a str, col: usize) -> Option<&'a str> {
+        self.id_refs(line)
+            .find(|(range, _)| (range.start..=range.end).contains(&col))
+            .map(|(_, id)| id)
+    }
+
+    /// Parse the header of a TOML-format note.
+    /// Returns `None` for legacy comment-format notes (run `zk-lsp migrate` first).
+    pub fn parse_header(&self, content: &str) -> Option<NoteHeader> {
+        self.parse_header_detailed(content).ok()
+    }
+
+    /// `parse_header`, saying why a note without a header was rejected.
+    pub fn parse_header_detailed(&self, content: &str) -> Result<NoteHeader, ParseError> {
+        #[cfg(test)]
+        parse_counts::HEADERS.set(parse_counts::HEADERS.get() + 1);
+        let lines: Vec<&str> = split_lines(content).collect();
+
+        let Some(block) = find_toml_metadata_block(content) else {
+            return Err(if content.trim().is_empty() {
+                ParseError::Empty
+            } else if lines
+                .iter()
+                .any(|l| l.trim_start().starts_with("#let zk-metadata"))
+            {
+                ParseError::UnterminatedMetadata
+            } else if !lines.iter().any(|l| self.is_import_line(l)) {
+                ParseError::NoImport
+            } else {
+                ParseError::NoMetadata
+            });
+        };
+        let parsed = parse_toml_metadata(&block.toml_content).unwrap_or_default();
+
+        // Title line is the first heading after the TOML block
+        let (before, after) = lines.split_at(block.end_line + 1);
+        let Some(title_line_idx) = after
+            .iter()
+            .position(|l| self.re_title.is_match(l))
+            .map(|offset| block.end_line + 1 + offset)
+        else {
+            return Err(if before.iter().any(|l| self.re_title.is_match(l)) {
+                ParseError::TitleBeforeMetadata
+            } else if after.iter().any(|l| l.starts_with("= ")) {
+                ParseError::UnlabelledTitle
+            } else {
+                ParseError::NoTitle
+            });
+        };
+
+        let title_line = lines[title_line_idx];
+        let captures = self
+            .re_title
+            .captures(title_line)
+            .ok_or(ParseError::NoTitle)?;
+        let id = captures
+            .get(1)
+            .ok_or(ParseError::UnlabelledTitle)?
+            .as_str()
+            .to_string();
+        let title = captures
+            .get(0)
+            .ok_or(ParseError::NoTitle)?
+            .as_str()
+            .trim_start_matches('=')
+            .trim()
+            .rsplit_once('<')
+            .map(|(t, _)| t.trim().to_string())
+            .unwrap_or_default();
+
+        let archived = parsed.relation == Relation::Archived;
+        let legacy = parsed.relation == Relation::Legacy;
+        let alt_id = if archived {
+            parsed.relation_target.first().cloned()
+        } else {
+            None
+        };
+        let evo_id = if legacy {
+            parsed.relation_target.first().cloned()
+        } else {
+            None
+        };
+        let checklist_status = parsed.checklist_status.clone();
+        let todos = self.count_todos(content);
+        let manual_status = if todos.completed + todos.incomplete > 0 {
+            None
+        } else {
+            match checklist_status {
+                ChecklistStatus::Todo => Some(StatusTag::Todo),
+                ChecklistStatus::Wip => Some(StatusTag::Wip),
+                ChecklistStatus::Done => Some(StatusTag::Done),
+                ChecklistStatus::None => None,
+            }
+        };
+
+        Ok(NoteHeader {
+            id,
+            title,
+            archived,
+            legacy,
+            alt_id,
+            evo_id,
+            relation_target: parsed.relation_target.clone(),
+            aliases: parsed.aliases,
+            abstract_text: parsed.abstract_text,
+            keywords: parsed.keywords,
+            generated: parsed.generated,
+            auto_status: parsed.auto_status,
+            tag_line_idx: None,
+            title_line_idx,
+            metadata_block: Some(block),
+            checklist_status: Some(checklist_status),
+            manual_status,
+            tags: self.topic_tags(&lines[title_line_idx + 1..]),
+        })
+    }
+
+    /// Distinct `#tag.*` / `#topic.*` tokens in `lines` (outside fenced code),
+    /// in order of appearance, without the `#` and without status tags.
+    pub fn topic_tags(&self, lines: &[&str]) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        let mut in_fence = false;
+        for line in lines {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+                continue;
+            }
+            if in_fence {
+                continue;
+            }
+            for cap in self.re_topic_tag.captures_iter(line) {
+                let tag = &cap[1];
+                if !STATUS_TAGS.contains(&tag) && !tags.iter().any(|t| t == tag) {
+                    tags.push(tag.to_string());
+                }
+            }
+        }
+        tags
+    }
+
+    /// Parse all checklist items from `content`, skipping fenced code blocks.
+    /// Items with a note reference (`id_refs`) in their text become `Ref` items;
+    /// all others are `Local`. `RefTarget.byte_start`/`byte_end` are byte offsets
+    /// of the reference within the full line.
+    pub fn parse_checklist_items(&self, content: &str) -> Vec<ChecklistItem> {
+        let mut items = Vec::new();
+        let mut in_fence = false;
+
+        for (line_idx, line) in split_lines(content).enumerate() {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") {
+                in_fence = !in_fence;
+                continue;
+            }
+            if in_fence {
+                continue;
+            }
+            if !(trimmed.starts_with("- [") && trimmed.len() >= 5) {
+                continue;
+            }
+            let marker = trimmed.chars().nth(3).unwrap_or(' ');
+            if marker != 'x' && marker != 'X' && marker != ' ' {
+                continue;
+            }
+            let checked = marker == 'x' || marker == 'X';
+            let indent = line.len() - trimmed.len();
+            // prefix_len: bytes before the checklist body (indent + "- [x] ")
+            let prefix_len = indent + 6;
+            // text after `- [x] ` (or `- [ ] `)
+            let body = trimmed.get(6..).unwrap_or("");
+            let text = body.to_string();
+            let targets: Vec<RefTarget> = self
+                .id_refs(body)
+                .map(|(range, id)| RefTarget {
+                    target_id: id.to_string(),
+                    byte_start: (prefix_len + range.start) as u32,
+                    byte_end: (prefix_len + range.end) as u32,
+                })
+                .collect();
+            let kind = if targets.is_empty() {
+                ChecklistItemKind::Local
+            } else {
+                ChecklistItemKind::Ref { targets }
+            };
+            items.push(ChecklistItem {
+                checked,
+                kind,
+                text,
+                line_idx,
+                indent,
+            });
+        }
+        items
+    }
+
+    /// Count todo items, skipping code blocks (``` fence heuristic).
+    pub fn count_todos(&self, content: &str) -> TodoStatus {
+        let mut status = TodoStatus::default();
+        let mut in_code_block = false;
+
+        for line in split_lines(content) {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") {
+                in_code_block = !in_code_block;
+                continue;
+            }
+            if in_code_block {
+                continue;
+            }
+            if trimmed.starts_with("- [") && trimmed.len() >= 5 {
+                let marker = trimmed.chars().nth(3).unwrap_or(' ');
+                if marker == 'x' || marker == 'X' {
+                    status.completed += 1;
+                } else if marker == ' ' {
+                    status.incomplete += 1;
+                }
+            }
+        }
+        status
+    }
+
+    /// Find all @ID occurrences in content (10-digit IDs).
+    /// `start_char` / `end_char` are **byte** offsets within the line (not UTF-16).
+    /// Convert with `byte_to_utf16` before using as LSP character positions.
+    pub fn find_all_refs(&self, content: &str) -> Vec<RefOccurrence> {
+        let mut refs = Vec::new();
+        for (line_num, line) in split_lines(content).enumerate() {
+            for (range, id) in self.id_refs(line) {
+                refs.push(RefOccurrence {
+                    id: id.to_string(),
+                    line: line_num as u32,
+                    start_char: range.start as u32,
+                    end_char: range.end as u32,
+                });
+            }
+        }
+        refs
+    }
+
+    /// Find all @ID occurrences in content, skipping:
+    /// - TOML metadata block lines
+    /// - Block comments (`/* ... */`, including multi-line)
+    /// - Fenced code blocks (``` ... ```)
+    pub fn find_all_refs_filtered(&self, content: &str) -> Vec<RefOccurrence> {
+        self.find_refs_filtered_in(content, 0..=usize::MAX)
+    }
+
+    /// `find_all_refs_filtered` restricted to the 0-based `lines`. Earlier
+    /// lines are only followed for comment and fence state, and scanning
+    /// stops after the last wanted line.
+    pub fn find_refs_filtered_in(
+        &self,
+        content: &str,
+        lines: RangeInclusive<usize>,
+    ) -> Vec<RefOccurrence> {
+        #[cfg(test)]
+        parse_counts::REFS.set(parse_counts::REFS.get() + 1);
+        let mut refs = Vec::new();
+
+        let toml_range = find_toml_metadata_block(content).map(|b| b.start_line..=b.end_line);
+
+        let mut in_block_comment = false;
+        let mut in_fence = false;
+
+        for (line_num, line) in split_lines(content).enumerate() {
+            if line_num > *lines.end() {
+                break;
+            }
+            let wanted = line_num >= *lines.start();
+            // Skip TOML metadata block lines
+            if let Some(ref range) = toml_range {
+                if range.contains(&line_num) {
+                    continue;
+                }
+            }
+
+            // Handle block comment continuation
+            if in_block_comment {
+                if let Some(end_offset) = line.find("*/") {
+                    in_block_comment = false;
+                    // Process visible content after end of block comment
+                    // by falling through with adjusted pos below
+                    let after_offset = end_offset + 2;
+                    let mut visible_segments: Vec<(usize, usize)> = Vec::new();
+                    let mut pos = after_offset;
+                    loop {
+                        let remaining = &line[pos..];
+                        if let Some(bc_start) = remaining.find("/*") {
+                            visible_segments.push((pos, pos + bc_start));
+                            let bc_abs = pos + bc_start;
+                            if let Some(end_off) = line[bc_abs + 2..].find("*/") {
+                                pos = bc_abs + 2 + end_off + 2;
+                            } else {
+                                in_block_comment = true;
+                                break;
+                            }
+                        } else {
+                            visible_segments.push((pos, line.len()));
+                            break;
+                        }
+                    }
+                    for (seg_start, seg_end) in visible_segments.into_iter().filter(|_| wanted) {
+                        let segment = &line[seg_start..seg_end];
+                        for (range, id) in self.id_refs_in(segment, false) {
+                            refs.push(RefOccurrence {
+                                id: id.to_string(),
+                                line: line_num as u32,
+                                start_char: (seg_start + range.start) as u32,
+                                end_char: (seg_start + range.end) as u32,
+                            });
+                        }
+                    }
+                }
+                // Whether we found */ or not, move to next line
+                continue;
+            }
+
+            // Fence toggle (only when not in block comment)
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+                continue;
+            }
+            if in_fence {
+                continue;
+            }
+
+            // Normal line: scan for block comment boundaries and collect visible segments
+            let mut visible_segments: Vec<(usize, usize)> = Vec::new();
+            let mut pos = 0;
+            loop {
+                let remaining = &line[pos..];
+                if let Some(bc_start) = remaining.find("/*") {
+                    visible_segments.push((pos, pos + bc_start));
+                    let bc_abs = pos + bc_start;
+                    if let Some(end_off) = line[bc_abs + 2..].find("*/") {
+                        pos = bc_abs + 2 + end_off + 2;
+                    } else {
+                        in_block_comment = true;
+                        break;
+                    }
+                } else {
+                    visible_segments.push((pos, line.len()));
+                    break;
+                }
+            }
+
+            let heading = line.trim_start().starts_with('=');
+            for (seg_start, seg_end) in visible_segments.into_iter().filter(|_| wanted) {
+                let segment = &line[seg_start..seg_end];
+                for (range, id) in self.id_refs_in(segment, heading) {
+                    refs.push(RefOccurrence {
+                        id: id.to_string(),
+                        line: line_num as u32,
+                        start_char: (seg_start + range.start) as u32,
+                        end_char: (seg_start + range.end) as u32,
+                    });
+                }
+            }
+        }
+
+        refs
+    }
+
+    /// Compute the status tag based on todo counts and archived flag.
+    pub fn compute_status_tag(&self, todos: &TodoStatus, has_archived: bool) -> Option<StatusTag> {
+        let has_todos = todos.completed > 0 || todos.incomplete > 0;
+        if !has_todos {
+            return None;
+        }
+        if has_archived {
+            return Some(StatusTag::Done);
+        }
+        if todos.incomplete == 0 && todos.completed > 0 {
+            Some(StatusTag::Done)
+        } else if todos.completed > 0 {
+            Some(StatusTag::Wip)
+        } else {
+            Some(StatusTag::Todo)
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ChecklistStatus {
@@ -38,8 +646,9 @@ pub struct ParsedToml {
     pub aliases: Vec<String>,
     pub abstract_text: Option<String>,
     pub keywords: Vec<String>,
-    #[allow(dead_code)]
     pub generated: bool,
+    /// `auto-status = false`: tooling leaves `checklist-status` alone.
+    pub auto_status: bool,
     pub checklist_status: ChecklistStatus,
     pub relation: Relation,
     pub relation_target: Vec<String>,
@@ -55,6 +664,7 @@ impl Default for ParsedToml {
             abstract_text: None,
             keywords: Vec::new(),
             generated: false,
+            auto_status: true,
             checklist_status: ChecklistStatus::None,
             relation: Relation::Active,
             relation_target: Vec::new(),
@@ -63,6 +673,47 @@ impl Default for ParsedToml {
     }
 }
 
+/// Why `Parser::parse_header_detailed` found no header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    /// Neither an `#import` line nor a `zk-metadata` block; often a legacy
+    /// comment-format note or not a note at all.
+    NoImport,
+    NoMetadata,
+    /// `#let zk-metadata` without its ```` ```toml ```` fence or closing fence.
+    UnterminatedMetadata,
+    TitleBeforeMetadata,
+    /// A `= Title` heading after the block, but without a `<ID>` label.
+    UnlabelledTitle,
+    NoTitle,
+}
+
+impl ParseError {
+    /// One-line explanation for reports such as `zk-lsp malformed`.
+    pub fn reason(self) -> &'static str {
+        match self {
+            ParseError::Empty => "empty file",
+            ParseError::NoImport => "no #import line and no zk-metadata block",
+            ParseError::NoMetadata => "no zk-metadata block",
+            ParseError::UnterminatedMetadata => {
+                "unterminated zk-metadata block (missing ```toml fence or closing ```)"
+            }
+            ParseError::TitleBeforeMetadata => "title heading comes before the zk-metadata block",
+            ParseError::UnlabelledTitle => "title heading has no <ID> label",
+            ParseError::NoTitle => "no `= Title <ID>` heading after the zk-metadata block",
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.reason())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 #[derive(Debug, Clone)]
 pub struct NoteHeader {
     pub id: String,
@@ -75,11 +726,37 @@ pub struct NoteHeader {
     pub aliases: Vec<String>,
     pub abstract_text: Option<String>,
     pub keywords: Vec<String>,
+    /// `generated = true`: an auto-created stub rather than a written note.
+    pub generated: bool,
+    /// False when the note sets `auto-status = false`: its status is never
+    /// derived from its todos.
+    pub auto_status: bool,
     pub tag_line_idx: Option<usize>, // 0-based; None for TOML-format notes
-    #[allow(dead_code)]
-    pub title_line_idx: usize, // 0-based
+    pub title_line_idx: usize,       // 0-based
     pub metadata_block: Option<TomlMetadataBlock>,
     pub checklist_status: Option<ChecklistStatus>,
+    /// Status the user declared on a note with no todos (`checklist-status`
+    /// other than `none`). Nothing derives it, so tooling must leave it alone.
+    pub manual_status: Option<StatusTag>,
+    /// Topic tags after the title, e.g. `tag.project` or `topic.rust`
+    /// (see `Parser::topic_tags`).
+    pub tags: Vec<String>,
+}
+
+impl NoteHeader {
+    /// `"alternative"` (archived) or `"evolution"` (legacy) when the note
+    /// lists its own ID in `relation-target`, usually a copy-paste slip.
+    pub fn self_link(&self) -> Option<&'static str> {
+        if !self.relation_target.contains(&self.id) {
+            None
+        } else if self.archived {
+            Some("alternative")
+        } else if self.legacy {
+            Some("evolution")
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -96,6 +773,72 @@ pub struct RefOccurrence {
     pub end_char: u32,
 }
 
+/// An `@<wiki>:<ID>` reference into another wiki. `start_char` / `end_char`
+/// are byte offsets within the line, as in `RefOccurrence`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrossRef {
+    pub wiki: String,
+    pub id: String,
+    pub start_char: u32,
+    pub end_char: u32,
+}
+
+/// A `[[Note Title]]` link. `title` is the inner text, trimmed;
+/// `start_char` / `end_char` are byte offsets of the brackets within the
+/// line, as in `RefOccurrence`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TitleLink {
+    pub title: String,
+    pub start_char: u32,
+    pub end_char: u32,
+}
+
+/// A note's content parsed once, so the indexer and the diagnostics that run
+/// on the same open or save share one parse instead of each redoing it.
+#[derive(Debug, Clone)]
+pub struct NoteDoc {
+    pub content: String,
+    /// `None` without a TOML metadata block or title heading.
+    pub header: Option<NoteHeader>,
+    /// `find_all_refs_filtered` over `content`.
+    pub refs: Vec<RefOccurrence>,
+}
+
+impl NoteDoc {
+    /// `parse_with` the default parser.
+    #[cfg(test)]
+    pub fn parse(content: String) -> Self {
+        Self::parse_with(default_parser(), content)
+    }
+
+    pub fn parse_with(parser: &Parser, content: String) -> Self {
+        let header = parser.parse_header(&content);
+        let refs = parser.find_all_refs_filtered(&content);
+        Self {
+            content,
+            header,
+            refs,
+        }
+    }
+}
+
+/// Per-thread counts of `parse_header` and `find_all_refs_filtered` calls,
+/// for tests that check a code path parses each note once.
+#[cfg(test)]
+pub(crate) mod parse_counts {
+    use std::cell::Cell;
+
+    thread_local! {
+        pub(crate) static HEADERS: Cell<usize> = const { Cell::new(0) };
+        pub(crate) static REFS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// `(headers, refs)` since the last call.
+    pub(crate) fn take() -> (usize, usize) {
+        (HEADERS.replace(0), REFS.replace(0))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum StatusTag {
     Todo,
@@ -103,10 +846,21 @@ pub enum StatusTag {
     Done,
 }
 
+impl StatusTag {
+    /// The `checklist-status` value for this tag.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatusTag::Todo => "todo",
+            StatusTag::Wip => "wip",
+            StatusTag::Done => "done",
+        }
+    }
+}
+
 /// Scan `content` for a `#let zk-metadata = toml(bytes(` block.
 /// Returns the block's location and extracted TOML string, or None.
 pub fn find_toml_metadata_block(content: &str) -> Option<TomlMetadataBlock> {
-    let lines: Vec<&str> = content.lines().collect();
+    let lines: Vec<&str> = split_lines(content).collect();
 
     // Find the #let zk-metadata = toml(bytes( line
     let start_line = lines
@@ -154,6 +908,11 @@ pub fn parse_toml_metadata(toml_str: &str) -> Option<ParsedToml> {
         .and_then(|v| v.as_bool())
         .unwrap_or(false);
 
+    let auto_status = table
+        .get("auto-status")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+
     let aliases = table
         .get("aliases")
         .and_then(|v| v.as_array())
@@ -217,6 +976,7 @@ pub fn parse_toml_metadata(toml_str: &str) -> Option<ParsedToml> {
         "abstract",
         "keywords",
         "generated",
+        "auto-status",
         "checklist-status",
         "relation",
         "relation-target",
@@ -233,6 +993,7 @@ pub fn parse_toml_metadata(toml_str: &str) -> Option<ParsedToml> {
         abstract_text,
         keywords,
         generated,
+        auto_status,
         checklist_status,
         relation,
         relation_target,
@@ -240,62 +1001,9 @@ pub fn parse_toml_metadata(toml_str: &str) -> Option<ParsedToml> {
     })
 }
 
-/// Parse the header of a TOML-format note.
-/// Returns `None` for legacy comment-format notes (run `zk-lsp migrate` first).
+/// `Parser::parse_header` with the default configuration.
 pub fn parse_header(content: &str) -> Option<NoteHeader> {
-    let lines: Vec<&str> = content.lines().collect();
-
-    let block = find_toml_metadata_block(content)?;
-    let parsed = parse_toml_metadata(&block.toml_content).unwrap_or_default();
-
-    // Title line is the first heading after the TOML block
-    let title_line_idx = lines[block.end_line + 1..]
-        .iter()
-        .position(|l| RE_TITLE.is_match(l))
-        .map(|offset| block.end_line + 1 + offset)?;
-
-    let title_line = lines[title_line_idx];
-    let id = RE_TITLE.captures(title_line)?.get(1)?.as_str().to_string();
-    let title = RE_TITLE
-        .captures(title_line)?
-        .get(0)?
-        .as_str()
-        .trim_start_matches('=')
-        .trim()
-        .rsplit_once('<')
-        .map(|(t, _)| t.trim().to_string())
-        .unwrap_or_default();
-
-    let archived = parsed.relation == Relation::Archived;
-    let legacy = parsed.relation == Relation::Legacy;
-    let alt_id = if archived {
-        parsed.relation_target.first().cloned()
-    } else {
-        None
-    };
-    let evo_id = if legacy {
-        parsed.relation_target.first().cloned()
-    } else {
-        None
-    };
-    let checklist_status = parsed.checklist_status.clone();
-
-    Some(NoteHeader {
-        id,
-        title,
-        archived,
-        legacy,
-        alt_id,
-        evo_id,
-        relation_target: parsed.relation_target.clone(),
-        aliases: parsed.aliases,
-        abstract_text: parsed.abstract_text,
-        keywords: parsed.keywords,
-        tag_line_idx: None,
-        title_line_idx,
-        metadata_block: Some(block),
-        checklist_status: Some(checklist_status),
-    })
+    default_parser().parse_header(content)
 }
 
 // ---------------------------------------------------------------------------
@@ -325,62 +1033,9 @@ pub struct ChecklistItem {
     pub indent: usize,
 }
 
-/// Parse all checklist items from `content`, skipping fenced code blocks.
-/// Items with `@(\d{10})` in their text become `Ref` items; all others are `Local`.
-/// `RefTarget.byte_start`/`byte_end` are byte offsets of `@ID` within the full line.
+/// `Parser::parse_checklist_items` with the default configuration.
 pub fn parse_checklist_items(content: &str) -> Vec<ChecklistItem> {
-    let mut items = Vec::new();
-    let mut in_fence = false;
-
-    for (line_idx, line) in content.lines().enumerate() {
-        let trimmed = line.trim_start();
-        if trimmed.starts_with("```") {
-            in_fence = !in_fence;
-            continue;
-        }
-        if in_fence {
-            continue;
-        }
-        if !(trimmed.starts_with("- [") && trimmed.len() >= 5) {
-            continue;
-        }
-        let marker = trimmed.chars().nth(3).unwrap_or(' ');
-        if marker != 'x' && marker != 'X' && marker != ' ' {
-            continue;
-        }
-        let checked = marker == 'x' || marker == 'X';
-        let indent = line.len() - trimmed.len();
-        // prefix_len: bytes before the checklist body (indent + "- [x] ")
-        let prefix_len = indent + 6;
-        // text after `- [x] ` (or `- [ ] `)
-        let body = trimmed.get(6..).unwrap_or("");
-        let text = body.to_string();
-        let targets: Vec<RefTarget> = RE_ID_REF
-            .captures_iter(body)
-            .map(|c| {
-                let full = c.get(0).unwrap();
-                let id = c.get(1).unwrap().as_str().to_string();
-                RefTarget {
-                    target_id: id,
-                    byte_start: (prefix_len + full.start()) as u32,
-                    byte_end: (prefix_len + full.end()) as u32,
-                }
-            })
-            .collect();
-        let kind = if targets.is_empty() {
-            ChecklistItemKind::Local
-        } else {
-            ChecklistItemKind::Ref { targets }
-        };
-        items.push(ChecklistItem {
-            checked,
-            kind,
-            text,
-            line_idx,
-            indent,
-        });
-    }
-    items
+    default_parser().parse_checklist_items(content)
 }
 
 /// Evaluate the semantic truth of a single checklist item.
@@ -419,30 +1074,44 @@ pub fn compute_note_done_from_items(
     leaves.iter().all(|item| eval_item_truth(item, done_lookup))
 }
 
-/// Count todo items, skipping code blocks (``` fence heuristic).
+/// `Parser::count_todos` with the default configuration.
 pub fn count_todos(content: &str) -> TodoStatus {
-    let mut status = TodoStatus::default();
-    let mut in_code_block = false;
+    default_parser().count_todos(content)
+}
 
-    for line in content.lines() {
-        let trimmed = line.trim_start();
-        if trimmed.starts_with("```") {
-            in_code_block = !in_code_block;
-            continue;
-        }
-        if in_code_block {
-            continue;
-        }
-        if trimmed.starts_with("- [") && trimmed.len() >= 5 {
-            let marker = trimmed.chars().nth(3).unwrap_or(' ');
-            if marker == 'x' || marker == 'X' {
-                status.completed += 1;
-            } else if marker == ' ' {
-                status.incomplete += 1;
-            }
+/// Split `content` into lines on `\n`, `\r\n` or a lone `\r`.
+///
+/// LSP clients count all three as line breaks, while `str::lines` ignores a
+/// bare `\r`. Like `str::lines`, a trailing terminator yields no final empty
+/// line and terminators are not included.
+pub fn split_lines(content: &str) -> SplitLines<'_> {
+    SplitLines { rest: content }
+}
+
+/// Iterator returned by `split_lines`.
+pub struct SplitLines<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for SplitLines<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.rest.is_empty() {
+            return None;
         }
+        let Some(i) = self.rest.find(['\r', '\n']) else {
+            return Some(std::mem::take(&mut self.rest));
+        };
+        let line = &self.rest[..i];
+        let len = if self.rest[i..].starts_with("\r\n") {
+            2
+        } else {
+            1
+        };
+        self.rest = &self.rest[i + len..];
+        Some(line)
     }
-    status
 }
 
 /// Convert a byte offset within `s` to a UTF-16 code-unit offset.
@@ -451,134 +1120,75 @@ pub fn byte_to_utf16(s: &str, byte_offset: usize) -> u32 {
     s[..byte_offset].chars().map(|c| c.len_utf16() as u32).sum()
 }
 
-/// Find all @ID occurrences in content (10-digit IDs).
-/// `start_char` / `end_char` are **byte** offsets within the line (not UTF-16).
-/// Convert with `byte_to_utf16` before using as LSP character positions.
-pub fn find_all_refs(content: &str) -> Vec<RefOccurrence> {
-    let mut refs = Vec::new();
-    for (line_num, line) in content.lines().enumerate() {
-        for cap in RE_ID_REF.captures_iter(line) {
-            let m = cap.get(0).unwrap();
-            let id_m = cap.get(1).unwrap();
-            refs.push(RefOccurrence {
-                id: id_m.as_str().to_string(),
-                line: line_num as u32,
-                start_char: m.start() as u32,
-                end_char: m.end() as u32,
-            });
+/// Inverse of `byte_to_utf16`: the byte offset of UTF-16 position
+/// `utf16_offset` in `s`, clamped to `s.len()`.
+pub fn utf16_to_byte(s: &str, utf16_offset: u32) -> usize {
+    let mut units = 0;
+    for (i, c) in s.char_indices() {
+        if units >= utf16_offset {
+            return i;
         }
+        units += c.len_utf16() as u32;
     }
-    refs
+    s.len()
 }
 
-/// Find all @ID occurrences in content, skipping:
-/// - TOML metadata block lines
-/// - Block comments (`/* ... */`, including multi-line)
-/// - Fenced code blocks (``` ... ```)
-pub fn find_all_refs_filtered(content: &str) -> Vec<RefOccurrence> {
-    let mut refs = Vec::new();
-
-    let toml_range = find_toml_metadata_block(content).map(|b| b.start_line..=b.end_line);
-
-    let mut in_block_comment = false;
-    let mut in_fence = false;
-
-    for (line_num, line) in content.lines().enumerate() {
-        // Skip TOML metadata block lines
-        if let Some(ref range) = toml_range {
-            if range.contains(&line_num) {
-                continue;
-            }
-        }
-
-        // Handle block comment continuation
-        if in_block_comment {
-            if let Some(end_offset) = line.find("*/") {
-                in_block_comment = false;
-                // Process visible content after end of block comment
-                // by falling through with adjusted pos below
-                let after_offset = end_offset + 2;
-                let mut visible_segments: Vec<(usize, usize)> = Vec::new();
-                let mut pos = after_offset;
-                loop {
-                    let remaining = &line[pos..];
-                    if let Some(bc_start) = remaining.find("/*") {
-                        visible_segments.push((pos, pos + bc_start));
-                        let bc_abs = pos + bc_start;
-                        if let Some(end_off) = line[bc_abs + 2..].find("*/") {
-                            pos = bc_abs + 2 + end_off + 2;
-                        } else {
-                            in_block_comment = true;
-                            break;
-                        }
-                    } else {
-                        visible_segments.push((pos, line.len()));
-                        break;
-                    }
-                }
-                for (seg_start, seg_end) in visible_segments {
-                    let segment = &line[seg_start..seg_end];
-                    for cap in RE_ID_REF.captures_iter(segment) {
-                        let m = cap.get(0).unwrap();
-                        let id_m = cap.get(1).unwrap();
-                        refs.push(RefOccurrence {
-                            id: id_m.as_str().to_string(),
-                            line: line_num as u32,
-                            start_char: (seg_start + m.start()) as u32,
-                            end_char: (seg_start + m.end()) as u32,
-                        });
-                    }
-                }
-            }
-            // Whether we found */ or not, move to next line
-            continue;
-        }
+/// The first `max_chars` characters of `text`, followed by `…` when anything
+/// was cut. Used for abstracts shown in hover and completion popups.
+pub fn truncate_preview(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((cut, _)) => format!("{}…", text[..cut].trim_end()),
+        None => text.to_string(),
+    }
+}
 
-        // Fence toggle (only when not in block comment)
-        if line.trim_start().starts_with("```") {
-            in_fence = !in_fence;
-            continue;
-        }
-        if in_fence {
-            continue;
-        }
+/// `Parser::find_all_refs` with the default configuration.
+#[allow(dead_code)]
+pub fn find_all_refs(content: &str) -> Vec<RefOccurrence> {
+    default_parser().find_all_refs(content)
+}
 
-        // Normal line: scan for block comment boundaries and collect visible segments
-        let mut visible_segments: Vec<(usize, usize)> = Vec::new();
-        let mut pos = 0;
-        loop {
-            let remaining = &line[pos..];
-            if let Some(bc_start) = remaining.find("/*") {
-                visible_segments.push((pos, pos + bc_start));
-                let bc_abs = pos + bc_start;
-                if let Some(end_off) = line[bc_abs + 2..].find("*/") {
-                    pos = bc_abs + 2 + end_off + 2;
-                } else {
-                    in_block_comment = true;
-                    break;
-                }
-            } else {
-                visible_segments.push((pos, line.len()));
-                break;
+/// Every `@<wiki>:<ID>` reference in `line`.
+pub fn find_cross_refs(line: &str) -> Vec<CrossRef> {
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new(CROSS_REF_PATTERN).unwrap());
+    RE.captures_iter(line)
+        .filter(|c| c.get(3).is_none())
+        .map(|c| {
+            let id = c.get(2).unwrap();
+            CrossRef {
+                wiki: c[1].to_string(),
+                id: id.as_str().to_string(),
+                start_char: c.get(0).unwrap().start() as u32,
+                end_char: id.end() as u32,
             }
-        }
+        })
+        .collect()
+}
 
-        for (seg_start, seg_end) in visible_segments {
-            let segment = &line[seg_start..seg_end];
-            for cap in RE_ID_REF.captures_iter(segment) {
-                let m = cap.get(0).unwrap();
-                let id_m = cap.get(1).unwrap();
-                refs.push(RefOccurrence {
-                    id: id_m.as_str().to_string(),
-                    line: line_num as u32,
-                    start_char: (seg_start + m.start()) as u32,
-                    end_char: (seg_start + m.end()) as u32,
-                });
+/// Every `[[Note Title]]` link in `line`. `[[ID]]` (a note reference under
+/// `ref_style = "wikilink"`) and empty brackets are left out.
+pub fn find_title_links(line: &str) -> Vec<TitleLink> {
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new(TITLE_LINK_PATTERN).unwrap());
+    RE.captures_iter(line)
+        .filter_map(|c| {
+            let title = c[1].trim();
+            if title.is_empty() || is_valid_id(title) {
+                return None;
             }
-        }
-    }
+            let link = c.get(0).unwrap();
+            Some(TitleLink {
+                title: title.to_string(),
+                start_char: link.start() as u32,
+                end_char: link.end() as u32,
+            })
+        })
+        .collect()
+}
 
-    refs
+/// `Parser::find_all_refs_filtered` with the default configuration.
+#[cfg(test)]
+pub fn find_all_refs_filtered(content: &str) -> Vec<RefOccurrence> {
+    default_parser().find_all_refs_filtered(content)
 }
 
 /// A heading parsed from note content (outside TOML block and fenced code).
@@ -601,7 +1211,7 @@ pub fn parse_headings(content: &str) -> Vec<Heading> {
     let mut headings = Vec::new();
     let mut in_fence = false;
 
-    for (line_idx, line) in content.lines().enumerate() {
+    for (line_idx, line) in split_lines(content).enumerate() {
         if let Some(ref range) = toml_range {
             if range.contains(&line_idx) {
                 continue;
@@ -628,22 +1238,9 @@ pub fn parse_headings(content: &str) -> Vec<Heading> {
     headings
 }
 
-/// Compute the status tag based on todo counts and archived flag.
+/// `Parser::compute_status_tag` with the default configuration.
 pub fn compute_status_tag(todos: &TodoStatus, has_archived: bool) -> Option<StatusTag> {
-    let has_todos = todos.completed > 0 || todos.incomplete > 0;
-    if !has_todos {
-        return None;
-    }
-    if has_archived {
-        return Some(StatusTag::Done);
-    }
-    if todos.incomplete == 0 && todos.completed > 0 {
-        Some(StatusTag::Done)
-    } else if todos.completed > 0 {
-        Some(StatusTag::Wip)
-    } else {
-        Some(StatusTag::Todo)
-    }
+    default_parser().compute_status_tag(todos, has_archived)
 }
 
 #[cfg(test)]
@@ -723,6 +1320,51 @@ pub(crate) mod tests {
         assert!(parse_header(NOTE_NO_META).is_none());
     }
 
+    #[test]
+    fn test_parse_header_detailed_names_each_failure() {
+        let parser = default_parser();
+        let block =
+            "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n";
+        let detailed = |content: &str| parser.parse_header_detailed(content).map(|h| h.id);
+        assert_eq!(
+            detailed(&format!("{block}= Fine <2603150000>\n")),
+            Ok("2603150000".into())
+        );
+        assert_eq!(detailed(" \n\n"), Err(ParseError::Empty));
+        assert_eq!(
+            detailed("= Title <2603150000>\n"),
+            Err(ParseError::NoImport)
+        );
+        assert_eq!(
+            detailed("#import \"../include.typ\": *\n= Title <2603150000>\n"),
+            Err(ParseError::NoMetadata)
+        );
+        assert_eq!(
+            detailed("#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n"),
+            Err(ParseError::UnterminatedMetadata)
+        );
+        assert_eq!(
+            detailed(&format!("= Early <2603150000>\n{block}")),
+            Err(ParseError::TitleBeforeMetadata)
+        );
+        assert_eq!(
+            detailed(&format!("{block}= No label\n")),
+            Err(ParseError::UnlabelledTitle)
+        );
+        assert_eq!(
+            detailed(&format!("{block}Prose.\n")),
+            Err(ParseError::NoTitle)
+        );
+        // `parse_header` is the same parse without the reason.
+        assert!(parser
+            .parse_header(&format!("{block}= No label\n"))
+            .is_none());
+        assert_eq!(
+            ParseError::UnlabelledTitle.to_string(),
+            "title heading has no <ID> label"
+        );
+    }
+
     #[test]
     fn test_parse_header_toml_active() {
         let h = parse_header(NOTE_TOML_META).unwrap();
@@ -738,6 +1380,15 @@ pub(crate) mod tests {
         assert!(h.metadata_block.is_some());
     }
 
+    #[test]
+    fn test_parse_header_generated_flag() {
+        assert!(parse_header(NOTE_TOML_META).unwrap().generated);
+        let manual = NOTE_TOML_META.replace("generated = true", "generated = false");
+        assert!(!parse_header(&manual).unwrap().generated);
+        let absent = NOTE_TOML_META.replace("  generated = true\n", "");
+        assert!(!parse_header(&absent).unwrap().generated);
+    }
+
     #[test]
     fn test_parse_header_toml_archived() {
         let h = parse_header(NOTE_TOML_ARCHIVED).unwrap();
@@ -750,6 +1401,48 @@ pub(crate) mod tests {
         assert_eq!(h.tag_line_idx, None);
     }
 
+    #[test]
+    fn test_self_link() {
+        assert_eq!(parse_header(NOTE_TOML_ARCHIVED).unwrap().self_link(), None);
+        let archived_self = NOTE_TOML_ARCHIVED.replace("[\"2603110001\"]", "[\"2603110002\"]");
+        assert_eq!(
+            parse_header(&archived_self).unwrap().self_link(),
+            Some("alternative")
+        );
+        let legacy_self = archived_self.replace("\"archived\"", "\"legacy\"");
+        assert_eq!(
+            parse_header(&legacy_self).unwrap().self_link(),
+            Some("evolution")
+        );
+    }
+
+    #[test]
+    fn test_parse_header_manual_status() {
+        // Declared status with no todos is manual; "none" is not a status.
+        assert_eq!(
+            parse_header(NOTE_TOML_ARCHIVED).unwrap().manual_status,
+            Some(StatusTag::Done)
+        );
+        assert_eq!(parse_header(NOTE_TOML_META).unwrap().manual_status, None);
+        // Once the note has todos, the status is derived from them.
+        let with_todos = NOTE_TOML_ARCHIVED.to_string() + "- [ ] task\n";
+        assert_eq!(parse_header(&with_todos).unwrap().manual_status, None);
+    }
+
+    #[test]
+    fn test_parse_header_topic_tags() {
+        let content = NOTE_TOML_META.to_string()
+            + "#tag.project #tag.done #topic.rust\n\n\
+               Body mentions #topic.rust again and #topic.type-theory.\n\
+               ```\n#tag.in-code\n```\n";
+        let h = parse_header(&content).unwrap();
+        assert_eq!(
+            h.tags,
+            vec!["tag.project", "topic.rust", "topic.type-theory"]
+        );
+        assert!(parse_header(NOTE_TOML_META).unwrap().tags.is_empty());
+    }
+
     #[test]
     fn test_count_todos() {
         let content = "- [ ] incomplete\n- [x] done\n```\n- [ ] skipped\n```\n- [X] also done\n";
@@ -766,6 +1459,136 @@ pub(crate) mod tests {
         assert_eq!(refs[1].id, "2602082106");
     }
 
+    #[test]
+    fn test_configured_parser_matches_free_functions() {
+        let parser = Parser::new(ParserConfig {
+            import_marker: Regex::new(r"^#import").unwrap(),
+            ..ParserConfig::default()
+        });
+        let content = "#import \"@preview/zettel:0.1.0\": *\n- [ ] see @2602082037\n";
+        assert!(parser.is_import_line("#import \"@preview/zettel:0.1.0\": *"));
+        assert!(!default_parser().is_import_line("#import \"@preview/zettel:0.1.0\": *"));
+        assert_eq!(
+            parser.find_all_refs(content)[0].id,
+            find_all_refs(content)[0].id
+        );
+        assert_eq!(
+            parser.count_todos(content).incomplete,
+            count_todos(content).incomplete
+        );
+    }
+
+    #[test]
+    fn test_find_all_refs_skips_typst_labels() {
+        let line = "See @figure-1 and @2602082037.";
+        let refs = find_all_refs(line);
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0].id, "2602082037");
+        assert_eq!(refs[0].start_char, 18);
+        assert_eq!(refs[0].end_char, 29);
+
+        let labels = "@2602082037-fig @26020820371 @2602082037.x @2602082037: ok";
+        let ids: Vec<_> = find_all_refs(labels).into_iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec!["2602082037"]);
+    }
+
+    #[test]
+    fn test_find_cross_refs() {
+        let line = "See @work:2602082037, @2602082037 and @my-wiki:2602082038.";
+        assert_eq!(
+            find_cross_refs(line),
+            vec![
+                CrossRef {
+                    wiki: "work".into(),
+                    id: "2602082037".into(),
+                    start_char: 4,
+                    end_char: 20,
+                },
+                CrossRef {
+                    wiki: "my-wiki".into(),
+                    id: "2602082038".into(),
+                    start_char: 38,
+                    end_char: 57,
+                },
+            ]
+        );
+        // Typst labels with a colon, and local refs, are not cross refs.
+        assert!(find_cross_refs("@fig:setup @eq:2602082037x @2602082037").is_empty());
+        // The ID digits after the prefix are not a local ref either.
+        assert!(find_all_refs("@work:2602082037").is_empty());
+    }
+
+    #[test]
+    fn test_label_refs_only_when_enabled() {
+        let content = "= Title <2602082037>\nSee <2602082106>, @<2602082107> and @2602082108.\n";
+        let ids = |refs: Vec<RefOccurrence>| -> Vec<(String, u32, u32, u32)> {
+            refs.into_iter()
+                .map(|r| (r.id, r.line, r.start_char, r.end_char))
+                .collect()
+        };
+        assert_eq!(
+            ids(find_all_refs(content)),
+            vec![("2602082108".into(), 1, 36, 47)]
+        );
+
+        let parser = Parser::new(ParserConfig {
+            label_refs: true,
+            ..ParserConfig::default()
+        });
+        // The title's own label is not a reference to the note.
+        assert_eq!(
+            ids(parser.find_all_refs(content)),
+            vec![
+                ("2602082106".into(), 1, 4, 16),
+                ("2602082107".into(), 1, 18, 31),
+                ("2602082108".into(), 1, 36, 47),
+            ]
+        );
+
+        // Nor is it once a comment splits the heading line.
+        let commented = "= Title /* draft */ <2602082037>\n";
+        assert!(parser.find_all_refs_filtered(commented).is_empty());
+    }
+
+    #[test]
+    fn test_wikilink_ref_style() {
+        let parser = Parser::new(ParserConfig {
+            ref_style: RefStyle::Wikilink,
+            ..ParserConfig::default()
+        });
+        let content =
+            "= Title <2602082037>\n见 [[2602082106]] and @2602082107\n- [ ] [[2602082108]]\n";
+        let refs: Vec<(String, u32, u32, u32)> = parser
+            .find_all_refs(content)
+            .into_iter()
+            .map(|r| (r.id, r.line, r.start_char, r.end_char))
+            .collect();
+        // The byte range covers the brackets; `见` is three bytes.
+        assert_eq!(
+            refs,
+            vec![
+                ("2602082106".into(), 1, 4, 18),
+                ("2602082108".into(), 2, 6, 20),
+            ]
+        );
+        let items = parser.parse_checklist_items(content);
+        assert!(matches!(
+            &items[0].kind,
+            ChecklistItemKind::Ref { targets } if targets[0].target_id == "2602082108"
+        ));
+    }
+
+    #[test]
+    fn test_truncate_preview() {
+        assert_eq!(truncate_preview("short", 5), "short");
+        assert_eq!(truncate_preview("", 3), "");
+        assert_eq!(truncate_preview("one two", 4), "one…");
+        // Multi-byte characters are counted, never split.
+        assert_eq!(truncate_preview("你好世界", 2), "你好…");
+        assert_eq!(truncate_preview("é😀é", 3), "é😀é");
+        assert_eq!(truncate_preview("é😀é", 2), "é😀…");
+    }
+
     #[test]
     fn test_byte_to_utf16_cjk() {
         // "你好 " = 3+3+1 = 7 bytes, but 3 UTF-16 code units
@@ -968,4 +1791,92 @@ pub(crate) mod tests {
         let user = parsed.extra["user"].as_table().unwrap();
         assert_eq!(user["course"].as_str(), Some("Topology"));
     }
+
+    #[test]
+    fn test_is_valid_id() {
+        assert!(is_valid_id("2603110900"));
+        assert!(!is_valid_id("260311090"));
+        assert!(!is_valid_id("26031109000"));
+        assert!(!is_valid_id("26031109a0"));
+        assert!(!is_valid_id("２６０３１１０９００"));
+    }
+
+    #[test]
+    fn test_is_lookalike_id() {
+        assert!(is_lookalike_id("２６０３１１０９００"));
+        assert!(is_lookalike_id("260311090٠"));
+        assert!(!is_lookalike_id("2603110900"));
+        assert!(!is_lookalike_id("scratch"));
+    }
+
+    #[test]
+    fn test_note_id_from_path() {
+        use std::path::Path;
+        assert_eq!(
+            note_id_from_path(Path::new("/wiki/note/2603110900.typ")),
+            Some("2603110900")
+        );
+        assert_eq!(
+            note_id_from_path(Path::new("/wiki/note/2603110900.md")),
+            None
+        );
+        assert_eq!(
+            note_id_from_path(Path::new("/wiki/note/２６０３１１０９００.typ")),
+            None
+        );
+        assert_eq!(note_id_from_path(Path::new("/wiki/include.typ")), None);
+    }
+
+    #[test]
+    fn test_id_to_datetime() {
+        let dt = id_to_datetime("2603110930").unwrap();
+        assert_eq!(dt.to_string(), "2026-03-11 09:30:00");
+        assert_eq!(id_to_datetime("2613110930"), None);
+        assert_eq!(id_to_datetime("2603112560"), None);
+        assert_eq!(id_to_datetime("abc"), None);
+    }
+
+    #[test]
+    fn test_parse_date_bound() {
+        assert_eq!(
+            parse_date_bound("2026-03-11", false).unwrap().to_string(),
+            "2026-03-11 00:00:00"
+        );
+        assert_eq!(
+            parse_date_bound("2026-03-11", true).unwrap().to_string(),
+            "2026-03-12 00:00:00"
+        );
+        assert_eq!(
+            parse_date_bound("2026-03-11T09:30", true)
+                .unwrap()
+                .to_string(),
+            "2026-03-11 09:30:00"
+        );
+        assert_eq!(parse_date_bound("yesterday", false), None);
+    }
+
+    #[test]
+    fn test_split_lines_handles_all_terminators() {
+        let lines: Vec<&str> = split_lines("a\nb\r\nc\rd\r\re").collect();
+        assert_eq!(lines, vec!["a", "b", "c", "d", "", "e"]);
+        assert_eq!(split_lines("a\r").collect::<Vec<_>>(), vec!["a"]);
+        assert_eq!(split_lines("").count(), 0);
+        // Matches str::lines wherever that handles the input correctly.
+        let unix = "x\n\ny\r\nz\n";
+        assert!(split_lines(unix).eq(unix.lines()));
+    }
+
+    #[test]
+    fn test_cr_only_content_parses_with_correct_lines() {
+        let content = NOTE_TOML_META.replace('\n', "\r") + "See @2603120000 here.\r";
+        let header = parse_header(&content).expect("header from \\r-only note");
+        assert_eq!(header.id, "2603110000");
+        assert_eq!(header.title_line_idx, 15);
+        let block = find_toml_metadata_block(&content).unwrap();
+        assert_eq!((block.start_line, block.end_line), (1, 11));
+        let refs = find_all_refs_filtered(&content);
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0].line, 16);
+        assert_eq!(split_lines(&content).nth(16), Some("See @2603120000 here."));
+    }
 }
@@ -31,7 +31,7 @@ use self::observe::WorkspaceSnapshot;
 use self::types::{
     DiagnosticKind, DiagnosticLocation, DiagnosticSeverity, NoteId, ReconcileDiagnostic, Value,
 };
-use self::writeback::normalize_note_from_checked;
+use self::writeback::{normalize_note_from_checked, unknown_ref_lines};
 
 #[derive(Debug)]
 struct WorkspaceCycle {
@@ -53,7 +53,7 @@ pub struct ReconcileStats {
 // ---------------------------------------------------------------------------
 
 pub async fn run_reconcile(config: &WikiConfig, dry_run: bool) -> Result<ReconcileStats> {
-    let notes = scan_notes(&config.note_dir).await?;
+    let notes = scan_notes(config).await?;
     let (eval_result, diagnostics) = evaluate_workspace(&notes, config)?;
 
     if !diagnostics.is_empty() {
@@ -61,17 +61,23 @@ pub async fn run_reconcile(config: &WikiConfig, dry_run: bool) -> Result<Reconci
     }
 
     let reconcile_result = materialize(eval_result);
+    let dep_states = note_done_states(&reconcile_result, &notes);
+    let note_parser = config.parser();
     let mut files_changed = 0usize;
     for (_id, (path, content)) in &notes {
-        let checked_by_line: HashMap<usize, bool> = reconcile_result
+        let mut checked_by_line: HashMap<usize, bool> = reconcile_result
             .materialized_checked
             .iter()
             .filter(|(cid, _)| cid.note_id == *_id)
             .map(|(cid, checked)| (cid.line_idx, *checked))
             .collect();
+        // Leave boxes that depend on missing notes as the user set them.
+        for line_idx in unknown_ref_lines(content, &dep_states, &note_parser) {
+            checked_by_line.remove(&line_idx);
+        }
 
-        let after_checked = normalize_note_from_checked(content, &checked_by_line);
-        let new_content = apply_materialized_metadata(&_id, &after_checked, &reconcile_result)?
+        let after_checked = normalize_note_from_checked(content, &checked_by_line, &note_parser);
+        let new_content = apply_materialized_metadata(_id, &after_checked, &reconcile_result)?
             .unwrap_or_else(|| after_checked.clone());
         if new_content != *content {
             files_changed += 1;
@@ -89,22 +95,22 @@ pub async fn run_reconcile(config: &WikiConfig, dry_run: bool) -> Result<Reconci
     Ok(ReconcileStats { files_changed })
 }
 
+/// Evaluate the whole wiki and return reconcile diagnostics.
+///
+/// `overlay` maps note paths to in-memory contents (open editor buffers) that
+/// take precedence over the files on disk.
 pub async fn collect_diagnostics(
     config: &WikiConfig,
-    overlay: Option<(&std::path::Path, &str)>,
+    overlay: &HashMap<PathBuf, String>,
 ) -> Result<Vec<ReconcileDiagnostic>> {
-    let mut notes = scan_notes(&config.note_dir).await?;
-
-    if let Some((path, content)) = overlay {
-        if path.extension().and_then(|ext| ext.to_str()) == Some("typ") {
-            if let Some(note_id) = path.file_stem().and_then(|stem| stem.to_str()) {
-                if note_id.len() == 10 && note_id.chars().all(|c| c.is_ascii_digit()) {
-                    notes.insert(
-                        note_id.to_string(),
-                        (path.to_path_buf(), content.to_string()),
-                    );
-                }
-            }
+    let mut notes = scan_notes(config).await?;
+
+    for (path, content) in overlay {
+        if !crate::note_ops::is_in_note_dir(&config.note_dir, path) {
+            continue;
+        }
+        if let Some(note_id) = crate::parser::note_id_from_path(path) {
+            notes.insert(note_id.to_string(), (path.clone(), content.clone()));
         }
     }
 
@@ -112,14 +118,36 @@ pub async fn collect_diagnostics(
     Ok(diagnostics)
 }
 
+/// Done-state of every note in the workspace, from its materialized
+/// checklist-status. Referenced notes that do not exist are left out, even
+/// though evaluation gives them a (default) status.
+fn note_done_states(
+    reconcile_result: &materialize::ReconcileResult,
+    notes: &HashMap<NoteId, (PathBuf, String)>,
+) -> HashMap<String, bool> {
+    reconcile_result
+        .materialized_meta
+        .iter()
+        .filter(|((note_id, field), _)| field == "checklist-status" && notes.contains_key(note_id))
+        .map(|((note_id, _), value)| {
+            (
+                note_id.clone(),
+                matches!(value, Value::Status(status) if status.is_done()),
+            )
+        })
+        .collect()
+}
+
 fn apply_materialized_metadata(
     note_id: &str,
     content: &str,
     reconcile_result: &materialize::ReconcileResult,
 ) -> Result<Option<String>> {
+    // `auto-status = false` notes keep the status their author set.
+    let auto_status = crate::parser::parse_header(content).is_none_or(|h| h.auto_status);
     let mut patch = HashMap::new();
     for ((materialized_note_id, field), value) in &reconcile_result.materialized_meta {
-        if materialized_note_id != note_id {
+        if materialized_note_id != note_id || (!auto_status && field == "checklist-status") {
             continue;
         }
         let Some(toml_value) = value_to_toml(value) else {
@@ -157,21 +185,13 @@ fn value_to_toml(value: &Value) -> Option<toml::Value> {
 // Scan helper
 // ---------------------------------------------------------------------------
 
-async fn scan_notes(note_dir: &std::path::Path) -> Result<HashMap<NoteId, (PathBuf, String)>> {
+async fn scan_notes(config: &WikiConfig) -> Result<HashMap<NoteId, (PathBuf, String)>> {
     let mut map = HashMap::new();
-    let mut rd = tokio::fs::read_dir(note_dir).await?;
-    while let Some(entry) = rd.next_entry().await? {
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) != Some("typ") {
-            continue;
-        }
-        let stem = match path.file_stem().and_then(|s| s.to_str()) {
+    for path in crate::note_ops::indexable_note_files(config).await? {
+        let stem = match crate::parser::note_id_from_path(&path) {
             Some(s) => s.to_string(),
             None => continue,
         };
-        if stem.len() != 10 || !stem.chars().all(|c| c.is_ascii_digit()) {
-            continue;
-        }
         match tokio::fs::read_to_string(&path).await {
             Ok(content) => {
                 map.insert(stem, (path, content));
@@ -186,8 +206,11 @@ fn evaluate_workspace(
     notes: &HashMap<NoteId, (PathBuf, String)>,
     config: &WikiConfig,
 ) -> Result<(eval::EvalResult, Vec<ReconcileDiagnostic>)> {
-    let snapshot =
-        WorkspaceSnapshot::from_note_map_with_metadata(notes, &config.zk_config.metadata.fields);
+    let snapshot = WorkspaceSnapshot::from_note_map_with_metadata(
+        notes,
+        &config.zk_config.metadata.fields,
+        &config.parser(),
+    );
     let module = load_module(
         &config.zk_config.reconcile_rules,
         config.zk_config.disable_default_reconcile_rules,
@@ -401,11 +424,19 @@ fn render_diagnostics(
         let col_1based = location.byte_start + 1;
         let line_text = notes
             .get(&diag.note_id)
-            .and_then(|(_, content)| content.lines().nth(location.line).map(str::to_string))
+            .and_then(|(_, content)| {
+                crate::parser::split_lines(content)
+                    .nth(location.line)
+                    .map(str::to_string)
+            })
             .or_else(|| {
                 std::fs::read_to_string(&location.file_path)
                     .ok()
-                    .and_then(|content| content.lines().nth(location.line).map(str::to_string))
+                    .and_then(|content| {
+                        crate::parser::split_lines(&content)
+                            .nth(location.line)
+                            .map(str::to_string)
+                    })
             })
             .unwrap_or_default();
 
@@ -522,17 +553,11 @@ mod tests {
     }
 
     fn make_test_config(root: PathBuf) -> WikiConfig {
-        let note_dir = root.join("note");
-        WikiConfig {
-            root,
-            note_dir,
-            link_file: PathBuf::from("link.typ"),
-            zk_config: crate::config::ZkLspConfig {
-                reconcile_rules: vec![test_rule_path()],
-                disable_default_reconcile_rules: true,
-                ..Default::default()
-            },
-        }
+        let mut config = WikiConfig::from_root(root);
+        config.link_file = PathBuf::from("link.typ");
+        config.zk_config.reconcile_rules = vec![test_rule_path()];
+        config.zk_config.disable_default_reconcile_rules = true;
+        config
     }
 
     #[test]
@@ -825,7 +850,7 @@ mod tests {
         .expect("write note b");
 
         let config = make_test_config(root.clone());
-        let diagnostics = collect_diagnostics(&config, None)
+        let diagnostics = collect_diagnostics(&config, &HashMap::new())
             .await
             .expect("collect diagnostics");
 
@@ -841,6 +866,172 @@ mod tests {
         let _ = std::fs::remove_dir_all(root);
     }
 
+    #[tokio::test]
+    async fn collect_diagnostics_prefers_overlay_over_disk() {
+        let root = std::env::temp_dir().join("zk_reconcile_diag_overlay");
+        let _ = std::fs::remove_dir_all(&root);
+        let note_dir = root.join("note");
+        std::fs::create_dir_all(&note_dir).expect("create note dir");
+
+        let note_a = note_dir.join("1111111111.typ");
+        let note_b = note_dir.join("2222222222.typ");
+        std::fs::write(
+            &note_a,
+            make_toml_note("A", "1111111111", "none", "- [ ] @2222222222\n"),
+        )
+        .expect("write note a");
+        std::fs::write(&note_b, make_toml_note("B", "2222222222", "none", ""))
+            .expect("write note b");
+
+        let config = make_test_config(root.clone());
+        let on_disk = collect_diagnostics(&config, &HashMap::new())
+            .await
+            .expect("collect diagnostics");
+        // Unsaved edit in B closes a cycle that disk does not have yet.
+        let overlay = HashMap::from([(
+            note_b.clone(),
+            make_toml_note("B", "2222222222", "none", "- [ ] @1111111111\n"),
+        )]);
+        let with_overlay = collect_diagnostics(&config, &overlay)
+            .await
+            .expect("collect diagnostics");
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert!(!on_disk.iter().any(|d| d.kind == DiagnosticKind::Cycle));
+        assert!(with_overlay.iter().any(|d| d.kind == DiagnosticKind::Cycle));
+    }
+
+    #[tokio::test]
+    async fn run_reconcile_keeps_checked_box_for_missing_note() {
+        let root = std::env::temp_dir().join("zk_reconcile_missing_target");
+        let _ = std::fs::remove_dir_all(&root);
+        let note_dir = root.join("note");
+        std::fs::create_dir_all(&note_dir).expect("create note dir");
+
+        let note_a = note_dir.join("1111111111.typ");
+        std::fs::write(
+            &note_a,
+            make_toml_note(
+                "A",
+                "1111111111",
+                "none",
+                "- [x] @2999999999 not written yet\n- [x] @2222222222 still open\n",
+            ),
+        )
+        .expect("write note a");
+        std::fs::write(
+            note_dir.join("2222222222.typ"),
+            make_toml_note("B", "2222222222", "todo", "- [ ] pending\n"),
+        )
+        .expect("write note b");
+
+        let config = make_test_config(root.clone());
+        run_reconcile(&config, false).await.expect("reconcile");
+        let after = std::fs::read_to_string(&note_a).expect("read note a");
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert!(after.contains("- [x] @2999999999 not written yet"));
+        assert!(after.contains("- [ ] @2222222222 still open"));
+    }
+
+    #[tokio::test]
+    async fn run_reconcile_leaves_ignored_notes_alone() {
+        let root = std::env::temp_dir().join("zk_reconcile_ignored");
+        let _ = std::fs::remove_dir_all(&root);
+        let note_dir = root.join("note");
+        std::fs::create_dir_all(&note_dir).expect("create note dir");
+
+        let ignored = note_dir.join("1111111111.typ");
+        let before = make_toml_note(
+            "A",
+            "1111111111",
+            "none",
+            "- [ ] @2222222222 done elsewhere\n",
+        );
+        std::fs::write(&ignored, &before).expect("write note a");
+        std::fs::write(
+            note_dir.join("2222222222.typ"),
+            make_toml_note("B", "2222222222", "done", "- [x] finished\n"),
+        )
+        .expect("write note b");
+
+        let mut config = make_test_config(root.clone());
+        config.zk_config.ignore = vec!["1111111111.typ".to_string()];
+        run_reconcile(&config, false).await.expect("reconcile");
+        let after = std::fs::read_to_string(&ignored).expect("read note a");
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(after, before);
+    }
+
+    #[tokio::test]
+    async fn run_reconcile_checks_box_for_done_note_in_subdirectory() {
+        let root = std::env::temp_dir().join("zk_reconcile_subdir_target");
+        let _ = std::fs::remove_dir_all(&root);
+        let note_dir = root.join("note");
+        let dated = note_dir.join("2026").join("03");
+        std::fs::create_dir_all(&dated).expect("create dated dir");
+
+        let note_a = note_dir.join("1111111111.typ");
+        std::fs::write(
+            &note_a,
+            make_toml_note("A", "1111111111", "none", "- [ ] @2603150000 filed away\n"),
+        )
+        .expect("write note a");
+        std::fs::write(
+            dated.join("2603150000.typ"),
+            make_toml_note("B", "2603150000", "done", "- [x] finished\n"),
+        )
+        .expect("write note b");
+
+        let config = make_test_config(root.clone());
+        run_reconcile(&config, false).await.expect("reconcile");
+        let after = std::fs::read_to_string(&note_a).expect("read note a");
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert!(after.contains("- [x] @2603150000 filed away"), "{after}");
+    }
+
+    #[tokio::test]
+    async fn run_reconcile_syncs_wikilink_checkboxes() {
+        let root = std::env::temp_dir().join("zk_reconcile_wikilink_refs");
+        let _ = std::fs::remove_dir_all(&root);
+        let note_dir = root.join("note");
+        std::fs::create_dir_all(&note_dir).expect("create note dir");
+        std::fs::write(root.join("zk-lsp.toml"), "ref_style = \"wikilink\"\n")
+            .expect("write config");
+
+        let note_a = note_dir.join("1111111111.typ");
+        std::fs::write(
+            &note_a,
+            make_toml_note(
+                "A",
+                "1111111111",
+                "none",
+                "- [ ] [[2222222222]] finished\n- [x] [[3333333333]] reopened\n",
+            ),
+        )
+        .expect("write note a");
+        std::fs::write(
+            note_dir.join("2222222222.typ"),
+            make_toml_note("B", "2222222222", "done", "- [x] finished\n"),
+        )
+        .expect("write note b");
+        std::fs::write(
+            note_dir.join("3333333333.typ"),
+            make_toml_note("C", "3333333333", "todo", "- [ ] pending\n"),
+        )
+        .expect("write note c");
+
+        let config = make_test_config(root.clone());
+        run_reconcile(&config, false).await.expect("reconcile");
+        let after = std::fs::read_to_string(&note_a).expect("read note a");
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert!(after.contains("- [x] [[2222222222]] finished"), "{after}");
+        assert!(after.contains("- [ ] [[3333333333]] reopened"), "{after}");
+    }
+
     #[tokio::test]
     async fn run_reconcile_returns_typst_style_cycle_errors_for_all_nodes() {
         let suffix = std::time::SystemTime::now()
@@ -879,6 +1070,23 @@ mod tests {
         let _ = std::fs::remove_dir_all(root);
     }
 
+    #[test]
+    fn materialized_meta_skips_status_of_auto_status_false_notes() {
+        let note = make_toml_note("A", "1111111111", "todo", "- [x] finished\n").replace(
+            "checklist-status = \"todo\"",
+            "checklist-status = \"todo\"\n  auto-status = false",
+        );
+        let snap = snapshot_from(&[("1111111111", &note)]);
+        let module = load_test_module();
+        let result = materialize(eval_all(&module, &snap));
+
+        let updated = apply_materialized_metadata("1111111111", &note, &result).expect("no error");
+        assert!(
+            updated.is_none_or(|u| u.contains("checklist-status = \"todo\"")),
+            "status must be left alone"
+        );
+    }
+
     #[test]
     fn materialized_meta_drives_status_writeback() {
         let note = make_toml_note("A", "1111111111", "none", "- [x] finished\n");
@@ -1,11 +1,10 @@
 /// Workspace snapshot — the observation layer for the Reconcile DSL.
 use std::collections::HashMap;
-use std::path::Path;
 use std::path::PathBuf;
 use std::rc::Rc;
 
 use crate::config::{MetadataFieldConfig, MetadataFieldKind};
-use crate::parser::{self, ChecklistItemKind, ChecklistStatus, Relation};
+use crate::parser::{self, ChecklistItemKind, ChecklistStatus, Parser, Relation};
 
 use super::types::{CheckboxId, NoteId, Status, Value};
 
@@ -115,12 +114,15 @@ impl WorkspaceSnapshot {
     /// Build a snapshot from a map of `note_id → (path, content)`.
     /// Used in tests and in production (after async scan).
     pub fn from_note_map(notes: &HashMap<NoteId, (PathBuf, String)>) -> Self {
-        Self::from_note_map_with_metadata(notes, &[])
+        Self::from_note_map_with_metadata(notes, &[], parser::default_parser())
     }
 
+    /// `from_note_map` with the wiki's metadata fields, reading headers and
+    /// checkbox references with `note_parser` (so `ref_style` applies).
     pub fn from_note_map_with_metadata(
         notes: &HashMap<NoteId, (PathBuf, String)>,
         metadata_fields: &[MetadataFieldConfig],
+        note_parser: &Parser,
     ) -> Self {
         let mut note_obs_map: HashMap<NoteId, NoteObs> = HashMap::new();
         let mut checkboxes: HashMap<CheckboxId, CheckboxObs> = HashMap::new();
@@ -131,28 +133,29 @@ impl WorkspaceSnapshot {
 
         for (id, (_path, content)) in notes {
             // Parse header for relation + checklist_status
-            let (relation, checklist_status) = if let Some(header) = parser::parse_header(content) {
-                let relation = if header.archived {
-                    Relation::Archived
+            let (relation, checklist_status) =
+                if let Some(header) = note_parser.parse_header(content) {
+                    let relation = if header.archived {
+                        Relation::Archived
+                    } else {
+                        // Parse full TOML to get relation
+                        parser::find_toml_metadata_block(content)
+                            .and_then(|b| parser::parse_toml_metadata(&b.toml_content))
+                            .map(|m| m.relation)
+                            .unwrap_or(Relation::Active)
+                    };
+                    let status = header.checklist_status.unwrap_or(ChecklistStatus::None);
+                    (relation, status)
                 } else {
-                    // Parse full TOML to get relation
-                    parser::find_toml_metadata_block(content)
-                        .and_then(|b| parser::parse_toml_metadata(&b.toml_content))
-                        .map(|m| m.relation)
-                        .unwrap_or(Relation::Active)
+                    (Relation::Active, ChecklistStatus::None)
                 };
-                let status = header.checklist_status.unwrap_or(ChecklistStatus::None);
-                (relation, status)
-            } else {
-                (Relation::Active, ChecklistStatus::None)
-            };
 
             let raw_meta = extract_raw_meta(content, &metadata_kinds, checklist_status, &relation);
 
             note_obs_map.insert(id.clone(), NoteObs { relation, raw_meta });
 
             // Parse checklist items.
-            let items = parser::parse_checklist_items(content);
+            let items = note_parser.parse_checklist_items(content);
             let mut checkbox_ids: Vec<CheckboxId> = Vec::new();
             let mut stack: Vec<(usize, CheckboxId)> = Vec::new();
 
@@ -345,21 +348,15 @@ fn toml_value_to_inline_string(value: &toml::Value) -> String {
 
 /// Async constructor for production use.
 #[allow(dead_code)]
-pub async fn build_workspace_snapshot(note_dir: &Path) -> anyhow::Result<WorkspaceSnapshot> {
+pub async fn build_workspace_snapshot(
+    config: &crate::config::WikiConfig,
+) -> anyhow::Result<WorkspaceSnapshot> {
     let mut map: HashMap<NoteId, (PathBuf, String)> = HashMap::new();
-    let mut rd = tokio::fs::read_dir(note_dir).await?;
-    while let Some(entry) = rd.next_entry().await? {
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) != Some("typ") {
-            continue;
-        }
-        let stem = match path.file_stem().and_then(|s| s.to_str()) {
+    for path in crate::note_ops::wiki_note_files(config).await? {
+        let stem = match crate::parser::note_id_from_path(&path) {
             Some(s) => s.to_string(),
             None => continue,
         };
-        if stem.len() != 10 || !stem.chars().all(|c| c.is_ascii_digit()) {
-            continue;
-        }
         match tokio::fs::read_to_string(&path).await {
             Ok(content) => {
                 map.insert(stem, (path, content));
@@ -470,7 +467,11 @@ mod tests {
             id.to_string(),
             (PathBuf::from(format!("{id}.typ")), content.to_string()),
         );
-        WorkspaceSnapshot::from_note_map_with_metadata(&map, metadata_fields)
+        WorkspaceSnapshot::from_note_map_with_metadata(
+            &map,
+            metadata_fields,
+            parser::default_parser(),
+        )
     }
 
     #[test]
@@ -1,24 +1,24 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use once_cell::sync::Lazy;
-use regex::Regex;
 use tower_lsp::lsp_types::{Position, Range, TextEdit};
 
 use crate::handlers::formatting::compute_toml_status_edit;
-use crate::parser::{self, ChecklistStatus, StatusTag};
+use crate::parser::{self, ChecklistStatus, Parser, StatusTag};
 
-static RE_TODO_ID: Lazy<Regex> = Lazy::new(|| Regex::new(r"@(\d{10})").unwrap());
+fn has_id_ref(line: &str) -> bool {
+    parser::id_refs(line).next().is_some()
+}
 
-fn apply_tag_edit(content: &str) -> String {
+pub(crate) fn apply_tag_edit(content: &str) -> String {
     let Some(edit) = compute_tag_edit(content) else {
         return content.to_string();
     };
     let line_num = edit.range.start.line as usize;
-    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let mut lines: Vec<String> = parser::split_lines(content).map(str::to_string).collect();
     if line_num < lines.len() {
         lines[line_num] = edit.new_text;
     }
-    let trailing_newline = content.ends_with('\n');
+    let trailing_newline = content.ends_with(['\n', '\r']);
     let mut out = lines.join("\n");
     if trailing_newline {
         out.push('\n');
@@ -27,20 +27,23 @@ fn apply_tag_edit(content: &str) -> String {
 }
 
 pub fn is_note_done_with_deps(content: &str, deps: &HashMap<String, bool>) -> bool {
-    let Some(header) = parser::parse_header(content) else {
+    note_done_with_deps(content, deps, parser::default_parser())
+}
+
+fn note_done_with_deps(content: &str, deps: &HashMap<String, bool>, note_parser: &Parser) -> bool {
+    let Some(header) = note_parser.parse_header(content) else {
         return false;
     };
     if header.archived {
         return true;
     }
-    let items = parser::parse_checklist_items(content);
+    let items = note_parser.parse_checklist_items(content);
     if items.is_empty() {
         return header.checklist_status == Some(ChecklistStatus::Done);
     }
     parser::compute_note_done_from_items(&items, &|id| deps.get(id).copied().unwrap_or(false))
 }
 
-#[allow(dead_code)]
 pub fn is_note_done(content: &str) -> bool {
     let Some(header) = parser::parse_header(content) else {
         return false;
@@ -56,9 +59,108 @@ pub fn is_note_done(content: &str) -> bool {
     is_note_done_with_deps(content, &HashMap::new())
 }
 
-#[allow(dead_code)]
+/// Done-states of the notes referenced by `@ID` checklist items in `content`,
+/// for use as `dep_states` in [`sync_ref_checkboxes`].
+///
+/// With `max_depth = 1` a referenced note counts as done by its rendered
+/// checkboxes alone. Each extra level re-evaluates that note's own `@ID` items
+/// against the notes they reference, so "done iff all refs are done,
+/// recursively" holds up to the limit. A reference back into the chain being
+/// evaluated is a cycle and counts as not done. Notes deeper in the chain that
+/// are missing from `notes` are not done; directly referenced ones are left
+/// out of the result, so their boxes stay `RefState::Unknown`.
+///
+/// The reconcile DSL (`eval`) is the primary, fully transitive resolver and
+/// reports cycles as diagnostics; this helper serves the one-note format path.
+pub fn resolve_dep_states(
+    note_id: &str,
+    content: &str,
+    notes: &HashMap<String, String>,
+    max_depth: usize,
+    note_parser: &Parser,
+) -> HashMap<String, bool> {
+    let mut chain = HashSet::from([note_id.to_string()]);
+    ref_target_ids(content, note_parser)
+        .into_iter()
+        .filter(|id| notes.contains_key(id))
+        .map(|id| {
+            let depth_left = max_depth.saturating_sub(1);
+            let done = note_done_to_depth(&id, notes, depth_left, &mut chain, note_parser);
+            (id, done)
+        })
+        .collect()
+}
+
+/// IDs referenced by `@ID` checklist items in `content`.
+pub fn ref_target_ids(content: &str, note_parser: &Parser) -> HashSet<String> {
+    note_parser
+        .parse_checklist_items(content)
+        .into_iter()
+        .filter_map(|item| match item.kind {
+            parser::ChecklistItemKind::Ref { targets } => Some(targets),
+            parser::ChecklistItemKind::Local => None,
+        })
+        .flatten()
+        .map(|t| t.target_id)
+        .collect()
+}
+
+fn note_done_to_depth(
+    id: &str,
+    notes: &HashMap<String, String>,
+    depth_left: usize,
+    chain: &mut HashSet<String>,
+    note_parser: &Parser,
+) -> bool {
+    let Some(content) = notes.get(id) else {
+        return false;
+    };
+    if chain.contains(id) {
+        return false;
+    }
+    if depth_left == 0 {
+        // Depth limit reached: trust the rendered checkboxes.
+        return is_note_done_as_rendered(content, note_parser);
+    }
+    chain.insert(id.to_string());
+    let deps: HashMap<String, bool> = ref_target_ids(content, note_parser)
+        .into_iter()
+        .map(|dep| {
+            let done = note_done_to_depth(&dep, notes, depth_left - 1, chain, note_parser);
+            (dep, done)
+        })
+        .collect();
+    chain.remove(id);
+    note_done_with_deps(content, &deps, note_parser)
+}
+
+/// Done-ness from the note's own checkbox marks, `@ID` items included.
+fn is_note_done_as_rendered(content: &str, note_parser: &Parser) -> bool {
+    let Some(header) = note_parser.parse_header(content) else {
+        return false;
+    };
+    if header.archived {
+        return true;
+    }
+    let items: Vec<parser::ChecklistItem> = note_parser
+        .parse_checklist_items(content)
+        .into_iter()
+        .map(|item| parser::ChecklistItem {
+            kind: parser::ChecklistItemKind::Local,
+            ..item
+        })
+        .collect();
+    if items.is_empty() {
+        return header.checklist_status == Some(ChecklistStatus::Done);
+    }
+    parser::compute_note_done_from_items(&items, &|_| false)
+}
+
+/// `sync_ref_checkboxes` followed by the nested-checkbox and status passes
+/// that the default hooks perform during formatting.
+#[cfg(test)]
 pub fn normalize_note(content: &str, dep_states: &HashMap<String, bool>) -> String {
-    let after_refs = update_ref_checkboxes_sync(content, dep_states);
+    let after_refs = sync_ref_checkboxes(content, dep_states, parser::default_parser());
     let after_nested = update_nested_checkboxes(&after_refs);
     apply_tag_edit(&after_nested)
 }
@@ -66,14 +168,21 @@ pub fn normalize_note(content: &str, dep_states: &HashMap<String, bool>) -> Stri
 pub fn normalize_note_from_checked(
     content: &str,
     checked_by_line: &HashMap<usize, bool>,
+    note_parser: &Parser,
 ) -> String {
-    let after_refs = update_ref_checkboxes_by_line(content, checked_by_line);
+    let after_refs = update_ref_checkboxes_by_line(content, checked_by_line, note_parser);
     let after_nested = update_nested_checkboxes(&after_refs);
     apply_tag_edit(&after_nested)
 }
 
-fn update_ref_checkboxes_sync(content: &str, dep_states: &HashMap<String, bool>) -> String {
-    let lines: Vec<&str> = content.lines().collect();
+/// Set the mark of each `@ID` checklist item in `content` from `dep_states`;
+/// items with an unknown target keep their mark.
+pub fn sync_ref_checkboxes(
+    content: &str,
+    dep_states: &HashMap<String, bool>,
+    note_parser: &Parser,
+) -> String {
+    let lines: Vec<&str> = parser::split_lines(content).collect();
     let mut result: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
     let mut changed = false;
     let mut in_fence = false;
@@ -83,32 +192,21 @@ fn update_ref_checkboxes_sync(content: &str, dep_states: &HashMap<String, bool>)
             in_fence = !in_fence;
             continue;
         }
-        if in_fence || !is_todo_line(line) {
+        if in_fence {
             continue;
         }
-        let ids: Vec<&str> = RE_TODO_ID
-            .captures_iter(line)
-            .filter_map(|c| c.get(1).map(|m| m.as_str()))
-            .collect();
-        if ids.is_empty() {
-            continue;
-        }
-        let all_done = ids
-            .iter()
-            .all(|id| dep_states.get(*id).copied().unwrap_or(false));
-        let new_state = if all_done { 'x' } else { ' ' };
-        if get_todo_state(line) != Some(new_state) {
-            if let Some(new_line) = replace_todo_state(line, new_state) {
-                result[i] = new_line;
-                changed = true;
-            }
+        if let Some(new_line) = ref_checkbox_update(line, dep_states, note_parser)
+            .and_then(|s| replace_todo_state(line, s))
+        {
+            result[i] = new_line;
+            changed = true;
         }
     }
 
     if !changed {
         return content.to_string();
     }
-    let trailing_newline = content.ends_with('\n');
+    let trailing_newline = content.ends_with(['\n', '\r']);
     let mut out = result.join("\n");
     if trailing_newline {
         out.push('\n');
@@ -116,8 +214,84 @@ fn update_ref_checkboxes_sync(content: &str, dep_states: &HashMap<String, bool>)
     out
 }
 
-fn update_ref_checkboxes_by_line(content: &str, checked_by_line: &HashMap<usize, bool>) -> String {
-    let lines: Vec<&str> = content.lines().collect();
+/// The mark an `@ID` checklist line should carry under `dep_states`, when it
+/// differs from the current one. `None` for lines that are not `@ID` todo
+/// items, for marks already correct, and for `RefState::Unknown`.
+pub(crate) fn ref_checkbox_update(
+    line: &str,
+    dep_states: &HashMap<String, bool>,
+    note_parser: &Parser,
+) -> Option<char> {
+    if !is_todo_line(line) {
+        return None;
+    }
+    let ids: Vec<&str> = note_parser.id_refs(line).map(|(_, id)| id).collect();
+    if ids.is_empty() {
+        return None;
+    }
+    let new_state = match ref_state(&ids, dep_states) {
+        RefState::Done => 'x',
+        RefState::NotDone => ' ',
+        RefState::Unknown => return None,
+    };
+    (get_todo_state(line) != Some(new_state)).then_some(new_state)
+}
+
+/// Resolution of an `@ID` checkbox against the states of its target notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefState {
+    /// Every target is known and done.
+    Done,
+    /// At least one known target is not done.
+    NotDone,
+    /// No known target blocks it, but some target is missing from
+    /// `dep_states` (not created yet, or unreadable).
+    Unknown,
+}
+
+/// Only `NotDone` should clear a checkbox; an `Unknown` box is left as the
+/// user set it, so a missing or unreadable note never wipes a manual check.
+pub fn ref_state(ids: &[&str], dep_states: &HashMap<String, bool>) -> RefState {
+    let mut unknown = false;
+    for id in ids {
+        match dep_states.get(*id) {
+            Some(false) => return RefState::NotDone,
+            Some(true) => {}
+            None => unknown = true,
+        }
+    }
+    if unknown {
+        RefState::Unknown
+    } else {
+        RefState::Done
+    }
+}
+
+/// Line indices of `@ID` checkbox items whose state is `RefState::Unknown`.
+pub fn unknown_ref_lines(
+    content: &str,
+    dep_states: &HashMap<String, bool>,
+    note_parser: &Parser,
+) -> Vec<usize> {
+    note_parser
+        .parse_checklist_items(content)
+        .into_iter()
+        .filter_map(|item| match &item.kind {
+            parser::ChecklistItemKind::Ref { targets } => {
+                let ids: Vec<&str> = targets.iter().map(|t| t.target_id.as_str()).collect();
+                (ref_state(&ids, dep_states) == RefState::Unknown).then_some(item.line_idx)
+            }
+            parser::ChecklistItemKind::Local => None,
+        })
+        .collect()
+}
+
+fn update_ref_checkboxes_by_line(
+    content: &str,
+    checked_by_line: &HashMap<usize, bool>,
+    note_parser: &Parser,
+) -> String {
+    let lines: Vec<&str> = parser::split_lines(content).collect();
     let mut result: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
     let mut changed = false;
     let mut in_fence = false;
@@ -127,7 +301,7 @@ fn update_ref_checkboxes_by_line(content: &str, checked_by_line: &HashMap<usize,
             in_fence = !in_fence;
             continue;
         }
-        if in_fence || !is_todo_line(line) || !RE_TODO_ID.is_match(line) {
+        if in_fence || !is_todo_line(line) || note_parser.id_refs(line).next().is_none() {
             continue;
         }
         if let Some(&checked) = checked_by_line.get(&i) {
@@ -144,7 +318,7 @@ fn update_ref_checkboxes_by_line(content: &str, checked_by_line: &HashMap<usize,
     if !changed {
         return content.to_string();
     }
-    let trailing_newline = content.ends_with('\n');
+    let trailing_newline = content.ends_with(['\n', '\r']);
     let mut out = result.join("\n");
     if trailing_newline {
         out.push('\n');
@@ -153,7 +327,7 @@ fn update_ref_checkboxes_by_line(content: &str, checked_by_line: &HashMap<usize,
 }
 
 fn update_nested_checkboxes(content: &str) -> String {
-    let mut owned_lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let mut owned_lines: Vec<String> = parser::split_lines(content).map(str::to_string).collect();
 
     let mut todo_items: Vec<(usize, usize)> = Vec::new();
     let mut in_fence = false;
@@ -176,8 +350,7 @@ fn update_nested_checkboxes(content: &str) -> String {
         let (line_idx, indent) = todo_items[i];
 
         let mut descendants: Vec<usize> = Vec::new();
-        for j in (i + 1)..todo_items.len() {
-            let (child_line_idx, child_indent) = todo_items[j];
+        for &(child_line_idx, child_indent) in &todo_items[i + 1..] {
             if child_indent <= indent {
                 break;
             }
@@ -192,7 +365,7 @@ fn update_nested_checkboxes(content: &str) -> String {
             .iter()
             .all(|&child_idx| get_todo_state(&owned_lines[child_idx]) == Some('x'));
 
-        let has_ref = RE_TODO_ID.is_match(&owned_lines[line_idx]);
+        let has_ref = has_id_ref(&owned_lines[line_idx]);
         let ref_satisfied = !has_ref || get_todo_state(&owned_lines[line_idx]) == Some('x');
         let new_state = if all_done && ref_satisfied { 'x' } else { ' ' };
         if get_todo_state(&owned_lines[line_idx]) != Some(new_state) {
@@ -202,7 +375,7 @@ fn update_nested_checkboxes(content: &str) -> String {
         }
     }
 
-    let trailing_newline = content.ends_with('\n');
+    let trailing_newline = content.ends_with(['\n', '\r']);
     let mut out = owned_lines.join("\n");
     if trailing_newline {
         out.push('\n');
@@ -212,15 +385,14 @@ fn update_nested_checkboxes(content: &str) -> String {
 
 pub fn compute_tag_edit(content: &str) -> Option<TextEdit> {
     let header = parser::parse_header(content)?;
+    if header.manual_status.is_some() || !header.auto_status {
+        return None;
+    }
     let todos = parser::count_todos(content);
     let new_tag = parser::compute_status_tag(&todos, header.archived)?;
 
     if header.metadata_block.is_some() {
-        let status_str = match new_tag {
-            StatusTag::Done => "done",
-            StatusTag::Wip => "wip",
-            StatusTag::Todo => "todo",
-        };
+        let status_str = new_tag.as_str();
         let current = header.checklist_status.as_ref();
         let already_correct = match new_tag {
             StatusTag::Done => current == Some(&ChecklistStatus::Done),
@@ -240,7 +412,7 @@ pub fn compute_tag_edit(content: &str) -> Option<TextEdit> {
         StatusTag::Todo => "#tag.todo",
     };
 
-    let lines: Vec<&str> = content.lines().collect();
+    let lines: Vec<&str> = parser::split_lines(content).collect();
     let tag_line = lines.get(tag_line_idx)?;
 
     let current_tag_str = if tag_line.contains("#tag.done") {
@@ -374,7 +546,7 @@ mod tests {
     fn fenced_checkboxes_are_not_modified() {
         let input = "- [ ] real item\n```\n- [ ] fake in fence\n```\n";
         let dep_states = HashMap::new();
-        let after_refs = update_ref_checkboxes_sync(input, &dep_states);
+        let after_refs = sync_ref_checkboxes(input, &dep_states, parser::default_parser());
         assert_eq!(after_refs, input);
         let after_nested = update_nested_checkboxes(input);
         assert_eq!(after_nested, input);
@@ -384,7 +556,7 @@ mod tests {
     fn parent_ref_not_overridden_by_done_children() {
         let input = "- [ ] @1234567890 task\n  - [x] child\n";
         let dep_states = HashMap::new();
-        let after_refs = update_ref_checkboxes_sync(input, &dep_states);
+        let after_refs = sync_ref_checkboxes(input, &dep_states, parser::default_parser());
         let out = update_nested_checkboxes(&after_refs);
         assert!(out.starts_with("- [ ]"));
     }
@@ -393,7 +565,7 @@ mod tests {
     fn parent_ref_and_children_both_done_promotes_parent() {
         let input = "- [ ] @1234567890 task\n  - [ ] child\n";
         let dep_states = HashMap::from([("1234567890".to_string(), true)]);
-        let after_refs = update_ref_checkboxes_sync(input, &dep_states);
+        let after_refs = sync_ref_checkboxes(input, &dep_states, parser::default_parser());
         let out = update_nested_checkboxes(&after_refs);
         assert!(out.starts_with("- [ ]"));
     }
@@ -408,4 +580,155 @@ mod tests {
         assert!(parser::ChecklistStatus::Done == parser::ChecklistStatus::Done);
         assert!(parser::ChecklistStatus::None != parser::ChecklistStatus::Done);
     }
+
+    #[test]
+    fn manual_status_without_todos_is_left_alone() {
+        let manual = dep_note("1111111111", "Prose only.\n").replace("\"none\"", "\"wip\"");
+        assert_eq!(
+            parser::parse_header(&manual).unwrap().manual_status,
+            Some(StatusTag::Wip)
+        );
+        assert!(compute_tag_edit(&manual).is_none());
+        assert_eq!(normalize_note(&manual, &HashMap::new()), manual);
+
+        // With todos the status is derived again and gets corrected.
+        let derived = manual.clone() + "- [x] task\n";
+        let edit = compute_tag_edit(&derived).expect("status edit");
+        assert!(edit.new_text.contains("\"done\""));
+    }
+
+    #[test]
+    fn auto_status_false_is_never_retagged() {
+        let note = dep_note("1111111111", "- [x] one\n- [x] two\n")
+            .replace("\"none\"", "\"todo\"\n  auto-status = false");
+        let header = parser::parse_header(&note).unwrap();
+        assert!(!header.auto_status);
+        assert_eq!(header.checklist_status, Some(ChecklistStatus::Todo));
+        assert!(compute_tag_edit(&note).is_none());
+        assert_eq!(apply_tag_edit(&note), note);
+
+        // Without the flag the same note is corrected to done.
+        let managed = note.replace("  auto-status = false\n", "");
+        assert!(parser::parse_header(&managed).unwrap().auto_status);
+        let edit = compute_tag_edit(&managed).expect("status edit");
+        assert!(edit.new_text.contains("\"done\""));
+    }
+
+    #[test]
+    fn apply_tag_edit_handles_bare_cr_line_breaks() {
+        let note = dep_note("1111111111", "- [x] one\n- [x] two\n").replace('\n', "\r");
+        assert!(compute_tag_edit(&note).is_some());
+        let updated = apply_tag_edit(&note);
+        assert!(
+            updated.contains("checklist-status = \"done\""),
+            "{updated:?}"
+        );
+        assert!(updated.ends_with("- [x] two\n"));
+    }
+
+    fn dep_note(id: &str, body: &str) -> String {
+        format!(
+            "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  checklist-status = \"none\"\n  relation = \"active\"\n  ```.text,\n))\n= Note <{id}>\n{body}"
+        )
+    }
+
+    fn chain_notes() -> HashMap<String, String> {
+        // A -> B -> C; C is done, B's rendered box for C is still unchecked.
+        HashMap::from([
+            (
+                "1111111111".into(),
+                dep_note("1111111111", "- [ ] @2222222222\n"),
+            ),
+            (
+                "2222222222".into(),
+                dep_note("2222222222", "- [ ] @3333333333\n"),
+            ),
+            (
+                "3333333333".into(),
+                dep_note("3333333333", "- [x] finished\n"),
+            ),
+        ])
+    }
+
+    #[test]
+    fn resolve_dep_states_depth_one_uses_rendered_state() {
+        let notes = chain_notes();
+        let states = resolve_dep_states(
+            "1111111111",
+            &notes["1111111111"],
+            &notes,
+            1,
+            parser::default_parser(),
+        );
+        assert_eq!(states.get("2222222222"), Some(&false));
+    }
+
+    #[test]
+    fn resolve_dep_states_follows_refs_transitively() {
+        let notes = chain_notes();
+        let states = resolve_dep_states(
+            "1111111111",
+            &notes["1111111111"],
+            &notes,
+            2,
+            parser::default_parser(),
+        );
+        assert_eq!(states.get("2222222222"), Some(&true));
+        let out = normalize_note(&notes["1111111111"], &states);
+        assert!(out.contains("- [x] @2222222222"));
+    }
+
+    #[test]
+    fn resolve_dep_states_cycle_terminates_as_not_done() {
+        let notes = HashMap::from([
+            (
+                "1111111111".into(),
+                dep_note("1111111111", "- [x] @2222222222\n"),
+            ),
+            (
+                "2222222222".into(),
+                dep_note("2222222222", "- [x] @1111111111\n"),
+            ),
+        ]);
+        let states = resolve_dep_states(
+            "1111111111",
+            &notes["1111111111"],
+            &notes,
+            50,
+            parser::default_parser(),
+        );
+        assert_eq!(states.get("2222222222"), Some(&false));
+    }
+
+    #[test]
+    fn ref_state_distinguishes_unknown_from_not_done() {
+        let states = HashMap::from([
+            ("1111111111".to_string(), true),
+            ("2222222222".to_string(), false),
+        ]);
+        assert_eq!(ref_state(&["1111111111"], &states), RefState::Done);
+        assert_eq!(
+            ref_state(&["9999999999", "2222222222"], &states),
+            RefState::NotDone
+        );
+        assert_eq!(
+            ref_state(&["1111111111", "9999999999"], &states),
+            RefState::Unknown
+        );
+    }
+
+    #[test]
+    fn unknown_ref_keeps_manual_check() {
+        let input = "- [x] @9999999999 not written yet\n- [x] @2222222222 known\n";
+        let states = HashMap::from([("2222222222".to_string(), false)]);
+        let out = sync_ref_checkboxes(input, &states, parser::default_parser());
+        assert_eq!(
+            out,
+            "- [x] @9999999999 not written yet\n- [ ] @2222222222 known\n"
+        );
+        assert_eq!(
+            unknown_ref_lines(input, &states, parser::default_parser()),
+            vec![0]
+        );
+    }
 }
@@ -1,24 +1,48 @@
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+use dashmap::DashMap;
 use serde_json::Value;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use tower_lsp::jsonrpc::Result as LspResult;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
-use tracing::{error, info};
+use tracing::{error, info, instrument, warn};
 
-use crate::config::WikiConfig;
+use crate::cancel::CancelToken;
+use crate::config::{ClientSettings, WikiConfig};
+use crate::context_export::{self, Direction};
+use crate::documents::DocumentStore;
 use crate::handlers::{
-    code_actions, completion, definition, diagnostics, hover, inlay_hints, references,
+    code_actions, completion, definition, diagnostics, formatting, hover, inlay_hints, references,
+};
+use crate::index::{IndexChange, NoteIndex};
+use crate::parser::NoteDoc;
+use crate::reconcile::types::ReconcileDiagnostic;
+use crate::reconcile::writeback;
+use crate::{
+    abstract_gen, archive_migration, link_gen, link_tree, malformed, note_ops, parser, reconcile,
+    stale_tags, timeline, vocabulary, watcher,
 };
-use crate::index::NoteIndex;
-use crate::{link_gen, note_ops, reconcile, watcher};
 
 pub struct ZkLspServer {
     client: Client,
     index: Arc<NoteIndex>,
     config: Arc<RwLock<WikiConfig>>,
     cli_root: Option<std::path::PathBuf>,
+    /// Client supports dynamic registration of `workspace/didChangeWatchedFiles`.
+    client_file_events: AtomicBool,
+    /// Client accepts server-initiated `window/workDoneProgress/create`.
+    client_work_done_progress: AtomicBool,
+    /// Set while the internal `notify` watcher runs (and maintains link.typ
+    /// itself): where to send the note_dir it should watch after a move.
+    internal_watcher: Arc<std::sync::Mutex<Option<std::sync::mpsc::Sender<std::path::PathBuf>>>>,
+    /// Open editor buffers; preferred over disk for cross-file evaluation.
+    documents: DocumentStore,
+    /// Text last pushed by `formatOnSave`, per file. Saving exactly that text
+    /// is not formatted again, so a non-idempotent user hook cannot cause an
+    /// edit/save loop.
+    formatted_on_save: DashMap<std::path::PathBuf, String>,
 }
 
 impl ZkLspServer {
@@ -33,6 +57,11 @@ impl ZkLspServer {
             index,
             config,
             cli_root,
+            client_file_events: AtomicBool::new(false),
+            client_work_done_progress: AtomicBool::new(false),
+            internal_watcher: Arc::new(std::sync::Mutex::new(None)),
+            documents: DocumentStore::new(),
+            formatted_on_save: DashMap::new(),
         }
     }
 
@@ -40,24 +69,317 @@ impl ZkLspServer {
         self.config.read().await.clone()
     }
 
-    async fn publish_diagnostics(&self, uri: Url, content: &str) {
+    /// The editor buffer for `path` if it is open, otherwise the file on disk
+    /// (empty when unreadable).
+    async fn note_text(&self, path: &std::path::Path) -> String {
+        match self.documents.get(path) {
+            Some(text) => text,
+            None => tokio::fs::read_to_string(path).await.unwrap_or_default(),
+        }
+    }
+
+    /// `NoteDoc::parse_with` the wiki's configured parser.
+    async fn parse_note(&self, content: String) -> NoteDoc {
+        NoteDoc::parse_with(&self.config.read().await.parser(), content)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(uri = %uri))]
+    async fn publish_diagnostics(&self, uri: Url, doc: &NoteDoc) {
         let file_path = uri.to_file_path().unwrap_or_default();
-        let mut diags = diagnostics::get_diagnostics(content, &self.index, uri.path());
-        diags.extend(diagnostics::get_schema_diagnostics(content, &self.index));
         let config = self.current_config().await;
-        if let Ok(reconcile_diags) =
-            reconcile::collect_diagnostics(&config, Some((&file_path, content))).await
+        let mut overlay = self.documents.snapshot();
+        overlay.insert(file_path, doc.content.clone());
+        let reconcile_diags = reconcile::collect_diagnostics(&config, &overlay)
+            .await
+            .unwrap_or_default();
+        let diags = self.note_diagnostics(&uri, doc, &config, &reconcile_diags);
+        self.client.publish_diagnostics(uri, diags, None).await;
+    }
+
+    /// `formatOnSave`: push the formatter's edits for a just-saved note via
+    /// `workspace/applyEdit`. Already-formatted notes produce no edit.
+    ///
+    /// The edit names the document version that was saved, so a client that
+    /// has had more typing since rejects it instead of applying line ranges
+    /// to different text.
+    async fn format_on_save(&self, uri: Url, content: &str) {
+        let Ok(path) = uri.to_file_path() else {
+            return;
+        };
+        let version = self.documents.version(&path);
+        if self
+            .formatted_on_save
+            .remove_if(&path, |_, pushed| pushed == content)
+            .is_some()
         {
-            diags.extend(diagnostics::get_reconcile_diagnostics(
+            return;
+        }
+        let config = self.current_config().await;
+        let formatted = formatting::format_content(content, &config, Some(&self.documents)).await;
+        let edits = formatting::format_edits(content, &formatted);
+        if edits.is_empty() {
+            return;
+        }
+        self.formatted_on_save.insert(path.clone(), formatted);
+        let edit = WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier { uri, version },
+                edits: edits.into_iter().map(OneOf::Left).collect(),
+            }])),
+            ..Default::default()
+        };
+        let applied = match self.client.apply_edit(edit).await {
+            Ok(resp) if !resp.applied => {
+                error!(
+                    "formatOnSave: edit rejected: {}",
+                    resp.failure_reason.unwrap_or_default()
+                );
+                false
+            }
+            Ok(_) => true,
+            Err(e) => {
+                error!("formatOnSave: {e}");
+                false
+            }
+        };
+        // Nothing was pushed, so the next save of this text is formatted.
+        if !applied {
+            self.formatted_on_save.remove(&path);
+        }
+    }
+
+    fn note_diagnostics(
+        &self,
+        uri: &Url,
+        doc: &NoteDoc,
+        config: &WikiConfig,
+        reconcile_diags: &[ReconcileDiagnostic],
+    ) -> Vec<Diagnostic> {
+        let file_path = uri.to_file_path().unwrap_or_default();
+        let content = doc.content.as_str();
+        let mut diags = diagnostics::get_diagnostics(content, &self.index, uri.path(), config);
+        diags.extend(diagnostics::get_schema_diagnostics(doc, Some(&self.index)));
+        diags.extend(diagnostics::get_reconcile_diagnostics(
+            content,
+            &file_path,
+            reconcile_diags,
+        ));
+        if let Some(d) = diagnostics::get_orphan_diagnostic(
+            doc,
+            uri.path(),
+            &self.index,
+            config.settings.exclude_generated,
+        ) {
+            diags.push(d);
+        }
+        if config.settings.missing_metadata_diagnostic {
+            diags.extend(diagnostics::get_missing_metadata_diagnostic(
                 content,
-                &file_path,
-                &reconcile_diags,
+                &config.zk_config,
+                &config.parser(),
             ));
         }
-        if let Some(d) = diagnostics::get_orphan_diagnostic(content, uri.path(), &self.index) {
-            diags.push(d);
+        diags
+    }
+
+    /// Publish diagnostics for every indexed note.
+    ///
+    /// Reconcile runs once for the whole wiki; notes are then processed in
+    /// batches of `workspaceDiagnosticsBatchSize`, yielding between batches so
+    /// other requests keep being served. Returns the number of notes published.
+    #[instrument(level = "debug", skip_all, fields(notes))]
+    async fn publish_workspace_diagnostics(&self) -> usize {
+        let config = self.current_config().await;
+        let batch_size = config.settings.workspace_diagnostics_batch_size.max(1);
+        // Open notes are checked as the buffer has them, like reconcile does,
+        // so positions match what the client shows.
+        let open = self.documents.snapshot();
+        let reconcile_diags = reconcile::collect_diagnostics(&config, &open)
+            .await
+            .unwrap_or_default();
+        let mut paths: Vec<std::path::PathBuf> = self
+            .index
+            .notes
+            .iter()
+            .map(|e| e.value().path.clone())
+            .collect();
+        paths.sort();
+        let parser = config.parser();
+
+        let mut published = 0;
+        for chunk in paths.chunks(batch_size) {
+            for path in chunk {
+                let content = match open.get(path) {
+                    Some(text) => text.clone(),
+                    None => match tokio::fs::read_to_string(path).await {
+                        Ok(text) => text,
+                        Err(_) => continue,
+                    },
+                };
+                let Ok(uri) = Url::from_file_path(path) else {
+                    continue;
+                };
+                let doc = NoteDoc::parse_with(&parser, content);
+                let diags = self.note_diagnostics(&uri, &doc, &config, &reconcile_diags);
+                self.client.publish_diagnostics(uri, diags, None).await;
+                published += 1;
+            }
+            tokio::task::yield_now().await;
+        }
+        tracing::Span::current().record("notes", published);
+        published
+    }
+
+    /// Re-read the project config after it or the editor settings changed.
+    ///
+    /// When the note directory moved and the client watches files for us,
+    /// the `didChangeWatchedFiles` registration is replaced to follow it.
+    async fn reload_config(&self) {
+        let note_dir = watcher::reload_config(&self.config, &self.index).await;
+        let _ = self.client.inlay_hint_refresh().await;
+        let Some(note_dir) = note_dir else {
+            return;
+        };
+        if let Some(note_dirs) = self.internal_watcher.lock().unwrap().as_ref() {
+            let _ = note_dirs.send(note_dir);
+            return;
+        }
+        let config = self.current_config().await;
+        let _ = self
+            .client
+            .unregister_capability(vec![Unregistration {
+                id: WATCHED_FILES_REGISTRATION_ID.into(),
+                method: "workspace/didChangeWatchedFiles".into(),
+            }])
+            .await;
+        if let Err(e) = self
+            .client
+            .register_capability(vec![watched_files_registration(&config)])
+            .await
+        {
+            error!("didChangeWatchedFiles re-registration failed: {e}");
         }
-        self.client.publish_diagnostics(uri, diags, None).await;
+    }
+}
+
+/// Custom notification sent whenever notes are added, removed or retitled in
+/// the index, or the index is rebuilt. Clients that don't care ignore it.
+enum IndexChangedNotification {}
+
+impl tower_lsp::lsp_types::notification::Notification for IndexChangedNotification {
+    type Params = IndexChange;
+    const METHOD: &'static str = "$/zk/indexChanged";
+}
+
+const WATCHED_FILES_REGISTRATION_ID: &str = "zk-lsp/watched-files";
+
+const INDEX_PROGRESS_TOKEN: &str = "zk-lsp/index";
+
+/// `rebuild_full`, shown to the client as `$/progress` ("Indexing notes",
+/// with a percentage) when `report` is set and it accepts the progress token.
+async fn rebuild_with_progress(
+    client: &Client,
+    index: &NoteIndex,
+    report: bool,
+) -> anyhow::Result<usize> {
+    let token = NumberOrString::String(INDEX_PROGRESS_TOKEN.into());
+    let created = report
+        && client
+            .send_request::<request::WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: token.clone(),
+            })
+            .await
+            .is_ok();
+    if !created {
+        return index.rebuild_full().await;
+    }
+    let progress = |value: WorkDoneProgress| ProgressParams {
+        token: token.clone(),
+        value: ProgressParamsValue::WorkDone(value),
+    };
+    client
+        .send_notification::<notification::Progress>(progress(WorkDoneProgress::Begin(
+            WorkDoneProgressBegin {
+                title: "Indexing notes".into(),
+                percentage: Some(0),
+                ..Default::default()
+            },
+        )))
+        .await;
+
+    // The callback cannot await, so counts go through a channel; a report is
+    // sent only when the percentage moves.
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<(usize, usize)>();
+    let reporter = client.clone();
+    let report_token = token.clone();
+    let forward = tokio::spawn(async move {
+        let mut last = 0;
+        while let Some((done, total)) = rx.recv().await {
+            let percentage = (done * 100 / total.max(1)) as u32;
+            if percentage == last {
+                continue;
+            }
+            last = percentage;
+            let value = WorkDoneProgress::Report(WorkDoneProgressReport {
+                message: Some(format!("{done}/{total}")),
+                percentage: Some(percentage),
+                ..Default::default()
+            });
+            reporter
+                .send_notification::<notification::Progress>(ProgressParams {
+                    token: report_token.clone(),
+                    value: ProgressParamsValue::WorkDone(value),
+                })
+                .await;
+        }
+    });
+    let result = index
+        .rebuild_full_with_progress(|done, total| {
+            let _ = tx.send((done, total));
+        })
+        .await;
+    drop(tx);
+    let _ = forward.await;
+
+    let message = match &result {
+        Ok(n) => format!("{n} notes"),
+        Err(e) => format!("failed: {e}"),
+    };
+    client
+        .send_notification::<notification::Progress>(progress(WorkDoneProgress::End(
+            WorkDoneProgressEnd {
+                message: Some(message),
+            },
+        )))
+        .await;
+    result
+}
+
+/// Registration asking the client to report create/change/delete events for
+/// note files and project config files, replacing the internal watcher.
+fn watched_files_registration(config: &WikiConfig) -> Registration {
+    let options = DidChangeWatchedFilesRegistrationOptions {
+        watchers: vec![
+            FileSystemWatcher {
+                glob_pattern: GlobPattern::String(format!(
+                    "{}/**/*.typ",
+                    config.note_dir.display()
+                )),
+                kind: None,
+            },
+            FileSystemWatcher {
+                glob_pattern: GlobPattern::String(format!(
+                    "{}/{{zk-lsp.toml,.zk-lsp.toml}}",
+                    config.root.display()
+                )),
+                kind: None,
+            },
+        ],
+    };
+    Registration {
+        id: WATCHED_FILES_REGISTRATION_ID.into(),
+        method: "workspace/didChangeWatchedFiles".into(),
+        register_options: serde_json::to_value(options).ok(),
     }
 }
 
@@ -65,11 +387,32 @@ impl ZkLspServer {
 impl LanguageServer for ZkLspServer {
     async fn initialize(&self, params: InitializeParams) -> LspResult<InitializeResult> {
         let init_root = WikiConfig::lsp_root(&params);
-        let resolved = WikiConfig::resolve(self.cli_root.clone(), init_root);
+        let mut resolved = WikiConfig::resolve(self.cli_root.clone(), init_root);
+        if let Some(options) = params.initialization_options.as_ref() {
+            resolved = resolved.with_settings(ClientSettings::from_json(options));
+        }
         let resolved_root = resolved.root.clone();
         *self.config.write().await = resolved;
         info!("initialize: resolved root to {}", resolved_root.display());
 
+        let dynamic_watch = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|w| w.did_change_watched_files.as_ref())
+            .and_then(|c| c.dynamic_registration)
+            .unwrap_or(false);
+        self.client_file_events
+            .store(dynamic_watch, Ordering::Relaxed);
+        let work_done_progress = params
+            .capabilities
+            .window
+            .as_ref()
+            .and_then(|w| w.work_done_progress)
+            .unwrap_or(false);
+        self.client_work_done_progress
+            .store(work_done_progress, Ordering::Relaxed);
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Options(
@@ -87,7 +430,7 @@ impl LanguageServer for ZkLspServer {
                 references_provider: Some(OneOf::Left(true)),
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
                 completion_provider: Some(CompletionOptions {
-                    trigger_characters: Some(vec!["\"".into(), "=".into(), "[".into()]),
+                    trigger_characters: Some(vec!["\"".into(), "=".into(), "[".into(), "@".into()]),
                     resolve_provider: Some(false),
                     ..Default::default()
                 }),
@@ -97,9 +440,38 @@ impl LanguageServer for ZkLspServer {
                 execute_command_provider: Some(ExecuteCommandOptions {
                     commands: vec![
                         "zk.newNote".into(),
+                        "zk.dailyNote".into(),
                         "zk.removeNote".into(),
                         "zk.generateLinkTyp".into(),
+                        "zk.verifyLinks".into(),
+                        "zk.normalizeLinks".into(),
                         "zk.exportContext".into(),
+                        "zk.pendingMigrations".into(),
+                        "zk.migrateArchived".into(),
+                        "zk.retireNote".into(),
+                        "zk.publishWorkspaceDiagnostics".into(),
+                        "zk.notesInRange".into(),
+                        "zk.notesTimeline".into(),
+                        "zk.hubNotes".into(),
+                        "zk.backlinkCounts".into(),
+                        "zk.leafNotes".into(),
+                        "zk.generateAbstract".into(),
+                        "zk.notesByTag".into(),
+                        "zk.relatedByTopic".into(),
+                        "zk.backlinksGrouped".into(),
+                        "zk.linkTree".into(),
+                        "zk.malformedNotes".into(),
+                        "zk.staleTags".into(),
+                        "zk.notePath".into(),
+                        "zk.insertReference".into(),
+                        "zk.splitNote".into(),
+                        "zk.noteStatus".into(),
+                        "zk.copyReference".into(),
+                        "zk.copyId".into(),
+                        "zk.previewFormat".into(),
+                        "zk.vocabulary".into(),
+                        "zk.status".into(),
+                        "zk.duplicateTitles".into(),
                     ],
                     work_done_progress_options: Default::default(),
                 }),
@@ -117,9 +489,45 @@ impl LanguageServer for ZkLspServer {
         let index = Arc::clone(&self.index);
         let config = Arc::clone(&self.config);
         let client = self.client.clone();
+        let internal_watcher = Arc::clone(&self.internal_watcher);
+        let use_client_events = self.client_file_events.load(Ordering::Relaxed)
+            && !self.config.read().await.settings.force_internal_watcher;
+        let report_progress = self.client_work_done_progress.load(Ordering::Relaxed);
+
+        // Forward index changes to the client as `$/zk/indexChanged`.
+        let mut changes = index.subscribe();
+        let notify_client = client.clone();
+        tokio::spawn(async move {
+            loop {
+                match changes.recv().await {
+                    Ok(change) => {
+                        notify_client
+                            .send_notification::<IndexChangedNotification>(change)
+                            .await
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        error!("dropped {n} index change notifications")
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
 
         tokio::spawn(async move {
-            match index.rebuild_full().await {
+            let note_dir = config.read().await.note_dir.clone();
+            let state = note_ops::NoteDirState::probe(&note_dir).await;
+            if let Some(problem) = state.describe(&note_dir) {
+                let kind = if state.is_usable() {
+                    MessageType::INFO
+                } else {
+                    MessageType::WARNING
+                };
+                warn!("{problem}");
+                client
+                    .show_message(kind, format!("zk-lsp: {problem}"))
+                    .await;
+            }
+            match rebuild_with_progress(&client, &index, report_progress).await {
                 Ok(n) => {
                     info!("index built: {n} notes");
                     // Tell the client to re-request inlay hints now that the index is ready.
@@ -129,9 +537,32 @@ impl LanguageServer for ZkLspServer {
                 }
                 Err(e) => error!("index build failed: {e}"),
             }
+            if use_client_events {
+                let snapshot = config.read().await.clone();
+                match client
+                    .register_capability(vec![watched_files_registration(&snapshot)])
+                    .await
+                {
+                    Ok(()) => {
+                        info!(
+                            "relying on client file events for {}",
+                            snapshot.note_dir.display()
+                        );
+                        return;
+                    }
+                    Err(e) => error!("didChangeWatchedFiles registration failed: {e}"),
+                }
+            }
             // Start filesystem watcher
-            if let Err(e) = watcher::start_watcher(config, index) {
-                error!("watcher start failed: {e}");
+            let refresh = move || {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    let _ = client.inlay_hint_refresh().await;
+                });
+            };
+            match watcher::start_watcher(config, index, refresh).await {
+                Ok(note_dirs) => *internal_watcher.lock().unwrap() = Some(note_dirs),
+                Err(e) => error!("watcher start failed: {e}"),
             }
         });
     }
@@ -146,12 +577,29 @@ impl LanguageServer for ZkLspServer {
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         let uri = params.text_document.uri.clone();
-        let content = params.text_document.text;
-        // Update index for this file
+        // Parsed once for both the index update and the diagnostics.
+        let doc = self.parse_note(params.text_document.text).await;
         if let Ok(path) = uri.to_file_path() {
-            let _ = self.index.update_file(&path).await;
+            self.documents.open(path.clone(), doc.content.clone());
+            self.documents
+                .set_version(&path, params.text_document.version);
+            let _ = self.index.update_doc(&path, &doc).await;
+        }
+        self.publish_diagnostics(uri, &doc).await;
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        if let Ok(path) = params.text_document.uri.to_file_path() {
+            self.documents.change(&path, &params.content_changes);
+            self.documents
+                .set_version(&path, params.text_document.version);
+        }
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        if let Ok(path) = params.text_document.uri.to_file_path() {
+            self.documents.close(&path);
         }
-        self.publish_diagnostics(uri, &content).await;
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
@@ -168,28 +616,93 @@ impl LanguageServer for ZkLspServer {
             },
         };
 
-        // Update index
+        // Parsed once for both the index update and the diagnostics.
+        let doc = self.parse_note(content).await;
         if let Ok(path) = uri.to_file_path() {
-            let _ = self.index.update_file(&path).await;
+            self.documents.open(path.clone(), doc.content.clone());
+            let _ = self.index.update_doc(&path, &doc).await;
         }
 
         // Publish diagnostics for the saved file
-        self.publish_diagnostics(uri.clone(), &content).await;
+        self.publish_diagnostics(uri.clone(), &doc).await;
+
+        if self.config.read().await.settings.format_on_save {
+            self.format_on_save(uri, &doc.content).await;
+        }
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        // Pull-model clients send `null`; keep the current settings then.
+        if params.settings.is_null() {
+            return;
+        }
+        let recount = {
+            let mut config = self.config.write().await;
+            let settings = config.settings.merge_json(&params.settings);
+            let recount = config.settings.count_self_references != settings.count_self_references;
+            config.settings = settings;
+            recount
+        };
+        self.reload_config().await;
+        // Self-references are filtered while indexing, so toggling
+        // `countSelfReferences` needs a rebuild.
+        if recount {
+            match self.index.rebuild_full().await {
+                Ok(n) => info!("index rebuilt for countSelfReferences: {n} notes"),
+                Err(e) => error!("index rebuild failed: {e}"),
+            }
+        }
     }
 
     async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        // When the internal watcher is running it already keeps link.typ in
+        // sync; otherwise these client events are the only source of truth.
+        let sync_link_typ = self.internal_watcher.lock().unwrap().is_none();
         for change in params.changes {
             let uri = change.uri.clone();
             if let Ok(path) = uri.to_file_path() {
+                if self.config.read().await.is_project_config_file(&path) {
+                    self.reload_config().await;
+                    continue;
+                }
+                if !watcher::is_note_file(&path) {
+                    continue;
+                }
+                let id = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("")
+                    .to_string();
                 match change.typ {
                     FileChangeType::CREATED | FileChangeType::CHANGED => {
-                        let _ = self.index.update_file(&path).await;
-                        if let Ok(content) = tokio::fs::read_to_string(&path).await {
-                            self.publish_diagnostics(uri, &content).await;
+                        let config = self.current_config().await;
+                        let read = note_ops::read_note_text(
+                            &path,
+                            config.zk_config.lossy_reads,
+                            config.max_note_bytes(),
+                        );
+                        let doc = match read.await {
+                            Ok(content) => Some(self.parse_note(content).await),
+                            Err(_) => None,
+                        };
+                        let _ = match &doc {
+                            Some(doc) => self.index.update_doc(&path, doc).await,
+                            None => self.index.update_file(&path).await,
+                        };
+                        if sync_link_typ && change.typ == FileChangeType::CREATED {
+                            let config = self.current_config().await;
+                            let _ = link_gen::add_entry(&id, &config).await;
+                        }
+                        if let Some(doc) = doc {
+                            self.publish_diagnostics(uri, &doc).await;
                         }
                     }
                     FileChangeType::DELETED => {
-                        self.index.remove_by_path(&path);
+                        self.index.remove_by_path(&path).await;
+                        if sync_link_typ {
+                            let config = self.current_config().await;
+                            let _ = link_gen::remove_entry(&id, &config).await;
+                        }
                     }
                     _ => {}
                 }
@@ -207,14 +720,29 @@ impl LanguageServer for ZkLspServer {
     ) -> LspResult<Option<GotoDefinitionResponse>> {
         let uri = &params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
-        let content = uri
-            .to_file_path()
-            .ok()
-            .and_then(|p| std::fs::read_to_string(p).ok())
-            .unwrap_or_default();
+        let from = uri.to_file_path().unwrap_or_default();
+        let content = std::fs::read_to_string(&from).unwrap_or_default();
+        let config = self.current_config().await;
+
+        let note_parser = config.parser();
 
-        Ok(definition::get_definition(&content, position, &self.index)
-            .map(GotoDefinitionResponse::Scalar))
+        Ok(
+            definition::get_cross_wiki_definition(&content, position, &config)
+                .or_else(|| definition::get_definition(&content, position, &self.index, &from))
+                .or_else(|| {
+                    definition::get_title_link_definition(&content, position, &self.index, &config)
+                })
+                .or_else(|| {
+                    definition::get_wikilink_definition(
+                        &content,
+                        position,
+                        &self.index,
+                        &note_parser,
+                        &from,
+                    )
+                })
+                .map(GotoDefinitionResponse::Scalar),
+        )
     }
 
     // -----------------------------------------------------------------------
@@ -232,8 +760,9 @@ impl LanguageServer for ZkLspServer {
             Some(c) => c,
             None => return Ok(None),
         };
-        let line = content.lines().nth(row).unwrap_or("");
-        let locs = references::find_references(&self.index, uri, line);
+        let line = parser::split_lines(&content).nth(row).unwrap_or("");
+        let note_parser = self.current_config().await.parser();
+        let locs = references::find_references(&self.index, uri, line, &note_parser);
         Ok(Some(locs))
     }
 
@@ -241,19 +770,38 @@ impl LanguageServer for ZkLspServer {
     // Code actions
     // -----------------------------------------------------------------------
 
+    /// Diagnostic quick-fixes, then actions on `params.range` built from the
+    /// document text (the editor buffer when open, so positions match unsaved
+    /// edits). The text is only read when the client accepts refactor actions.
     async fn code_action(&self, params: CodeActionParams) -> LspResult<Option<CodeActionResponse>> {
         let uri = &params.text_document.uri;
-        let content = uri
-            .to_file_path()
-            .ok()
-            .and_then(|p| std::fs::read_to_string(p).ok())
-            .unwrap_or_default();
-        let mut actions = code_actions::get_code_actions(uri, &params.context.diagnostics);
+        let config = self.current_config().await;
+        let mut actions =
+            code_actions::get_code_actions(uri, &params.context.diagnostics, config.ref_style());
+        let wants_refactor = params.context.only.as_ref().is_none_or(|kinds| {
+            kinds
+                .iter()
+                .any(|kind| kind.as_str().starts_with(CodeActionKind::REFACTOR.as_str()))
+        });
+        if !wants_refactor {
+            return Ok(Some(actions));
+        }
+        let Ok(path) = uri.to_file_path() else {
+            return Ok(Some(actions));
+        };
+        let content = self.note_text(&path).await;
         actions.extend(code_actions::get_metadata_actions(
             uri,
             &content,
             params.range,
         ));
+        actions.extend(code_actions::get_selection_actions(
+            uri,
+            &content,
+            params.range,
+            &config.zk_config,
+            &config.parser(),
+        ));
         Ok(Some(actions))
     }
 
@@ -264,12 +812,23 @@ impl LanguageServer for ZkLspServer {
     async fn completion(&self, params: CompletionParams) -> LspResult<Option<CompletionResponse>> {
         let uri = &params.text_document_position.text_document.uri;
         let position = params.text_document_position.position;
-        let content = uri
-            .to_file_path()
-            .ok()
-            .and_then(|p| std::fs::read_to_string(p).ok())
+        let path = uri.to_file_path().unwrap_or_default();
+        // A brand-new note may exist only in the editor buffer.
+        let content = self
+            .documents
+            .get(&path)
+            .or_else(|| std::fs::read_to_string(&path).ok())
             .unwrap_or_default();
-        let items = completion::get_completions(&content, position, &self.index);
+        let config = self.current_config().await;
+        let mut items = completion::get_completions(&content, position, &self.index);
+        items.extend(completion::get_reference_completions(
+            &content,
+            position,
+            &self.index,
+            &config.parser(),
+            config.settings.max_preview_chars,
+        ));
+        items.extend(completion::get_header_snippet(&content, &path, &config));
         Ok(if items.is_empty() {
             None
         } else {
@@ -289,7 +848,20 @@ impl LanguageServer for ZkLspServer {
             .ok()
             .and_then(|p| std::fs::read_to_string(p).ok())
             .unwrap_or_default();
-        Ok(hover::get_hover(&content, position, &self.index))
+        let config = self.current_config().await;
+        let note_parser = config.parser();
+        Ok(hover::get_cross_wiki_hover(&content, position, &config)
+            .or_else(|| hover::get_hover(&content, position, &self.index, &config.settings))
+            .or_else(|| hover::get_title_link_hover(&content, position, &self.index, &config))
+            .or_else(|| {
+                hover::get_wikilink_hover(
+                    &content,
+                    position,
+                    &self.index,
+                    &note_parser,
+                    &config.settings,
+                )
+            }))
     }
 
     // -----------------------------------------------------------------------
@@ -306,7 +878,14 @@ impl LanguageServer for ZkLspServer {
             Some(c) => c,
             None => return Ok(None),
         };
-        let hints = inlay_hints::get_inlay_hints(&content, params.range, &self.index);
+        let config = self.current_config().await;
+        let hints = inlay_hints::get_inlay_hints(
+            &content,
+            params.range,
+            &self.index,
+            &config.parser(),
+            &config.settings,
+        );
         Ok(Some(hints))
     }
 
@@ -318,10 +897,22 @@ impl LanguageServer for ZkLspServer {
         &self,
         params: WorkspaceSymbolParams,
     ) -> LspResult<Option<Vec<SymbolInformation>>> {
+        let cancel = CancelToken::new();
+        let _guard = cancel.drop_guard();
+        let index = Arc::clone(&self.index);
+        let (exclude_generated, max_results) = {
+            let settings = &self.config.read().await.settings;
+            (settings.exclude_generated, settings.max_symbol_results)
+        };
+        let mut matches = tokio::task::spawn_blocking(move || {
+            index.search(&params.query, exclude_generated, &cancel)
+        })
+        .await
+        .unwrap_or_default();
+        // Ranked best-first, so the cap keeps the most relevant notes.
+        matches.truncate(max_results);
         #[allow(deprecated)]
-        let symbols = self
-            .index
-            .search(&params.query)
+        let symbols = matches
             .into_iter()
             .map(|info| {
                 let uri = Url::from_file_path(&info.path)
@@ -355,9 +946,42 @@ impl LanguageServer for ZkLspServer {
                     Err(e) => error!("generate_link_typ: {e}"),
                 }
             }
+            "zk.normalizeLinks" => {
+                let config = self.current_config().await;
+                return match link_gen::normalize_link_typ(&config).await {
+                    Ok(report) => Ok(serde_json::to_value(report).ok()),
+                    Err(e) => {
+                        error!("normalize_link_typ: {e}");
+                        Err(tower_lsp::jsonrpc::Error {
+                            code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                            message: format!("zk.normalizeLinks: {e}").into(),
+                            data: None,
+                        })
+                    }
+                };
+            }
+            "zk.verifyLinks" => {
+                let config = self.current_config().await;
+                return match link_gen::verify_link_typ(&config).await {
+                    Ok(report) => Ok(serde_json::to_value(report).ok()),
+                    Err(e) => {
+                        error!("verify_link_typ: {e}");
+                        Err(tower_lsp::jsonrpc::Error {
+                            code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                            message: format!("zk.verifyLinks: {e}").into(),
+                            data: None,
+                        })
+                    }
+                };
+            }
             "zk.newNote" => {
                 let config = self.current_config().await;
-                match note_ops::create_note(&config).await {
+                // An explicit ID comes from the "Create note" quick fix.
+                let created = match params.arguments.first().and_then(|v| v.as_str()) {
+                    Some(id) => note_ops::create_note_with_id(&config, id, "").await,
+                    None => note_ops::create_note(&config).await,
+                };
+                match created {
                     Ok(path) => {
                         info!("created note: {}", path.display());
                         let uri = Url::from_file_path(&path).ok();
@@ -370,14 +994,101 @@ impl LanguageServer for ZkLspServer {
                     Err(e) => error!("create_note: {e}"),
                 }
             }
+            "zk.dailyNote" => {
+                let config = self.current_config().await;
+                return match note_ops::daily_note(&config).await {
+                    Ok(path) => {
+                        self.index.update_file(&path).await.ok();
+                        Ok(Url::from_file_path(&path)
+                            .ok()
+                            .map(|uri| Value::String(uri.to_string())))
+                    }
+                    Err(e) => {
+                        error!("daily_note: {e}");
+                        Err(tower_lsp::jsonrpc::Error::internal_error())
+                    }
+                };
+            }
             "zk.removeNote" => {
-                if let Some(id) = params.arguments.first().and_then(|v| v.as_str()) {
-                    let config = self.current_config().await;
-                    match note_ops::delete_note(id, &config).await {
-                        Ok(()) => info!("deleted note {id}"),
-                        Err(e) => error!("delete_note: {e}"),
+                let Some(id) = params.arguments.first().and_then(|v| v.as_str()) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "zk.removeNote: missing note ID argument",
+                    ));
+                };
+                let config = self.current_config().await;
+                let path = match self.index.path_of(id).or_else(|| config.locate_note(id)) {
+                    Some(path) if parser::is_valid_id(id) && path.exists() => path,
+                    _ => {
+                        return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "zk.removeNote: no note {id:?} in {}",
+                            config.note_dir.display()
+                        )))
                     }
+                };
+                let trash = params
+                    .arguments
+                    .get(1)
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(config.zk_config.trash_on_delete);
+                let force = params
+                    .arguments
+                    .get(2)
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                let referencing = self.index.referencing_files(id);
+                if !referencing.is_empty() && !force {
+                    // Let the client confirm, then retry with `force`.
+                    return Err(tower_lsp::jsonrpc::Error {
+                        code: tower_lsp::jsonrpc::ErrorCode::InvalidRequest,
+                        message: format!(
+                            "zk.removeNote: {} note(s) still link to {id}; pass force to remove it",
+                            referencing.len()
+                        )
+                        .into(),
+                        data: Some(serde_json::json!({ "referencedBy": referencing })),
+                    });
+                }
+                let trashed = match note_ops::delete_note(id, &config, trash).await {
+                    Ok(trashed) => trashed,
+                    Err(e) => {
+                        error!("delete_note: {e}");
+                        return Err(tower_lsp::jsonrpc::Error {
+                            code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                            message: format!("zk.removeNote: {e}").into(),
+                            data: None,
+                        });
+                    }
+                };
+                info!("deleted note {id}");
+                let referrers = self.index.get_backlinks(id);
+                self.index.remove_by_path(&path).await;
+                let uri = Url::from_file_path(&path).ok();
+                if let Some(uri) = &uri {
+                    self.client
+                        .publish_diagnostics(uri.clone(), vec![], None)
+                        .await;
                 }
+                // Notes that linked here now hold dead links.
+                let mut files: Vec<_> = referrers.into_iter().map(|loc| loc.file).collect();
+                files.sort();
+                files.dedup();
+                for file in files {
+                    let (Ok(content), Ok(uri)) = (
+                        tokio::fs::read_to_string(&file).await,
+                        Url::from_file_path(&file),
+                    ) else {
+                        continue;
+                    };
+                    let doc = self.parse_note(content).await;
+                    self.publish_diagnostics(uri, &doc).await;
+                }
+                return Ok(Some(serde_json::json!({
+                    "id": id,
+                    "path": path,
+                    "uri": uri,
+                    "trashed": trashed,
+                    "backlinks": referencing.len(),
+                })));
             }
             "zk.exportContext" => {
                 let id = params
@@ -391,19 +1102,1068 @@ impl LanguageServer for ZkLspServer {
                     .get(1)
                     .and_then(|v| v.as_u64())
                     .unwrap_or(2) as usize;
-                let inverse = params
+                // `true`/`false` (inverse) or "out" / "in" / "both".
+                let direction = match params.arguments.get(2) {
+                    Some(Value::Bool(true)) => Direction::Incoming,
+                    Some(Value::String(s)) => Direction::parse(s).ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!(
+                            "invalid direction {s:?}: expected \"out\", \"in\" or \"both\""
+                        ))
+                    })?,
+                    _ => Direction::Outgoing,
+                };
+                let max_chars = params
+                    .arguments
+                    .get(3)
+                    .and_then(|v| v.as_u64())
+                    .map(|n| n as usize);
+                let config = self.current_config().await;
+                match context_export::export_context(&id, depth, direction, max_chars, &config)
+                    .await
+                {
+                    Ok(text) => return Ok(Some(Value::String(text))),
+                    Err(e) => error!("exportContext: {e}"),
+                }
+            }
+            "zk.pendingMigrations" => {
+                let cancel = CancelToken::new();
+                let _guard = cancel.drop_guard();
+                // Run the scan as its own task: a cancelled request drops this
+                // future and the guard, and the task stops at the next note.
+                let index = Arc::clone(&self.index);
+                let pending = tokio::spawn(async move {
+                    archive_migration::pending_migrations(&index, &cancel).await
+                })
+                .await
+                .unwrap_or_default();
+                return Ok(serde_json::to_value(pending).ok());
+            }
+            "zk.migrateArchived" => {
+                let id = params
                     .arguments
+                    .first()
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                let edit = archive_migration::migrate_archived_edit(&self.index, id)
+                    .await
+                    .map_err(tower_lsp::jsonrpc::Error::invalid_params)?;
+                let value = serde_json::to_value(&edit).ok();
+                match self.client.apply_edit(edit).await {
+                    Ok(resp) if !resp.applied => error!(
+                        "migrateArchived: edit rejected: {}",
+                        resp.failure_reason.unwrap_or_default()
+                    ),
+                    Ok(_) => info!("migrated references to archived note {id}"),
+                    Err(e) => error!("migrateArchived: {e}"),
+                }
+                return Ok(value);
+            }
+            "zk.retireNote" => {
+                let args = &params.arguments;
+                let (Some(id), Some(replacement)) = (
+                    args.first().and_then(|v| v.as_str()),
+                    args.get(1).and_then(|v| v.as_str()),
+                ) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "zk.retireNote: expected a note ID and a replacement ID",
+                    ));
+                };
+                let config = self.current_config().await;
+                let trash = args
                     .get(2)
                     .and_then(|v| v.as_bool())
+                    .unwrap_or(config.zk_config.trash_on_delete);
+                let Some(path) = self.index.path_of(id) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                        "zk.retireNote: unknown note {id:?}"
+                    )));
+                };
+                let edit = archive_migration::retire_edit(&self.index, id, replacement)
+                    .await
+                    .map_err(|e| {
+                        tower_lsp::jsonrpc::Error::invalid_params(format!("zk.retireNote: {e}"))
+                    })?;
+                let value = serde_json::to_value(&edit).ok();
+                // Only delete once every reference points at the replacement.
+                let rejected = match self.client.apply_edit(edit).await {
+                    Ok(resp) if resp.applied => None,
+                    Ok(resp) => Some(resp.failure_reason.unwrap_or_default()),
+                    Err(e) => Some(e.to_string()),
+                };
+                if let Some(reason) = rejected {
+                    error!("retireNote: edit not applied: {reason}");
+                    return Err(tower_lsp::jsonrpc::Error {
+                        code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                        message: format!("zk.retireNote: references not rewritten: {reason}")
+                            .into(),
+                        data: None,
+                    });
+                }
+                let trashed = note_ops::delete_note(id, &config, trash)
+                    .await
+                    .map_err(|e| tower_lsp::jsonrpc::Error {
+                        code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                        message: format!("zk.retireNote: {e}").into(),
+                        data: None,
+                    })?;
+                info!("retired note {id} in favour of {replacement}");
+                self.index.remove_by_path(&path).await;
+                if let Ok(uri) = Url::from_file_path(&path) {
+                    self.client.publish_diagnostics(uri, vec![], None).await;
+                }
+                return Ok(Some(serde_json::json!({
+                    "edit": value,
+                    "path": path,
+                    "trashed": trashed,
+                })));
+            }
+            "zk.generateAbstract" => {
+                let id = params
+                    .arguments
+                    .first()
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                let Some(path) = self.index.path_of(id) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                        "zk.generateAbstract: unknown note {id:?}"
+                    )));
+                };
+                // Prefer the editor buffer so unsaved edits are summarised.
+                let content = self.note_text(&path).await;
+                let config = self.current_config().await;
+                let edit = abstract_gen::generate_abstract_edit(
+                    &content,
+                    &config.zk_config,
+                    &config.parser(),
+                )
+                .map_err(|e| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!("zk.generateAbstract: {e}"))
+                })?;
+                let value = serde_json::to_value(&edit).ok();
+                if let Ok(uri) = Url::from_file_path(&path) {
+                    let changes = [(uri, vec![edit])].into_iter().collect();
+                    match self.client.apply_edit(WorkspaceEdit::new(changes)).await {
+                        Ok(resp) if !resp.applied => error!(
+                            "generateAbstract: edit rejected: {}",
+                            resp.failure_reason.unwrap_or_default()
+                        ),
+                        Ok(_) => info!("generated abstract for {id}"),
+                        Err(e) => error!("generateAbstract: {e}"),
+                    }
+                }
+                return Ok(value);
+            }
+            "zk.hubNotes" => {
+                let limit = params
+                    .arguments
+                    .first()
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(10) as usize;
+                let hubs: Vec<Value> = self
+                    .index
+                    .hub_notes(limit)
+                    .into_iter()
+                    .map(|(note, count)| {
+                        serde_json::json!({
+                            "id": note.id,
+                            "title": note.title,
+                            "count": count,
+                            "uri": Url::from_file_path(&note.path).ok(),
+                        })
+                    })
+                    .collect();
+                return Ok(Some(Value::Array(hubs)));
+            }
+            "zk.backlinkCounts" => {
+                // Without IDs, count for every indexed note.
+                let ids: Vec<String> = match params.arguments.first().and_then(|v| v.as_array()) {
+                    Some(ids) => ids
+                        .iter()
+                        .filter_map(|v| v.as_str().map(str::to_string))
+                        .collect(),
+                    None => self.index.notes.iter().map(|e| e.key().clone()).collect(),
+                };
+                let references = self.index.backlink_counts(&ids);
+                let linking = self.index.linking_note_counts(&ids);
+                let counts: serde_json::Map<String, Value> = ids
+                    .into_iter()
+                    .map(|id| {
+                        let counts = serde_json::json!({
+                            "references": references[&id],
+                            "linkingNotes": linking[&id],
+                        });
+                        (id, counts)
+                    })
+                    .collect();
+                return Ok(Some(Value::Object(counts)));
+            }
+            "zk.leafNotes" => {
+                let include_archived = params
+                    .arguments
+                    .first()
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                let leaves: Vec<Value> = self
+                    .index
+                    .leaf_notes(include_archived)
+                    .into_iter()
+                    .map(|note| {
+                        serde_json::json!({
+                            "id": note.id,
+                            "title": note.title,
+                            "uri": Url::from_file_path(&note.path).ok(),
+                        })
+                    })
+                    .collect();
+                return Ok(Some(Value::Array(leaves)));
+            }
+            "zk.duplicateTitles" => {
+                let max_distance = params
+                    .arguments
+                    .first()
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0) as usize;
+                let include_archived = params
+                    .arguments
+                    .get(1)
+                    .and_then(|v| v.as_bool())
                     .unwrap_or(false);
+                let cancel = CancelToken::new();
+                let _guard = cancel.drop_guard();
+                let index = Arc::clone(&self.index);
+                let groups = tokio::task::spawn_blocking(move || {
+                    index.duplicate_titles(include_archived, max_distance, &cancel)
+                })
+                .await
+                .unwrap_or_default();
+                let groups: Vec<Value> = groups
+                    .into_iter()
+                    .map(|group| {
+                        group
+                            .into_iter()
+                            .map(|note| {
+                                serde_json::json!({
+                                    "id": note.id,
+                                    "title": note.title,
+                                    "path": note.path,
+                                    "uri": Url::from_file_path(&note.path).ok(),
+                                })
+                            })
+                            .collect()
+                    })
+                    .collect();
+                return Ok(Some(Value::Array(groups)));
+            }
+            "zk.vocabulary" => {
+                let vocab = vocabulary::vocabulary(&self.index);
+                return Ok(Some(serde_json::to_value(vocab).unwrap_or_default()));
+            }
+            "zk.notesByTag" => {
+                // Without a tag, return the tag facets instead.
+                let Some(tag) = params.arguments.first().and_then(|v| v.as_str()) else {
+                    let facets: Vec<Value> = self
+                        .index
+                        .tag_counts()
+                        .into_iter()
+                        .map(|(tag, count)| serde_json::json!({ "tag": tag, "count": count }))
+                        .collect();
+                    return Ok(Some(Value::Array(facets)));
+                };
+                let notes: Vec<Value> = self
+                    .index
+                    .by_tag(tag)
+                    .into_iter()
+                    .map(|note| {
+                        serde_json::json!({
+                            "id": note.id,
+                            "title": note.title,
+                            "tags": note.tags,
+                            "uri": Url::from_file_path(&note.path).ok(),
+                        })
+                    })
+                    .collect();
+                return Ok(Some(Value::Array(notes)));
+            }
+            "zk.relatedByTopic" => {
+                let Some(id) = params.arguments.first().and_then(|v| v.as_str()) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "zk.relatedByTopic: expected a note ID",
+                    ));
+                };
+                let related: Vec<Value> = self
+                    .index
+                    .notes_sharing_keywords(id)
+                    .into_iter()
+                    .map(|(note, shared)| {
+                        serde_json::json!({
+                            "id": note.id,
+                            "title": note.title,
+                            "shared": shared,
+                            "uri": Url::from_file_path(&note.path).ok(),
+                        })
+                    })
+                    .collect();
+                return Ok(Some(Value::Array(related)));
+            }
+            "zk.backlinksGrouped" => {
+                let Some(id) = params.arguments.first().and_then(|v| v.as_str()) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "zk.backlinksGrouped: expected a note ID",
+                    ));
+                };
+                let mut groups = Vec::new();
+                for group in self.index.backlinks_by_source(id) {
+                    let text = self.note_text(&group.file).await;
+                    let lines: Vec<&str> = parser::split_lines(&text).collect();
+                    let locations: Vec<Value> = group
+                        .locations
+                        .iter()
+                        .map(|loc| {
+                            let snippet = lines.get(loc.line as usize).map(|l| l.trim());
+                            serde_json::json!({
+                                "line": loc.line,
+                                "startChar": loc.start_char,
+                                "endChar": loc.end_char,
+                                "snippet": snippet,
+                            })
+                        })
+                        .collect();
+                    groups.push(serde_json::json!({
+                        "sourceId": group.source_id,
+                        "sourceTitle": group.source_title,
+                        "uri": Url::from_file_path(&group.file).ok(),
+                        "locations": locations,
+                    }));
+                }
+                return Ok(Some(Value::Array(groups)));
+            }
+            "zk.linkTree" => {
+                let Some(id) = params.arguments.first().and_then(|v| v.as_str()) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "zk.linkTree: expected a root note ID",
+                    ));
+                };
+                let depth = params
+                    .arguments
+                    .get(1)
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(2) as usize;
+                let Some(tree) = link_tree::link_tree(&self.index.snapshot(), id, depth) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                        "zk.linkTree: note {id} is not indexed"
+                    )));
+                };
+                return Ok(serde_json::to_value(tree).ok());
+            }
+            "zk.insertReference" => {
+                let args = &params.arguments;
+                let uri = args
+                    .first()
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| Url::parse(s).ok());
+                let position = args
+                    .get(1)
+                    .and_then(|v| serde_json::from_value::<Position>(v.clone()).ok());
+                let id = args.get(2).and_then(|v| v.as_str());
+                let (Some(uri), Some(position), Some(id)) = (uri, position, id) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "zk.insertReference: expected [uri, position, id]",
+                    ));
+                };
+                if !self.index.contains(id) {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                        "zk.insertReference: unknown note {id:?}"
+                    )));
+                }
+                let content = match uri.to_file_path() {
+                    Ok(path) => self.note_text(&path).await,
+                    Err(_) => String::new(),
+                };
+                // Done-states of every note the line will reference.
+                let note_parser = self.config.read().await.parser();
+                let line = parser::split_lines(&content)
+                    .nth(position.line as usize)
+                    .unwrap_or("");
+                let mut dep_states = std::collections::HashMap::new();
+                for dep in note_parser
+                    .id_refs(line)
+                    .map(|(_, dep)| dep)
+                    .chain(std::iter::once(id))
+                {
+                    let path = match self.index.path_of(dep) {
+                        Some(path) => Some(path),
+                        None => self.config.read().await.locate_note(dep),
+                    };
+                    if let Some(path) = path {
+                        let done = writeback::is_note_done(&self.note_text(&path).await);
+                        dep_states.insert(dep.to_string(), done);
+                    }
+                }
+                let edit = code_actions::insert_reference_edit(
+                    &uri,
+                    &content,
+                    position,
+                    id,
+                    &dep_states,
+                    &note_parser,
+                );
+                return Ok(serde_json::to_value(edit).ok());
+            }
+            "zk.splitNote" => {
+                let args = &params.arguments;
+                let uri = args
+                    .first()
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| Url::parse(s).ok());
+                let start = args.get(1).and_then(|v| v.as_u64());
+                let end = args.get(2).and_then(|v| v.as_u64());
+                let title = args.get(3).and_then(|v| v.as_str()).unwrap_or("");
+                let (Some(uri), Some(start), Some(end)) = (uri, start, end) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "zk.splitNote: expected [uri, startLine, endLine, newTitle]",
+                    ));
+                };
+                let Ok(path) = uri.to_file_path() else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                        "zk.splitNote: not a file URI: {uri}"
+                    )));
+                };
+                let content = self.note_text(&path).await;
                 let config = self.current_config().await;
-                match crate::context_export::export_context(&id, depth, inverse, &config).await {
-                    Ok(text) => return Ok(Some(Value::String(text))),
-                    Err(e) => error!("exportContext: {e}"),
+                let split =
+                    note_ops::split_note(&config, &content, start as usize, end as usize, title)
+                        .await
+                        .map_err(|e| {
+                            tower_lsp::jsonrpc::Error::invalid_params(format!("zk.splitNote: {e}"))
+                        })?;
+                info!("split {} lines into note {}", end - start + 1, split.id);
+                let _ = self.index.update_file(&split.path).await;
+                let edit = WorkspaceEdit::new([(uri, vec![split.edit])].into_iter().collect());
+                return Ok(Some(serde_json::json!({
+                    "id": split.id,
+                    "path": split.path.display().to_string(),
+                    "uri": Url::from_file_path(&split.path).ok(),
+                    "edit": edit,
+                })));
+            }
+            "zk.noteStatus" => {
+                let Some(path) = params
+                    .arguments
+                    .first()
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| Url::parse(s).ok())
+                    .and_then(|uri| uri.to_file_path().ok())
+                else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "zk.noteStatus: expected a note URI",
+                    ));
+                };
+                // Read-only: the buffer (or file) is never written.
+                let content = self.note_text(&path).await;
+                let Some(header) = parser::parse_header(&content) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                        "zk.noteStatus: {} is not a TOML note",
+                        path.display()
+                    )));
+                };
+                let todos = parser::count_todos(&content);
+                let status = parser::compute_status_tag(&todos, header.archived);
+                return Ok(Some(serde_json::json!({
+                    "status": status.as_ref().map(parser::StatusTag::as_str),
+                    "completed": todos.completed,
+                    "incomplete": todos.incomplete,
+                })));
+            }
+            "zk.copyReference" | "zk.copyId" => {
+                let command = params.command.as_str();
+                let Some(path) = params
+                    .arguments
+                    .first()
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| Url::parse(s).ok())
+                    .and_then(|uri| uri.to_file_path().ok())
+                else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                        "{command}: expected a note URI"
+                    )));
+                };
+                let content = self.note_text(&path).await;
+                let config = self.current_config().await;
+                // Files that are not notes yield `null`.
+                let Some(header) = config.parser().parse_header(&content) else {
+                    return Ok(Some(serde_json::Value::Null));
+                };
+                if command == "zk.copyId" {
+                    return Ok(Some(header.id.into()));
                 }
+                return Ok(Some(serde_json::json!({
+                    "reference": config.ref_style().format(&header.id),
+                    "id": header.id,
+                    "title": header.title,
+                })));
+            }
+            "zk.previewFormat" => {
+                let Some(path) = params
+                    .arguments
+                    .first()
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| Url::parse(s).ok())
+                    .and_then(|uri| uri.to_file_path().ok())
+                else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "zk.previewFormat: expected a note URI",
+                    ));
+                };
+                let content = self.note_text(&path).await;
+                let config = self.current_config().await;
+                let formatted =
+                    formatting::format_content(&content, &config, Some(&self.documents)).await;
+                let label = path
+                    .strip_prefix(&config.root)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string();
+                return Ok(Some(serde_json::json!({
+                    "edits": formatting::format_edits(&content, &formatted),
+                    "diff": formatting::unified_diff(&content, &formatted, &label),
+                })));
+            }
+            "zk.notePath" => {
+                let Some(id) = params.arguments.first().and_then(|v| v.as_str()) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                        "zk.notePath: expected a note ID",
+                    ));
+                };
+                // Unknown IDs yield `null`.
+                let path = self.index.path_of(id).map(|p| p.display().to_string());
+                return Ok(Some(serde_json::to_value(path).unwrap_or_default()));
+            }
+            "zk.status" => {
+                let config = self.current_config().await;
+                let state = note_ops::NoteDirState::probe(&config.note_dir).await;
+                return Ok(Some(serde_json::json!({
+                    "root": config.root.display().to_string(),
+                    "noteDir": config.note_dir.display().to_string(),
+                    "noteDirState": state.as_str(),
+                    "message": state.describe(&config.note_dir),
+                    "indexedNotes": self.index.notes.len(),
+                })));
+            }
+            "zk.malformedNotes" => {
+                let config = self.current_config().await;
+                return match malformed::find_malformed_notes(&config).await {
+                    Ok(malformed) => Ok(serde_json::to_value(malformed).ok()),
+                    Err(e) => {
+                        error!("find_malformed_notes: {e}");
+                        Err(tower_lsp::jsonrpc::Error {
+                            code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                            message: format!("zk.malformedNotes: {e}").into(),
+                            data: None,
+                        })
+                    }
+                };
+            }
+            "zk.staleTags" => {
+                let config = self.current_config().await;
+                return match stale_tags::find_stale_tags(&config).await {
+                    Ok(stale) => Ok(serde_json::to_value(stale).ok()),
+                    Err(e) => {
+                        error!("find_stale_tags: {e}");
+                        Err(tower_lsp::jsonrpc::Error {
+                            code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                            message: format!("zk.staleTags: {e}").into(),
+                            data: None,
+                        })
+                    }
+                };
+            }
+            "zk.notesInRange" => {
+                let bound = |i: usize, end: bool| {
+                    params
+                        .arguments
+                        .get(i)
+                        .and_then(|v| v.as_str())
+                        .map(|s| {
+                            parser::parse_date_bound(s, end).ok_or_else(|| {
+                                tower_lsp::jsonrpc::Error::invalid_params(format!(
+                                    "invalid date {s:?}: expected YYYY-MM-DD or YYYY-MM-DDTHH:MM"
+                                ))
+                            })
+                        })
+                        .transpose()
+                };
+                let start = bound(0, false)?.unwrap_or_default();
+                let end = bound(1, true)?.unwrap_or_else(|| {
+                    chrono::Local::now().naive_local() + chrono::Duration::minutes(1)
+                });
+                let notes: Vec<Value> = self
+                    .index
+                    .created_between(start, end)
+                    .into_iter()
+                    .map(|(created, note)| {
+                        serde_json::json!({
+                            "id": note.id,
+                            "title": note.title,
+                            "created": created.format("%Y-%m-%dT%H:%M").to_string(),
+                            "uri": Url::from_file_path(&note.path).ok(),
+                        })
+                    })
+                    .collect();
+                return Ok(Some(Value::Array(notes)));
+            }
+            "zk.notesTimeline" => {
+                let by = params
+                    .arguments
+                    .first()
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("day");
+                let Some(period) = timeline::TimelinePeriod::parse(by) else {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                        "zk.notesTimeline: unknown period {by:?}: expected day, week or month"
+                    )));
+                };
+                let entries = timeline::notes_timeline(&self.index, period);
+                return Ok(serde_json::to_value(entries).ok());
+            }
+            "zk.publishWorkspaceDiagnostics" => {
+                let published = self.publish_workspace_diagnostics().await;
+                info!("published diagnostics for {published} notes");
+                return Ok(Some(Value::from(published)));
             }
             cmd => info!("unhandled command: {cmd}"),
         }
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::LspService;
+
+    fn remove_params(args: Vec<Value>) -> ExecuteCommandParams {
+        ExecuteCommandParams {
+            command: "zk.removeNote".into(),
+            arguments: args,
+            work_done_progress_params: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_symbol_results_are_capped() {
+        let tmp = std::env::temp_dir().join("zk_server_test_symbol_cap");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        for (id, title) in [
+            ("2603150000", "Rust"),
+            ("2603150001", "Rust traits"),
+            ("2603150002", "Ownership in Rust"),
+        ] {
+            std::fs::write(
+                tmp.join(format!("note/{id}.typ")),
+                format!(
+                    "#import \"../include.typ\": *\n\
+                     #let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n\
+                     #show: zettel.with(metadata: zk-metadata)\n\n= {title} <{id}>\n"
+                ),
+            )
+            .unwrap();
+        }
+        let settings = ClientSettings {
+            max_symbol_results: 2,
+            ..ClientSettings::default()
+        };
+        let config = Arc::new(RwLock::new(
+            WikiConfig::from_root(tmp.clone()).with_settings(settings),
+        ));
+        let (service, _socket) =
+            LspService::new(|client| ZkLspServer::new(client, Arc::clone(&config), None));
+        let server = service.inner();
+        server.index.rebuild_full().await.unwrap();
+
+        let symbols = server
+            .symbol(WorkspaceSymbolParams {
+                query: "rust".into(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let names: Vec<&str> = symbols.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["[2603150000] Rust", "[2603150001] Rust traits"]);
+    }
+
+    #[tokio::test]
+    async fn test_backlinks_grouped_command() {
+        let tmp = std::env::temp_dir().join("zk_server_test_backlinks_grouped");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        for (id, title, body) in [
+            ("2603150000", "Target", ""),
+            (
+                "2603150001",
+                "Reader",
+                "First @2603150000 here.\n\n  - [ ] @2603150000\n",
+            ),
+        ] {
+            std::fs::write(
+                tmp.join(format!("note/{id}.typ")),
+                format!(
+                    "#import \"../include.typ\": *\n\
+                     #let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n\
+                     #show: zettel.with(metadata: zk-metadata)\n\n= {title} <{id}>\n{body}"
+                ),
+            )
+            .unwrap();
+        }
+        let config = Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone())));
+        let (service, _socket) =
+            LspService::new(|client| ZkLspServer::new(client, Arc::clone(&config), None));
+        let server = service.inner();
+        server.index.rebuild_full().await.unwrap();
+
+        let grouped = server
+            .execute_command(ExecuteCommandParams {
+                command: "zk.backlinksGrouped".into(),
+                arguments: vec!["2603150000".into()],
+                work_done_progress_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let groups = grouped.as_array().unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0]["sourceId"], "2603150001");
+        assert_eq!(groups[0]["sourceTitle"], "Reader");
+        assert_eq!(
+            groups[0]["locations"],
+            serde_json::json!([
+                {"line": 9, "startChar": 6, "endChar": 17, "snippet": "First @2603150000 here."},
+                {"line": 11, "startChar": 8, "endChar": 19, "snippet": "- [ ] @2603150000"},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_path_parses_note_once() {
+        let tmp = std::env::temp_dir().join("zk_server_test_parse_once");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let path = tmp.join("note/2603150000.typ");
+        let content = "#import \"../include.typ\": *\n\
+             #let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n\
+             #show: zettel.with(metadata: zk-metadata)\n\n= Lonely <2603150000>\n";
+        std::fs::write(&path, content).unwrap();
+        let config = Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone())));
+        let (service, _socket) =
+            LspService::new(|client| ZkLspServer::new(client, Arc::clone(&config), None));
+        let server = service.inner();
+        server.index.rebuild_full().await.unwrap();
+        let uri = Url::from_file_path(&path).unwrap();
+        let snapshot = server.current_config().await;
+
+        // What `did_save` does before reconcile: index update + diagnostics.
+        crate::parser::parse_counts::take();
+        let doc = NoteDoc::parse(content.to_string());
+        server.index.update_doc(&path, &doc).await.unwrap();
+        let diags = server.note_diagnostics(&uri, &doc, &snapshot, &[]);
+        let counts = crate::parser::parse_counts::take();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        // Previously two of each: the index re-read and re-parsed the file,
+        // and the schema and orphan checks parsed it again.
+        assert_eq!(counts, (1, 1));
+        assert!(diags.iter().any(|d| d.message.starts_with("Orphan note")));
+    }
+
+    #[tokio::test]
+    async fn test_note_status_command() {
+        let tmp = std::env::temp_dir().join("zk_server_test_note_status");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let header = "#import \"../include.typ\": *\n\
+             #let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+             checklist-status = \"todo\"\n  ```.text,\n))\n\
+             #show: zettel.with(metadata: zk-metadata)\n\n= Plan <2603150000>\n";
+        let path = tmp.join("note/2603150000.typ");
+        std::fs::write(&path, format!("{header}- [x] a\n- [ ] b\n")).unwrap();
+        let config = Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone())));
+        let (service, _socket) =
+            LspService::new(|client| ZkLspServer::new(client, Arc::clone(&config), None));
+        let server = service.inner();
+        let uri = Url::from_file_path(&path).unwrap();
+        let params = || ExecuteCommandParams {
+            command: "zk.noteStatus".into(),
+            arguments: vec![uri.to_string().into()],
+            work_done_progress_params: Default::default(),
+        };
+
+        let on_disk = server.execute_command(params()).await.unwrap().unwrap();
+        // An open buffer wins over the file; a note without todos has no status.
+        server.documents.open(path.clone(), header.to_string());
+        let no_todos = server.execute_command(params()).await.unwrap().unwrap();
+        let unchanged = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(
+            on_disk,
+            serde_json::json!({"status": "wip", "completed": 1, "incomplete": 1})
+        );
+        assert_eq!(
+            no_todos,
+            serde_json::json!({"status": null, "completed": 0, "incomplete": 0})
+        );
+        assert!(unchanged.contains("checklist-status = \"todo\""));
+    }
+
+    #[tokio::test]
+    async fn test_copy_reference_and_id_commands() {
+        let tmp = std::env::temp_dir().join("zk_server_test_copy_reference");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let path = tmp.join("note/2603150000.typ");
+        std::fs::write(
+            &path,
+            "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n\
+             = Plan <2603150000>\n",
+        )
+        .unwrap();
+        let stray = tmp.join("note/scratch.typ");
+        std::fs::write(&stray, "Just text.\n").unwrap();
+        let config = Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone())));
+        let (service, _socket) =
+            LspService::new(|client| ZkLspServer::new(client, Arc::clone(&config), None));
+        let server = service.inner();
+        let run = |command: &str, path: &std::path::Path| ExecuteCommandParams {
+            command: command.into(),
+            arguments: vec![Url::from_file_path(path).unwrap().to_string().into()],
+            work_done_progress_params: Default::default(),
+        };
+
+        let reference = server
+            .execute_command(run("zk.copyReference", &path))
+            .await
+            .unwrap();
+        let id = server
+            .execute_command(run("zk.copyId", &path))
+            .await
+            .unwrap();
+        let not_a_note = server
+            .execute_command(run("zk.copyReference", &stray))
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(
+            reference,
+            Some(serde_json::json!({
+                "reference": "@2603150000", "id": "2603150000", "title": "Plan"
+            }))
+        );
+        assert_eq!(id, Some(serde_json::json!("2603150000")));
+        assert_eq!(not_a_note, Some(serde_json::Value::Null));
+    }
+
+    #[tokio::test]
+    async fn test_failed_format_on_save_edit_is_forgotten() {
+        let tmp = std::env::temp_dir().join("zk_server_test_format_on_save");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let path = tmp.join("note/2603150000.typ");
+        let content = "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+                       checklist-status = \"todo\"\n  ```.text,\n))\n\
+                       = Plan <2603150000>\n- [x] done\n";
+        let config = Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone())));
+        let (service, _socket) =
+            LspService::new(|client| ZkLspServer::new(client, Arc::clone(&config), None));
+        let server = service.inner();
+        server.documents.open(path.clone(), content.to_string());
+        server.documents.set_version(&path, 7);
+
+        // The client was never initialized, so `workspace/applyEdit` fails.
+        server
+            .format_on_save(Url::from_file_path(&path).unwrap(), content)
+            .await;
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert!(server.formatted_on_save.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_preview_format_matches_applied_formatting() {
+        let tmp = std::env::temp_dir().join("zk_server_test_preview_format");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let path = tmp.join("note/2603150000.typ");
+        let content = "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+                       checklist-status = \"todo\"\n  ```.text,\n))\n\
+                       = Plan <2603150000>\n- [x] done\n";
+        std::fs::write(&path, content).unwrap();
+        let config = Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone())));
+        let (service, _socket) =
+            LspService::new(|client| ZkLspServer::new(client, Arc::clone(&config), None));
+        let server = service.inner();
+        let preview = || async {
+            server
+                .execute_command(ExecuteCommandParams {
+                    command: "zk.previewFormat".into(),
+                    arguments: vec![Url::from_file_path(&path).unwrap().to_string().into()],
+                    work_done_progress_params: Default::default(),
+                })
+                .await
+                .unwrap()
+                .unwrap()
+        };
+
+        let before = preview().await;
+        let formatted = formatting::format_content(content, &*config.read().await, None).await;
+        std::fs::write(&path, &formatted).unwrap();
+        let after = preview().await;
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let edits: Vec<TextEdit> = serde_json::from_value(before["edits"].clone()).unwrap();
+        assert_eq!(edits, formatting::format_edits(content, &formatted));
+        assert_eq!(edits.len(), 1);
+        // Applying the previewed edit gives what formatting writes.
+        let offset = |pos: Position| {
+            content
+                .split_inclusive('\n')
+                .take(pos.line as usize)
+                .map(str::len)
+                .sum::<usize>()
+                + pos.character as usize
+        };
+        let (start, end) = (offset(edits[0].range.start), offset(edits[0].range.end));
+        let applied = format!(
+            "{}{}{}",
+            &content[..start],
+            edits[0].new_text,
+            &content[end..]
+        );
+        assert_eq!(applied, formatted);
+        let diff = before["diff"].as_str().unwrap();
+        assert!(diff.starts_with("--- a/note/2603150000.typ\n+++ b/note/2603150000.typ\n@@"));
+        assert!(diff.contains("-  checklist-status = \"todo\"\n"));
+        assert!(diff.contains("+  checklist-status = \"done\"\n"));
+        assert_eq!(after, serde_json::json!({"edits": [], "diff": ""}));
+    }
+
+    #[tokio::test]
+    async fn test_status_command_reports_missing_note_dir() {
+        let tmp = std::env::temp_dir().join("zk_server_test_status");
+        let _ = std::fs::remove_dir_all(&tmp);
+        let config = Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone())));
+        let (service, _socket) =
+            LspService::new(|client| ZkLspServer::new(client, Arc::clone(&config), None));
+        let server = service.inner();
+        let params = || ExecuteCommandParams {
+            command: "zk.status".into(),
+            arguments: vec![],
+            work_done_progress_params: Default::default(),
+        };
+
+        let missing = server.execute_command(params()).await.unwrap().unwrap();
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let empty = server.execute_command(params()).await.unwrap().unwrap();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(missing["noteDirState"], "missing");
+        assert_eq!(
+            missing["noteDir"],
+            tmp.join("note").display().to_string().as_str()
+        );
+        assert!(missing["message"]
+            .as_str()
+            .unwrap()
+            .contains("does not exist"));
+        assert_eq!(empty["noteDirState"], "empty");
+        assert_eq!(empty["indexedNotes"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_remove_note_command() {
+        let tmp = std::env::temp_dir().join("zk_server_test_remove");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let note = tmp.join("note/2603150000.typ");
+        std::fs::write(&note, "= Note <2603150000>\n").unwrap();
+        let config = Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone())));
+        let (service, _socket) =
+            LspService::new(|client| ZkLspServer::new(client, Arc::clone(&config), None));
+        let server = service.inner();
+        server.index.rebuild_full().await.unwrap();
+
+        let missing_arg = server.execute_command(remove_params(vec![])).await;
+        let unknown = server
+            .execute_command(remove_params(vec!["2603159999".into()]))
+            .await;
+        let removed = server
+            .execute_command(remove_params(vec!["2603150000".into()]))
+            .await;
+        let still_exists = note.exists();
+        let still_indexed = server.index.contains("2603150000");
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert!(missing_arg.is_err());
+        assert!(unknown.is_err());
+        let removed = removed.unwrap().unwrap();
+        assert_eq!(removed["path"], Value::String(note.display().to_string()));
+        assert_eq!(removed["trashed"], Value::Null);
+        assert_eq!(removed["backlinks"], 0);
+        assert!(!still_exists);
+        assert!(!still_indexed);
+    }
+
+    #[tokio::test]
+    async fn test_remove_note_command_requires_force_when_linked() {
+        let tmp = std::env::temp_dir().join("zk_server_test_remove_linked");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        for (id, body) in [("2603150000", ""), ("2603150001", "See @2603150000.\n")] {
+            std::fs::write(
+                tmp.join(format!("note/{id}.typ")),
+                format!(
+                    "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  ```.text,\n))\n\
+                     = Note <{id}>\n{body}"
+                ),
+            )
+            .unwrap();
+        }
+        let config = Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone())));
+        let (service, _socket) =
+            LspService::new(|client| ZkLspServer::new(client, Arc::clone(&config), None));
+        let server = service.inner();
+        server.index.rebuild_full().await.unwrap();
+        let target = tmp.join("note/2603150000.typ");
+
+        let refused = server
+            .execute_command(remove_params(vec!["2603150000".into()]))
+            .await;
+        let kept = target.exists();
+        let forced = server
+            .execute_command(remove_params(vec![
+                "2603150000".into(),
+                false.into(),
+                true.into(),
+            ]))
+            .await;
+        let gone = !target.exists();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let err = refused.unwrap_err();
+        assert_eq!(
+            err.data,
+            Some(serde_json::json!({
+                "referencedBy": [tmp.join("note/2603150001.typ")]
+            }))
+        );
+        assert!(kept);
+        assert_eq!(forced.unwrap().unwrap()["backlinks"], 1);
+        assert!(gone);
+    }
+}
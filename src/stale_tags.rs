@@ -0,0 +1,141 @@
+/// Bulk report of notes whose persisted status tag is out of date.
+///
+/// `find_stale_tags` runs `compute_tag_edit` over every note and lists those
+/// whose `#tag.*` line or TOML `checklist-status` would change on format.
+/// `fix_stale_tags` writes the same edits back; `render_stale_tags` formats
+/// the report for the CLI.
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::config::WikiConfig;
+use crate::reconcile::writeback::{apply_tag_edit, compute_tag_edit};
+use crate::{note_ops, parser};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaleTag {
+    pub id: String,
+    pub path: PathBuf,
+    pub line: u32, // 0-based
+    /// The tag line (or `checklist-status` line) as persisted, trimmed.
+    pub current: String,
+    /// The same line after formatting, trimmed.
+    pub expected: String,
+}
+
+/// Every note in `note_dir` that `compute_tag_edit` would change, by ID.
+pub async fn find_stale_tags(config: &WikiConfig) -> Result<Vec<StaleTag>> {
+    let mut stale = Vec::new();
+    let files = note_ops::indexable_note_files(config)
+        .await
+        .with_context(|| format!("reading note dir {}", config.note_dir.display()))?;
+    for path in files {
+        if config.is_ignored(&path) {
+            continue;
+        }
+        let Some(id) = parser::note_id_from_path(&path).map(str::to_string) else {
+            continue;
+        };
+        let Ok(content) = tokio::fs::read_to_string(&path).await else {
+            continue;
+        };
+        let Some(edit) = compute_tag_edit(&content) else {
+            continue;
+        };
+        let line = edit.range.start.line;
+        let current = parser::split_lines(&content)
+            .nth(line as usize)
+            .unwrap_or("")
+            .trim();
+        stale.push(StaleTag {
+            id,
+            path,
+            line,
+            current: current.to_string(),
+            expected: edit.new_text.trim().to_string(),
+        });
+    }
+    stale.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(stale)
+}
+
+/// Apply the tag edit to each listed note. Notes are re-read so a file that
+/// changed since the report gets its current fix; returns the number written.
+pub async fn fix_stale_tags(stale: &[StaleTag]) -> Result<usize> {
+    let mut written = 0;
+    for entry in stale {
+        let content = tokio::fs::read_to_string(&entry.path)
+            .await
+            .with_context(|| format!("reading {}", entry.path.display()))?;
+        let fixed = apply_tag_edit(&content);
+        if fixed == content {
+            continue;
+        }
+        // Atomic write: tmp → rename
+        let tmp = entry.path.with_extension("typ.tmp");
+        tokio::fs::write(&tmp, fixed.as_bytes()).await?;
+        tokio::fs::rename(&tmp, &entry.path).await?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// One `ID  current → expected` line per stale note.
+pub fn render_stale_tags(stale: &[StaleTag]) -> String {
+    stale
+        .iter()
+        .map(|s| format!("{}  {}  →  {}\n", s.id, s.current, s.expected))
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(id: &str, status: &str, body: &str) -> String {
+        format!(
+            "#import \"../include.typ\": *\n\
+             #let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  \
+             checklist-status = \"{status}\"\n  ```.text,\n))\n\
+             #show: zettel.with(metadata: zk-metadata)\n\n= Note <{id}>\n{body}"
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_and_fix_stale_tags() {
+        let tmp = std::env::temp_dir().join("zk_stale_tags_fix");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        let stale_path = tmp.join("note/2603010000.typ");
+        std::fs::write(&stale_path, note("2603010000", "todo", "- [x] done\n")).unwrap();
+        std::fs::write(
+            tmp.join("note/2603020000.typ"),
+            note("2603020000", "wip", "- [x] a\n- [ ] b\n"),
+        )
+        .unwrap();
+        let config = WikiConfig::from_root(tmp.clone());
+
+        let stale = find_stale_tags(&config).await.unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].id, "2603010000");
+        assert_eq!(stale[0].line, 4);
+        assert_eq!(stale[0].current, "checklist-status = \"todo\"");
+        assert_eq!(stale[0].expected, "checklist-status = \"done\"");
+        assert_eq!(
+            render_stale_tags(&stale),
+            "2603010000  checklist-status = \"todo\"  →  checklist-status = \"done\"\n"
+        );
+
+        assert_eq!(fix_stale_tags(&stale).await.unwrap(), 1);
+        let fixed = std::fs::read_to_string(&stale_path).unwrap();
+        assert!(fixed.contains("checklist-status = \"done\""));
+        assert!(find_stale_tags(&config).await.unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&tmp);
+    }
+}
@@ -0,0 +1,129 @@
+/// Writing cadence: how many notes were created per day, week or month.
+///
+/// Creation times come from the note IDs (`parser::id_to_datetime`); IDs
+/// that are not valid timestamps are left out.
+use std::collections::BTreeMap;
+
+use chrono::Datelike;
+use serde::Serialize;
+
+use crate::index::NoteIndex;
+use crate::parser;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelinePeriod {
+    Day,
+    /// ISO 8601 week, Monday first.
+    Week,
+    Month,
+}
+
+impl TimelinePeriod {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "day" => Some(TimelinePeriod::Day),
+            "week" => Some(TimelinePeriod::Week),
+            "month" => Some(TimelinePeriod::Month),
+            _ => None,
+        }
+    }
+
+    /// Bucket label: `2026-03-15`, `2026-W11` or `2026-03`. Labels sort in
+    /// chronological order.
+    fn label(self, date: chrono::NaiveDate) -> String {
+        match self {
+            TimelinePeriod::Day => date.format("%Y-%m-%d").to_string(),
+            TimelinePeriod::Week => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            TimelinePeriod::Month => date.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TimelineEntry {
+    pub period: String,
+    pub count: usize,
+}
+
+/// Notes created per `period`, oldest first. Periods without notes are
+/// left out.
+pub fn notes_timeline(index: &NoteIndex, period: TimelinePeriod) -> Vec<TimelineEntry> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for entry in index.notes.iter() {
+        if let Some(created) = parser::id_to_datetime(entry.key()) {
+            *counts.entry(period.label(created.date())).or_default() += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(period, count)| TimelineEntry { period, count })
+        .collect()
+}
+
+/// One `<period>  <count>` line per entry.
+pub fn render_timeline(entries: &[TimelineEntry]) -> String {
+    entries
+        .iter()
+        .map(|e| format!("{}  {}\n", e.period, e.count))
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::NoteInfo;
+
+    fn make_index(ids: &[&str]) -> NoteIndex {
+        NoteIndex::with_notes(ids.iter().map(|id| NoteInfo::stub(id)))
+    }
+
+    fn counts(entries: Vec<TimelineEntry>) -> Vec<(String, usize)> {
+        entries.into_iter().map(|e| (e.period, e.count)).collect()
+    }
+
+    #[test]
+    fn test_timeline_buckets_by_period() {
+        // Sun 2026-03-15, Mon 2026-03-16 (twice), Wed 2026-04-01, and an ID
+        // with month 13 that is skipped.
+        let idx = make_index(&[
+            "2603150900",
+            "2603160800",
+            "2603162300",
+            "2604011200",
+            "2613010000",
+        ]);
+        let pairs = |v: &[(&str, usize)]| -> Vec<(String, usize)> {
+            v.iter().map(|(p, c)| (p.to_string(), *c)).collect()
+        };
+        assert_eq!(
+            counts(notes_timeline(&idx, TimelinePeriod::Day)),
+            pairs(&[("2026-03-15", 1), ("2026-03-16", 2), ("2026-04-01", 1)])
+        );
+        assert_eq!(
+            counts(notes_timeline(&idx, TimelinePeriod::Week)),
+            pairs(&[("2026-W11", 1), ("2026-W12", 2), ("2026-W14", 1)])
+        );
+        assert_eq!(
+            counts(notes_timeline(&idx, TimelinePeriod::Month)),
+            pairs(&[("2026-03", 3), ("2026-04", 1)])
+        );
+    }
+
+    #[test]
+    fn test_iso_week_crosses_year_boundary() {
+        // Thu 2026-01-01 is in 2026-W01; Sun 2028-01-02 still in 2027-W52.
+        let idx = make_index(&["2601010000", "2801020000"]);
+        assert_eq!(
+            render_timeline(&notes_timeline(&idx, TimelinePeriod::Week)),
+            "2026-W01  1\n2027-W52  1\n"
+        );
+        assert_eq!(TimelinePeriod::parse("year"), None);
+    }
+}
@@ -0,0 +1,120 @@
+/// `[[Note Title]]` links, resolved by title or alias when `title_links` is
+/// set.
+///
+/// Titles and aliases are compared case-insensitively through the index's
+/// title lookup. A link several notes answer to is ambiguous: navigation
+/// still follows the first (lowest) ID, and diagnostics point it out.
+use tower_lsp::lsp_types::Position;
+
+use crate::index::NoteIndex;
+use crate::parser::{self, TitleLink};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TitleTarget {
+    /// No note has this title or alias.
+    Missing,
+    Found(String),
+    /// Several notes match; IDs sorted.
+    Ambiguous(Vec<String>),
+}
+
+impl TitleTarget {
+    /// The note navigation goes to: the match, or the first of several.
+    pub fn first(&self) -> Option<&str> {
+        match self {
+            TitleTarget::Missing => None,
+            TitleTarget::Found(id) => Some(id),
+            TitleTarget::Ambiguous(ids) => ids.first().map(String::as_str),
+        }
+    }
+}
+
+/// Which notes `[[title]]` names.
+pub fn resolve(index: &NoteIndex, title: &str) -> TitleTarget {
+    let mut ids = index.ids_with_title(title);
+    match ids.len() {
+        0 => TitleTarget::Missing,
+        1 => TitleTarget::Found(ids.remove(0)),
+        _ => TitleTarget::Ambiguous(ids),
+    }
+}
+
+/// The title link under `position` (UTF-16 column) in `content`.
+pub fn title_link_at(content: &str, position: Position) -> Option<TitleLink> {
+    let line = parser::split_lines(content).nth(position.line as usize)?;
+    let col = parser::utf16_to_byte(line, position.character);
+    parser::find_title_links(line)
+        .into_iter()
+        .find(|l| (l.start_char as usize..=l.end_char as usize).contains(&col))
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::WikiConfig;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    /// An index over a fresh wiki under the temp dir `name`, with
+    /// `title_links = true` and three notes: "Rust Ownership" (alias
+    /// "Borrowing") and two titled "Draft". Returns it with the wiki root.
+    pub(crate) async fn titled_wiki(name: &str) -> (Arc<NoteIndex>, PathBuf) {
+        let tmp = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::write(tmp.join("zk-lsp.toml"), "title_links = true\n").unwrap();
+        let write = |id: &str, title: &str, aliases: &str| {
+            std::fs::write(
+                tmp.join(format!("note/{id}.typ")),
+                format!(
+                    "#let zk-metadata = toml(bytes(\n  ```toml\n  schema-version = 1\n  aliases = [{aliases}]\n  ```.text,\n))\n= {title} <{id}>\n"
+                ),
+            )
+            .unwrap();
+        };
+        write("2603150000", "Rust Ownership", "\"Borrowing\"");
+        write("2603150001", "Draft", "");
+        write("2603150002", "Draft", "");
+        let index = Arc::new(NoteIndex::new(Arc::new(RwLock::new(
+            WikiConfig::from_root(tmp.clone()),
+        ))));
+        index.rebuild_full().await.unwrap();
+        (index, tmp)
+    }
+
+    #[tokio::test]
+    async fn test_resolve_by_title_and_alias() {
+        let (index, tmp) = titled_wiki("zk_title_links_resolve").await;
+        let by_title = resolve(&index, "rust ownership");
+        let by_alias = resolve(&index, "BORROWING");
+        let ambiguous = resolve(&index, "Draft");
+        let missing = resolve(&index, "Rust");
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(by_title, TitleTarget::Found("2603150000".into()));
+        assert_eq!(by_alias, TitleTarget::Found("2603150000".into()));
+        assert_eq!(
+            ambiguous,
+            TitleTarget::Ambiguous(vec!["2603150001".into(), "2603150002".into()])
+        );
+        assert_eq!(ambiguous.first(), Some("2603150001"));
+        assert_eq!(missing, TitleTarget::Missing);
+    }
+
+    #[test]
+    fn test_title_link_at_position() {
+        let content = "见 [[Rust Ownership]] and [[2603150000]]\n";
+        let at = |character| title_link_at(content, Position { line: 0, character });
+        // `见 ` is two UTF-16 units; the link spans columns 2..=20.
+        assert_eq!(at(2).unwrap().title, "Rust Ownership");
+        assert!(at(20).is_some());
+        assert!(at(1).is_none());
+        // `[[ID]]` is a note reference, not a title link.
+        assert!(at(30).is_none());
+    }
+}
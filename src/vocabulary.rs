@@ -0,0 +1,163 @@
+/// Every keyword, alias, topic tag and status the indexed notes use, with
+/// how many notes use each: a dump for client-side completion pickers.
+///
+/// Keywords are merged case-insensitively as in `NoteIndex::keyword_counts`;
+/// aliases and tags are counted by exact spelling.
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::index::NoteIndex;
+use crate::parser::ChecklistStatus;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Term {
+    pub name: String,
+    /// Notes using the term.
+    pub count: usize,
+}
+
+/// Each list is most used first; ties by name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Vocabulary {
+    pub keywords: Vec<Term>,
+    pub aliases: Vec<Term>,
+    /// Topic tags such as `tag.project`.
+    pub tags: Vec<Term>,
+    /// `todo`, `wip` and `done`, each listed even when no note has it.
+    pub status_tags: Vec<Term>,
+}
+
+pub fn vocabulary(index: &NoteIndex) -> Vocabulary {
+    let mut aliases: HashMap<String, usize> = HashMap::new();
+    let mut statuses: HashMap<String, usize> = ["todo", "wip", "done"]
+        .into_iter()
+        .map(|status| (status.to_string(), 0))
+        .collect();
+    for entry in index.notes.iter() {
+        let note = entry.value();
+        let mut distinct = note.aliases.clone();
+        distinct.sort();
+        distinct.dedup();
+        for alias in distinct {
+            *aliases.entry(alias).or_default() += 1;
+        }
+        let status = match note.checklist_status {
+            Some(ChecklistStatus::Todo) => "todo",
+            Some(ChecklistStatus::Wip) => "wip",
+            Some(ChecklistStatus::Done) => "done",
+            Some(ChecklistStatus::None) | None => continue,
+        };
+        *statuses.entry(status.to_string()).or_default() += 1;
+    }
+    Vocabulary {
+        keywords: terms(index.keyword_counts()),
+        aliases: terms(sorted(aliases)),
+        tags: terms(index.tag_counts()),
+        status_tags: terms(sorted(statuses)),
+    }
+}
+
+/// `counts` most used first; ties by name.
+fn sorted(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+fn terms(counts: Vec<(String, usize)>) -> Vec<Term> {
+    counts
+        .into_iter()
+        .map(|(name, count)| Term { name, count })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WikiConfig;
+    use crate::index::NoteInfo;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    fn note(
+        id: &str,
+        keywords: &[&str],
+        aliases: &[&str],
+        tags: &[&str],
+        status: Option<ChecklistStatus>,
+    ) -> NoteInfo {
+        let strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect();
+        NoteInfo {
+            aliases: strings(aliases),
+            keywords: strings(keywords),
+            checklist_status: status,
+            tags: strings(tags),
+            ..NoteInfo::stub(id)
+        }
+    }
+
+    #[test]
+    fn test_vocabulary_counts_and_sorts_by_frequency() {
+        let idx = NoteIndex::new(Arc::new(RwLock::new(WikiConfig::from_root(PathBuf::from(
+            "/tmp",
+        )))));
+        for info in [
+            note(
+                "2603150000",
+                &["rust", "Lsp"],
+                &["Borrowing"],
+                &["tag.project"],
+                Some(ChecklistStatus::Todo),
+            ),
+            note(
+                "2603150001",
+                &["Rust"],
+                &["Borrowing", "Borrowing"],
+                &["tag.project", "topic.rust"],
+                Some(ChecklistStatus::Done),
+            ),
+            note(
+                "2603150002",
+                &["rust"],
+                &["Ownership"],
+                &[],
+                Some(ChecklistStatus::Done),
+            ),
+            note("2603150003", &[], &[], &[], Some(ChecklistStatus::None)),
+        ] {
+            idx.notes.insert(info.id.clone(), info);
+        }
+
+        let vocab = vocabulary(&idx);
+        let pairs = |terms: &[Term]| -> Vec<(String, usize)> {
+            terms.iter().map(|t| (t.name.clone(), t.count)).collect()
+        };
+        let expect = |v: &[(&str, usize)]| -> Vec<(String, usize)> {
+            v.iter().map(|(n, c)| (n.to_string(), *c)).collect()
+        };
+        assert_eq!(pairs(&vocab.keywords), expect(&[("rust", 3), ("Lsp", 1)]));
+        assert_eq!(
+            pairs(&vocab.aliases),
+            expect(&[("Borrowing", 2), ("Ownership", 1)])
+        );
+        assert_eq!(
+            pairs(&vocab.tags),
+            expect(&[("tag.project", 2), ("topic.rust", 1)])
+        );
+        assert_eq!(
+            pairs(&vocab.status_tags),
+            expect(&[("done", 2), ("todo", 1), ("wip", 0)])
+        );
+        assert_eq!(
+            serde_json::to_value(&vocab).unwrap()["statusTags"][0],
+            serde_json::json!({"name": "done", "count": 2})
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -6,23 +6,34 @@ use anyhow::Result;
 use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode, DebouncedEvent};
 use tokio::sync::mpsc;
 use tokio::sync::RwLock;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::config::WikiConfig;
 use crate::index::NoteIndex;
-use crate::link_gen;
+use crate::{link_gen, note_ops, parser};
 
-/// Start the filesystem watcher on note_dir.
+/// Start the filesystem watcher on note_dir (and its date subdirectories)
+/// and the project config files.
 /// Sends events (Create / Modify / Remove) to the returned receiver.
-pub fn start_watcher(
+///
+/// Calls `on_config_reload` after each config file change it reloads.
+/// Returns where to send the new note_dir when a config change the watcher
+/// did not see itself (`didChangeConfiguration`) moves it.
+///
+/// Only needed when the client cannot deliver `workspace/didChangeWatchedFiles`
+/// events itself; see `ZkLspServer::initialized`.
+pub async fn start_watcher(
     config: Arc<RwLock<WikiConfig>>,
     index: Arc<NoteIndex>,
-) -> Result<tokio::task::JoinHandle<()>> {
+    on_config_reload: impl Fn() + Send + 'static,
+) -> Result<std::sync::mpsc::Sender<PathBuf>> {
     let (tx, mut rx) = mpsc::channel::<Vec<DebouncedEvent>>(64);
+    // New note_dir paths to watch after a config reload moved the note directory.
+    let (dir_tx, dir_rx) = std::sync::mpsc::channel::<PathBuf>();
 
-    let note_dir = {
-        let config = config.blocking_read();
-        config.note_dir.clone()
+    let (root, note_dir) = {
+        let config = config.read().await;
+        (config.root.clone(), config.note_dir.clone())
     };
 
     // Spawn the blocking watcher thread
@@ -32,29 +43,73 @@ pub fn start_watcher(
         let mut debouncer = new_debouncer(Duration::from_millis(300), fs_tx).expect("debouncer");
         debouncer
             .watcher()
-            .watch(&note_dir, RecursiveMode::NonRecursive)
+            .watch(&note_dir, RecursiveMode::Recursive)
             .expect("watch note_dir");
+        // The wiki root holds zk-lsp.toml / .zk-lsp.toml.
+        if root != note_dir {
+            if let Err(e) = debouncer
+                .watcher()
+                .watch(&root, RecursiveMode::NonRecursive)
+            {
+                error!("watch wiki root: {e:?}");
+            }
+        }
+        let mut watched_dir = note_dir;
 
-        for result in fs_rx {
-            match result {
-                Ok(events) => {
+        loop {
+            match fs_rx.recv_timeout(Duration::from_millis(500)) {
+                Ok(Ok(events)) => {
                     let _ = tx.blocking_send(events);
                 }
-                Err(e) => {
+                Ok(Err(e)) => {
                     error!("watcher error: {e:?}");
                 }
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            while let Ok(new_dir) = dir_rx.try_recv() {
+                if watched_dir != root {
+                    let _ = debouncer.watcher().unwatch(&watched_dir);
+                }
+                if new_dir != root {
+                    if let Err(e) = debouncer
+                        .watcher()
+                        .watch(&new_dir, RecursiveMode::Recursive)
+                    {
+                        error!("watch {}: {e:?}", new_dir.display());
+                    }
+                }
+                watched_dir = new_dir;
             }
         }
     });
 
-    let handle = tokio::spawn(async move {
+    let note_dirs = dir_tx.clone();
+    tokio::spawn(async move {
         while let Some(events) = rx.recv().await {
             for event in events {
                 let path = event.path.clone();
-                if !is_note_file(&path) {
+                if config.read().await.is_project_config_file(&path) {
+                    if let Some(new_dir) = reload_config(&config, &index).await {
+                        let _ = dir_tx.send(new_dir);
+                    }
+                    on_config_reload();
+                    continue;
+                }
+                if !is_note_file(&path)
+                    || !note_ops::is_in_note_dir(&config.read().await.note_dir, &path)
+                {
                     continue;
                 }
                 if path.exists() {
+                    let max_bytes = config.read().await.max_note_bytes();
+                    if let Some(len) = note_ops::oversized(&path, max_bytes).await {
+                        warn!(
+                            "ignoring {}: {len} bytes, over max_note_bytes ({max_bytes})",
+                            path.display()
+                        );
+                        continue;
+                    }
                     info!("note changed/created: {}", path.display());
                     let _ = index.update_file(&path).await;
                     let id = path
@@ -66,7 +121,7 @@ pub fn start_watcher(
                     let _ = link_gen::add_entry(&id, &config_snapshot).await;
                 } else {
                     info!("note removed: {}", path.display());
-                    index.remove_by_path(&path);
+                    index.remove_by_path(&path).await;
                     let id = path
                         .file_stem()
                         .and_then(|s| s.to_str())
@@ -79,15 +134,64 @@ pub fn start_watcher(
         }
     });
 
-    Ok(handle)
+    Ok(note_dirs)
 }
 
-fn is_note_file(path: &PathBuf) -> bool {
-    if path.extension().and_then(|e| e.to_str()) != Some("typ") {
-        return false;
+/// Re-read the config files for the current root and swap the result in.
+/// Editor settings already held by the config are re-applied on top.
+///
+/// If the change affects which files are indexed (`note_dir`, `ignore`), the
+/// index and link.typ are rebuilt. Returns the new note_dir when it moved.
+pub async fn reload_config(config: &RwLock<WikiConfig>, index: &NoteIndex) -> Option<PathBuf> {
+    let (old, new) = {
+        let mut guard = config.write().await;
+        let new = WikiConfig::from_root(guard.root.clone()).with_settings(guard.settings.clone());
+        let old = std::mem::replace(&mut *guard, new.clone());
+        (old, new)
+    };
+    info!("config reloaded from {}", new.root.display());
+    if !old.needs_reindex(&new) {
+        return None;
+    }
+    match index.rebuild_full().await {
+        Ok(n) => info!("index rebuilt after config change: {n} notes"),
+        Err(e) => error!("index rebuild failed: {e}"),
+    }
+    if let Err(e) = link_gen::generate_link_typ(&new).await {
+        error!("generate_link_typ: {e}");
+    }
+    (old.note_dir != new.note_dir).then_some(new.note_dir)
+}
+
+pub fn is_note_file(path: &Path) -> bool {
+    parser::note_id_from_path(path).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reload_config_rebuilds_when_note_dir_moves() {
+        let tmp = std::env::temp_dir().join("zk_watcher_test_reload");
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("note")).unwrap();
+        std::fs::create_dir_all(tmp.join("notes")).unwrap();
+        let config = Arc::new(RwLock::new(WikiConfig::from_root(tmp.clone())));
+        let index = NoteIndex::new(Arc::clone(&config));
+
+        // Unchanged config: nothing to do.
+        let unchanged = reload_config(&config, &index).await;
+
+        std::fs::write(tmp.join(".zk-lsp.toml"), "note_dir = \"notes\"\n").unwrap();
+        let moved = reload_config(&config, &index).await;
+        let note_dir = config.read().await.note_dir.clone();
+        let link_typ_written = tmp.join("link.typ").exists();
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        assert_eq!(unchanged, None);
+        assert_eq!(moved, Some(tmp.join("notes")));
+        assert_eq!(note_dir, tmp.join("notes"));
+        assert!(link_typ_written);
     }
-    path.file_stem()
-        .and_then(|s| s.to_str())
-        .map(|s| s.len() == 10 && s.chars().all(|c| c.is_ascii_digit()))
-        .unwrap_or(false)
 }